@@ -2,8 +2,40 @@
 // Licensed under the Apache License, Version 2.0
 // http://www.apache.org/licenses/LICENSE-2.0
 
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
 #[derive(Clone)]
 pub enum Signal {
+    /// Write the contents of storage to the file at the path
+    Dump(PathBuf, Completion),
     FlushAll,
+    /// Load the contents of storage from the file at the path
+    Load(PathBuf, Completion),
+    /// Resize the storage heap to the size in bytes
+    Resize(usize, Completion),
     Shutdown,
 }
+
+/// Reports the outcome of a signal which completes some time after it has
+/// been received, such as a dump, back to the thread which sent the signal.
+/// The signal is sent to every thread, and the outcome is reported by the
+/// thread which handles it. If every thread drops the signal without handling
+/// it, the receiver sees the channel disconnect.
+#[derive(Clone)]
+pub struct Completion {
+    sender: Sender<Result<(), String>>,
+}
+
+impl Completion {
+    /// Create a new `Completion` along with the receiver for its outcome.
+    pub fn new() -> (Self, Receiver<Result<(), String>>) {
+        let (sender, receiver) = channel();
+        (Self { sender }, receiver)
+    }
+
+    /// Report the outcome of the signal.
+    pub fn complete(&self, result: Result<(), std::io::Error>) {
+        let _ = self.sender.send(result.map_err(|e| e.to_string()));
+    }
+}
@@ -16,6 +16,8 @@ const NEVENT_MAX: usize = 1024;
 const FRONTEND_THREADS: usize = 1;
 const BACKEND_THREADS: usize = 1;
 const BACKEND_POOLSIZE: usize = 1;
+const SHADOW_FRACTION: f64 = 1.0;
+const REPLICATION_QUORUM: usize = 1;
 
 // helper functions
 fn address() -> String {
@@ -42,6 +44,14 @@ fn backend_poolsize() -> usize {
     BACKEND_POOLSIZE
 }
 
+fn shadow_fraction() -> f64 {
+    SHADOW_FRACTION
+}
+
+fn replication_quorum() -> usize {
+    REPLICATION_QUORUM
+}
+
 // definitions
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Listener {
@@ -77,6 +87,61 @@ pub struct Backend {
     zk_server: Option<String>,
     zk_path: Option<String>,
     zk_endpoint: Option<String>,
+    #[serde(default)]
+    pools: Vec<Pool>,
+    #[serde(default)]
+    routes: Vec<Route>,
+    #[serde(default)]
+    shadow: Option<Shadow>,
+}
+
+/// An additional named pool of backend servers. Requests are sent to a pool
+/// based on the configured routes.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Pool {
+    name: String,
+    endpoints: Vec<String>,
+    #[serde(default)]
+    replication: Option<Replication>,
+}
+
+/// Makes a pool into a replica group, where each endpoint holds a replica.
+/// Writes are sent to every replica and answered once `quorum` replicas have
+/// acknowledged them. Reads are sent to one replica and fail over to the next
+/// replica on an error or when no response arrives within `timeout`
+/// milliseconds. Any method which is not listed in `writes` is a read.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Replication {
+    #[serde(default)]
+    writes: Vec<String>,
+    #[serde(default = "replication_quorum")]
+    quorum: usize,
+    #[serde(default = "timeout")]
+    timeout: usize,
+}
+
+/// Maps a method to a named backend pool. A method which ends with `*` matches
+/// every method with that prefix, a route for the exact method takes
+/// precedence and otherwise the longest prefix is used. The pool named
+/// `default` refers to the endpoints of the backend itself, which also serves
+/// any method without a route. Stats are reported for each route, and a method
+/// may only have one route.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Route {
+    method: String,
+    pool: String,
+}
+
+/// Mirrors a fraction of requests to a named backend pool. Responses from the
+/// shadow pool are discarded and may optionally be compared against the
+/// responses from the primary pool.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Shadow {
+    pool: String,
+    #[serde(default = "shadow_fraction")]
+    fraction: f64,
+    #[serde(default)]
+    compare: bool,
 }
 
 // implementation
@@ -142,25 +207,7 @@ impl Backend {
     // used to handle service discovery.
     pub fn socket_addrs(&self) -> Result<Vec<SocketAddr>, std::io::Error> {
         if !self.endpoints.is_empty() {
-            let mut endpoints = Vec::new();
-            for endpoint in &self.endpoints {
-                match endpoint.to_socket_addrs() {
-                    Ok(mut addrs) => {
-                        if let Some(addr) = addrs.next() {
-                            endpoints.push(addr)
-                        } else {
-                            return Err(std::io::Error::new(
-                                std::io::ErrorKind::Other,
-                                "failed to resolve endpoint address",
-                            ));
-                        }
-                    }
-                    Err(e) => {
-                        return Err(e);
-                    }
-                }
-            }
-            Ok(endpoints)
+            resolve(&self.endpoints)
         } else if let (Some(server), Some(path), endpoint) = (
             self.zk_server.as_ref(),
             self.zk_path.as_ref(),
@@ -234,6 +281,107 @@ impl Backend {
             // Vec::new()
         }
     }
+
+    /// Additional named pools of backend servers
+    pub fn pools(&self) -> &[Pool] {
+        &self.pools
+    }
+
+    /// Routes which map methods to backend pools
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    /// Optional mirroring of requests to a shadow pool
+    pub fn shadow(&self) -> Option<&Shadow> {
+        self.shadow.as_ref()
+    }
+}
+
+impl Pool {
+    /// The name of the pool, which is used by routes
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Return the result of resolving the pool endpoints
+    pub fn socket_addrs(&self) -> Result<Vec<SocketAddr>, std::io::Error> {
+        if self.endpoints.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "no endpoints provided",
+            ));
+        }
+        resolve(&self.endpoints)
+    }
+
+    /// Optional replication settings which make the pool a replica group
+    pub fn replication(&self) -> Option<&Replication> {
+        self.replication.as_ref()
+    }
+}
+
+impl Replication {
+    /// The methods which are writes and are sent to every replica
+    pub fn writes(&self) -> &[String] {
+        &self.writes
+    }
+
+    /// The number of replicas which must acknowledge a write
+    pub fn quorum(&self) -> usize {
+        self.quorum
+    }
+
+    /// The read timeout in milliseconds before failing over to the next
+    /// replica
+    pub fn timeout(&self) -> usize {
+        self.timeout
+    }
+}
+
+impl Route {
+    /// The method which is matched by this route, or a prefix followed by `*`
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// The name of the pool requests for the method are sent to
+    pub fn pool(&self) -> &str {
+        &self.pool
+    }
+}
+
+impl Shadow {
+    /// The name of the pool which receives the mirrored requests
+    pub fn pool(&self) -> &str {
+        &self.pool
+    }
+
+    /// The fraction of requests, between 0.0 and 1.0, which are mirrored
+    pub fn fraction(&self) -> f64 {
+        self.fraction
+    }
+
+    /// Whether shadow responses are compared against the primary responses
+    pub fn compare(&self) -> bool {
+        self.compare
+    }
+}
+
+// resolves each endpoint to the first matching socket address
+fn resolve(endpoints: &[String]) -> Result<Vec<SocketAddr>, std::io::Error> {
+    let mut addrs = Vec::new();
+    for endpoint in endpoints {
+        if let Some(addr) = endpoint.to_socket_addrs()?.next() {
+            addrs.push(addr)
+        } else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "failed to resolve endpoint address",
+            ));
+        }
+    }
+    Ok(addrs)
 }
 
 struct ExitWatcher;
@@ -275,6 +423,9 @@ impl Default for Backend {
             zk_path: None,
             zk_endpoint: None,
             poolsize: backend_poolsize(),
+            pools: Vec::new(),
+            routes: Vec::new(),
+            shadow: None,
         }
     }
 }
@@ -10,10 +10,12 @@ const MB: usize = 1024 * 1024;
 
 // defaults for hashtable
 const HASH_POWER: u8 = 16;
+const MAX_HASH_POWER: Option<u8> = None;
 const OVERFLOW_FACTOR: f64 = 1.0;
 
 // default heap/segment sizing
 const HEAP_SIZE: usize = 64 * MB;
+const MAX_HEAP_SIZE: Option<usize> = None;
 const SEGMENT_SIZE: i32 = MB as i32;
 
 // default eviction strategy
@@ -26,6 +28,51 @@ const MERGE_MAX: usize = 8;
 
 // datapool
 const DATAPOOL_PATH: Option<&str> = None;
+const HUGE_PAGES: HugePages = HugePages::None;
+const CHECKPOINT_INTERVAL: Option<u64> = None;
+
+// tiered storage, disabled by default
+const TIER2_PATH: Option<&str> = None;
+const TIER2_SIZE: usize = 1024 * MB;
+
+// namespaces, disabled by default
+const NAMESPACE_DELIMITER: Option<&str> = None;
+
+// compression, disabled by default
+const COMPRESSION_THRESHOLD: Option<usize> = None;
+
+// per-item cas values, disabled by default
+const ITEM_CAS: bool = false;
+
+// retaining expired items for leases, disabled by default
+const STALE_GRACE: u32 = 0;
+
+// default admission strategy
+const ADMISSION: Admission = Admission::None;
+
+// related to bloom filter admission
+const ADMISSION_ITEMS: usize = 1024 * 1024;
+const ADMISSION_WINDOW: u32 = 60;
+
+// limits on the work done by each expiration pass, unlimited by default
+const EXPIRE_MAX_SEGMENTS: Option<usize> = None;
+const EXPIRE_MAX_NS: Option<u64> = None;
+
+// reading items from the worker threads, disabled by default
+const SHARED_READS: bool = false;
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum Admission {
+    None,
+    Bloom,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum HugePages {
+    None,
+    Transparent,
+    Explicit,
+}
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum Eviction {
@@ -38,11 +85,33 @@ pub enum Eviction {
     Merge,
 }
 
+/// A namespace is the set of keys with a common prefix, which may occupy up
+/// to `quota` segments.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Namespace {
+    name: String,
+    quota: usize,
+}
+
+impl Namespace {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn quota(&self) -> usize {
+        self.quota
+    }
+}
+
 // helper functions for default values
 fn hash_power() -> u8 {
     HASH_POWER
 }
 
+fn max_hash_power() -> Option<u8> {
+    MAX_HASH_POWER
+}
+
 fn overflow_factor() -> f64 {
     OVERFLOW_FACTOR
 }
@@ -51,6 +120,10 @@ fn heap_size() -> usize {
     HEAP_SIZE
 }
 
+fn max_heap_size() -> Option<usize> {
+    MAX_HEAP_SIZE
+}
+
 fn segment_size() -> i32 {
     SEGMENT_SIZE
 }
@@ -75,15 +148,83 @@ fn datapool_path() -> Option<String> {
     DATAPOOL_PATH.map(|v| v.to_string())
 }
 
+fn huge_pages() -> HugePages {
+    HUGE_PAGES
+}
+
+fn checkpoint_interval() -> Option<u64> {
+    CHECKPOINT_INTERVAL
+}
+
+fn tier2_path() -> Option<String> {
+    TIER2_PATH.map(|v| v.to_string())
+}
+
+fn tier2_size() -> usize {
+    TIER2_SIZE
+}
+
+fn namespace_delimiter() -> Option<String> {
+    NAMESPACE_DELIMITER.map(|v| v.to_string())
+}
+
+fn namespaces() -> Vec<Namespace> {
+    Vec::new()
+}
+
+fn max_value_size() -> Option<usize> {
+    None
+}
+
+fn compression_threshold() -> Option<usize> {
+    COMPRESSION_THRESHOLD
+}
+
+fn item_cas() -> bool {
+    ITEM_CAS
+}
+
+fn stale_grace() -> u32 {
+    STALE_GRACE
+}
+
+fn admission() -> Admission {
+    ADMISSION
+}
+
+fn admission_items() -> usize {
+    ADMISSION_ITEMS
+}
+
+fn admission_window() -> u32 {
+    ADMISSION_WINDOW
+}
+
+fn expire_max_segments() -> Option<usize> {
+    EXPIRE_MAX_SEGMENTS
+}
+
+fn expire_max_ns() -> Option<u64> {
+    EXPIRE_MAX_NS
+}
+
+fn shared_reads() -> bool {
+    SHARED_READS
+}
+
 // definitions
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Seg {
     #[serde(default = "hash_power")]
     hash_power: u8,
+    #[serde(default = "max_hash_power")]
+    max_hash_power: Option<u8>,
     #[serde(default = "overflow_factor")]
     overflow_factor: f64,
     #[serde(default = "heap_size")]
     heap_size: usize,
+    #[serde(default = "max_heap_size")]
+    max_heap_size: Option<usize>,
     #[serde(default = "segment_size")]
     segment_size: i32,
     #[serde(default = "eviction")]
@@ -96,20 +237,70 @@ pub struct Seg {
     compact_target: usize,
     #[serde(default = "datapool_path")]
     datapool_path: Option<String>,
+    #[serde(default = "huge_pages")]
+    huge_pages: HugePages,
+    #[serde(default = "checkpoint_interval")]
+    checkpoint_interval: Option<u64>,
+    #[serde(default = "tier2_path")]
+    tier2_path: Option<String>,
+    #[serde(default = "tier2_size")]
+    tier2_size: usize,
+    #[serde(default = "namespace_delimiter")]
+    namespace_delimiter: Option<String>,
+    #[serde(default = "namespaces")]
+    namespaces: Vec<Namespace>,
+    #[serde(default = "max_value_size")]
+    max_value_size: Option<usize>,
+    #[serde(default = "compression_threshold")]
+    compression_threshold: Option<usize>,
+    #[serde(default = "item_cas")]
+    item_cas: bool,
+    #[serde(default = "stale_grace")]
+    stale_grace: u32,
+    #[serde(default = "admission")]
+    admission: Admission,
+    #[serde(default = "admission_items")]
+    admission_items: usize,
+    #[serde(default = "admission_window")]
+    admission_window: u32,
+    #[serde(default = "expire_max_segments")]
+    expire_max_segments: Option<usize>,
+    #[serde(default = "expire_max_ns")]
+    expire_max_ns: Option<u64>,
+    #[serde(default = "shared_reads")]
+    shared_reads: bool,
 }
 
 impl Default for Seg {
     fn default() -> Self {
         Self {
             hash_power: hash_power(),
+            max_hash_power: max_hash_power(),
             overflow_factor: overflow_factor(),
             heap_size: heap_size(),
+            max_heap_size: max_heap_size(),
             segment_size: segment_size(),
             eviction: eviction(),
             merge_target: merge_target(),
             merge_max: merge_max(),
             compact_target: compact_target(),
             datapool_path: datapool_path(),
+            huge_pages: huge_pages(),
+            checkpoint_interval: checkpoint_interval(),
+            tier2_path: tier2_path(),
+            tier2_size: tier2_size(),
+            namespace_delimiter: namespace_delimiter(),
+            namespaces: namespaces(),
+            max_value_size: max_value_size(),
+            compression_threshold: compression_threshold(),
+            item_cas: item_cas(),
+            stale_grace: stale_grace(),
+            admission: admission(),
+            admission_items: admission_items(),
+            admission_window: admission_window(),
+            expire_max_segments: expire_max_segments(),
+            expire_max_ns: expire_max_ns(),
+            shared_reads: shared_reads(),
         }
     }
 }
@@ -120,6 +311,10 @@ impl Seg {
         self.hash_power
     }
 
+    pub fn max_hash_power(&self) -> Option<u8> {
+        self.max_hash_power
+    }
+
     pub fn overflow_factor(&self) -> f64 {
         self.overflow_factor
     }
@@ -128,6 +323,11 @@ impl Seg {
         self.heap_size
     }
 
+    /// The largest size, in bytes, the heap may be resized to at runtime.
+    pub fn max_heap_size(&self) -> Option<usize> {
+        self.max_heap_size
+    }
+
     pub fn segment_size(&self) -> i32 {
         self.segment_size
     }
@@ -151,9 +351,97 @@ impl Seg {
     pub fn datapool_path(&self) -> Option<PathBuf> {
         self.datapool_path.as_ref().map(|v| Path::new(v).to_owned())
     }
+
+    /// Whether the datapool is backed by huge pages.
+    pub fn huge_pages(&self) -> HugePages {
+        self.huge_pages
+    }
+
+    /// The interval, in seconds, between checkpoints of a file-backed
+    /// datapool.
+    pub fn checkpoint_interval(&self) -> Option<u64> {
+        self.checkpoint_interval
+    }
+
+    pub fn tier2_path(&self) -> Option<PathBuf> {
+        self.tier2_path.as_ref().map(|v| Path::new(v).to_owned())
+    }
+
+    pub fn tier2_size(&self) -> usize {
+        self.tier2_size
+    }
+
+    /// The delimiter which ends the namespace portion of a key. Only the first
+    /// byte of the configured string is used.
+    pub fn namespace_delimiter(&self) -> Option<u8> {
+        self.namespace_delimiter
+            .as_ref()
+            .and_then(|v| v.as_bytes().first().copied())
+    }
+
+    pub fn namespaces(&self) -> &[Namespace] {
+        &self.namespaces
+    }
+
+    pub fn max_value_size(&self) -> usize {
+        self.max_value_size.unwrap_or(self.segment_size as usize)
+    }
+
+    pub fn compression_threshold(&self) -> Option<usize> {
+        self.compression_threshold
+    }
+
+    /// Whether each item is stored with its own 64 bit CAS value, which adds
+    /// 8 bytes to each item.
+    pub fn item_cas(&self) -> bool {
+        self.item_cas
+    }
+
+    /// The time, in seconds, that items are retained after they expire so
+    /// that they can be returned to clients waiting on a lease.
+    pub fn stale_grace(&self) -> u32 {
+        self.stale_grace
+    }
+
+    pub fn admission(&self) -> Admission {
+        self.admission
+    }
+
+    pub fn admission_items(&self) -> usize {
+        self.admission_items
+    }
+
+    pub fn admission_window(&self) -> u32 {
+        self.admission_window
+    }
+
+    /// The maximum number of expired segments to remove on each iteration of
+    /// the storage loop.
+    pub fn expire_max_segments(&self) -> Option<usize> {
+        self.expire_max_segments
+    }
+
+    /// The maximum time, in nanoseconds, to spend removing expired segments on
+    /// each iteration of the storage loop.
+    pub fn expire_max_ns(&self) -> Option<u64> {
+        self.expire_max_ns
+    }
+
+    /// Whether the worker threads read items directly from storage, instead
+    /// of sending each request to the storage thread. This requires a hash
+    /// table and a heap which do not grow.
+    pub fn shared_reads(&self) -> bool {
+        self.shared_reads
+    }
+
+    pub fn set_shared_reads(&mut self, shared_reads: bool) {
+        self.shared_reads = shared_reads
+    }
 }
 
 // trait definitions
 pub trait SegConfig {
     fn seg(&self) -> &Seg;
+
+    fn seg_mut(&mut self) -> &mut Seg;
 }
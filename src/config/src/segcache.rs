@@ -134,6 +134,10 @@ impl SegConfig for SegcacheConfig {
     fn seg(&self) -> &Seg {
         &self.seg
     }
+
+    fn seg_mut(&mut self) -> &mut Seg {
+        &mut self.seg
+    }
 }
 
 impl ServerConfig for SegcacheConfig {
@@ -4,7 +4,7 @@
 
 use ::net::event::{Event, Source};
 use ::net::*;
-use common::signal::Signal;
+use common::signal::{Completion, Signal};
 use common::ssl::tls_acceptor;
 use config::{AdminConfig, TlsConfig};
 use crossbeam_channel::Receiver;
@@ -16,6 +16,8 @@ use session::{Buf, ServerSession, Session};
 use slab::Slab;
 use std::collections::VecDeque;
 use std::io::{Error, ErrorKind, Result};
+use std::ops::RangeInclusive;
+use std::sync::mpsc::TryRecvError;
 use std::sync::Arc;
 use std::time::Duration;
 use tiny_http::{Method, Request, Response};
@@ -66,6 +68,9 @@ counter!(
 
 gauge!(ADMIN_SESSION_CURR, "current number of admin sessions");
 
+// the receiver for the outcome of a signal which completes asynchronously
+type CompletionReceiver = std::sync::mpsc::Receiver<std::result::Result<(), String>>;
+
 // consts
 
 const LISTENER_TOKEN: Token = Token(usize::MAX - 1);
@@ -87,6 +92,11 @@ fn map_err(e: std::io::Error) -> Result<()> {
 pub struct Admin {
     /// A backlog of tokens that need to be handled
     backlog: VecDeque<Token>,
+    /// The sessions which are waiting for a dump, load, or resize to
+    /// complete, along with the command and the receiver for its outcome
+    completions: Vec<(Token, &'static str, CompletionReceiver)>,
+    /// The range of sizes, in bytes, the storage heap may be resized to
+    heap_size_range: Option<RangeInclusive<usize>>,
     http_server: Option<tiny_http::Server>,
     /// The actual network listener for the ASCII Admin Endpoint
     listener: ::net::Listener,
@@ -112,6 +122,7 @@ pub struct Admin {
 
 pub struct AdminBuilder {
     backlog: VecDeque<Token>,
+    heap_size_range: Option<RangeInclusive<usize>>,
     http_server: Option<tiny_http::Server>,
     listener: ::net::Listener,
     nevent: usize,
@@ -169,6 +180,7 @@ impl AdminBuilder {
 
         Ok(Self {
             backlog,
+            heap_size_range: None,
             http_server,
             listener,
             nevent,
@@ -184,6 +196,12 @@ impl AdminBuilder {
         self.version = version.to_string();
     }
 
+    /// Set the range of sizes, in bytes, the storage heap may be resized to.
+    /// Resize requests outside of the range are rejected by the admin thread.
+    pub fn heap_size_range(&mut self, range: RangeInclusive<usize>) {
+        self.heap_size_range = Some(range);
+    }
+
     pub fn waker(&self) -> Arc<Waker> {
         self.waker.clone()
     }
@@ -196,6 +214,8 @@ impl AdminBuilder {
     ) -> Admin {
         Admin {
             backlog: self.backlog,
+            completions: Vec::new(),
+            heap_size_range: self.heap_size_range,
             http_server: self.http_server,
             listener: self.listener,
             log_drain,
@@ -314,10 +334,50 @@ impl Admin {
 
                 // do some request handling
                 match request {
+                    AdminRequest::Dump(path) => {
+                        // the reply is sent once the dump has completed
+                        let (completion, receiver) = Completion::new();
+                        let _ = self
+                            .signal_queue_tx
+                            .try_send_all(Signal::Dump(path, completion));
+                        let _ = self.signal_queue_tx.wake();
+                        self.completions.push((token, "dump", receiver));
+                    }
                     AdminRequest::FlushAll => {
                         let _ = self.signal_queue_tx.try_send_all(Signal::FlushAll);
                         session.send(AdminResponse::Ok)?;
                     }
+                    AdminRequest::Load(path) => {
+                        // the reply is sent once the load has completed
+                        let (completion, receiver) = Completion::new();
+                        let _ = self
+                            .signal_queue_tx
+                            .try_send_all(Signal::Load(path, completion));
+                        let _ = self.signal_queue_tx.wake();
+                        self.completions.push((token, "load", receiver));
+                    }
+                    AdminRequest::Resize(heap_size) => {
+                        let valid = heap_size > 0
+                            && self
+                                .heap_size_range
+                                .as_ref()
+                                .map(|range| range.contains(&heap_size))
+                                .unwrap_or(true);
+                        if valid {
+                            // the reply is sent once the resize has completed
+                            let (completion, receiver) = Completion::new();
+                            let _ = self
+                                .signal_queue_tx
+                                .try_send_all(Signal::Resize(heap_size, completion));
+                            let _ = self.signal_queue_tx.wake();
+                            self.completions.push((token, "resize", receiver));
+                        } else {
+                            session.send(AdminResponse::error(format!(
+                                "invalid heap size: {}",
+                                heap_size
+                            )))?;
+                        }
+                    }
                     AdminRequest::Quit => {
                         return Err(Error::new(ErrorKind::Other, "should hangup"));
                     }
@@ -369,8 +429,58 @@ impl Admin {
         }
     }
 
+    /// Replies to the sessions which were waiting for a dump, load, or resize
+    /// which has since completed. A command which is dropped by every thread without
+    /// being handled is not supported by the storage.
+    fn complete(&mut self) {
+        let mut i = 0;
+        while i < self.completions.len() {
+            let (token, command, receiver) = &self.completions[i];
+            let response = match receiver.try_recv() {
+                Ok(Ok(())) => AdminResponse::Ok,
+                Ok(Err(e)) => AdminResponse::error(e),
+                Err(TryRecvError::Disconnected) => {
+                    AdminResponse::error(format!("{} is not supported", command))
+                }
+                Err(TryRecvError::Empty) => {
+                    i += 1;
+                    continue;
+                }
+            };
+            let token = *token;
+            self.completions.swap_remove(i);
+
+            if self.reply(token, response).is_err() {
+                self.close(token);
+            }
+        }
+    }
+
+    /// Sends a response to the session outside of the handling of a read
+    fn reply(&mut self, token: Token, response: AdminResponse) -> Result<()> {
+        let session = self
+            .sessions
+            .get_mut(token.0)
+            .ok_or_else(|| Error::new(ErrorKind::Other, "non-existant session"))?;
+
+        session.send(response)?;
+        ADMIN_RESPONSE_COMPOSE.increment();
+
+        match session.flush() {
+            Ok(_) => Ok(()),
+            Err(e) => map_err(e),
+        }?;
+
+        if session.write_pending() > 0 {
+            let interest = session.interest();
+            session.reregister(self.poll.registry(), token, interest)?;
+        }
+        Ok(())
+    }
+
     /// Closes the session with the given token
     fn close(&mut self, token: Token) {
+        self.completions.retain(|(t, _, _)| *t != token);
         if self.sessions.contains(token.0) {
             ADMIN_SESSION_CLOSE.increment();
             ADMIN_SESSION_CURR.decrement();
@@ -693,10 +803,14 @@ impl Admin {
                 }
             }
 
+            // reply to any dump or load which has completed
+            self.complete();
+
             // handle all signals
             while let Ok(signal) = self.signal_queue_rx.try_recv() {
                 match signal {
-                    Signal::FlushAll => {}
+                    Signal::Dump(..) | Signal::FlushAll | Signal::Load(..) | Signal::Resize(..) => {
+                    }
                     Signal::Shutdown => {
                         // if a shutdown is received from any
                         // thread, we will broadcast it to all
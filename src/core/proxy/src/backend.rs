@@ -3,10 +3,16 @@
 // http://www.apache.org/licenses/LICENSE-2.0
 
 use super::map_result;
+use crate::replica::{Replicas, Reply};
+use crate::route::{MethodStats, Stats, DEFAULT_POOL};
+use crate::shadow;
 use crate::*;
+use protocol_common::Method;
 use session::ClientSession;
 use std::collections::HashMap;
 use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::ops::Range;
 
 heatmap!(
     BACKEND_EVENT_DEPTH,
@@ -31,6 +37,7 @@ pub struct BackendWorkerBuilder<Parser, Request, Response> {
     nevent: usize,
     parser: Parser,
     poll: Poll,
+    replicas: Option<Replicas<Request, Response>>,
     sessions: Slab<ClientSession<Parser, Request, Response>>,
     timeout: Duration,
     waker: Arc<Waker>,
@@ -39,11 +46,15 @@ pub struct BackendWorkerBuilder<Parser, Request, Response> {
 impl<Parser, Request, Response> BackendWorkerBuilder<Parser, Request, Response>
 where
     Parser: Clone + Parse<Response>,
-    Request: Compose,
+    Request: Compose + Method + Clone,
+    Response: Compose + Method + ErrorResponse<Request>,
 {
-    pub fn new<T: BackendConfig>(config: &T, parser: Parser) -> Result<Self> {
-        let config = config.backend();
-
+    pub fn new(
+        config: &Backend,
+        endpoints: &[SocketAddr],
+        replication: Option<&Replication>,
+        parser: Parser,
+    ) -> Result<Self> {
         let poll = Poll::new()?;
 
         let waker = Arc::new(Waker::from(
@@ -53,11 +64,17 @@ where
         let nevent = config.nevent();
         let timeout = Duration::from_millis(config.timeout() as u64);
 
+        let replicas = replication
+            .map(|replication| Replicas::new(replication, endpoints.len()))
+            .transpose()?;
+
+        // the sessions are added in the order of the endpoints, which allows
+        // a replica to be addressed by the index of its endpoint
         let mut sessions = Slab::new();
         let mut free_queue = VecDeque::new();
 
-        for endpoint in config.socket_addrs()? {
-            let stream = TcpStream::connect(endpoint)?;
+        for endpoint in endpoints {
+            let stream = TcpStream::connect(*endpoint)?;
             let mut session = ClientSession::new(Session::from(stream), parser.clone());
             let s = sessions.vacant_entry();
             let interest = session.interest();
@@ -73,6 +90,7 @@ where
             nevent,
             parser,
             poll,
+            replicas,
             sessions,
             timeout,
             waker,
@@ -85,8 +103,9 @@ where
 
     pub fn build(
         self,
-        data_queue: Queues<(Request, Response, Token), (Request, Token)>,
+        data_queue: Queues<(Request, Option<Response>, Token), (Request, Token)>,
         signal_queue: Queues<(), Signal>,
+        stats: Stats,
     ) -> BackendWorker<Parser, Request, Response> {
         BackendWorker {
             backlog: VecDeque::new(),
@@ -96,8 +115,11 @@ where
             parser: self.parser,
             pending: HashMap::new(),
             poll: self.poll,
+            replicas: self.replicas,
+            replies: Vec::new(),
             sessions: self.sessions,
             signal_queue,
+            stats,
             timeout: self.timeout,
             waker: self.waker,
         }
@@ -106,14 +128,17 @@ where
 
 pub struct BackendWorker<Parser, Request, Response> {
     backlog: VecDeque<(Request, Token)>,
-    data_queue: Queues<(Request, Response, Token), (Request, Token)>,
+    data_queue: Queues<(Request, Option<Response>, Token), (Request, Token)>,
     free_queue: VecDeque<Token>,
     nevent: usize,
     parser: Parser,
-    pending: HashMap<Token, Token>,
+    pending: HashMap<Token, (Token, Instant)>,
     poll: Poll,
+    replicas: Option<Replicas<Request, Response>>,
+    replies: Vec<Reply<Request, Response>>,
     sessions: Slab<ClientSession<Parser, Request, Response>>,
     signal_queue: Queues<(), Signal>,
+    stats: Stats,
     timeout: Duration,
     waker: Arc<Waker>,
 }
@@ -121,14 +146,47 @@ pub struct BackendWorker<Parser, Request, Response> {
 impl<Parser, Request, Response> BackendWorker<Parser, Request, Response>
 where
     Parser: Parse<Response> + Clone,
-    Request: Compose,
+    Request: Compose + Method + Clone,
+    Response: Compose + Method + ErrorResponse<Request>,
 {
     /// Return the `Session` to the `Listener` to handle flush/close
     fn close(&mut self, token: Token) {
         if self.sessions.contains(token.0) {
             let mut session = self.sessions.remove(token.0);
             let _ = session.flush();
+
+            if let Some(replicas) = self.replicas.as_mut() {
+                replicas.failed(&mut self.sessions, token, &mut self.replies);
+                let _ = self.reply();
+            }
+        }
+    }
+
+    /// Send the responses for replicated requests back to the frontend
+    fn reply(&mut self) -> Result<()> {
+        let mut result = Ok(());
+        for (request, response, fe_token, start) in std::mem::take(&mut self.replies) {
+            if let Some(stats) = self.stats(fe_token, &request) {
+                stats.response(response.as_ref(), start);
+            }
+            if self
+                .data_queue
+                .try_send_to(0, (request, response, fe_token))
+                .is_err()
+            {
+                result = Err(Error::new(ErrorKind::Other, "data queue is full"));
+            }
+        }
+        result
+    }
+
+    /// Returns the stats for the request's method. Requests which are mirrored
+    /// to the shadow pool are excluded from the stats.
+    fn stats(&self, fe_token: Token, request: &Request) -> Option<&MethodStats> {
+        if shadow::is_shadow(fe_token) {
+            return None;
         }
+        request.method().and_then(|m| self.stats.get(m))
     }
 
     /// Handle up to one response for a session
@@ -144,10 +202,23 @@ where
         // process up to one request
         match session.receive() {
             Ok((request, response)) => {
-                if let Some(fe_token) = self.pending.remove(&token) {
+                if let Some(replicas) = self.replicas.as_mut() {
+                    replicas.response(
+                        &mut self.sessions,
+                        token,
+                        request,
+                        response,
+                        &mut self.replies,
+                    );
+                    return self.reply();
+                }
+                if let Some((fe_token, start)) = self.pending.remove(&token) {
+                    if let Some(stats) = self.stats(fe_token, &request) {
+                        stats.response(Some(&response), start);
+                    }
                     self.free_queue.push_back(token);
                     self.data_queue
-                        .try_send_to(0, (request, response, fe_token))
+                        .try_send_to(0, (request, Some(response), fe_token))
                         .map_err(|_| Error::new(ErrorKind::Other, "data queue is full"))
                 } else {
                     panic!("corrupted state");
@@ -205,12 +276,24 @@ where
                         // handle all pending messages on the data queue
                         self.data_queue.try_recv_all(&mut messages);
                         for (request, fe_token) in messages.drain(..).map(|v| v.into_inner()) {
+                            if let Some(stats) = self.stats(fe_token, &request) {
+                                stats.request();
+                            }
+                            if let Some(replicas) = self.replicas.as_mut() {
+                                replicas.request(
+                                    &mut self.sessions,
+                                    request,
+                                    fe_token,
+                                    &mut self.replies,
+                                );
+                                continue;
+                            }
                             if let Some(be_token) = self.free_queue.pop_front() {
                                 let session = &mut self.sessions[be_token.0];
                                 if session.send(request).is_err() {
                                     panic!("we don't handle this right now");
                                 } else {
-                                    self.pending.insert(be_token, fe_token);
+                                    self.pending.insert(be_token, (fe_token, Instant::now()));
                                 }
                             } else {
                                 self.backlog.push_back((request, token));
@@ -222,7 +305,10 @@ where
                             self.signal_queue.try_recv().map(|v| v.into_inner())
                         {
                             match signal {
-                                Signal::FlushAll => {}
+                                Signal::Dump(..)
+                                | Signal::FlushAll
+                                | Signal::Load(..)
+                                | Signal::Resize(..) => {}
                                 Signal::Shutdown => {
                                     // if we received a shutdown, we can return
                                     // and stop processing events
@@ -260,6 +346,12 @@ where
                 }
             }
 
+            // fail over any replicated reads which have timed out
+            if let Some(replicas) = self.replicas.as_mut() {
+                replicas.expire(&mut self.sessions, Instant::now(), &mut self.replies);
+            }
+            let _ = self.reply();
+
             // wakes the storage thread if necessary
             let _ = self.data_queue.wake();
         }
@@ -268,41 +360,87 @@ where
 
 pub struct BackendBuilder<Parser, Request, Response> {
     builders: Vec<BackendWorkerBuilder<Parser, Request, Response>>,
+    pools: Vec<(String, Range<usize>)>,
 }
 
 impl<BackendParser, BackendRequest, BackendResponse>
     BackendBuilder<BackendParser, BackendRequest, BackendResponse>
 where
     BackendParser: Parse<BackendResponse> + Clone,
-    BackendRequest: Compose,
+    BackendRequest: Compose + Method + Clone,
+    BackendResponse: Compose + Method + ErrorResponse<BackendRequest>,
 {
+    /// Creates the workers for each backend pool. The default pool is always
+    /// first and is followed by any additional pools in the order they are
+    /// configured. Each pool is served by `threads` workers.
     pub fn new<T: BackendConfig>(
         config: &T,
         parser: BackendParser,
         threads: usize,
     ) -> Result<Self> {
+        let config = config.backend();
+
+        let mut pools = vec![(DEFAULT_POOL.to_owned(), config.socket_addrs()?, None)];
+        for pool in config.pools() {
+            if pools.iter().any(|(name, _, _)| name == pool.name()) {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!("duplicate backend pool: {}", pool.name()),
+                ));
+            }
+            pools.push((
+                pool.name().to_owned(),
+                pool.socket_addrs()?,
+                pool.replication(),
+            ));
+        }
+
         let mut builders = Vec::new();
-        for _ in 0..threads {
-            builders.push(BackendWorkerBuilder::new(config, parser.clone())?);
+        let mut ranges = Vec::new();
+        for (name, endpoints, replication) in pools {
+            let start = builders.len();
+            for _ in 0..threads {
+                builders.push(BackendWorkerBuilder::new(
+                    config,
+                    &endpoints,
+                    replication,
+                    parser.clone(),
+                )?);
+            }
+            ranges.push((name, start..builders.len()));
         }
-        Ok(Self { builders })
+
+        Ok(Self {
+            builders,
+            pools: ranges,
+        })
     }
 
     pub fn wakers(&self) -> Vec<Arc<Waker>> {
         self.builders.iter().map(|b| b.waker()).collect()
     }
 
+    /// The name of each pool along with the indices of the workers for that
+    /// pool.
+    pub fn pools(&self) -> &[(String, Range<usize>)] {
+        &self.pools
+    }
+
     #[allow(clippy::type_complexity)]
     pub fn build(
         mut self,
-        mut data_queues: Vec<
-            Queues<(BackendRequest, BackendResponse, Token), (BackendRequest, Token)>,
+        data_queues: Vec<
+            Queues<(BackendRequest, Option<BackendResponse>, Token), (BackendRequest, Token)>,
         >,
-        mut signal_queues: Vec<Queues<(), Signal>>,
+        signal_queues: Vec<Queues<(), Signal>>,
+        stats: Stats,
     ) -> Vec<BackendWorker<BackendParser, BackendRequest, BackendResponse>> {
+        // the queues are in the same order as the wakers, which makes sure
+        // that the frontends can address each worker by index
         self.builders
             .drain(..)
-            .map(|b| b.build(data_queues.pop().unwrap(), signal_queues.pop().unwrap()))
+            .zip(data_queues.into_iter().zip(signal_queues))
+            .map(|(b, (data_queue, signal_queue))| b.build(data_queue, signal_queue, stats.clone()))
             .collect()
     }
 }
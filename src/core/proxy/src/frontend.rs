@@ -3,7 +3,10 @@
 // http://www.apache.org/licenses/LICENSE-2.0
 
 use super::map_result;
+use crate::route::Router;
+use crate::shadow::{self, Shadow};
 use crate::*;
+use protocol_common::Method;
 
 heatmap!(
     FRONTEND_EVENT_DEPTH,
@@ -33,7 +36,9 @@ pub struct FrontendWorkerBuilder<
     nevent: usize,
     parser: FrontendParser,
     poll: Poll,
+    router: Router,
     sessions: Slab<ServerSession<FrontendParser, FrontendResponse, FrontendRequest>>,
+    shadow: Option<Shadow>,
     timeout: Duration,
     waker: Arc<Waker>,
     _backend_request: PhantomData<BackendRequest>,
@@ -49,7 +54,12 @@ impl<FrontendParser, FrontendRequest, FrontendResponse, BackendRequest, BackendR
         BackendResponse,
     >
 {
-    pub fn new<T: FrontendConfig>(config: &T, parser: FrontendParser) -> Result<Self> {
+    pub fn new<T: FrontendConfig>(
+        config: &T,
+        parser: FrontendParser,
+        router: Router,
+        shadow: Option<Shadow>,
+    ) -> Result<Self> {
         let config = config.frontend();
 
         let poll = Poll::new()?;
@@ -65,7 +75,9 @@ impl<FrontendParser, FrontendRequest, FrontendResponse, BackendRequest, BackendR
             nevent,
             parser,
             poll,
+            router,
             sessions: Slab::new(),
+            shadow,
             timeout,
             waker,
             _backend_request: PhantomData,
@@ -79,7 +91,10 @@ impl<FrontendParser, FrontendRequest, FrontendResponse, BackendRequest, BackendR
 
     pub fn build(
         self,
-        data_queue: Queues<(BackendRequest, Token), (BackendRequest, BackendResponse, Token)>,
+        data_queue: Queues<
+            (BackendRequest, Token),
+            (BackendRequest, Option<BackendResponse>, Token),
+        >,
         session_queue: Queues<Session, Session>,
         signal_queue: Queues<(), Signal>,
     ) -> FrontendWorker<
@@ -94,8 +109,10 @@ impl<FrontendParser, FrontendRequest, FrontendResponse, BackendRequest, BackendR
             nevent: self.nevent,
             parser: self.parser,
             poll: self.poll,
+            router: self.router,
             session_queue,
             sessions: self.sessions,
+            shadow: self.shadow,
             signal_queue,
             timeout: self.timeout,
             waker: self.waker,
@@ -110,12 +127,14 @@ pub struct FrontendWorker<
     BackendRequest,
     BackendResponse,
 > {
-    data_queue: Queues<(BackendRequest, Token), (BackendRequest, BackendResponse, Token)>,
+    data_queue: Queues<(BackendRequest, Token), (BackendRequest, Option<BackendResponse>, Token)>,
     nevent: usize,
     parser: FrontendParser,
     poll: Poll,
+    router: Router,
     session_queue: Queues<Session, Session>,
     sessions: Slab<ServerSession<FrontendParser, FrontendResponse, FrontendRequest>>,
+    shadow: Option<Shadow>,
     signal_queue: Queues<(), Signal>,
     timeout: Duration,
     waker: Arc<Waker>,
@@ -134,7 +153,7 @@ where
     FrontendResponse: Compose,
     FrontendResponse: From<BackendResponse>,
     BackendRequest: From<FrontendRequest>,
-    BackendRequest: Compose,
+    BackendRequest: Compose + Method + Clone,
     BackendResponse: Compose,
 {
     /// Return the `Session` to the `Listener` to handle flush/close
@@ -145,6 +164,9 @@ where
             let _ = self.session_queue.try_send_any(session);
             let _ = self.session_queue.wake();
         }
+        if let Some(shadow) = self.shadow.as_mut() {
+            shadow.close(token);
+        }
     }
 
     /// Handle up to one request for a session
@@ -159,10 +181,24 @@ where
 
         // process up to one request
         match session.receive() {
-            Ok(request) => self
-                .data_queue
-                .try_send_to(0, (BackendRequest::from(request), token))
-                .map_err(|_| Error::new(ErrorKind::Other, "data queue is full")),
+            Ok(request) => {
+                let request = BackendRequest::from(request);
+                let backend = self.router.route(&request);
+                if let Some(shadow) = self.shadow.as_mut() {
+                    if let Some((worker, shadow_token)) = shadow.sample(token) {
+                        if self
+                            .data_queue
+                            .try_send_to(worker, (request.clone(), shadow_token))
+                            .is_err()
+                        {
+                            shadow.dropped(token);
+                        }
+                    }
+                }
+                self.data_queue
+                    .try_send_to(backend, (request, token))
+                    .map_err(|_| Error::new(ErrorKind::Other, "data queue is full"))
+            }
             Err(e) => map_err(e),
         }
     }
@@ -235,6 +271,27 @@ where
                         for (_request, response, token) in
                             messages.drain(..).map(|v| v.into_inner())
                         {
+                            // responses from the shadow pool never reach the client
+                            if shadow::is_shadow(token) {
+                                if let (Some(shadow), Some(response)) =
+                                    (self.shadow.as_mut(), response.as_ref())
+                                {
+                                    shadow.shadow(token, response);
+                                }
+                                continue;
+                            }
+                            // without a response the client can not be
+                            // answered, so its connection is closed
+                            let response = match response {
+                                Some(response) => response,
+                                None => {
+                                    self.close(token);
+                                    continue;
+                                }
+                            };
+                            if let Some(shadow) = self.shadow.as_mut() {
+                                shadow.primary(token, &response);
+                            }
                             if let Some(session) = self.sessions.get_mut(token.0) {
                                 if response.should_hangup() {
                                     let _ = session.send(FrontendResponse::from(response));
@@ -265,7 +322,10 @@ where
                             self.signal_queue.try_recv().map(|v| v.into_inner())
                         {
                             match signal {
-                                Signal::FlushAll => {}
+                                Signal::Dump(..)
+                                | Signal::FlushAll
+                                | Signal::Load(..)
+                                | Signal::Resize(..) => {}
                                 Signal::Shutdown => {
                                     // if we received a shutdown, we can return
                                     // and stop processing events
@@ -340,16 +400,24 @@ where
     FrontendResponse: Compose,
     FrontendResponse: From<BackendResponse>,
     BackendRequest: From<FrontendRequest>,
-    BackendRequest: Compose,
+    BackendRequest: Compose + Method + Clone,
+    BackendResponse: Compose,
 {
     pub fn new<T: FrontendConfig>(
         config: &T,
         parser: FrontendParser,
+        router: Router,
+        shadow: Option<Shadow>,
         threads: usize,
     ) -> Result<Self> {
         let mut builders = Vec::new();
         for _ in 0..threads {
-            builders.push(FrontendWorkerBuilder::new(config, parser.clone())?);
+            builders.push(FrontendWorkerBuilder::new(
+                config,
+                parser.clone(),
+                router.clone(),
+                shadow.clone(),
+            )?);
         }
         Ok(Self { builders })
     }
@@ -361,11 +429,11 @@ where
     #[allow(clippy::type_complexity)]
     pub fn build(
         mut self,
-        mut data_queues: Vec<
-            Queues<(BackendRequest, Token), (BackendRequest, BackendResponse, Token)>,
+        data_queues: Vec<
+            Queues<(BackendRequest, Token), (BackendRequest, Option<BackendResponse>, Token)>,
         >,
-        mut session_queues: Vec<Queues<Session, Session>>,
-        mut signal_queues: Vec<Queues<(), Signal>>,
+        session_queues: Vec<Queues<Session, Session>>,
+        signal_queues: Vec<Queues<(), Signal>>,
     ) -> Vec<
         FrontendWorker<
             FrontendParser,
@@ -375,14 +443,13 @@ where
             BackendResponse,
         >,
     > {
+        // the queues are in the same order as the wakers
         self.builders
             .drain(..)
-            .map(|b| {
-                b.build(
-                    data_queues.pop().unwrap(),
-                    session_queues.pop().unwrap(),
-                    signal_queues.pop().unwrap(),
-                )
+            .zip(data_queues.into_iter().zip(session_queues))
+            .zip(signal_queues)
+            .map(|((b, (data_queue, session_queue)), signal_queue)| {
+                b.build(data_queue, session_queue, signal_queue)
             })
             .collect()
     }
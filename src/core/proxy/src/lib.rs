@@ -23,7 +23,7 @@ use core::time::Duration;
 use crossbeam_channel::{bounded, Receiver, Sender};
 use entrystore::EntryStore;
 use logger::Drain;
-use protocol_common::{Compose, Execute, Parse};
+use protocol_common::{Compose, ErrorResponse, Execute, Method, Parse};
 use queues::Queues;
 use rustcommon_metrics::*;
 use session::{Buf, ServerSession, Session};
@@ -38,6 +38,9 @@ mod backend;
 mod frontend;
 mod listener;
 mod process;
+mod replica;
+mod route;
+mod shadow;
 
 use backend::BackendBuilder;
 use frontend::FrontendBuilder;
@@ -295,7 +295,10 @@ impl Listener {
                             self.signal_queue.try_recv().map(|v| v.into_inner())
                         {
                             match signal {
-                                Signal::FlushAll => {}
+                                Signal::Dump(..)
+                                | Signal::FlushAll
+                                | Signal::Load(..)
+                                | Signal::Resize(..) => {}
                                 Signal::Shutdown => {
                                     // if we received a shutdown, we can return
                                     // and stop processing events
@@ -2,6 +2,8 @@
 // Licensed under the Apache License, Version 2.0
 // http://www.apache.org/licenses/LICENSE-2.0
 
+use crate::route::Router;
+use crate::shadow::Shadow;
 use crate::*;
 use config::proxy::BackendConfig;
 use config::proxy::FrontendConfig;
@@ -27,6 +29,7 @@ pub struct ProcessBuilder<
     >,
     listener: ListenerBuilder,
     log_drain: Box<dyn Drain>,
+    stats: route::Stats,
 }
 
 impl<
@@ -47,8 +50,8 @@ impl<
     >
 where
     BackendParser: 'static + Parse<BackendResponse> + Clone + Send,
-    BackendRequest: 'static + Send + Compose + From<FrontendRequest> + Compose,
-    BackendResponse: 'static + Compose + Send,
+    BackendRequest: 'static + Send + Compose + From<FrontendRequest> + Compose + Method + Clone,
+    BackendResponse: 'static + Compose + Send + Method + ErrorResponse<BackendRequest>,
     FrontendParser: 'static + Parse<FrontendRequest> + Clone + Send,
     FrontendRequest: 'static + Send,
     FrontendResponse: 'static + Compose + Send,
@@ -62,7 +65,14 @@ where
     ) -> Result<Self> {
         let admin = AdminBuilder::new(config)?;
         let backend = BackendBuilder::new(config, backend_parser, 1)?;
-        let frontend = FrontendBuilder::new(config, frontend_parser, 1)?;
+        let routes = config.backend().routes();
+        let router = Router::new(routes, backend.pools())?;
+        let shadow = config
+            .backend()
+            .shadow()
+            .map(|shadow| Shadow::new(shadow, backend.pools()))
+            .transpose()?;
+        let frontend = FrontendBuilder::new(config, frontend_parser, router, shadow, 1)?;
         let listener = ListenerBuilder::new(config)?;
 
         Ok(Self {
@@ -71,6 +81,7 @@ where
             frontend,
             listener,
             log_drain,
+            stats: route::stats(routes)?,
         })
     }
 
@@ -117,6 +128,7 @@ where
         let mut backend_workers = self.backend.build(
             be_data_queues,
             signal_queue_rx.drain(0..be_threads).collect(),
+            self.stats,
         );
         let mut frontend_workers =
             self.frontend
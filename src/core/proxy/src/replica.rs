@@ -0,0 +1,611 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Replication of requests across a backend pool which is configured as a
+//! replica group. Each endpoint of the pool holds a replica. Writes are sent
+//! to every replica and the client is answered once a quorum of replicas have
+//! acknowledged the write. Reads are sent to a single replica and fail over to
+//! the next replica on an error or timeout.
+
+use crate::*;
+use protocol_common::Method;
+use session::ClientSession;
+use std::collections::{HashSet, VecDeque};
+
+counter!(REPLICA_READ, "the number of reads sent to a replica group");
+counter!(
+    REPLICA_WRITE,
+    "the number of writes sent to a replica group"
+);
+counter!(
+    REPLICA_FAILOVER,
+    "the number of times a read was sent to the next replica"
+);
+counter!(
+    REPLICA_TIMEOUT,
+    "the number of reads which timed out waiting for a replica"
+);
+counter!(
+    REPLICA_WRITE_ERROR,
+    "the number of writes which failed on an individual replica"
+);
+counter!(
+    REPLICA_QUORUM_FAILED,
+    "the number of writes which were not acknowledged by a quorum of replicas"
+);
+counter!(
+    REPLICA_MISMATCH,
+    "the number of replica responses which differed from the first successful response"
+);
+counter!(
+    REPLICA_UNAVAILABLE,
+    "the number of requests which could not be answered by any replica"
+);
+
+/// A response which should be returned to the frontend, along with the time
+/// the request was received from the frontend. There is no response when no
+/// replica could answer and the protocol has no error response, in which case
+/// the client connection should be closed.
+pub type Reply<Request, Response> = (Request, Option<Response>, Token, Instant);
+
+// the state of a single request which is being replicated
+struct Replicated<Request, Response> {
+    fe_token: Token,
+    request: Request,
+    start: Instant,
+    write: bool,
+    // the first replica tried for a read and the number of replicas tried
+    base: usize,
+    tried: usize,
+    // when the latest read attempt was sent
+    sent: Instant,
+    // the number of replicas which have not yet responded
+    outstanding: usize,
+    // the number of replicas which responded without an error
+    acks: usize,
+    responded: bool,
+    // the first successful response, used to detect inconsistent replicas
+    first: Option<Vec<u8>>,
+    // an error response which is returned if no replica succeeds
+    error: Option<(Request, Response)>,
+}
+
+/// Tracks the requests which are in-flight to a replica group. The session
+/// for each replica is expected to use the replica index as its token.
+pub struct Replicas<Request, Response> {
+    writes: HashSet<String>,
+    quorum: usize,
+    timeout: u64,
+    replicas: usize,
+    next: usize,
+    requests: Slab<Replicated<Request, Response>>,
+    // the ids of the requests awaiting a response from each replica, in the
+    // order they were sent
+    pending: Vec<VecDeque<usize>>,
+}
+
+impl<Request, Response> Replicas<Request, Response>
+where
+    Request: Compose + Method + Clone,
+    Response: Compose + Method + ErrorResponse<Request>,
+{
+    /// Create a new `Replicas` for a replica group with the provided number
+    /// of replicas.
+    pub fn new(config: &Replication, replicas: usize) -> Result<Self> {
+        if config.quorum() == 0 || config.quorum() > replicas {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!(
+                    "replication quorum must be between 1 and {}, got: {}",
+                    replicas,
+                    config.quorum()
+                ),
+            ));
+        }
+
+        Ok(Self {
+            writes: config.writes().iter().cloned().collect(),
+            quorum: config.quorum(),
+            timeout: config.timeout() as u64 * 1_000_000,
+            replicas,
+            next: 0,
+            requests: Slab::new(),
+            pending: vec![VecDeque::new(); replicas],
+        })
+    }
+
+    /// Handle a new request from the frontend.
+    pub fn request<Parser>(
+        &mut self,
+        sessions: &mut Slab<ClientSession<Parser, Request, Response>>,
+        request: Request,
+        fe_token: Token,
+        replies: &mut Vec<Reply<Request, Response>>,
+    ) where
+        Parser: Parse<Response>,
+    {
+        let write = request
+            .method()
+            .map(|m| self.writes.contains(m))
+            .unwrap_or(false);
+
+        // reads start on a different replica each time to spread the load
+        self.next = self.next.wrapping_add(1);
+
+        let now = Instant::now();
+        let id = self.requests.insert(Replicated {
+            fe_token,
+            request,
+            start: now,
+            write,
+            base: self.next % self.replicas,
+            tried: 0,
+            sent: now,
+            outstanding: 0,
+            acks: 0,
+            responded: false,
+            first: None,
+            error: None,
+        });
+
+        if write {
+            REPLICA_WRITE.increment();
+            for replica in 0..self.replicas {
+                if self.send(sessions, replica, id) {
+                    self.requests[id].outstanding += 1;
+                } else {
+                    REPLICA_WRITE_ERROR.increment();
+                }
+            }
+        } else {
+            REPLICA_READ.increment();
+            self.attempt(sessions, id);
+        }
+
+        self.finish(id, replies);
+    }
+
+    /// Handle a response received from the replica with the provided token.
+    pub fn response<Parser>(
+        &mut self,
+        sessions: &mut Slab<ClientSession<Parser, Request, Response>>,
+        token: Token,
+        request: Request,
+        response: Response,
+        replies: &mut Vec<Reply<Request, Response>>,
+    ) where
+        Parser: Parse<Response>,
+    {
+        let id = match self.pending.get_mut(token.0).and_then(|p| p.pop_front()) {
+            Some(id) => id,
+            None => return,
+        };
+
+        let quorum = self.quorum;
+        let state = &mut self.requests[id];
+        state.outstanding -= 1;
+
+        if response.is_error() {
+            if state.write {
+                REPLICA_WRITE_ERROR.increment();
+            }
+            if !state.responded {
+                state.error = Some((request, response));
+                if !state.write {
+                    REPLICA_FAILOVER.increment();
+                    self.attempt(sessions, id);
+                }
+            }
+        } else {
+            let mut composed = Vec::new();
+            response.compose(&mut composed);
+            match &state.first {
+                Some(first) => {
+                    if *first != composed {
+                        REPLICA_MISMATCH.increment();
+                    }
+                }
+                None => {
+                    state.first = Some(composed);
+                }
+            }
+
+            state.acks += 1;
+            if !state.responded && (!state.write || state.acks >= quorum) {
+                state.responded = true;
+                replies.push((request, Some(response), state.fe_token, state.start));
+            }
+        }
+
+        self.finish(id, replies);
+    }
+
+    /// Handle the loss of the session for a replica. Every request which is
+    /// awaiting a response from that replica is treated as failed.
+    pub fn failed<Parser>(
+        &mut self,
+        sessions: &mut Slab<ClientSession<Parser, Request, Response>>,
+        token: Token,
+        replies: &mut Vec<Reply<Request, Response>>,
+    ) where
+        Parser: Parse<Response>,
+    {
+        let ids: Vec<usize> = match self.pending.get_mut(token.0) {
+            Some(pending) => pending.drain(..).collect(),
+            None => return,
+        };
+
+        for id in ids {
+            let state = &mut self.requests[id];
+            state.outstanding -= 1;
+            if state.write {
+                REPLICA_WRITE_ERROR.increment();
+            } else if !state.responded {
+                REPLICA_FAILOVER.increment();
+                self.attempt(sessions, id);
+            }
+            self.finish(id, replies);
+        }
+    }
+
+    /// Fail over any reads which have not received a response within the
+    /// timeout. The original attempt remains outstanding, and whichever
+    /// replica responds first is used. Reads which have already been tried on
+    /// every replica are answered with an error once they time out.
+    pub fn expire<Parser>(
+        &mut self,
+        sessions: &mut Slab<ClientSession<Parser, Request, Response>>,
+        now: Instant,
+        replies: &mut Vec<Reply<Request, Response>>,
+    ) where
+        Parser: Parse<Response>,
+    {
+        let timeout = self.timeout;
+        let expired: Vec<usize> = self
+            .requests
+            .iter()
+            .filter(|(_, r)| !r.write && !r.responded && (now - r.sent).as_nanos() >= timeout)
+            .map(|(id, _)| id)
+            .collect();
+
+        for id in expired {
+            REPLICA_TIMEOUT.increment();
+            if self.requests[id].tried < self.replicas {
+                REPLICA_FAILOVER.increment();
+                self.attempt(sessions, id);
+            } else {
+                self.timed_out(id, replies);
+            }
+        }
+    }
+
+    // sends a read to the next replica which is available, returning false if
+    // every replica has been tried
+    fn attempt<Parser>(
+        &mut self,
+        sessions: &mut Slab<ClientSession<Parser, Request, Response>>,
+        id: usize,
+    ) -> bool
+    where
+        Parser: Parse<Response>,
+    {
+        while self.requests[id].tried < self.replicas {
+            let state = &mut self.requests[id];
+            let replica = (state.base + state.tried) % self.replicas;
+            state.tried += 1;
+            if self.send(sessions, replica, id) {
+                let state = &mut self.requests[id];
+                state.outstanding += 1;
+                state.sent = Instant::now();
+                return true;
+            }
+        }
+        false
+    }
+
+    // answers a read which has timed out on every replica with an error. the
+    // request is only removed once each outstanding replica has responded or
+    // its session is lost, so that later responses are matched correctly
+    fn timed_out(&mut self, id: usize, replies: &mut Vec<Reply<Request, Response>>) {
+        let state = &mut self.requests[id];
+        state.responded = true;
+        if let Some((request, response)) = state.error.take() {
+            replies.push((request, Some(response), state.fe_token, state.start));
+        } else {
+            REPLICA_UNAVAILABLE.increment();
+            let response = Response::error_response(&state.request, "replica timeout");
+            replies.push((state.request.clone(), response, state.fe_token, state.start));
+        }
+        self.finish(id, replies);
+    }
+
+    // sends a copy of the request to a single replica
+    fn send<Parser>(
+        &mut self,
+        sessions: &mut Slab<ClientSession<Parser, Request, Response>>,
+        replica: usize,
+        id: usize,
+    ) -> bool
+    where
+        Parser: Parse<Response>,
+    {
+        if let Some(session) = sessions.get_mut(replica) {
+            if session.send(self.requests[id].request.clone()).is_ok() {
+                self.pending[replica].push_back(id);
+                return true;
+            }
+        }
+        false
+    }
+
+    // completes the request once no replica is outstanding, returning an
+    // error response if no successful response was returned. if no replica
+    // returned an error either, the error response is built for the request
+    fn finish(&mut self, id: usize, replies: &mut Vec<Reply<Request, Response>>) {
+        if self.requests[id].outstanding > 0 {
+            return;
+        }
+
+        let state = self.requests.remove(id);
+        if state.responded {
+            return;
+        }
+
+        if state.write {
+            REPLICA_QUORUM_FAILED.increment();
+        }
+
+        if let Some((request, response)) = state.error {
+            replies.push((request, Some(response), state.fe_token, state.start));
+        } else {
+            REPLICA_UNAVAILABLE.increment();
+            let response = Response::error_response(&state.request, "no replica available");
+            replies.push((state.request, response, state.fe_token, state.start));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol_common::{BufMut, ParseOk};
+
+    // a message which is identified by its method, where the method `error`
+    // represents an error response
+    #[derive(Clone, Debug, PartialEq)]
+    struct Message(&'static str);
+
+    impl Compose for Message {
+        fn compose(&self, dst: &mut dyn BufMut) -> usize {
+            dst.put_slice(self.0.as_bytes());
+            self.0.len()
+        }
+    }
+
+    impl Method for Message {
+        fn method(&self) -> Option<&str> {
+            Some(self.0)
+        }
+
+        fn is_error(&self) -> bool {
+            self.0 == "error"
+        }
+    }
+
+    impl ErrorResponse<Message> for Message {
+        fn error_response(_request: &Message, _message: &str) -> Option<Self> {
+            Some(Message("unavailable"))
+        }
+    }
+
+    #[derive(Clone)]
+    struct Parser;
+
+    impl Parse<Message> for Parser {
+        fn parse(&self, _buffer: &[u8]) -> Result<ParseOk<Message>> {
+            Err(Error::from(ErrorKind::WouldBlock))
+        }
+    }
+
+    type Sessions = Slab<ClientSession<Parser, Message, Message>>;
+
+    // creates a replica group where `set` is a write, along with a session
+    // for each replica which is connected to a listener that never responds
+    fn replicas(quorum: usize, replicas: usize) -> (Replicas<Message, Message>, Sessions) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut sessions = Slab::new();
+        for _ in 0..replicas {
+            let stream = TcpStream::connect(addr).unwrap();
+            sessions.insert(ClientSession::new(Session::from(stream), Parser));
+        }
+
+        (
+            Replicas {
+                writes: ["set".to_owned()].into_iter().collect(),
+                quorum,
+                timeout: 1_000_000_000,
+                replicas,
+                next: 0,
+                requests: Slab::new(),
+                pending: vec![VecDeque::new(); replicas],
+            },
+            sessions,
+        )
+    }
+
+    fn responses(replies: &mut Vec<Reply<Message, Message>>) -> Vec<Option<Message>> {
+        replies.drain(..).map(|(_, r, _, _)| r).collect()
+    }
+
+    #[test]
+    fn all_failed() {
+        let (mut replicas, mut sessions) = replicas(2, 2);
+        let mut replies = Vec::new();
+
+        // a write where every replica session is lost
+        replicas.request(&mut sessions, Message("set"), Token(7), &mut replies);
+        assert!(replies.is_empty());
+        replicas.failed(&mut sessions, Token(0), &mut replies);
+        assert!(replies.is_empty());
+        replicas.failed(&mut sessions, Token(1), &mut replies);
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].2, Token(7));
+        assert_eq!(responses(&mut replies), vec![Some(Message("unavailable"))]);
+
+        // a read where every replica responds with an error
+        replicas.request(&mut sessions, Message("get"), Token(7), &mut replies);
+        let first = replicas.next % 2;
+        replicas.response(
+            &mut sessions,
+            Token(first),
+            Message("get"),
+            Message("error"),
+            &mut replies,
+        );
+        assert!(replies.is_empty());
+        replicas.response(
+            &mut sessions,
+            Token(1 - first),
+            Message("get"),
+            Message("error"),
+            &mut replies,
+        );
+        assert_eq!(responses(&mut replies), vec![Some(Message("error"))]);
+
+        // a request when no replica is connected is answered immediately
+        let mut sessions: Sessions = Slab::new();
+        replicas.request(&mut sessions, Message("get"), Token(7), &mut replies);
+        assert_eq!(responses(&mut replies), vec![Some(Message("unavailable"))]);
+        assert!(replicas.requests.is_empty());
+    }
+
+    #[test]
+    fn partial_failure() {
+        // a quorum of one replica is met despite an error from the other
+        let (mut replicas, mut sessions) = replicas(1, 2);
+        let mut replies = Vec::new();
+
+        replicas.request(&mut sessions, Message("set"), Token(7), &mut replies);
+        replicas.response(
+            &mut sessions,
+            Token(0),
+            Message("set"),
+            Message("error"),
+            &mut replies,
+        );
+        assert!(replies.is_empty());
+        replicas.response(
+            &mut sessions,
+            Token(1),
+            Message("set"),
+            Message("stored"),
+            &mut replies,
+        );
+        assert_eq!(responses(&mut replies), vec![Some(Message("stored"))]);
+
+        // a read fails over to the next replica after an error
+        replicas.request(&mut sessions, Message("get"), Token(7), &mut replies);
+        let first = replicas.next % 2;
+        replicas.response(
+            &mut sessions,
+            Token(first),
+            Message("get"),
+            Message("error"),
+            &mut replies,
+        );
+        assert!(replies.is_empty());
+        replicas.response(
+            &mut sessions,
+            Token(1 - first),
+            Message("get"),
+            Message("value"),
+            &mut replies,
+        );
+        assert_eq!(responses(&mut replies), vec![Some(Message("value"))]);
+
+        // a quorum of two replicas is not met, and the error is returned
+        let (mut replicas, mut sessions) = self::replicas(2, 2);
+        replicas.request(&mut sessions, Message("set"), Token(7), &mut replies);
+        replicas.response(
+            &mut sessions,
+            Token(0),
+            Message("set"),
+            Message("stored"),
+            &mut replies,
+        );
+        replicas.failed(&mut sessions, Token(1), &mut replies);
+        assert_eq!(responses(&mut replies), vec![Some(Message("unavailable"))]);
+        assert!(replicas.requests.is_empty());
+    }
+
+    #[test]
+    fn all_success() {
+        let (mut replicas, mut sessions) = replicas(2, 3);
+        let mut replies = Vec::new();
+
+        // the client is answered once the quorum is reached, and only once
+        replicas.request(&mut sessions, Message("set"), Token(7), &mut replies);
+        for replica in 0..3 {
+            replicas.response(
+                &mut sessions,
+                Token(replica),
+                Message("set"),
+                Message("stored"),
+                &mut replies,
+            );
+            let expected = if replica == 1 { 1 } else { 0 };
+            assert_eq!(replies.len(), expected);
+            replies.clear();
+        }
+        assert!(replicas.requests.is_empty());
+
+        // a read is only sent to a single replica
+        replicas.request(&mut sessions, Message("get"), Token(7), &mut replies);
+        let first = replicas.next % 3;
+        assert_eq!(replicas.pending.iter().map(|p| p.len()).sum::<usize>(), 1);
+        replicas.response(
+            &mut sessions,
+            Token(first),
+            Message("get"),
+            Message("value"),
+            &mut replies,
+        );
+        assert_eq!(responses(&mut replies), vec![Some(Message("value"))]);
+        assert!(replicas.requests.is_empty());
+    }
+
+    #[test]
+    fn all_stalled() {
+        let (mut replicas, mut sessions) = replicas(1, 2);
+        replicas.timeout = 0;
+        let mut replies = Vec::new();
+
+        // a read fails over to the next replica when the first stalls
+        replicas.request(&mut sessions, Message("get"), Token(7), &mut replies);
+        replicas.expire(&mut sessions, Instant::now(), &mut replies);
+        assert!(replies.is_empty());
+        assert_eq!(replicas.pending.iter().map(|p| p.len()).sum::<usize>(), 2);
+
+        // once every replica has stalled the read times out
+        replicas.expire(&mut sessions, Instant::now(), &mut replies);
+        assert_eq!(replies[0].2, Token(7));
+        assert_eq!(responses(&mut replies), vec![Some(Message("unavailable"))]);
+        replicas.expire(&mut sessions, Instant::now(), &mut replies);
+        assert!(replies.is_empty());
+
+        // late responses are consumed without answering the client again
+        for replica in 0..2 {
+            replicas.response(
+                &mut sessions,
+                Token(replica),
+                Message("get"),
+                Message("value"),
+                &mut replies,
+            );
+        }
+        assert!(replies.is_empty());
+        assert!(replicas.requests.is_empty());
+    }
+}
@@ -0,0 +1,269 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Routing of requests to backend pools by method, and the stats which are
+//! reported for each routed method.
+//!
+//! A route matches a single method by name, or every method with a prefix
+//! when the route ends with `*`. A route for the exact method takes precedence
+//! over a prefix, and the longest matching prefix is used when several match.
+
+use crate::*;
+use protocol_common::Method;
+use rustcommon_metrics::time::Nanoseconds;
+use std::collections::HashMap;
+use std::ops::Range;
+
+type Duration = rustcommon_metrics::time::Duration<Nanoseconds<u64>>;
+
+/// The name of the pool which holds the endpoints of the backend itself.
+pub const DEFAULT_POOL: &str = "default";
+
+// the largest latency, in nanoseconds, which is tracked for each method
+const LATENCY_MAX: u64 = 1_000_000_000;
+
+counter!(
+    ROUTE_DEFAULT,
+    "the number of requests sent to the default pool without a route"
+);
+
+/// Maps methods to the value of the route which matches them.
+struct Routes<T> {
+    exact: HashMap<String, T>,
+    // ordered from the longest to the shortest prefix
+    prefixes: Vec<(String, T)>,
+}
+
+impl<T> Routes<T> {
+    /// Create the table from the route methods and their values. Returns an
+    /// error if more than one route is configured for the same method or
+    /// prefix.
+    fn new<'a, I: IntoIterator<Item = (&'a str, T)>>(routes: I) -> Result<Self> {
+        let mut exact = HashMap::new();
+        let mut prefixes: Vec<(String, T)> = Vec::new();
+
+        for (method, value) in routes {
+            let duplicate = match method.strip_suffix('*') {
+                Some(prefix) => {
+                    if prefixes.iter().any(|(p, _)| p == prefix) {
+                        true
+                    } else {
+                        prefixes.push((prefix.to_owned(), value));
+                        false
+                    }
+                }
+                None => exact.insert(method.to_owned(), value).is_some(),
+            };
+            if duplicate {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!("duplicate route for method: {}", method),
+                ));
+            }
+        }
+
+        prefixes.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+
+        Ok(Self { exact, prefixes })
+    }
+
+    /// Returns the value of the route which matches the method, if any.
+    fn get(&self, method: &str) -> Option<&T> {
+        self.exact.get(method).or_else(|| {
+            self.prefixes
+                .iter()
+                .find(|(prefix, _)| method.starts_with(prefix.as_str()))
+                .map(|(_, value)| value)
+        })
+    }
+}
+
+/// Stats which are reported for a single method.
+pub struct MethodStats {
+    request: DynBoxedMetric<Counter>,
+    error: DynBoxedMetric<Counter>,
+    latency: DynBoxedMetric<Heatmap>,
+}
+
+impl MethodStats {
+    fn new(method: &str) -> Self {
+        // a prefix route is reported as `<prefix>_prefix`
+        let method = match method.strip_suffix('*') {
+            Some(prefix) => format!("{}_prefix", prefix),
+            None => method.to_owned(),
+        };
+        Self {
+            request: DynBoxedMetric::new(Counter::new(), format!("method_{}_request", method)),
+            error: DynBoxedMetric::new(Counter::new(), format!("method_{}_error", method)),
+            latency: DynBoxedMetric::new(
+                Heatmap::new(
+                    LATENCY_MAX,
+                    3,
+                    Duration::from_secs(60),
+                    Duration::from_secs(1),
+                ),
+                format!("method_{}_latency", method),
+            ),
+        }
+    }
+
+    /// Record that a request for this method was sent to a backend.
+    pub fn request(&self) {
+        self.request.increment();
+    }
+
+    /// Record the response for a request which was sent at `start`. A request
+    /// which could not be answered has no response and is an error.
+    pub fn response<T: Method>(&self, response: Option<&T>, start: Instant) {
+        let now = Instant::now();
+        let latency = (now - start).as_nanos();
+        self.latency.increment(now, latency, 1);
+        if response.map(|r| r.is_error()).unwrap_or(true) {
+            self.error.increment();
+        }
+    }
+}
+
+/// The stats for every route, shared by all backend workers.
+#[derive(Clone)]
+pub struct Stats {
+    routes: Arc<Routes<MethodStats>>,
+}
+
+impl Stats {
+    /// Returns the stats of the route which matches the method, if any.
+    pub fn get(&self, method: &str) -> Option<&MethodStats> {
+        self.routes.get(method)
+    }
+}
+
+/// Selects the backend worker for each request based on the method.
+#[derive(Clone)]
+pub struct Router {
+    routes: Arc<Routes<usize>>,
+    pools: Vec<Range<usize>>,
+    next: usize,
+}
+
+impl Router {
+    /// Create a new `Router` from the configured routes. The `pools` are the
+    /// names of the backend pools along with the range of backend workers which
+    /// hold connections to that pool. Returns an error if a route references
+    /// an unknown pool or more than one route is configured for a method.
+    pub fn new(routes: &[Route], pools: &[(String, Range<usize>)]) -> Result<Self> {
+        let mut table = Vec::new();
+
+        for route in routes {
+            let pool = pools
+                .iter()
+                .position(|(name, _)| name == route.pool())
+                .ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::Other,
+                        format!(
+                            "route for method: {} references unknown pool: {}",
+                            route.method(),
+                            route.pool()
+                        ),
+                    )
+                })?;
+            table.push((route.method(), pool));
+        }
+
+        Ok(Self {
+            routes: Arc::new(Routes::new(table)?),
+            pools: pools.iter().map(|(_, workers)| workers.clone()).collect(),
+            next: 0,
+        })
+    }
+
+    /// Returns the index of the backend worker which should handle the
+    /// request. Requests without a route are sent to the default pool, and the
+    /// workers within a pool are selected in a round-robin fashion.
+    pub fn route<T: Method>(&mut self, request: &T) -> usize {
+        let pool = match request.method().and_then(|m| self.routes.get(m)) {
+            Some(pool) => *pool,
+            None => {
+                ROUTE_DEFAULT.increment();
+                0
+            }
+        };
+
+        let workers = &self.pools[pool];
+        self.next = self.next.wrapping_add(1);
+        workers.start + self.next % workers.len()
+    }
+}
+
+/// Creates the stats for every route. Returns an error if more than one route
+/// is configured for a method.
+pub fn stats(routes: &[Route]) -> Result<Stats> {
+    let routes = Routes::new(
+        routes
+            .iter()
+            .map(|r| (r.method(), MethodStats::new(r.method()))),
+    )?;
+    Ok(Stats {
+        routes: Arc::new(routes),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Call(Option<&'static str>);
+
+    impl Method for Call {
+        fn method(&self) -> Option<&str> {
+            self.0
+        }
+    }
+
+    fn router(routes: &[(&'static str, usize)]) -> Router {
+        Router {
+            routes: Arc::new(Routes::new(routes.iter().copied()).unwrap()),
+            pools: vec![0..2, 2..3, 3..4],
+            next: 0,
+        }
+    }
+
+    #[test]
+    fn prefix() {
+        let routes = Routes::new([("get*", 1), ("getUser*", 2), ("getUserById", 3)]).unwrap();
+
+        assert_eq!(routes.get("getUserById"), Some(&3));
+        assert_eq!(routes.get("getUserByName"), Some(&2));
+        assert_eq!(routes.get("getUser"), Some(&2));
+        assert_eq!(routes.get("getTimeline"), Some(&1));
+        assert_eq!(routes.get("setUser"), None);
+        assert_eq!(routes.get("ge"), None);
+    }
+
+    #[test]
+    fn duplicate() {
+        assert!(Routes::new([("getUser", 1), ("getUser", 2)]).is_err());
+        assert!(Routes::new([("get*", 1), ("get*", 2)]).is_err());
+
+        // a method and a prefix with the same name are different routes
+        assert!(Routes::new([("get", 1), ("get*", 2)]).is_ok());
+    }
+
+    #[test]
+    fn default_route() {
+        let mut router = router(&[("getTimeline", 1), ("getUser*", 2)]);
+
+        assert_eq!(router.route(&Call(Some("getTimeline"))), 2);
+        assert_eq!(router.route(&Call(Some("getUserById"))), 3);
+
+        // methods without a route, and requests without a method, are sent
+        // to each worker of the default pool in turn
+        let default = ROUTE_DEFAULT.value();
+        let first = router.route(&Call(Some("setUser")));
+        let second = router.route(&Call(None));
+        assert!(first < 2 && second < 2);
+        assert_ne!(first, second);
+        assert_eq!(ROUTE_DEFAULT.value(), default + 2);
+    }
+}
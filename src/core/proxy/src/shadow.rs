@@ -0,0 +1,324 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Mirroring of requests to a shadow pool. A copy of a fraction of requests is
+//! sent to the shadow pool and the responses are discarded so that they never
+//! reach the client. Optionally, each shadow response is compared against the
+//! primary response for the same request.
+
+use crate::*;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+
+counter!(
+    SHADOW_REQUEST,
+    "the number of requests mirrored to the shadow pool"
+);
+counter!(
+    SHADOW_REQUEST_DROP,
+    "the number of mirrored requests dropped because the queue was full or too many comparisons were outstanding"
+);
+counter!(
+    SHADOW_RESPONSE,
+    "the number of responses received from the shadow pool"
+);
+counter!(
+    SHADOW_MATCH,
+    "the number of shadow responses which matched the primary response"
+);
+counter!(
+    SHADOW_MISMATCH,
+    "the number of shadow responses which differed from the primary response"
+);
+
+// requests which are mirrored to the shadow pool are sent with a token that
+// has this bit set, which allows the responses to be recognized and discarded
+const SHADOW_TOKEN: usize = 1 << (usize::BITS - 2);
+
+// the number of comparisons which may be outstanding before old comparisons
+// are abandoned, such as when the shadow pool stops responding. this is also
+// the number of mirrored requests which may await a primary response, beyond
+// which requests are not mirrored
+const MAX_PENDING: usize = 16 * 1024;
+
+/// Returns true if the token belongs to a request which was mirrored to the
+/// shadow pool.
+pub fn is_shadow(token: Token) -> bool {
+    token.0 & SHADOW_TOKEN != 0
+}
+
+/// The mirrored requests on a frontend session which are awaiting a primary
+/// response.
+#[derive(Clone, Default)]
+struct Session {
+    // the number of requests and primary responses seen on the session
+    requests: u64,
+    responses: u64,
+    // the sequence number of each mirrored request within the session, and its
+    // comparison id, oldest first
+    ids: VecDeque<(u64, usize)>,
+}
+
+/// Selects which requests are mirrored to the shadow pool and compares the
+/// shadow responses against the primary responses.
+#[derive(Clone)]
+pub struct Shadow {
+    workers: Range<usize>,
+    fraction: f64,
+    credit: f64,
+    compare: bool,
+    next: usize,
+    // the mirrored requests awaiting a primary response for each frontend
+    // session, and the total across all the sessions
+    sessions: HashMap<Token, Session>,
+    queued: usize,
+    // the composed response for comparisons where only one of the primary or
+    // shadow responses has been received
+    pending: HashMap<usize, Vec<u8>>,
+}
+
+impl Shadow {
+    /// Create a new `Shadow` from the config. The `pools` are the names of
+    /// the backend pools along with the range of backend workers which hold
+    /// connections to that pool.
+    pub fn new(config: &config::proxy::Shadow, pools: &[(String, Range<usize>)]) -> Result<Self> {
+        let workers = pools
+            .iter()
+            .find(|(name, _)| name == config.pool())
+            .map(|(_, workers)| workers.clone())
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::Other,
+                    format!("shadow references unknown pool: {}", config.pool()),
+                )
+            })?;
+
+        if !(0.0..=1.0).contains(&config.fraction()) {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!(
+                    "shadow fraction must be between 0.0 and 1.0, got: {}",
+                    config.fraction()
+                ),
+            ));
+        }
+
+        Ok(Self {
+            workers,
+            fraction: config.fraction(),
+            credit: 0.0,
+            compare: config.compare(),
+            next: 0,
+            sessions: HashMap::new(),
+            queued: 0,
+            pending: HashMap::new(),
+        })
+    }
+
+    /// Called for every request received on the frontend session. Returns the
+    /// backend worker and token which should be used to mirror the request,
+    /// or `None` if this request should not be mirrored. Requests are dropped
+    /// instead of mirrored while too many comparisons are outstanding.
+    pub fn sample(&mut self, token: Token) -> Option<(usize, Token)> {
+        // requests are mirrored at a steady rate, without randomness
+        self.credit += self.fraction;
+        let mut mirror = false;
+        if self.credit >= 1.0 {
+            self.credit -= 1.0;
+            if self.queued < MAX_PENDING {
+                mirror = true;
+            } else {
+                SHADOW_REQUEST_DROP.increment();
+            }
+        }
+
+        let id = if mirror {
+            self.next = self.next.wrapping_add(1) & (SHADOW_TOKEN - 1);
+            Some(self.next)
+        } else {
+            None
+        };
+
+        if self.compare {
+            let session = self.sessions.entry(token).or_default();
+            session.requests += 1;
+            if let Some(id) = id {
+                session.ids.push_back((session.requests, id));
+                self.queued += 1;
+            }
+        }
+
+        id.map(|id| {
+            SHADOW_REQUEST.increment();
+            (
+                self.workers.start + id % self.workers.len(),
+                Token(SHADOW_TOKEN | id),
+            )
+        })
+    }
+
+    /// Called when a mirrored request could not be sent to the shadow pool.
+    pub fn dropped(&mut self, token: Token) {
+        SHADOW_REQUEST_DROP.increment();
+        if let Some(session) = self.sessions.get_mut(&token) {
+            if session.ids.back().map(|(seq, _)| *seq) == Some(session.requests) {
+                session.ids.pop_back();
+                self.queued -= 1;
+            }
+        }
+    }
+
+    /// Called with each response from the primary pool for the session.
+    pub fn primary<T: Compose>(&mut self, token: Token, response: &T) {
+        if !self.compare {
+            return;
+        }
+
+        let session = match self.sessions.get_mut(&token) {
+            Some(session) => session,
+            None => {
+                return;
+            }
+        };
+        session.responses += 1;
+        if session.ids.front().map(|(seq, _)| *seq) == Some(session.responses) {
+            if let Some((_, id)) = session.ids.pop_front() {
+                self.queued -= 1;
+                self.check(id, response);
+            }
+        }
+    }
+
+    /// Called with each response from the shadow pool.
+    pub fn shadow<T: Compose>(&mut self, token: Token, response: &T) {
+        SHADOW_RESPONSE.increment();
+
+        if self.compare {
+            self.check(token.0 & !SHADOW_TOKEN, response);
+        }
+    }
+
+    /// Called when the frontend session is closed.
+    pub fn close(&mut self, token: Token) {
+        if let Some(session) = self.sessions.remove(&token) {
+            self.queued -= session.ids.len();
+        }
+    }
+
+    // compares the response against the other response for the same request
+    // if it has already been received, otherwise holds onto the response
+    fn check<T: Compose>(&mut self, id: usize, response: &T) {
+        let mut composed = Vec::new();
+        response.compose(&mut composed);
+
+        if let Some(other) = self.pending.remove(&id) {
+            if other == composed {
+                SHADOW_MATCH.increment();
+            } else {
+                SHADOW_MISMATCH.increment();
+            }
+            return;
+        }
+
+        if self.pending.len() >= MAX_PENDING {
+            let next = self.next;
+            self.pending
+                .retain(|id, _| (next.wrapping_sub(*id) & (SHADOW_TOKEN - 1)) < MAX_PENDING);
+        }
+        self.pending.insert(id, composed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Reply(&'static [u8]);
+
+    impl Compose for Reply {
+        fn compose(&self, dst: &mut dyn protocol_common::BufMut) -> usize {
+            dst.put_slice(self.0);
+            self.0.len()
+        }
+    }
+
+    fn shadow(fraction: f64, compare: bool) -> Shadow {
+        Shadow {
+            workers: 4..6,
+            fraction,
+            credit: 0.0,
+            compare,
+            next: 0,
+            sessions: HashMap::new(),
+            queued: 0,
+            pending: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn delivery() {
+        let mut shadow = shadow(0.5, true);
+        let session = Token(1);
+
+        // every other request is mirrored, to each worker of the pool in turn
+        assert_eq!(shadow.sample(session), None);
+        let (worker, first) = shadow.sample(session).unwrap();
+        assert!(is_shadow(first));
+        assert!(!is_shadow(session));
+        assert!((4..6).contains(&worker));
+        assert_eq!(shadow.sample(session), None);
+        let (other, second) = shadow.sample(session).unwrap();
+        assert_ne!(worker, other);
+        assert_eq!(shadow.queued, 2);
+
+        // the shadow response for the first mirrored request arrives before
+        // the primary response, the second after it
+        let matched = SHADOW_MATCH.value();
+        let mismatched = SHADOW_MISMATCH.value();
+        shadow.shadow(first, &Reply(b"coffee"));
+        shadow.primary(session, &Reply(b"tea"));
+        shadow.primary(session, &Reply(b"coffee"));
+        shadow.primary(session, &Reply(b"tea"));
+        shadow.primary(session, &Reply(b"latte"));
+        assert_eq!(shadow.queued, 0);
+        shadow.shadow(second, &Reply(b"mocha"));
+
+        assert_eq!(SHADOW_MATCH.value(), matched + 1);
+        assert_eq!(SHADOW_MISMATCH.value(), mismatched + 1);
+        assert!(shadow.pending.is_empty());
+    }
+
+    #[test]
+    fn cap() {
+        let mut shadow = shadow(1.0, true);
+
+        // primary responses which never arrive leave comparisons outstanding
+        for session in 0..MAX_PENDING {
+            assert!(shadow.sample(Token(session)).is_some());
+        }
+        assert_eq!(shadow.queued, MAX_PENDING);
+
+        // further requests are dropped rather than mirrored
+        let session = Token(MAX_PENDING);
+        assert_eq!(shadow.sample(session), None);
+        assert_eq!(shadow.queued, MAX_PENDING);
+
+        // which does not change the order of the primary responses
+        shadow.primary(session, &Reply(b"coffee"));
+        assert_eq!(shadow.queued, MAX_PENDING);
+
+        // once comparisons complete, requests are mirrored again
+        shadow.close(Token(0));
+        shadow.primary(Token(1), &Reply(b"coffee"));
+        assert_eq!(shadow.queued, MAX_PENDING - 2);
+        assert!(shadow.sample(session).is_some());
+        assert!(shadow.sample(session).is_some());
+        assert_eq!(shadow.sample(session), None);
+
+        // a mirrored request which could not be sent is not compared
+        shadow.close(session);
+        shadow.dropped(Token(2));
+        assert_eq!(shadow.queued, MAX_PENDING - 3);
+    }
+}
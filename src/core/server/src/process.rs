@@ -42,6 +42,13 @@ where
         self
     }
 
+    /// Set the range of sizes, in bytes, the storage heap may be resized to
+    /// with the admin `resize` command.
+    pub fn heap_size_range(mut self, range: std::ops::RangeInclusive<usize>) -> Self {
+        self.admin.heap_size_range(range);
+        self
+    }
+
     pub fn spawn(self) -> Process {
         let mut thread_wakers = vec![self.listener.waker()];
         thread_wakers.extend_from_slice(&self.workers.wakers());
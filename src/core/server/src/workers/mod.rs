@@ -3,6 +3,8 @@
 // http://www.apache.org/licenses/LICENSE-2.0
 
 use crate::*;
+use common::signal::Completion;
+use std::path::PathBuf;
 use std::thread::JoinHandle;
 
 mod multi;
@@ -31,6 +33,10 @@ counter!(WORKER_EVENT_READ, "the number of read events received");
 counter!(WORKER_EVENT_TOTAL, "the total number of events received");
 counter!(WORKER_EVENT_WRITE, "the number of write events received");
 
+/// Executes requests which only read from storage on the worker threads, if
+/// the storage supports it
+type Shared<Request, Response> = Option<Arc<dyn ExecuteShared<Request, Response>>>;
+
 fn map_result(result: Result<usize>) -> Result<()> {
     match result {
         Ok(0) => Err(Error::new(ErrorKind::Other, "client hangup")),
@@ -39,6 +45,112 @@ fn map_result(result: Result<usize>) -> Result<()> {
     }
 }
 
+/// A dump or load which is in progress, along with the completion which is
+/// used to report its outcome to the admin thread.
+struct Task {
+    verb: &'static str,
+    path: PathBuf,
+    completion: Completion,
+}
+
+/// Begin writing the contents of storage to a file. Returns the task which is
+/// in progress, or `None` if the dump failed to start.
+fn dump<Storage: EntryStore>(
+    storage: &mut Storage,
+    path: PathBuf,
+    completion: Completion,
+) -> Option<Task> {
+    info!("dumping storage to: {}", path.display());
+    start(storage.dump(&path), "dump", path, completion)
+}
+
+/// Begin loading the contents of storage from a file. Returns the task which
+/// is in progress, or `None` if the load failed to start.
+fn load<Storage: EntryStore>(
+    storage: &mut Storage,
+    path: PathBuf,
+    completion: Completion,
+) -> Option<Task> {
+    info!("loading storage from: {}", path.display());
+    start(storage.load(&path), "load", path, completion)
+}
+
+fn start(
+    result: Result<()>,
+    verb: &'static str,
+    path: PathBuf,
+    completion: Completion,
+) -> Option<Task> {
+    match result {
+        Ok(()) => Some(Task {
+            verb,
+            path,
+            completion,
+        }),
+        Err(e) => {
+            error!("failed to {} storage: {}: {}", verb, path.display(), e);
+            completion.complete(Err(e));
+            None
+        }
+    }
+}
+
+/// Reports the outcome of the dump or load which is in progress once it has
+/// completed, logging the result
+fn finish<Storage: EntryStore>(storage: &mut Storage, task: &mut Option<Task>) {
+    let result = match storage.finished() {
+        Some(result) => result,
+        None => return,
+    };
+    if let Some(task) = task.take() {
+        match &result {
+            Ok(count) => info!(
+                "completed {} of {} items: {}",
+                task.verb,
+                count,
+                task.path.display()
+            ),
+            Err(e) => error!(
+                "failed to {} storage: {}: {}",
+                task.verb,
+                task.path.display(),
+                e
+            ),
+        }
+        task.completion.complete(result.map(|_| ()));
+    }
+}
+
+/// Resize the storage heap, logging the result and reporting it to the
+/// completion
+fn resize<Storage: EntryStore>(storage: &mut Storage, heap_size: usize, completion: Completion) {
+    info!("resizing storage heap to: {} bytes", heap_size);
+    let result = storage.resize(heap_size);
+    match &result {
+        Ok(()) => info!("set storage heap size to: {} bytes", heap_size),
+        Err(e) => error!(
+            "failed to resize storage heap to: {} bytes: {}",
+            heap_size, e
+        ),
+    }
+    completion.complete(result);
+}
+
+/// Checkpoint the storage if a checkpoint is due, logging any failure
+fn checkpoint<Storage: EntryStore>(storage: &mut Storage) {
+    if let Err(e) = storage.checkpoint() {
+        error!("failed to checkpoint storage: {}", e);
+    }
+}
+
+/// Persist the storage on shutdown, logging the result
+fn shutdown<Storage: EntryStore>(storage: &mut Storage) {
+    info!("closing storage");
+    if let Err(e) = storage.close() {
+        error!("failed to close storage: {}", e);
+    }
+}
+
 pub enum Workers<Parser, Request, Response, Storage> {
     Single {
         worker: SingleWorker<Parser, Request, Response, Storage>,
@@ -104,13 +216,18 @@ where
     Response: Compose,
     Storage: Execute<Request, Response> + EntryStore,
 {
-    pub fn new<T: WorkerConfig>(config: &T, parser: Parser, storage: Storage) -> Result<Self> {
+    pub fn new<T: WorkerConfig>(config: &T, parser: Parser, mut storage: Storage) -> Result<Self> {
         let threads = config.worker().threads();
 
         if threads > 1 {
+            let shared = storage.shared();
             let mut workers = vec![];
             for _ in 0..threads {
-                workers.push(MultiWorkerBuilder::new(config, parser.clone())?)
+                workers.push(MultiWorkerBuilder::new(
+                    config,
+                    parser.clone(),
+                    shared.clone(),
+                )?)
             }
 
             Ok(Self::Multi {
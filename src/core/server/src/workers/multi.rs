@@ -9,12 +9,17 @@ pub struct MultiWorkerBuilder<Parser, Request, Response> {
     parser: Parser,
     poll: Poll,
     sessions: Slab<ServerSession<Parser, Response, Request>>,
+    shared: Shared<Request, Response>,
     timeout: Duration,
     waker: Arc<Waker>,
 }
 
 impl<Parser, Request, Response> MultiWorkerBuilder<Parser, Request, Response> {
-    pub fn new<T: WorkerConfig>(config: &T, parser: Parser) -> Result<Self> {
+    pub fn new<T: WorkerConfig>(
+        config: &T,
+        parser: Parser,
+        shared: Shared<Request, Response>,
+    ) -> Result<Self> {
         let config = config.worker();
 
         let poll = Poll::new()?;
@@ -31,6 +36,7 @@ impl<Parser, Request, Response> MultiWorkerBuilder<Parser, Request, Response> {
             parser,
             poll,
             sessions: Slab::new(),
+            shared,
             timeout,
             waker,
         })
@@ -53,6 +59,7 @@ impl<Parser, Request, Response> MultiWorkerBuilder<Parser, Request, Response> {
             poll: self.poll,
             session_queue,
             sessions: self.sessions,
+            shared: self.shared,
             signal_queue,
             timeout: self.timeout,
             waker: self.waker,
@@ -67,6 +74,7 @@ pub struct MultiWorker<Parser, Request, Response> {
     poll: Poll,
     session_queue: Queues<Session, Session>,
     sessions: Slab<ServerSession<Parser, Response, Request>>,
+    shared: Shared<Request, Response>,
     signal_queue: Queues<(), Signal>,
     timeout: Duration,
     waker: Arc<Waker>,
@@ -98,16 +106,79 @@ where
         // fill the session
         map_result(session.fill())?;
 
-        // process up to one request
-        match session.receive() {
-            Ok(request) => self
-                .data_queue
-                .try_send_to(0, (request, token))
-                .map_err(|_| Error::new(ErrorKind::Other, "data queue is full")),
-            Err(e) => map_err(e),
+        self.receive(token)
+    }
+
+    /// Handle the requests which have been read for a session. Requests which
+    /// storage can execute on this thread are responded to immediately, and
+    /// up to one other request is sent to the storage thread.
+    fn receive(&mut self, token: Token) -> Result<()> {
+        loop {
+            let session = self
+                .sessions
+                .get_mut(token.0)
+                .ok_or_else(|| Error::new(ErrorKind::Other, "non-existant session"))?;
+
+            let request = match session.receive() {
+                Ok(request) => request,
+                Err(e) => return map_err(e),
+            };
+
+            let response = match self
+                .shared
+                .as_ref()
+                .and_then(|s| s.execute_shared(&request))
+            {
+                Some(response) => response,
+                None => {
+                    return self
+                        .data_queue
+                        .try_send_to(0, (request, token))
+                        .map_err(|_| Error::new(ErrorKind::Other, "data queue is full"));
+                }
+            };
+
+            self.respond(token, request, response)?;
+
+            match self.sessions.get(token.0) {
+                Some(session) if session.remaining() > 0 => {}
+                _ => return Ok(()),
+            }
         }
     }
 
+    /// Send the response to a request, returns an error if the session should
+    /// be closed
+    fn respond(&mut self, token: Token, request: Request, response: Response) -> Result<()> {
+        request.klog(&response);
+        let session = self
+            .sessions
+            .get_mut(token.0)
+            .ok_or_else(|| Error::new(ErrorKind::Other, "non-existant session"))?;
+
+        if response.should_hangup() {
+            let _ = session.send(response);
+            return Err(Error::new(ErrorKind::Other, "hangup"));
+        }
+        session.send(response)?;
+
+        if session.write_pending() > 0 {
+            // try to immediately flush, if we still have pending bytes,
+            // reregister. This saves us one syscall when flushing would not
+            // block.
+            if let Err(e) = session.flush() {
+                map_err(e)?;
+            }
+
+            if session.write_pending() > 0 {
+                let interest = session.interest();
+                session.reregister(self.poll.registry(), token, interest)?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Handle write by flushing the session
     fn write(&mut self, token: Token) -> Result<()> {
         let session = self
@@ -175,43 +246,20 @@ where
                         self.data_queue.try_recv_all(&mut messages);
                         for (request, response, token) in messages.drain(..).map(|v| v.into_inner())
                         {
-                            request.klog(&response);
-                            if let Some(session) = self.sessions.get_mut(token.0) {
-                                if response.should_hangup() {
-                                    let _ = session.send(response);
-                                    self.close(token);
-                                    continue;
-                                } else if session.send(response).is_err() {
-                                    self.close(token);
-                                    continue;
-                                } else if session.write_pending() > 0 {
-                                    // try to immediately flush, if we still
-                                    // have pending bytes, reregister. This
-                                    // saves us one syscall when flushing would
-                                    // not block.
-                                    if let Err(e) = session.flush() {
-                                        if map_err(e).is_err() {
-                                            self.close(token);
-                                            continue;
-                                        }
-                                    }
-
-                                    if session.write_pending() > 0 {
-                                        let interest = session.interest();
-                                        if session
-                                            .reregister(self.poll.registry(), token, interest)
-                                            .is_err()
-                                        {
-                                            self.close(token);
-                                            continue;
-                                        }
-                                    }
-                                }
+                            if !self.sessions.contains(token.0) {
+                                request.klog(&response);
+                                continue;
+                            }
 
-                                if session.remaining() > 0 && self.read(token).is_err() {
-                                    self.close(token);
-                                    continue;
-                                }
+                            if self.respond(token, request, response).is_err() {
+                                self.close(token);
+                                continue;
+                            }
+
+                            let remaining = self.sessions[token.0].remaining();
+                            if remaining > 0 && self.read(token).is_err() {
+                                self.close(token);
+                                continue;
                             }
                         }
 
@@ -220,7 +268,10 @@ where
                             self.signal_queue.try_recv().map(|v| v.into_inner())
                         {
                             match signal {
-                                Signal::FlushAll => {}
+                                Signal::Dump(..)
+                                | Signal::FlushAll
+                                | Signal::Load(..)
+                                | Signal::Resize(..) => {}
                                 Signal::Shutdown => {
                                     // if we received a shutdown, we can return
                                     // and stop processing events
@@ -59,6 +59,7 @@ impl<Parser, Request, Response, Storage> SingleWorkerBuilder<Parser, Request, Re
             sessions: self.sessions,
             signal_queue,
             storage: self.storage,
+            task: None,
             timeout: self.timeout,
             waker: self.waker,
         }
@@ -74,6 +75,7 @@ pub struct SingleWorker<Parser, Request, Response, Storage> {
     sessions: Slab<ServerSession<Parser, Response, Request>>,
     signal_queue: Queues<(), Signal>,
     storage: Storage,
+    task: Option<Task>,
     timeout: Duration,
     waker: Arc<Waker>,
 }
@@ -185,7 +187,15 @@ where
         loop {
             WORKER_EVENT_LOOP.increment();
 
-            self.storage.expire();
+            // expiration is bounded by the storage, if there is expiration
+            // work remaining we poll without waiting so it continues promptly
+            let timeout = if self.storage.expire() {
+                Duration::ZERO
+            } else {
+                self.timeout
+            };
+            finish(&mut self.storage, &mut self.task);
+            checkpoint(&mut self.storage);
 
             // we need another wakeup if there are still pending reads
             if !self.pending.is_empty() {
@@ -193,7 +203,7 @@ where
             }
 
             // get events with timeout
-            if self.poll.poll(&mut events, Some(self.timeout)).is_err() {
+            if self.poll.poll(&mut events, Some(timeout)).is_err() {
                 error!("Error polling");
             }
 
@@ -245,12 +255,27 @@ where
                         // check if we received any signals from the admin thread
                         while let Some(signal) = self.signal_queue.try_recv() {
                             match signal.into_inner() {
+                                Signal::Dump(path, completion) => {
+                                    if let Some(task) = dump(&mut self.storage, path, completion) {
+                                        self.task = Some(task);
+                                    }
+                                }
                                 Signal::FlushAll => {
                                     self.storage.clear();
                                 }
+                                Signal::Load(path, completion) => {
+                                    if let Some(task) = load(&mut self.storage, path, completion) {
+                                        self.task = Some(task);
+                                    }
+                                }
+                                Signal::Resize(heap_size, completion) => {
+                                    resize(&mut self.storage, heap_size, completion);
+                                }
                                 Signal::Shutdown => {
-                                    // if we received a shutdown, we can return
-                                    // and stop processing events
+                                    // if we received a shutdown, we can persist
+                                    // the storage, return, and stop processing
+                                    // events
+                                    shutdown(&mut self.storage);
                                     return;
                                 }
                             }
@@ -2,6 +2,7 @@
 // Licensed under the Apache License, Version 2.0
 // http://www.apache.org/licenses/LICENSE-2.0
 
+use super::{checkpoint, dump, finish, load, resize, shutdown, Task};
 use crate::*;
 
 counter!(
@@ -63,6 +64,7 @@ impl<Request, Response, Storage> StorageWorkerBuilder<Request, Response, Storage
             poll: self.poll,
             signal_queue,
             storage: self.storage,
+            task: None,
             timeout: self.timeout,
             waker: self.waker,
             _request: PhantomData,
@@ -77,6 +79,7 @@ pub struct StorageWorker<Request, Response, Storage, Token> {
     poll: Poll,
     signal_queue: Queues<(), Signal>,
     storage: Storage,
+    task: Option<Task>,
     timeout: Duration,
     #[allow(dead_code)]
     waker: Arc<Waker>,
@@ -98,10 +101,18 @@ where
         loop {
             STORAGE_EVENT_LOOP.increment();
 
-            self.storage.expire();
+            // expiration is bounded by the storage, if there is expiration
+            // work remaining we poll without waiting so it continues promptly
+            let timeout = if self.storage.expire() {
+                Duration::ZERO
+            } else {
+                self.timeout
+            };
+            finish(&mut self.storage, &mut self.task);
+            checkpoint(&mut self.storage);
 
             // get events with timeout
-            if self.poll.poll(&mut events, Some(self.timeout)).is_err() {
+            if self.poll.poll(&mut events, Some(timeout)).is_err() {
                 error!("Error polling");
             }
 
@@ -142,17 +153,27 @@ where
                 // check if we received any signals from the admin thread
                 while let Some(s) = self.signal_queue.try_recv().map(|v| v.into_inner()) {
                     match s {
+                        Signal::Dump(path, completion) => {
+                            if let Some(task) = dump(&mut self.storage, path, completion) {
+                                self.task = Some(task);
+                            }
+                        }
                         Signal::FlushAll => {
                             warn!("received flush_all");
                             self.storage.clear();
                         }
+                        Signal::Load(path, completion) => {
+                            if let Some(task) = load(&mut self.storage, path, completion) {
+                                self.task = Some(task);
+                            }
+                        }
+                        Signal::Resize(heap_size, completion) => {
+                            resize(&mut self.storage, heap_size, completion);
+                        }
                         Signal::Shutdown => {
-                            // if we received a shutdown, we can return and stop
-                            // processing events
-
-                            // TODO(bmartin): graceful shutdown would occur here
-                            // when we add persistence
-
+                            // if we received a shutdown, we can persist the
+                            // storage, return, and stop processing events
+                            shutdown(&mut self.storage);
                             return;
                         }
                     }
@@ -7,6 +7,8 @@
 //! addition to the base `EntryStore` trait. For example [`Seg`] implements both
 //! [`EntryStore`] and [`protocol::memcache::MemcacheStorage`].
 
+use std::path::Path;
+
 mod noop;
 mod seg;
 
@@ -20,8 +22,69 @@ pub trait EntryStore {
     /// will be able to efficiently implement this function. The default
     /// implementation is a no-op. Types which can efficiently implement eager
     /// expiration should implement their own handling logic for this function.
-    fn expire(&mut self) {}
+    ///
+    /// Returns true if there is expiration work remaining, in which case this
+    /// should be called again without waiting for new events.
+    fn expire(&mut self) -> bool {
+        false
+    }
 
     /// Remove all existing values from the entry store.
     fn clear(&mut self);
+
+    /// Begin writing the contents of the entry store to a file at the
+    /// provided path. The dump is written a part at a time by `expire()` so
+    /// that requests continue to be served, and its outcome is returned by
+    /// `finished()`. The default implementation returns an error for storage
+    /// types which do not support dumps.
+    fn dump(&mut self, _path: &Path) -> Result<(), std::io::Error> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "dump is not supported",
+        ))
+    }
+
+    /// Begin loading the contents of a file written by `dump()` into the
+    /// entry store. Like a dump, the load is made a part at a time by
+    /// `expire()` and its outcome is returned by `finished()`. The default
+    /// implementation returns an error for storage types which do not support
+    /// dumps.
+    fn load(&mut self, _path: &Path) -> Result<(), std::io::Error> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "load is not supported",
+        ))
+    }
+
+    /// Returns the outcome of a dump or load once it has completed, which is
+    /// the number of entries written or loaded.
+    fn finished(&mut self) -> Option<Result<usize, std::io::Error>> {
+        None
+    }
+
+    /// Resize the memory used to hold entries to the provided size in bytes.
+    /// The default implementation returns an error for storage types which
+    /// cannot be resized.
+    fn resize(&mut self, _heap_size: usize) -> Result<(), std::io::Error> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "resize is not supported",
+        ))
+    }
+
+    /// Persist any state which is kept in files, if a checkpoint is due. This
+    /// is called regularly by the thread which owns the entry store, between
+    /// handling requests. The default implementation is a no-op for storage
+    /// types which do not persist state.
+    fn checkpoint(&mut self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
+    /// Persist any state which is kept in files so that it may be restored
+    /// when the entry store is next created. This is called on graceful
+    /// shutdown. The default implementation is a no-op for storage types which
+    /// do not persist state.
+    fn close(&mut self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
 }
@@ -21,7 +21,9 @@ impl Noop {
 }
 
 impl EntryStore for Noop {
-    fn expire(&mut self) {}
+    fn expire(&mut self) -> bool {
+        false
+    }
 
     fn clear(&mut self) {}
 }
@@ -9,8 +9,10 @@ use super::*;
 use protocol_common::*;
 
 use protocol_memcache::*;
+use seg::LeaseResult;
 
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
 
 impl Execute<Request, Response> for Seg {
     fn execute(&mut self, request: &Request) -> Response {
@@ -22,6 +24,9 @@ impl Execute<Request, Response> for Seg {
             Request::Replace(replace) => self.replace(replace),
             Request::Cas(cas) => self.cas(cas),
             Request::Incr(incr) => self.incr(incr),
+            Request::Invalidate(invalidate) => self.invalidate(invalidate),
+            Request::MetaDebug(meta_debug) => self.meta_debug(meta_debug),
+            Request::MetaGet(meta_get) => self.meta_get(meta_get),
             Request::Decr(decr) => self.decr(decr),
             Request::Append(append) => self.append(append),
             Request::Prepend(prepend) => self.prepend(prepend),
@@ -30,13 +35,59 @@ impl Execute<Request, Response> for Seg {
             Request::Quit(quit) => self.quit(quit),
         }
     }
+
+    fn shared(&mut self) -> Option<Arc<dyn ExecuteShared<Request, Response>>> {
+        self.reader()
+            .map(|reader| Arc::new(reader) as Arc<dyn ExecuteShared<Request, Response>>)
+    }
+}
+
+impl ExecuteShared<Request, Response> for SegReader {
+    fn execute_shared(&self, request: &Request) -> Option<Response> {
+        match request {
+            Request::Get(get) => self.values(get.keys(), false),
+            Request::Gets(gets) => self.values(gets.keys(), true),
+            _ => None,
+        }
+    }
+}
+
+impl SegReader {
+    // reads the values for a get or gets, or returns `None` if any of the keys
+    // must be read by the storage thread
+    fn values(&self, keys: &[Box<[u8]>], cas: bool) -> Option<Response> {
+        let mut values = Vec::with_capacity(keys.len());
+        for key in keys.iter() {
+            if let Some(item) = self.data.get(key).ok()? {
+                let o = item.optional().unwrap_or(&[0, 0, 0, 0]);
+                let flags = u32::from_be_bytes([o[0], o[1], o[2], o[3]]);
+                let cas = cas.then(|| item.cas());
+                match item.value() {
+                    seg::Value::Bytes(b) => {
+                        values.push(Value::new(item.key(), flags, cas, b));
+                    }
+                    seg::Value::U64(v) => {
+                        values.push(Value::new(
+                            item.key(),
+                            flags,
+                            cas,
+                            format!("{}", v).as_bytes(),
+                        ));
+                    }
+                }
+            } else {
+                values.push(Value::none(key));
+            }
+        }
+        Some(Values::new(values.into_boxed_slice()).into())
+    }
 }
 
 impl Storage for Seg {
     fn get(&mut self, get: &Get) -> Response {
         let mut values = Vec::with_capacity(get.keys().len());
         for key in get.keys().iter() {
-            if let Some(item) = self.data.get(key) {
+            if let Some(item) = self.data.write().get(key) {
                 let o = item.optional().unwrap_or(&[0, 0, 0, 0]);
                 let flags = u32::from_be_bytes([o[0], o[1], o[2], o[3]]);
                 match item.value() {
@@ -62,18 +113,18 @@ impl Storage for Seg {
     fn gets(&mut self, get: &Gets) -> Response {
         let mut values = Vec::with_capacity(get.keys().len());
         for key in get.keys().iter() {
-            if let Some(item) = self.data.get(key) {
+            if let Some(item) = self.data.write().get(key) {
                 let o = item.optional().unwrap_or(&[0, 0, 0, 0]);
                 let flags = u32::from_be_bytes([o[0], o[1], o[2], o[3]]);
                 match item.value() {
                     seg::Value::Bytes(b) => {
-                        values.push(Value::new(item.key(), flags, Some(item.cas().into()), b));
+                        values.push(Value::new(item.key(), flags, Some(item.cas()), b));
                     }
                     seg::Value::U64(v) => {
                         values.push(Value::new(
                             item.key(),
                             flags,
-                            Some(item.cas().into()),
+                            Some(item.cas()),
                             format!("{}", v).as_bytes(),
                         ));
                     }
@@ -88,50 +139,30 @@ impl Storage for Seg {
     fn set(&mut self, set: &Set) -> Response {
         let ttl = set.ttl().get().unwrap_or(0);
 
+        // a refill is dropped if the lease it was made under has ended
+        if let Some(token) = set.lease() {
+            if !self.data.write().release_lease(set.key(), token) {
+                return Response::not_stored(set.noreply());
+            }
+        }
+
         if ttl < 0 {
             // immediate expire maps to a delete
-            self.data.delete(set.key());
+            self.data.write().delete(set.key());
             Response::stored(set.noreply())
         } else if let Ok(s) = std::str::from_utf8(set.value()) {
             if let Ok(v) = s.parse::<u64>() {
-                if self
-                    .data
-                    .insert(
-                        set.key(),
-                        v,
-                        Some(&set.flags().to_be_bytes()),
-                        Duration::from_secs(ttl as u64),
-                    )
-                    .is_ok()
-                {
+                if self.insert_set(set, v).is_ok() {
                     Response::stored(set.noreply())
                 } else {
                     Response::server_error("")
                 }
-            } else if self
-                .data
-                .insert(
-                    set.key(),
-                    set.value(),
-                    Some(&set.flags().to_be_bytes()),
-                    Duration::from_secs(ttl as u64),
-                )
-                .is_ok()
-            {
+            } else if self.insert_set(set, set.value()).is_ok() {
                 Response::stored(set.noreply())
             } else {
                 Response::server_error("")
             }
-        } else if self
-            .data
-            .insert(
-                set.key(),
-                set.value(),
-                Some(&set.flags().to_be_bytes()),
-                Duration::from_secs(ttl as u64),
-            )
-            .is_ok()
-        {
+        } else if self.insert_set(set, set.value()).is_ok() {
             Response::stored(set.noreply())
         } else {
             Response::server_error("")
@@ -139,7 +170,7 @@ impl Storage for Seg {
     }
 
     fn add(&mut self, add: &Add) -> Response {
-        if self.data.get_no_freq_incr(add.key()).is_some() {
+        if self.data.write().get_no_freq_incr(add.key()).is_some() {
             return Response::not_stored(add.noreply());
         }
 
@@ -147,12 +178,13 @@ impl Storage for Seg {
 
         if ttl < 0 {
             // immediate expire maps to a delete
-            self.data.delete(add.key());
+            self.data.write().delete(add.key());
             Response::stored(add.noreply())
         } else if let Ok(s) = std::str::from_utf8(add.value()) {
             if let Ok(v) = s.parse::<u64>() {
                 if self
                     .data
+                    .write()
                     .insert(
                         add.key(),
                         v,
@@ -167,6 +199,7 @@ impl Storage for Seg {
                 }
             } else if self
                 .data
+                .write()
                 .insert(
                     add.key(),
                     add.value(),
@@ -181,6 +214,7 @@ impl Storage for Seg {
             }
         } else if self
             .data
+            .write()
             .insert(
                 add.key(),
                 add.value(),
@@ -196,7 +230,7 @@ impl Storage for Seg {
     }
 
     fn replace(&mut self, replace: &Replace) -> Response {
-        if self.data.get_no_freq_incr(replace.key()).is_none() {
+        if self.data.write().get_no_freq_incr(replace.key()).is_none() {
             return Response::not_stored(replace.noreply());
         }
 
@@ -204,12 +238,13 @@ impl Storage for Seg {
 
         if ttl < 0 {
             // immediate expire maps to a delete
-            self.data.delete(replace.key());
+            self.data.write().delete(replace.key());
             Response::stored(replace.noreply())
         } else if let Ok(s) = std::str::from_utf8(replace.value()) {
             if let Ok(v) = s.parse::<u64>() {
                 if self
                     .data
+                    .write()
                     .insert(
                         replace.key(),
                         v,
@@ -224,6 +259,7 @@ impl Storage for Seg {
                 }
             } else if self
                 .data
+                .write()
                 .insert(
                     replace.key(),
                     replace.value(),
@@ -238,6 +274,7 @@ impl Storage for Seg {
             }
         } else if self
             .data
+            .write()
             .insert(
                 replace.key(),
                 replace.value(),
@@ -252,16 +289,24 @@ impl Storage for Seg {
         }
     }
 
-    fn append(&mut self, _: &Append) -> Response {
-        Response::error()
+    fn append(&mut self, append: &Append) -> Response {
+        match self.data.write().append(append.key(), append.value()) {
+            Ok(_) => Response::stored(append.noreply()),
+            Err(SegError::NotFound) => Response::not_stored(append.noreply()),
+            Err(_) => Response::server_error(""),
+        }
     }
 
-    fn prepend(&mut self, _: &Prepend) -> Response {
-        Response::error()
+    fn prepend(&mut self, prepend: &Prepend) -> Response {
+        match self.data.write().prepend(prepend.key(), prepend.value()) {
+            Ok(_) => Response::stored(prepend.noreply()),
+            Err(SegError::NotFound) => Response::not_stored(prepend.noreply()),
+            Err(_) => Response::server_error(""),
+        }
     }
 
     fn incr(&mut self, incr: &Incr) -> Response {
-        match self.data.wrapping_add(incr.key(), incr.value()) {
+        match self.data.write().wrapping_add(incr.key(), incr.value()) {
             Ok(item) => match item.value() {
                 seg::Value::U64(v) => Response::numeric(v, incr.noreply()),
                 _ => Response::server_error(""),
@@ -272,8 +317,65 @@ impl Storage for Seg {
         }
     }
 
+    fn invalidate(&mut self, invalidate: &Invalidate) -> Response {
+        self.data.write().invalidate_tag(invalidate.tag());
+        Response::deleted(invalidate.noreply())
+    }
+
+    fn meta_debug(&mut self, meta_debug: &MetaDebug) -> Response {
+        // the lookup should not count as an access of the item
+        if let Some(item) = self.data.write().get_no_freq_incr(meta_debug.key()) {
+            // items which do not expire are reported with -1
+            let expire_at = item
+                .expire_at()
+                .map(|e| {
+                    e.duration_since(UNIX_EPOCH)
+                        .map_or(0, |d| d.as_secs() as i64)
+                })
+                .unwrap_or(-1);
+            let ttl = item.ttl().map(|ttl| ttl.as_secs() as i64).unwrap_or(-1);
+            DebugInfo::new(
+                item.key(),
+                ttl,
+                expire_at,
+                item.age().as_secs(),
+                item.freq(),
+                item.cas(),
+            )
+            .into()
+        } else {
+            DebugInfo::miss().into()
+        }
+    }
+
+    fn meta_get(&mut self, meta_get: &MetaGet) -> Response {
+        let key = meta_get.key();
+        let timeout = match meta_get.lease() {
+            Some(timeout) => Duration::from_secs(timeout as u64),
+            None => {
+                return match self.data.write().get(key) {
+                    Some(item) => meta_value(&item, meta_get).into(),
+                    None => MetaValue::miss().into(),
+                };
+            }
+        };
+
+        match self.data.write().get_lease(key, timeout) {
+            Ok(LeaseResult::Hit(item)) => meta_value(&item, meta_get),
+            Ok(LeaseResult::Win {
+                token,
+                stale: Some(item),
+            }) => meta_value(&item, meta_get).with_stale().with_win(token),
+            Ok(LeaseResult::Win { token, stale: None }) => MetaValue::miss().with_win(token),
+            Ok(LeaseResult::Stale(item)) => meta_value(&item, meta_get).with_stale().with_wait(),
+            Ok(LeaseResult::Wait) => MetaValue::miss().with_wait(),
+            Err(e) => return Response::client_error(e),
+        }
+        .into()
+    }
+
     fn decr(&mut self, decr: &Decr) -> Response {
-        match self.data.saturating_sub(decr.key(), decr.value()) {
+        match self.data.write().saturating_sub(decr.key(), decr.value()) {
             Ok(item) => match item.value() {
                 seg::Value::U64(v) => Response::numeric(v, decr.noreply()),
                 _ => Response::server_error(""),
@@ -299,12 +401,12 @@ impl Storage for Seg {
 
         if let Ok(s) = std::str::from_utf8(cas.value()) {
             if let Ok(v) = s.parse::<u64>() {
-                match self.data.cas(
+                match self.data.write().cas(
                     cas.key(),
                     v,
                     Some(&cas.flags().to_be_bytes()),
                     ttl,
-                    cas.cas() as u32,
+                    cas.cas(),
                 ) {
                     Ok(_) => Response::stored(cas.noreply()),
                     Err(SegError::NotFound) => Response::not_found(cas.noreply()),
@@ -312,12 +414,12 @@ impl Storage for Seg {
                     Err(_) => Response::error(),
                 }
             } else {
-                match self.data.cas(
+                match self.data.write().cas(
                     cas.key(),
                     cas.value(),
                     Some(&cas.flags().to_be_bytes()),
                     ttl,
-                    cas.cas() as u32,
+                    cas.cas(),
                 ) {
                     Ok(_) => Response::stored(cas.noreply()),
                     Err(SegError::NotFound) => Response::not_found(cas.noreply()),
@@ -326,12 +428,12 @@ impl Storage for Seg {
                 }
             }
         } else {
-            match self.data.cas(
+            match self.data.write().cas(
                 cas.key(),
                 cas.value(),
                 Some(&cas.flags().to_be_bytes()),
                 ttl,
-                cas.cas() as u32,
+                cas.cas(),
             ) {
                 Ok(_) => Response::stored(cas.noreply()),
                 Err(SegError::NotFound) => Response::not_found(cas.noreply()),
@@ -342,7 +444,7 @@ impl Storage for Seg {
     }
 
     fn delete(&mut self, delete: &Delete) -> Response {
-        if self.data.delete(delete.key()) {
+        if self.data.write().delete(delete.key()) {
             Response::deleted(delete.noreply())
         } else {
             Response::not_found(delete.noreply())
@@ -357,3 +459,51 @@ impl Storage for Seg {
         Response::hangup()
     }
 }
+
+impl Seg {
+    // stores the value for a set request, with the tag if one was provided
+    fn insert_set<'a, T: Into<seg::Value<'a>>>(
+        &mut self,
+        set: &'a Set,
+        value: T,
+    ) -> Result<(), SegError> {
+        let flags = set.flags().to_be_bytes();
+        let ttl = Duration::from_secs(set.ttl().get().unwrap_or(0) as u64);
+        match set.tag() {
+            Some(tag) => self
+                .data
+                .write()
+                .insert_tagged(set.key(), value, Some(&flags), ttl, tag),
+            None => self
+                .data
+                .write()
+                .insert(set.key(), value, Some(&flags), ttl),
+        }
+    }
+}
+
+// the response to a meta get which found the item, with the requested fields
+fn meta_value(item: &seg::Item, meta_get: &MetaGet) -> MetaValue {
+    let mut response = MetaValue::new();
+    if meta_get.value() {
+        response = match item.value() {
+            seg::Value::Bytes(b) => response.with_value(b),
+            seg::Value::U64(v) => response.with_value(format!("{}", v).as_bytes()),
+        };
+    }
+    if meta_get.flags() {
+        let o = item.optional().unwrap_or(&[0, 0, 0, 0]);
+        response = response.with_flags(u32::from_be_bytes([o[0], o[1], o[2], o[3]]));
+    }
+    if meta_get.cas() {
+        response = response.with_cas(item.cas());
+    }
+    if meta_get.ttl() {
+        // items which do not expire are reported with -1
+        response = response.with_ttl(item.ttl().map(|ttl| ttl.as_secs() as i64).unwrap_or(-1));
+    }
+    if meta_get.return_key() {
+        response = response.with_key(item.key());
+    }
+    response
+}
@@ -8,16 +8,82 @@
 
 use crate::EntryStore;
 
-use config::seg::Eviction;
+use config::seg::{Admission, Eviction, HugePages};
 use config::SegConfig;
-use seg::{Policy, SegError};
+use seg::{DumpTask, LoadTask, Policy, SegError, SegWriter, WriteGuard};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
 
 mod memcache;
+mod resp;
+
+// the number of items which are dumped or loaded on each call to `expire()`
+const TASK_ITEMS: usize = 1024;
+
+/// A dump or load which is in progress
+enum Task {
+    Dump {
+        dump: DumpTask<BufWriter<File>>,
+        tmp: PathBuf,
+        path: PathBuf,
+    },
+    Load(LoadTask<BufReader<File>>),
+}
+
+/// The cache, which is split into a writer and readers when the worker threads
+/// read from it directly
+enum Data {
+    Owned(Box<::seg::Seg>),
+    Shared(SegWriter),
+}
+
+impl Data {
+    // begin an operation on the cache, which readers wait for until the
+    // returned guard is dropped
+    fn write(&mut self) -> Write<'_> {
+        match self {
+            Self::Owned(seg) => Write::Owned(seg),
+            Self::Shared(writer) => Write::Shared(writer.write()),
+        }
+    }
+}
+
+enum Write<'a> {
+    Owned(&'a mut ::seg::Seg),
+    Shared(WriteGuard<'a>),
+}
+
+impl Deref for Write<'_> {
+    type Target = ::seg::Seg;
+
+    fn deref(&self) -> &::seg::Seg {
+        match self {
+            Self::Owned(seg) => seg,
+            Self::Shared(guard) => guard,
+        }
+    }
+}
+
+impl DerefMut for Write<'_> {
+    fn deref_mut(&mut self) -> &mut ::seg::Seg {
+        match self {
+            Self::Owned(seg) => seg,
+            Self::Shared(guard) => guard,
+        }
+    }
+}
 
 /// A wrapper around [`seg::Seg`] which implements `EntryStore` and storage
 /// protocol traits.
 pub struct Seg {
-    data: ::seg::Seg,
+    data: Data,
+    reader: Option<::seg::SegReader>,
+    task: Option<Task>,
+    finished: Option<Result<usize, std::io::Error>>,
+    checkpoint_interval: Option<std::time::Duration>,
+    checkpoint_at: std::time::Instant,
 }
 
 impl Seg {
@@ -41,26 +107,197 @@ impl Seg {
             },
         };
 
+        // build up the admission policy from the config
+        let admission = match config.admission() {
+            Admission::None => ::seg::Admission::None,
+            Admission::Bloom => ::seg::Admission::Bloom {
+                items: config.admission_items(),
+                window: std::time::Duration::from_secs(config.admission_window() as u64),
+            },
+        };
+
+        let huge_pages = match config.huge_pages() {
+            HugePages::None => ::seg::HugePages::None,
+            HugePages::Transparent => ::seg::HugePages::Transparent,
+            HugePages::Explicit => ::seg::HugePages::Explicit,
+        };
+
+        let namespaces = config
+            .namespaces()
+            .iter()
+            .map(|namespace| ::seg::Namespace::new(namespace.name(), namespace.quota()))
+            .collect();
+
         // build the datastructure from the config
         let data = ::seg::Seg::builder()
             .hash_power(config.hash_power())
+            .max_hash_power(config.max_hash_power())
             .overflow_factor(config.overflow_factor())
             .heap_size(config.heap_size())
+            .max_heap_size(config.max_heap_size())
             .segment_size(config.segment_size())
             .eviction(eviction)
             .datapool_path(config.datapool_path())
+            .huge_pages(huge_pages)
+            .tier2_path(config.tier2_path())
+            .tier2_size(config.tier2_size())
+            .namespaces(namespaces)
+            .namespace_delimiter(config.namespace_delimiter())
+            .compression(config.compression_threshold())
+            .item_cas(config.item_cas())
+            .stale_grace(std::time::Duration::from_secs(config.stale_grace() as u64))
+            .admission(admission)
+            .expire_max_segments(config.expire_max_segments())
+            .expire_max_time(config.expire_max_ns().map(std::time::Duration::from_nanos))
             .build()?;
 
-        Ok(Self { data })
+        let (data, reader) = if config.shared_reads() {
+            if !data.shareable() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "shared reads require a hash table and a heap which do not grow",
+                ));
+            }
+            let (writer, reader) = data.into_shared();
+            (Data::Shared(writer), Some(reader))
+        } else {
+            (Data::Owned(Box::new(data)), None)
+        };
+
+        Ok(Self {
+            data,
+            reader,
+            task: None,
+            finished: None,
+            checkpoint_interval: config
+                .checkpoint_interval()
+                .map(std::time::Duration::from_secs),
+            checkpoint_at: std::time::Instant::now(),
+        })
+    }
+
+    // returns a handle for the worker threads to read from the cache, if the
+    // cache is shared
+    fn reader(&self) -> Option<SegReader> {
+        self.reader.clone().map(|data| SegReader { data })
+    }
+
+    // makes progress on the dump or load which is in progress, if any
+    fn step(&mut self) {
+        let done = match self.task.as_mut() {
+            Some(Task::Dump { dump, .. }) => dump
+                .step(&mut self.data.write(), TASK_ITEMS)
+                .map(|done| done.then_some(0)),
+            Some(Task::Load(load)) => load.step(&mut self.data.write(), TASK_ITEMS),
+            None => return,
+        };
+
+        let result = match (done, self.task.take()) {
+            (Ok(None), task) => {
+                self.task = task;
+                return;
+            }
+            (Ok(Some(_)), Some(Task::Dump { dump, tmp, path })) => {
+                // the dump only replaces the file at the path once complete
+                let result = dump
+                    .finish()
+                    .and_then(|count| std::fs::rename(&tmp, path).map(|_| count));
+                if result.is_err() {
+                    let _ = std::fs::remove_file(tmp);
+                }
+                result
+            }
+            (Ok(Some(loaded)), _) => Ok(loaded),
+            (Err(e), task) => {
+                if let Some(Task::Dump { tmp, .. }) = task {
+                    let _ = std::fs::remove_file(tmp);
+                }
+                Err(e)
+            }
+        };
+        self.finished = Some(result);
+    }
+
+    // returns an error if a dump or load is already in progress
+    fn idle(&self) -> Result<(), std::io::Error> {
+        if self.task.is_some() || self.finished.is_some() {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "a dump or load is already in progress",
+            ))
+        } else {
+            Ok(())
+        }
     }
 }
 
+/// Reads from a [`Seg`] on the worker threads, when enabled by
+/// `shared_reads` in the config. Reads which the reader cannot complete are
+/// executed by the storage thread instead, see [`seg::SegReader`].
+pub struct SegReader {
+    data: ::seg::SegReader,
+}
+
 impl EntryStore for Seg {
-    fn expire(&mut self) {
-        self.data.expire();
+    fn expire(&mut self) -> bool {
+        self.data.write().expire();
+        self.step();
+        self.data.write().expire_pending() || self.task.is_some()
     }
 
     fn clear(&mut self) {
-        self.data.clear();
+        self.data.write().clear();
+    }
+
+    fn dump(&mut self, path: &Path) -> Result<(), std::io::Error> {
+        self.idle()?;
+        // write to a temporary file which is unique to this process so that a
+        // partial dump never replaces a complete one
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(format!(".tmp.{}", std::process::id()));
+        let tmp = PathBuf::from(tmp);
+        let file = File::create(&tmp)?;
+        let dump = DumpTask::new(BufWriter::new(file)).inspect_err(|_| {
+            let _ = std::fs::remove_file(&tmp);
+        })?;
+        self.task = Some(Task::Dump {
+            dump,
+            tmp,
+            path: path.to_owned(),
+        });
+        Ok(())
+    }
+
+    fn load(&mut self, path: &Path) -> Result<(), std::io::Error> {
+        self.idle()?;
+        let file = File::open(path)?;
+        self.task = Some(Task::Load(LoadTask::new(BufReader::new(file))?));
+        Ok(())
+    }
+
+    fn finished(&mut self) -> Option<Result<usize, std::io::Error>> {
+        self.finished.take()
+    }
+
+    fn resize(&mut self, heap_size: usize) -> Result<(), std::io::Error> {
+        self.data.write().resize(heap_size)
+    }
+
+    fn checkpoint(&mut self) -> Result<(), std::io::Error> {
+        match self.checkpoint_interval {
+            Some(interval) if self.checkpoint_at.elapsed() >= interval => {
+                self.checkpoint_at = std::time::Instant::now();
+                self.data.write().checkpoint()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn close(&mut self) -> Result<(), std::io::Error> {
+        // a dump which is in progress is abandoned
+        if let Some(Task::Dump { tmp, .. }) = self.task.take() {
+            let _ = std::fs::remove_file(tmp);
+        }
+        self.data.write().close()
     }
 }
@@ -0,0 +1,143 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! This module defines how `Seg` storage will be used to execute `RESP`
+//! storage commands.
+
+use super::*;
+use protocol_common::*;
+
+use protocol_resp::*;
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+impl Execute<Request, Response> for Seg {
+    fn execute(&mut self, request: &Request) -> Response {
+        match request {
+            Request::Get(get) => self.get(get),
+            Request::Invalidate(invalidate) => self.invalidate(invalidate),
+            Request::Set(set) => self.set(set),
+            Request::Ping(_) => Response::simple_string("PONG"),
+            Request::BAdd(_) => Response::error("ERR unsupported command"),
+        }
+    }
+
+    fn shared(&mut self) -> Option<Arc<dyn ExecuteShared<Request, Response>>> {
+        self.reader()
+            .map(|reader| Arc::new(reader) as Arc<dyn ExecuteShared<Request, Response>>)
+    }
+}
+
+impl ExecuteShared<Request, Response> for SegReader {
+    fn execute_shared(&self, request: &Request) -> Option<Response> {
+        match request {
+            Request::Get(get) => match self.data.get(get.key()).ok()? {
+                Some(item) => Some(bulk_string(item.value())),
+                None => Some(Response::null()),
+            },
+            _ => None,
+        }
+    }
+}
+
+impl Storage for Seg {
+    fn get(&mut self, get: &GetRequest) -> Response {
+        match self.data.write().get(get.key()) {
+            Some(item) => value(&item),
+            None => Response::null(),
+        }
+    }
+
+    fn invalidate(&mut self, invalidate: &InvalidateRequest) -> Response {
+        self.data.write().invalidate_tag(invalidate.tag());
+        Response::simple_string("OK")
+    }
+
+    fn set(&mut self, set: &SetRequest) -> Response {
+        // the current item decides if the mode allows the write, and it
+        // provides the old value and the ttl which may be kept
+        let current = self.data.write().get_no_freq_incr(set.key());
+
+        let ttl = match set.expire_time() {
+            Some(ExpireTime::Seconds(0)) | Some(ExpireTime::Milliseconds(0)) => {
+                return Response::error("ERR invalid expire time in 'set' command");
+            }
+            Some(ExpireTime::Seconds(s)) => Some(Duration::from_secs(s)),
+            Some(ExpireTime::Milliseconds(ms)) => Some(whole_secs(Duration::from_millis(ms))),
+            Some(ExpireTime::UnixSeconds(s)) => remaining(Duration::from_secs(s)),
+            Some(ExpireTime::UnixMilliseconds(ms)) => remaining(Duration::from_millis(ms)),
+            Some(ExpireTime::KeepTtl) => Some(
+                current
+                    .as_ref()
+                    .and_then(|item| item.ttl())
+                    .unwrap_or(Duration::ZERO),
+            ),
+            None => Some(Duration::ZERO),
+        };
+
+        let old = set
+            .get_old()
+            .then(|| current.as_ref().map_or_else(Response::null, value));
+
+        let write = match set.mode() {
+            SetMode::Add => current.is_none(),
+            SetMode::Replace => current.is_some(),
+            SetMode::Set => true,
+        };
+
+        if write {
+            let result = match (ttl, set.tag()) {
+                // an expire time which has passed maps to a delete
+                (None, _) => {
+                    self.data.write().delete(set.key());
+                    Ok(())
+                }
+                (Some(ttl), Some(tag)) => {
+                    self.data
+                        .write()
+                        .insert_tagged(set.key(), set.value(), None, ttl, tag)
+                }
+                (Some(ttl), None) => self.data.write().insert(set.key(), set.value(), None, ttl),
+            };
+
+            if result.is_err() {
+                return Response::error("ERR server error");
+            }
+        }
+
+        match old {
+            Some(old) => old,
+            None if write => Response::simple_string("OK"),
+            None => Response::null(),
+        }
+    }
+}
+
+// the value of the item as a bulk string
+fn value(item: &seg::Item) -> Response {
+    bulk_string(item.value())
+}
+
+fn bulk_string(value: seg::Value) -> Response {
+    match value {
+        seg::Value::Bytes(b) => Response::bulk_string(b),
+        seg::Value::U64(v) => Response::bulk_string(format!("{}", v).as_bytes()),
+    }
+}
+
+// the time until a unix expire time in whole seconds, or `None` if it has
+// already passed
+fn remaining(expire_at: Duration) -> Option<Duration> {
+    (UNIX_EPOCH + expire_at)
+        .duration_since(SystemTime::now())
+        .ok()
+        .map(whole_secs)
+        .filter(|ttl| !ttl.is_zero())
+}
+
+// items expire on a whole second, so the ttl is rounded up
+fn whole_secs(ttl: Duration) -> Duration {
+    Duration::from_secs(ttl.as_secs() + u64::from(ttl.subsec_nanos() > 0))
+}
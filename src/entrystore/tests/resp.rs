@@ -0,0 +1,107 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Runs RESP requests against `Seg` storage, from the request bytes which are
+//! parsed to the response bytes which are composed.
+
+use config::SegcacheConfig;
+use entrystore::Seg;
+use protocol_common::{Compose, Execute, Parse};
+use protocol_resp::RequestParser;
+
+// sends each request and checks the response, using a new cache for each test
+fn test(name: &str, data: &[(&str, &str)]) {
+    println!("testing: {}", name);
+
+    let mut storage = Seg::new(&SegcacheConfig::default()).expect("failed to create storage");
+    let parser = RequestParser::new();
+
+    for (request, expected) in data {
+        let parsed = parser
+            .parse(request.as_bytes())
+            .expect("failed to parse request");
+        assert_eq!(parsed.consumed(), request.len());
+
+        let response = storage.execute(&parsed.into_inner());
+        let mut buf = Vec::new();
+        response.compose(&mut buf);
+
+        assert_eq!(
+            std::str::from_utf8(&buf).unwrap(),
+            *expected,
+            "{}: {:?}",
+            name,
+            request
+        );
+    }
+}
+
+#[test]
+fn get_and_set() {
+    test("get miss", &[("*2\r\n$3\r\nGET\r\n$1\r\n0\r\n", "$-1\r\n")]);
+
+    test(
+        "set and get",
+        &[
+            ("set 1 one\r\n", "+OK\r\n"),
+            ("get 1\r\n", "$3\r\none\r\n"),
+            ("SET 1 uno EX 60 GET\r\n", "$3\r\none\r\n"),
+            ("get 1\r\n", "$3\r\nuno\r\n"),
+        ],
+    );
+
+    test(
+        "set modes",
+        &[
+            ("set 2 two XX\r\n", "$-1\r\n"),
+            ("get 2\r\n", "$-1\r\n"),
+            ("set 2 two NX\r\n", "+OK\r\n"),
+            ("set 2 dos NX\r\n", "$-1\r\n"),
+            ("get 2\r\n", "$3\r\ntwo\r\n"),
+        ],
+    );
+
+    test(
+        "set expired",
+        &[
+            ("set 3 three\r\n", "+OK\r\n"),
+            ("set 3 tres EXAT 1\r\n", "+OK\r\n"),
+            ("get 3\r\n", "$-1\r\n"),
+            (
+                "set 3 three EX 0\r\n",
+                "-ERR invalid expire time in 'set' command\r\n",
+            ),
+        ],
+    );
+}
+
+#[test]
+fn invalidate() {
+    test(
+        "invalidate tag",
+        &[
+            ("set user:1:name alice TAG user:1\r\n", "+OK\r\n"),
+            ("set user:1:email a@b.c EX 60 TAG user:1\r\n", "+OK\r\n"),
+            ("set user:2:name bob TAG user:2\r\n", "+OK\r\n"),
+            ("set other value\r\n", "+OK\r\n"),
+            ("invalidate user:1\r\n", "+OK\r\n"),
+            ("get user:1:name\r\n", "$-1\r\n"),
+            ("get user:1:email\r\n", "$-1\r\n"),
+            ("get user:2:name\r\n", "$3\r\nbob\r\n"),
+            ("get other\r\n", "$5\r\nvalue\r\n"),
+            // items which are written after the invalidation are kept
+            ("set user:1:name alice TAG user:1\r\n", "+OK\r\n"),
+            ("get user:1:name\r\n", "$5\r\nalice\r\n"),
+        ],
+    );
+
+    test(
+        "invalidate unknown tag",
+        &[
+            ("*2\r\n$10\r\nINVALIDATE\r\n$6\r\nuser:3\r\n", "+OK\r\n"),
+            ("set user:3:name carol TAG user:3\r\n", "+OK\r\n"),
+            ("get user:3:name\r\n", "$5\r\ncarol\r\n"),
+        ],
+    );
+}
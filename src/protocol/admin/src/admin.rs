@@ -12,13 +12,17 @@ use common::bytes::SliceExtension;
 use rustcommon_metrics::*;
 
 use std::io::{Error, ErrorKind, Result};
+use std::path::PathBuf;
 
 // TODO(bmartin): see TODO for protocol::data::Request, this is cleaner here
 // since the variants are simple, but better to take the same approach in both
 // modules.
 #[derive(PartialEq, Eq, Debug)]
 pub enum AdminRequest {
+    Dump(PathBuf),
     FlushAll,
+    Load(PathBuf),
+    Resize(usize),
     Stats,
     Version,
     Quit,
@@ -46,10 +50,20 @@ impl Parse<AdminRequest> for AdminRequestParser {
             let mut single_byte_windows = trimmed_buffer.windows(1);
             if let Some(command_verb_end) = single_byte_windows.position(|w| w == b" ") {
                 let command_verb = &trimmed_buffer[0..command_verb_end];
-                // TODO(bmartin): 'stats slab' will go here eventually which will
-                // remove the need for ignoring this lint.
-                #[allow(clippy::match_single_binding)]
+                let argument = trimmed_buffer[command_verb_end..].trim();
                 match command_verb {
+                    b"dump" => Ok(ParseOk::new(
+                        AdminRequest::Dump(parse_path(argument)?),
+                        command_end + CRLF.len(),
+                    )),
+                    b"load" => Ok(ParseOk::new(
+                        AdminRequest::Load(parse_path(argument)?),
+                        command_end + CRLF.len(),
+                    )),
+                    b"resize" => Ok(ParseOk::new(
+                        AdminRequest::Resize(parse_size(argument)?),
+                        command_end + CRLF.len(),
+                    )),
                     _ => Err(Error::from(ErrorKind::InvalidInput)),
                 }
             } else {
@@ -73,6 +87,21 @@ impl Parse<AdminRequest> for AdminRequestParser {
     }
 }
 
+// the path argument for dump and load must be valid utf8
+fn parse_path(argument: &[u8]) -> Result<PathBuf> {
+    std::str::from_utf8(argument)
+        .map(PathBuf::from)
+        .map_err(|_| Error::from(ErrorKind::InvalidInput))
+}
+
+// the size argument for resize is a number of bytes
+fn parse_size(argument: &[u8]) -> Result<usize> {
+    std::str::from_utf8(argument)
+        .ok()
+        .and_then(|size| size.parse::<usize>().ok())
+        .ok_or_else(|| Error::from(ErrorKind::InvalidInput))
+}
+
 pub struct Version {
     version: String,
 }
@@ -88,6 +117,7 @@ impl Compose for Version {
 }
 
 pub enum AdminResponse {
+    Error(String),
     Hangup,
     Ok,
     Stats,
@@ -95,6 +125,10 @@ pub enum AdminResponse {
 }
 
 impl AdminResponse {
+    pub fn error(message: String) -> Self {
+        Self::Error(message)
+    }
+
     pub fn hangup() -> Self {
         Self::Hangup
     }
@@ -115,6 +149,12 @@ impl AdminResponse {
 impl Compose for AdminResponse {
     fn compose(&self, buf: &mut dyn BufMut) -> usize {
         match self {
+            Self::Error(message) => {
+                buf.put_slice(b"ERROR ");
+                buf.put_slice(message.as_bytes());
+                buf.put_slice(b"\r\n");
+                8 + message.len()
+            }
             Self::Hangup => 0,
             Self::Ok => {
                 buf.put_slice(b"OK\r\n");
@@ -189,6 +229,45 @@ mod tests {
         assert_eq!(parsed.unwrap().into_inner(), AdminRequest::FlushAll);
     }
 
+    #[test]
+    fn parse_dump_load() {
+        let parser = AdminRequestParser::new();
+
+        let parsed = parser.parse(b"dump /tmp/cache.dump\r\n");
+        assert!(parsed.is_ok());
+        assert_eq!(
+            parsed.unwrap().into_inner(),
+            AdminRequest::Dump(PathBuf::from("/tmp/cache.dump"))
+        );
+
+        let parsed = parser.parse(b"load  /tmp/cache.dump \r\n");
+        assert!(parsed.is_ok());
+        assert_eq!(
+            parsed.unwrap().into_inner(),
+            AdminRequest::Load(PathBuf::from("/tmp/cache.dump"))
+        );
+
+        // a path is required
+        assert!(parser.parse(b"dump\r\n").is_err());
+    }
+
+    #[test]
+    fn parse_resize() {
+        let parser = AdminRequestParser::new();
+
+        let parsed = parser.parse(b"resize 1073741824\r\n");
+        assert!(parsed.is_ok());
+        assert_eq!(
+            parsed.unwrap().into_inner(),
+            AdminRequest::Resize(1073741824)
+        );
+
+        // the size must be a number of bytes
+        assert!(parser.parse(b"resize\r\n").is_err());
+        assert!(parser.parse(b"resize 1GB\r\n").is_err());
+        assert!(parser.parse(b"resize -1\r\n").is_err());
+    }
+
     #[test]
     fn parse_quit() {
         let parser = AdminRequestParser::new();
@@ -8,6 +8,8 @@
 
 pub use bytes::BufMut;
 
+use std::sync::Arc;
+
 pub const CRLF: &str = "\r\n";
 
 pub trait Compose {
@@ -21,8 +23,45 @@ pub trait Compose {
     }
 }
 
+/// Identifies the method of a message. This allows proxies to route requests
+/// by method and to track stats for each method.
+pub trait Method {
+    /// The name of the method, if it can be determined for this message.
+    fn method(&self) -> Option<&str>;
+
+    /// Indicates that the message represents an error. Override this function
+    /// as appropriate for the protocol.
+    fn is_error(&self) -> bool {
+        false
+    }
+}
+
+/// Builds a response which reports an error for a request. This allows a proxy
+/// to reply to the client when no backend could serve the request. Protocols
+/// which have no way to express an error use the default, and the proxy will
+/// close the client connection instead.
+pub trait ErrorResponse<Request>: Sized {
+    fn error_response(_request: &Request, _message: &str) -> Option<Self> {
+        None
+    }
+}
+
 pub trait Execute<Request, Response: Compose> {
     fn execute(&mut self, request: &Request) -> Response;
+
+    /// Returns a handle which executes requests that only read from storage on
+    /// other threads, if the storage supports it. The default is `None`, in
+    /// which case every request is executed by `execute()`.
+    fn shared(&mut self) -> Option<Arc<dyn ExecuteShared<Request, Response>>> {
+        None
+    }
+}
+
+/// Executes requests which only read from storage, from any number of threads
+/// at the same time as the owner of the storage. Returns `None` when a request
+/// must be executed by the owner instead, see `Execute::shared()`.
+pub trait ExecuteShared<Request, Response: Compose>: Send + Sync {
+    fn execute_shared(&self, request: &Request) -> Option<Response>;
 }
 
 #[derive(Debug, PartialEq, Eq)]
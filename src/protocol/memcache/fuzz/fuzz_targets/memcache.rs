@@ -9,12 +9,12 @@
 #![no_main]
 use libfuzzer_sys::fuzz_target;
 
-use protocol_memcache::*;
 use protocol_common::Parse;
+use protocol_memcache::*;
 
 const MAX_KEY_LEN: usize = 128;
 const MAX_BATCH_SIZE: usize = 128;
-const MAX_VALUE_SIZE: usize = 4*4096;
+const MAX_VALUE_SIZE: usize = 4 * 4096;
 
 fuzz_target!(|data: &[u8]| {
     let parser = RequestParser::new()
@@ -49,6 +49,9 @@ fuzz_target!(|data: &[u8]| {
             Request::Set(set) => {
                 validate_key(set.key());
                 validate_value(set.value());
+                if let Some(tag) = set.tag() {
+                    validate_key(tag);
+                }
             }
             Request::Add(add) => {
                 validate_key(add.key());
@@ -76,9 +79,18 @@ fuzz_target!(|data: &[u8]| {
             Request::Incr(incr) => {
                 validate_key(incr.key());
             }
+            Request::Invalidate(invalidate) => {
+                validate_key(invalidate.tag());
+            }
             Request::Decr(decr) => {
                 validate_key(decr.key());
             }
+            Request::MetaDebug(meta_debug) => {
+                validate_key(meta_debug.key());
+            }
+            Request::MetaGet(meta_get) => {
+                validate_key(meta_get.key());
+            }
             Request::FlushAll(_) => {}
             Request::Quit(_) => {}
         }
@@ -96,6 +96,19 @@ counter!(CAS_EXISTS);
 counter!(CAS_NOT_FOUND);
 counter!(CAS_STORED);
 
+counter!(INVALIDATE);
+counter!(INVALIDATE_EX);
+
+counter!(META_DEBUG);
+counter!(META_DEBUG_EX);
+counter!(META_DEBUG_HIT);
+counter!(META_DEBUG_MISS);
+
+counter!(META_GET);
+counter!(META_GET_EX);
+counter!(META_GET_HIT);
+counter!(META_GET_MISS);
+
 counter!(FLUSH_ALL);
 counter!(FLUSH_ALL_EX);
 
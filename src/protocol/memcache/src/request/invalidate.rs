@@ -0,0 +1,150 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+
+/// Invalidates every item which was stored with the tag, see `Set::tag()`.
+/// This is an extension to the protocol.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Invalidate {
+    pub(crate) tag: Box<[u8]>,
+    pub(crate) noreply: bool,
+}
+
+impl Invalidate {
+    pub fn tag(&self) -> &[u8] {
+        self.tag.as_ref()
+    }
+
+    pub fn noreply(&self) -> bool {
+        self.noreply
+    }
+}
+
+impl RequestParser {
+    // this is to be called after parsing the command, so we do not match the verb
+    pub(crate) fn parse_invalidate_no_stats<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> IResult<&'a [u8], Invalidate> {
+        let (input, _) = space1(input)?;
+
+        let (mut input, tag) = key(input, self.max_key_len)?;
+
+        let tag = match tag {
+            Some(t) => t,
+            None => {
+                return Err(nom::Err::Failure((input, nom::error::ErrorKind::Tag)));
+            }
+        };
+
+        let mut noreply = false;
+
+        // if we have a space, we might have a noreply
+        if let Ok((i, _)) = space1(input) {
+            if i.len() > 7 && &i[0..7] == b"noreply" {
+                input = &i[7..];
+                noreply = true;
+            }
+        }
+
+        let (input, _) = space0(input)?;
+
+        let (input, _) = crlf(input)?;
+        Ok((
+            input,
+            Invalidate {
+                tag: tag.to_owned().into_boxed_slice(),
+                noreply,
+            },
+        ))
+    }
+
+    // this is to be called after parsing the command, so we do not match the verb
+    pub fn parse_invalidate<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], Invalidate> {
+        match self.parse_invalidate_no_stats(input) {
+            Ok((input, request)) => {
+                INVALIDATE.increment();
+                Ok((input, request))
+            }
+            Err(e) => {
+                if !e.is_incomplete() {
+                    INVALIDATE.increment();
+                    INVALIDATE_EX.increment();
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Compose for Invalidate {
+    fn compose(&self, session: &mut dyn BufMut) -> usize {
+        let verb = b"invalidate ";
+        let header_end = if self.noreply {
+            " noreply\r\n".as_bytes()
+        } else {
+            "\r\n".as_bytes()
+        };
+
+        let size = verb.len() + self.tag.len() + header_end.len();
+
+        session.put_slice(verb);
+        session.put_slice(&self.tag);
+        session.put_slice(header_end);
+
+        size
+    }
+}
+
+impl Klog for Invalidate {
+    type Response = Response;
+
+    fn klog(&self, response: &Self::Response) {
+        let (code, len) = match response {
+            Response::Deleted(ref res) => (DELETED, res.len()),
+            _ => {
+                return;
+            }
+        };
+        klog!("\"invalidate {}\" {} {}", string_key(self.tag()), code, len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let parser = RequestParser::new();
+
+        // basic invalidate command
+        assert_eq!(
+            parser.parse_request(b"invalidate user:1\r\n"),
+            Ok((
+                &b""[..],
+                Request::Invalidate(Invalidate {
+                    tag: b"user:1".to_vec().into_boxed_slice(),
+                    noreply: false,
+                })
+            ))
+        );
+
+        // noreply
+        assert_eq!(
+            parser.parse_request(b"INVALIDATE user:1 noreply\r\n"),
+            Ok((
+                &b""[..],
+                Request::Invalidate(Invalidate {
+                    tag: b"user:1".to_vec().into_boxed_slice(),
+                    noreply: true,
+                })
+            ))
+        );
+
+        // a tag is required
+        assert!(parser.parse_request(b"invalidate \r\n").is_err());
+    }
+}
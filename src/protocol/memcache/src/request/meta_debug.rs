@@ -0,0 +1,136 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+
+/// Requests the expiry and access metadata for a single key. This follows the
+/// memcached `me` (meta debug) command and does not count as an access of the
+/// item.
+#[derive(Debug, PartialEq, Eq)]
+pub struct MetaDebug {
+    pub(crate) key: Box<[u8]>,
+}
+
+impl MetaDebug {
+    pub fn key(&self) -> &[u8] {
+        self.key.as_ref()
+    }
+}
+
+impl RequestParser {
+    // this is to be called after parsing the command, so we do not match the verb
+    pub(crate) fn parse_meta_debug_no_stats<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> IResult<&'a [u8], MetaDebug> {
+        let (input, _) = space1(input)?;
+
+        let (input, key) = key(input, self.max_key_len)?;
+
+        let key = match key {
+            Some(k) => k,
+            None => {
+                return Err(nom::Err::Failure((input, nom::error::ErrorKind::Tag)));
+            }
+        };
+
+        let (input, _) = space0(input)?;
+
+        let (input, _) = crlf(input)?;
+        Ok((
+            input,
+            MetaDebug {
+                key: key.to_owned().into_boxed_slice(),
+            },
+        ))
+    }
+
+    // this is to be called after parsing the command, so we do not match the verb
+    pub fn parse_meta_debug<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], MetaDebug> {
+        match self.parse_meta_debug_no_stats(input) {
+            Ok((input, request)) => {
+                META_DEBUG.increment();
+                Ok((input, request))
+            }
+            Err(e) => {
+                if !e.is_incomplete() {
+                    META_DEBUG.increment();
+                    META_DEBUG_EX.increment();
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Compose for MetaDebug {
+    fn compose(&self, session: &mut dyn BufMut) -> usize {
+        let verb = b"me ";
+
+        let size = verb.len() + self.key.len() + CRLF.len();
+
+        session.put_slice(verb);
+        session.put_slice(&self.key);
+        session.put_slice(CRLF);
+
+        size
+    }
+}
+
+impl Klog for MetaDebug {
+    type Response = Response;
+
+    fn klog(&self, response: &Self::Response) {
+        let (code, len) = match response {
+            Response::DebugInfo(ref res) => {
+                if res.is_hit() {
+                    META_DEBUG_HIT.increment();
+                    (HIT, res.len())
+                } else {
+                    META_DEBUG_MISS.increment();
+                    (MISS, res.len())
+                }
+            }
+            _ => {
+                return;
+            }
+        };
+        klog!("\"me {}\" {} {}", string_key(self.key()), code, len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let parser = RequestParser::new();
+
+        // basic meta debug command
+        assert_eq!(
+            parser.parse_request(b"me 0\r\n"),
+            Ok((
+                &b""[..],
+                Request::MetaDebug(MetaDebug {
+                    key: b"0".to_vec().into_boxed_slice(),
+                })
+            ))
+        );
+
+        // trailing spaces are ignored
+        assert_eq!(
+            parser.parse_request(b"ME key \r\n"),
+            Ok((
+                &b""[..],
+                Request::MetaDebug(MetaDebug {
+                    key: b"key".to_vec().into_boxed_slice(),
+                })
+            ))
+        );
+
+        // a key is required
+        assert!(parser.parse_request(b"me \r\n").is_err());
+    }
+}
@@ -0,0 +1,261 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+
+/// Looks up a single key, following the memcached `mg` (meta get) command.
+/// The flags select what is returned: `v` the value, `f` the client flags, `c`
+/// the CAS value, `t` the remaining TTL, and `k` the key.
+///
+/// The `N<ttl>` flag requests a lease when the item is missing or has
+/// expired. The first such request is granted a lease which lasts for the
+/// provided number of seconds, and is told so with the `W` flag, in which
+/// case the `c` flag holds the lease token instead of the CAS value. The
+/// lease token is passed to a `set` of the refilled item, see `Set::lease()`.
+/// Requests made while the lease is held are given the `Z` flag, along with
+/// the expired item and the `X` flag if the item is still retained.
+#[derive(Debug, PartialEq, Eq)]
+pub struct MetaGet {
+    pub(crate) key: Box<[u8]>,
+    pub(crate) value: bool,
+    pub(crate) flags: bool,
+    pub(crate) cas: bool,
+    pub(crate) ttl: bool,
+    pub(crate) return_key: bool,
+    pub(crate) lease: Option<u32>,
+}
+
+impl MetaGet {
+    pub fn key(&self) -> &[u8] {
+        self.key.as_ref()
+    }
+
+    /// Returns true if the value should be returned
+    pub fn value(&self) -> bool {
+        self.value
+    }
+
+    /// Returns true if the client flags should be returned
+    pub fn flags(&self) -> bool {
+        self.flags
+    }
+
+    /// Returns true if the CAS value should be returned
+    pub fn cas(&self) -> bool {
+        self.cas
+    }
+
+    /// Returns true if the remaining TTL should be returned
+    pub fn ttl(&self) -> bool {
+        self.ttl
+    }
+
+    /// Returns true if the key should be returned
+    pub fn return_key(&self) -> bool {
+        self.return_key
+    }
+
+    /// The number of seconds a lease granted by this request lasts, if a lease
+    /// was requested
+    pub fn lease(&self) -> Option<u32> {
+        self.lease
+    }
+}
+
+impl RequestParser {
+    // this is to be called after parsing the command, so we do not match the verb
+    pub(crate) fn parse_meta_get_no_stats<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> IResult<&'a [u8], MetaGet> {
+        let (input, _) = space1(input)?;
+
+        let (mut input, key) = key(input, self.max_key_len)?;
+
+        let key = match key {
+            Some(k) => k,
+            None => {
+                return Err(nom::Err::Failure((input, nom::error::ErrorKind::Tag)));
+            }
+        };
+
+        let mut request = MetaGet {
+            key: key.to_owned().into_boxed_slice(),
+            value: false,
+            flags: false,
+            cas: false,
+            ttl: false,
+            return_key: false,
+            lease: None,
+        };
+
+        // each flag is a single character, some are followed by a token
+        while let Ok((i, _)) = space1(input) {
+            let (i, flag) = take_till(|b| b == b' ' || b == b'\r')(i)?;
+            match flag {
+                [] => {
+                    break;
+                }
+                b"v" => request.value = true,
+                b"f" => request.flags = true,
+                b"c" => request.cas = true,
+                b"t" => request.ttl = true,
+                b"k" => request.return_key = true,
+                [b'N', ttl @ ..] => {
+                    request.lease = std::str::from_utf8(ttl)
+                        .ok()
+                        .filter(|ttl| ttl.bytes().all(|b| b.is_ascii_digit()))
+                        .and_then(|ttl| ttl.parse().ok());
+                    if request.lease.is_none() {
+                        return Err(nom::Err::Failure((input, nom::error::ErrorKind::Tag)));
+                    }
+                }
+                _ => {
+                    return Err(nom::Err::Failure((input, nom::error::ErrorKind::Tag)));
+                }
+            }
+            input = i;
+        }
+
+        let (input, _) = space0(input)?;
+
+        let (input, _) = crlf(input)?;
+        Ok((input, request))
+    }
+
+    // this is to be called after parsing the command, so we do not match the verb
+    pub fn parse_meta_get<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], MetaGet> {
+        match self.parse_meta_get_no_stats(input) {
+            Ok((input, request)) => {
+                META_GET.increment();
+                Ok((input, request))
+            }
+            Err(e) => {
+                if !e.is_incomplete() {
+                    META_GET.increment();
+                    META_GET_EX.increment();
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Compose for MetaGet {
+    fn compose(&self, session: &mut dyn BufMut) -> usize {
+        let verb = b"mg ";
+        let mut flags = Vec::new();
+        for (enabled, flag) in [
+            (self.value, &b" v"[..]),
+            (self.flags, b" f"),
+            (self.cas, b" c"),
+            (self.ttl, b" t"),
+            (self.return_key, b" k"),
+        ] {
+            if enabled {
+                flags.extend_from_slice(flag);
+            }
+        }
+        if let Some(ttl) = self.lease {
+            flags.extend_from_slice(format!(" N{}", ttl).as_bytes());
+        }
+
+        let size = verb.len() + self.key.len() + flags.len() + CRLF.len();
+
+        session.put_slice(verb);
+        session.put_slice(&self.key);
+        session.put_slice(&flags);
+        session.put_slice(CRLF);
+
+        size
+    }
+}
+
+impl Klog for MetaGet {
+    type Response = Response;
+
+    fn klog(&self, response: &Self::Response) {
+        // the length is the length of the value, as for `get`, which is zero
+        // unless the value was returned
+        let (code, len) = match response {
+            Response::MetaValue(ref res) => {
+                if res.is_hit() {
+                    META_GET_HIT.increment();
+                    (HIT, res.value().map_or(0, |value| value.len()))
+                } else {
+                    META_GET_MISS.increment();
+                    (MISS, 0)
+                }
+            }
+            _ => {
+                return;
+            }
+        };
+        klog!("\"mg {}\" {} {}", string_key(self.key()), code, len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let parser = RequestParser::new();
+
+        // no flags
+        assert_eq!(
+            parser.parse_request(b"mg 0\r\n"),
+            Ok((
+                &b""[..],
+                Request::MetaGet(MetaGet {
+                    key: b"0".to_vec().into_boxed_slice(),
+                    value: false,
+                    flags: false,
+                    cas: false,
+                    ttl: false,
+                    return_key: false,
+                    lease: None,
+                })
+            ))
+        );
+
+        // flags in any order, with a lease
+        assert_eq!(
+            parser.parse_request(b"mg key t v N30 f c k \r\n"),
+            Ok((
+                &b""[..],
+                Request::MetaGet(MetaGet {
+                    key: b"key".to_vec().into_boxed_slice(),
+                    value: true,
+                    flags: true,
+                    cas: true,
+                    ttl: true,
+                    return_key: true,
+                    lease: Some(30),
+                })
+            ))
+        );
+
+        // a lease needs a ttl, and unknown flags are rejected
+        assert!(parser.parse_request(b"mg key N\r\n").is_err());
+        assert!(parser.parse_request(b"mg key Nx\r\n").is_err());
+        assert!(parser.parse_request(b"mg key q\r\n").is_err());
+
+        // a key is required
+        assert!(parser.parse_request(b"mg \r\n").is_err());
+    }
+
+    #[test]
+    fn compose() {
+        let parser = RequestParser::new();
+        for request in [&b"mg key\r\n"[..], b"mg key v f c t k N30\r\n"] {
+            let (_, parsed) = parser.parse_request(request).unwrap();
+            let mut buffer = Vec::new();
+            let len = parsed.compose(&mut buffer);
+            assert_eq!(&buffer[..], request);
+            assert_eq!(len, request.len());
+        }
+    }
+}
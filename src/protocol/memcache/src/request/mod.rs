@@ -18,6 +18,9 @@ mod flush_all;
 mod get;
 mod gets;
 mod incr;
+mod invalidate;
+mod meta_debug;
+mod meta_get;
 mod prepend;
 mod quit;
 mod replace;
@@ -32,6 +35,9 @@ pub use flush_all::FlushAll;
 pub use get::Get;
 pub use gets::Gets;
 pub use incr::Incr;
+pub use invalidate::Invalidate;
+pub use meta_debug::MetaDebug;
+pub use meta_get::MetaGet;
 pub use prepend::Prepend;
 pub use quit::Quit;
 pub use replace::Replace;
@@ -97,6 +103,9 @@ impl RequestParser {
             b"delete" | b"DELETE" => Command::Delete,
             b"flush_all" | b"FLUSH_ALL" => Command::FlushAll,
             b"incr" | b"INCR" => Command::Incr,
+            b"invalidate" | b"INVALIDATE" => Command::Invalidate,
+            b"me" | b"ME" => Command::MetaDebug,
+            b"mg" | b"MG" => Command::MetaGet,
             b"get" | b"GET" => Command::Get,
             b"gets" | b"GETS" => Command::Gets,
             b"prepend" | b"PREPEND" => Command::Prepend,
@@ -141,6 +150,18 @@ impl RequestParser {
                 let (input, request) = self.parse_incr(input)?;
                 Ok((input, Request::Incr(request)))
             }
+            (input, Command::Invalidate) => {
+                let (input, request) = self.parse_invalidate(input)?;
+                Ok((input, Request::Invalidate(request)))
+            }
+            (input, Command::MetaDebug) => {
+                let (input, request) = self.parse_meta_debug(input)?;
+                Ok((input, Request::MetaDebug(request)))
+            }
+            (input, Command::MetaGet) => {
+                let (input, request) = self.parse_meta_get(input)?;
+                Ok((input, Request::MetaGet(request)))
+            }
             (input, Command::Get) => {
                 let (input, request) = self.parse_get(input)?;
                 Ok((input, Request::Get(request)))
@@ -200,6 +221,9 @@ impl Compose for Request {
             Self::Delete(r) => r.compose(session),
             Self::FlushAll(r) => r.compose(session),
             Self::Incr(r) => r.compose(session),
+            Self::Invalidate(r) => r.compose(session),
+            Self::MetaDebug(r) => r.compose(session),
+            Self::MetaGet(r) => r.compose(session),
             Self::Get(r) => r.compose(session),
             Self::Gets(r) => r.compose(session),
             Self::Prepend(r) => r.compose(session),
@@ -222,6 +246,9 @@ impl Klog for Request {
             Self::Delete(r) => r.klog(response),
             Self::FlushAll(r) => r.klog(response),
             Self::Incr(r) => r.klog(response),
+            Self::Invalidate(r) => r.klog(response),
+            Self::MetaDebug(r) => r.klog(response),
+            Self::MetaGet(r) => r.klog(response),
             Self::Get(r) => r.klog(response),
             Self::Gets(r) => r.klog(response),
             Self::Prepend(r) => r.klog(response),
@@ -241,6 +268,9 @@ pub enum Request {
     Delete(Delete),
     FlushAll(FlushAll),
     Incr(Incr),
+    Invalidate(Invalidate),
+    MetaDebug(MetaDebug),
+    MetaGet(MetaGet),
     Get(Get),
     Gets(Gets),
     Prepend(Prepend),
@@ -259,6 +289,9 @@ impl Display for Request {
             Request::Delete(_) => write!(f, "delete"),
             Request::FlushAll(_) => write!(f, "flush_all"),
             Request::Incr(_) => write!(f, "incr"),
+            Request::Invalidate(_) => write!(f, "invalidate"),
+            Request::MetaDebug(_) => write!(f, "me"),
+            Request::MetaGet(_) => write!(f, "mg"),
             Request::Get(_) => write!(f, "get"),
             Request::Gets(_) => write!(f, "gets"),
             Request::Prepend(_) => write!(f, "prepend"),
@@ -278,6 +311,9 @@ pub enum Command {
     Delete,
     FlushAll,
     Incr,
+    Invalidate,
+    MetaDebug,
+    MetaGet,
     Get,
     Gets,
     Prepend,
@@ -39,7 +39,7 @@ impl RequestParser {
     // this is to be called after parsing the command, so we do not match the verb
     pub fn parse_replace<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], Replace> {
         // we can use the set parser here and convert the request
-        match self.parse_set_no_stats(input) {
+        match self.parse_set_no_stats(input, false) {
             Ok((input, request)) => {
                 REPLACE.increment();
                 Ok((
@@ -11,6 +11,8 @@ pub struct Set {
     pub(crate) flags: u32,
     pub(crate) ttl: Ttl,
     pub(crate) noreply: bool,
+    pub(crate) tag: Option<Box<[u8]>>,
+    pub(crate) lease: Option<u64>,
 }
 
 impl Set {
@@ -33,12 +35,33 @@ impl Set {
     pub fn noreply(&self) -> bool {
         self.noreply
     }
+
+    /// The tag for the item, which is an extension to the protocol. The tag
+    /// is given after the value length as `tag=<tag>`.
+    pub fn tag(&self) -> Option<&[u8]> {
+        self.tag.as_deref()
+    }
+
+    /// The lease token from a meta get, which is an extension to the protocol.
+    /// The token is given after the value length as `lease=<token>`, and the
+    /// item is only stored if the lease is still held.
+    pub fn lease(&self) -> Option<u64> {
+        self.lease
+    }
 }
 
 impl RequestParser {
-    // this is to be called after parsing the command, so we do not match the verb
-    pub(crate) fn parse_set_no_stats<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], Set> {
+    // this is to be called after parsing the command, so we do not match the verb.
+    // A tag or a lease is only accepted when `extended` is set, as the other
+    // storage commands which share this parser do not use them.
+    pub(crate) fn parse_set_no_stats<'a>(
+        &self,
+        input: &'a [u8],
+        extended: bool,
+    ) -> IResult<&'a [u8], Set> {
         let mut noreply = false;
+        let mut tag = None;
+        let mut lease = None;
 
         let (input, _) = space1(input)?;
         let (input, key) = key(input, self.max_key_len)?;
@@ -61,6 +84,33 @@ impl RequestParser {
             return Err(nom::Err::Failure((input, nom::error::ErrorKind::Tag)));
         }
 
+        // if we have a space, we might have a tag or a lease, in either order
+        while let Ok((i, _)) = space1(input) {
+            if i.len() > 4 && &i[0..4] == b"tag=" && tag.is_none() {
+                if !extended {
+                    return Err(nom::Err::Failure((input, nom::error::ErrorKind::Tag)));
+                }
+                match crate::util::key(&i[4..], self.max_key_len)? {
+                    (i, Some(t)) => {
+                        input = i;
+                        tag = Some(t.to_owned().into_boxed_slice());
+                    }
+                    (_, None) => {
+                        return Err(nom::Err::Failure((input, nom::error::ErrorKind::Tag)));
+                    }
+                }
+            } else if i.len() > 6 && &i[0..6] == b"lease=" && lease.is_none() {
+                if !extended {
+                    return Err(nom::Err::Failure((input, nom::error::ErrorKind::Tag)));
+                }
+                let (i, token) = parse_u64(&i[6..])?;
+                input = i;
+                lease = Some(token);
+            } else {
+                break;
+            }
+        }
+
         // if we have a space, we might have a noreply
         if let Ok((i, _)) = space1(input) {
             if i.len() > 7 && &i[0..7] == b"noreply" {
@@ -82,12 +132,14 @@ impl RequestParser {
                 ttl,
                 flags,
                 noreply,
+                tag,
+                lease,
             },
         ))
     }
 
     pub fn parse_set<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], Set> {
-        match self.parse_set_no_stats(input) {
+        match self.parse_set_no_stats(input, true) {
             Ok((input, request)) => {
                 SET.increment();
                 Ok((input, request))
@@ -109,6 +161,14 @@ impl Compose for Set {
         let flags = format!(" {}", self.flags).into_bytes();
         let ttl = format!(" {}", self.ttl.get().unwrap_or(0)).into_bytes();
         let vlen = format!(" {}", self.value.len()).into_bytes();
+        let tag = match &self.tag {
+            Some(tag) => [b" tag=", &tag[..]].concat(),
+            None => Vec::new(),
+        };
+        let lease = match self.lease {
+            Some(token) => format!(" lease={}", token).into_bytes(),
+            None => Vec::new(),
+        };
         let header_end = if self.noreply {
             " noreply\r\n".as_bytes()
         } else {
@@ -120,6 +180,8 @@ impl Compose for Set {
             + flags.len()
             + ttl.len()
             + vlen.len()
+            + tag.len()
+            + lease.len()
             + header_end.len()
             + self.value.len()
             + CRLF.len();
@@ -129,6 +191,8 @@ impl Compose for Set {
         session.put_slice(&flags);
         session.put_slice(&ttl);
         session.put_slice(&vlen);
+        session.put_slice(&tag);
+        session.put_slice(&lease);
         session.put_slice(header_end);
         session.put_slice(&self.value);
         session.put_slice(CRLF);
@@ -185,6 +249,8 @@ mod tests {
                     flags: 0,
                     ttl: Ttl::none(),
                     noreply: false,
+                    tag: None,
+                    lease: None,
                 })
             ))
         );
@@ -200,8 +266,55 @@ mod tests {
                     flags: 0,
                     ttl: Ttl::none(),
                     noreply: true,
+                    tag: None,
+                    lease: None,
                 })
             ))
         );
+
+        // tag
+        assert_eq!(
+            parser.parse_request(b"set 0 0 0 1 tag=user:1 noreply\r\n0\r\n"),
+            Ok((
+                &b""[..],
+                Request::Set(Set {
+                    key: b"0".to_vec().into_boxed_slice(),
+                    value: b"0".to_vec().into_boxed_slice(),
+                    flags: 0,
+                    ttl: Ttl::none(),
+                    noreply: true,
+                    tag: Some(b"user:1".to_vec().into_boxed_slice()),
+                    lease: None,
+                })
+            ))
+        );
+
+        // lease, which may follow or precede a tag
+        assert_eq!(
+            parser.parse_request(b"set 0 0 0 1 lease=42 tag=user:1\r\n0\r\n"),
+            Ok((
+                &b""[..],
+                Request::Set(Set {
+                    key: b"0".to_vec().into_boxed_slice(),
+                    value: b"0".to_vec().into_boxed_slice(),
+                    flags: 0,
+                    ttl: Ttl::none(),
+                    noreply: false,
+                    tag: Some(b"user:1".to_vec().into_boxed_slice()),
+                    lease: Some(42),
+                })
+            ))
+        );
+        assert!(parser
+            .parse_request(b"set 0 0 0 1 lease=x\r\n0\r\n")
+            .is_err());
+
+        // tags and leases are only accepted by set
+        assert!(parser
+            .parse_request(b"add 0 0 0 1 tag=user:1\r\n0\r\n")
+            .is_err());
+        assert!(parser
+            .parse_request(b"replace 0 0 0 1 lease=42\r\n0\r\n")
+            .is_err());
     }
 }
@@ -0,0 +1,225 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+
+const MISS: &[u8] = b"EN\r\n";
+
+/// The response to a meta debug request. For a hit this holds the remaining
+/// TTL, the absolute expiry as a UNIX time, the age, and the access frequency
+/// of the item. All times are in whole seconds. The TTL and expiry are -1 for
+/// items which do not expire.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DebugInfo {
+    item: Option<Item>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Item {
+    key: Box<[u8]>,
+    ttl: i64,
+    expire_at: i64,
+    age: u64,
+    freq: u8,
+    cas: u64,
+}
+
+impl DebugInfo {
+    pub fn new(key: &[u8], ttl: i64, expire_at: i64, age: u64, freq: u8, cas: u64) -> Self {
+        Self {
+            item: Some(Item {
+                key: key.to_owned().into_boxed_slice(),
+                ttl,
+                expire_at,
+                age,
+                freq,
+                cas,
+            }),
+        }
+    }
+
+    pub fn miss() -> Self {
+        Self { item: None }
+    }
+
+    pub fn is_hit(&self) -> bool {
+        self.item.is_some()
+    }
+
+    pub fn key(&self) -> Option<&[u8]> {
+        self.item.as_ref().map(|i| i.key.as_ref())
+    }
+
+    /// The number of seconds until the item expires, -1 if it does not expire
+    pub fn ttl(&self) -> Option<i64> {
+        self.item.as_ref().map(|i| i.ttl)
+    }
+
+    /// The UNIX time in seconds at which the item expires, -1 if it does not
+    /// expire
+    pub fn expire_at(&self) -> Option<i64> {
+        self.item.as_ref().map(|i| i.expire_at)
+    }
+
+    /// The number of seconds since the item was stored
+    pub fn age(&self) -> Option<u64> {
+        self.item.as_ref().map(|i| i.age)
+    }
+
+    /// The approximate access frequency of the item
+    pub fn freq(&self) -> Option<u8> {
+        self.item.as_ref().map(|i| i.freq)
+    }
+
+    pub fn cas(&self) -> Option<u64> {
+        self.item.as_ref().map(|i| i.cas)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn len(&self) -> usize {
+        match self.item {
+            Some(ref item) => b"ME ".len() + item.key.len() + item.fields().len(),
+            None => MISS.len(),
+        }
+    }
+}
+
+impl Item {
+    fn fields(&self) -> String {
+        format!(
+            " exp={} exp_at={} age={} freq={} cas={}\r\n",
+            self.ttl, self.expire_at, self.age, self.freq, self.cas
+        )
+    }
+}
+
+impl Compose for DebugInfo {
+    fn compose(&self, session: &mut dyn BufMut) -> usize {
+        match self.item {
+            Some(ref item) => {
+                let prefix = b"ME ";
+                let fields = item.fields().into_bytes();
+
+                session.put_slice(prefix);
+                session.put_slice(&item.key);
+                session.put_slice(&fields);
+
+                prefix.len() + item.key.len() + fields.len()
+            }
+            None => {
+                session.put_slice(MISS);
+                MISS.len()
+            }
+        }
+    }
+}
+
+// parses a single `name=value` field which is preceded by one or more spaces
+fn field<'a>(input: &'a [u8], name: &'static [u8]) -> IResult<&'a [u8], u64> {
+    let (input, _) = space1(input)?;
+    let (input, _) = tag(name)(input)?;
+    let (input, _) = tag(b"=")(input)?;
+    parse_u64(input)
+}
+
+// parses a single `name=value` field with a signed value
+fn signed_field<'a>(input: &'a [u8], name: &'static [u8]) -> IResult<&'a [u8], i64> {
+    let (input, _) = space1(input)?;
+    let (input, _) = tag(name)(input)?;
+    let (input, _) = tag(b"=")(input)?;
+    parse_i64(input)
+}
+
+pub fn parse(input: &[u8]) -> IResult<&[u8], DebugInfo> {
+    let (input, _) = space1(input)?;
+    let (input, key) = take_till(|b| b == b' ' || b == b'\r')(input)?;
+
+    let (input, ttl) = signed_field(input, b"exp")?;
+    let (input, expire_at) = signed_field(input, b"exp_at")?;
+    let (input, age) = field(input, b"age")?;
+    let (input, freq) = field(input, b"freq")?;
+    let (input, cas) = field(input, b"cas")?;
+
+    let (input, _) = space0(input)?;
+    let (input, _) = crlf(input)?;
+
+    if freq > u8::MAX as u64 {
+        return Err(nom::Err::Failure((input, nom::error::ErrorKind::Tag)));
+    }
+
+    Ok((
+        input,
+        DebugInfo::new(key, ttl, expire_at, age, freq as u8, cas),
+    ))
+}
+
+pub fn parse_miss(input: &[u8]) -> IResult<&[u8], DebugInfo> {
+    let (input, _) = space0(input)?;
+    let (input, _) = crlf(input)?;
+    Ok((input, DebugInfo::miss()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compose() {
+        let mut buffer = Vec::new();
+        let response = Response::debug_info(DebugInfo::new(b"key", 30, 1700000030, 5, 2, 7));
+        let len = response.compose(&mut buffer);
+        assert_eq!(
+            &buffer[..],
+            &b"ME key exp=30 exp_at=1700000030 age=5 freq=2 cas=7\r\n"[..]
+        );
+        assert_eq!(len, buffer.len());
+        assert_eq!(len, DebugInfo::new(b"key", 30, 1700000030, 5, 2, 7).len());
+
+        let mut buffer = Vec::new();
+        let response = Response::debug_info(DebugInfo::new(b"key", -1, -1, 5, 2, 7));
+        let len = response.compose(&mut buffer);
+        assert_eq!(
+            &buffer[..],
+            &b"ME key exp=-1 exp_at=-1 age=5 freq=2 cas=7\r\n"[..]
+        );
+        assert_eq!(len, buffer.len());
+
+        let mut buffer = Vec::new();
+        let len = Response::debug_info(DebugInfo::miss()).compose(&mut buffer);
+        assert_eq!(&buffer[..], MISS);
+        assert_eq!(len, MISS.len());
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            response(b"ME key exp=30 exp_at=1700000030 age=5 freq=2 cas=7\r\n"),
+            Ok((
+                &b""[..],
+                Response::debug_info(DebugInfo::new(b"key", 30, 1700000030, 5, 2, 7)),
+            ))
+        );
+
+        assert_eq!(
+            response(b"ME key exp=-1 exp_at=-1 age=5 freq=2 cas=7\r\n"),
+            Ok((
+                &b""[..],
+                Response::debug_info(DebugInfo::new(b"key", -1, -1, 5, 2, 7)),
+            ))
+        );
+
+        assert_eq!(
+            response(b"EN\r\n"),
+            Ok((&b""[..], Response::debug_info(DebugInfo::miss()),))
+        );
+
+        assert_eq!(
+            response(b"EN \r\n"),
+            Ok((&b""[..], Response::debug_info(DebugInfo::miss()),))
+        );
+    }
+}
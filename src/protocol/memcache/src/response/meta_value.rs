@@ -0,0 +1,314 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+
+const MISS: &[u8] = b"EN\r\n";
+
+/// The response to a meta get request. An item is returned as `VA` with the
+/// value, or as `HD` when the value was not requested, followed by the
+/// requested return flags. A miss is returned as `EN`, unless a lease was
+/// requested, in which case it is returned as `HD` with the lease flags.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MetaValue {
+    found: bool,
+    value: Option<Box<[u8]>>,
+    flags: Option<u32>,
+    cas: Option<u64>,
+    ttl: Option<i64>,
+    key: Option<Box<[u8]>>,
+    win: bool,
+    stale: bool,
+    wait: bool,
+}
+
+impl MetaValue {
+    /// A response for an item which was found
+    pub fn new() -> Self {
+        Self {
+            found: true,
+            ..Default::default()
+        }
+    }
+
+    /// A response for an item which was not found
+    pub fn miss() -> Self {
+        Self::default()
+    }
+
+    pub fn with_value(mut self, value: &[u8]) -> Self {
+        self.value = Some(value.to_owned().into_boxed_slice());
+        self
+    }
+
+    pub fn with_flags(mut self, flags: u32) -> Self {
+        self.flags = Some(flags);
+        self
+    }
+
+    pub fn with_cas(mut self, cas: u64) -> Self {
+        self.cas = Some(cas);
+        self
+    }
+
+    pub fn with_ttl(mut self, ttl: i64) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn with_key(mut self, key: &[u8]) -> Self {
+        self.key = Some(key.to_owned().into_boxed_slice());
+        self
+    }
+
+    /// Marks that a lease was granted, the lease token is returned in place of
+    /// the CAS value
+    pub fn with_win(mut self, token: u64) -> Self {
+        self.win = true;
+        self.cas = Some(token);
+        self
+    }
+
+    /// Marks the item as expired
+    pub fn with_stale(mut self) -> Self {
+        self.stale = true;
+        self
+    }
+
+    /// Marks that another client holds the lease
+    pub fn with_wait(mut self) -> Self {
+        self.wait = true;
+        self
+    }
+
+    /// Returns true if an item, which may have expired, was found
+    pub fn is_hit(&self) -> bool {
+        self.found
+    }
+
+    pub fn value(&self) -> Option<&[u8]> {
+        self.value.as_deref()
+    }
+
+    pub fn flags(&self) -> Option<u32> {
+        self.flags
+    }
+
+    /// The CAS value of the item, or the lease token if a lease was granted
+    pub fn cas(&self) -> Option<u64> {
+        self.cas
+    }
+
+    pub fn ttl(&self) -> Option<i64> {
+        self.ttl
+    }
+
+    pub fn key(&self) -> Option<&[u8]> {
+        self.key.as_deref()
+    }
+
+    /// Returns true if a lease was granted
+    pub fn is_win(&self) -> bool {
+        self.win
+    }
+
+    /// Returns true if the item has expired
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    /// Returns true if another client holds the lease
+    pub fn is_wait(&self) -> bool {
+        self.wait
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn len(&self) -> usize {
+        if self.is_miss() {
+            return MISS.len();
+        }
+        let header = self.header();
+        match self.value {
+            Some(ref value) => header.len() + value.len() + CRLF.len(),
+            None => header.len(),
+        }
+    }
+
+    fn is_miss(&self) -> bool {
+        !self.found && !self.win && !self.wait
+    }
+
+    fn header(&self) -> Vec<u8> {
+        let mut header = match self.value {
+            Some(ref value) => format!("VA {}", value.len()).into_bytes(),
+            None => b"HD".to_vec(),
+        };
+        if let Some(flags) = self.flags {
+            header.extend_from_slice(format!(" f{}", flags).as_bytes());
+        }
+        if let Some(cas) = self.cas {
+            header.extend_from_slice(format!(" c{}", cas).as_bytes());
+        }
+        if let Some(ttl) = self.ttl {
+            header.extend_from_slice(format!(" t{}", ttl).as_bytes());
+        }
+        if let Some(ref key) = self.key {
+            header.extend_from_slice(b" k");
+            header.extend_from_slice(key);
+        }
+        for (enabled, flag) in [
+            (self.win, &b" W"[..]),
+            (self.stale, b" X"),
+            (self.wait, b" Z"),
+        ] {
+            if enabled {
+                header.extend_from_slice(flag);
+            }
+        }
+        header.extend_from_slice(CRLF);
+        header
+    }
+}
+
+impl Compose for MetaValue {
+    fn compose(&self, session: &mut dyn BufMut) -> usize {
+        if self.is_miss() {
+            session.put_slice(MISS);
+            return MISS.len();
+        }
+
+        let header = self.header();
+        session.put_slice(&header);
+        match self.value {
+            Some(ref value) => {
+                session.put_slice(value);
+                session.put_slice(CRLF);
+                header.len() + value.len() + CRLF.len()
+            }
+            None => header.len(),
+        }
+    }
+}
+
+// parses a numeric return flag
+fn number<T: std::str::FromStr>(token: &[u8]) -> Option<T> {
+    std::str::from_utf8(token).ok()?.parse().ok()
+}
+
+// parses the return flags which follow the response code
+fn return_flags(mut input: &[u8]) -> IResult<&[u8], MetaValue> {
+    let mut response = MetaValue::default();
+    while let Ok((i, _)) = space1(input) {
+        let (i, flag) = take_till(|b| b == b' ' || b == b'\r')(i)?;
+        let invalid = || nom::Err::Failure((input, nom::error::ErrorKind::Tag));
+        match flag {
+            [] => break,
+            [b'f', flags @ ..] => response.flags = Some(number(flags).ok_or_else(invalid)?),
+            [b'c', cas @ ..] => response.cas = Some(number(cas).ok_or_else(invalid)?),
+            [b't', ttl @ ..] => response.ttl = Some(number(ttl).ok_or_else(invalid)?),
+            [b'k', key @ ..] => response.key = Some(key.to_owned().into_boxed_slice()),
+            b"W" => response.win = true,
+            b"X" => response.stale = true,
+            b"Z" => response.wait = true,
+            _ => return Err(invalid()),
+        }
+        input = i;
+    }
+    let (input, _) = space0(input)?;
+    let (input, _) = crlf(input)?;
+
+    // a lease without an expired item means that no item was found
+    response.found = response.stale || !(response.win || response.wait);
+    Ok((input, response))
+}
+
+pub fn parse_header(input: &[u8]) -> IResult<&[u8], MetaValue> {
+    return_flags(input)
+}
+
+pub fn parse_value(input: &[u8]) -> IResult<&[u8], MetaValue> {
+    let (input, _) = space1(input)?;
+    let (input, bytes) = parse_usize(input)?;
+    let (input, mut response) = return_flags(input)?;
+    let (input, value) = take(bytes)(input)?;
+    let (input, _) = crlf(input)?;
+    response.value = Some(value.to_owned().into_boxed_slice());
+    Ok((input, response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compose() {
+        let responses: [(MetaValue, &[u8]); 5] = [
+            (MetaValue::miss(), b"EN\r\n"),
+            (MetaValue::new(), b"HD\r\n"),
+            (
+                MetaValue::new()
+                    .with_value(b"value")
+                    .with_flags(1)
+                    .with_cas(2)
+                    .with_ttl(30)
+                    .with_key(b"key"),
+                b"VA 5 f1 c2 t30 kkey\r\nvalue\r\n",
+            ),
+            (MetaValue::miss().with_win(7), b"HD c7 W\r\n"),
+            (
+                MetaValue::new().with_value(b"old").with_stale().with_wait(),
+                b"VA 3 X Z\r\nold\r\n",
+            ),
+        ];
+
+        for (response, expected) in responses {
+            let mut buffer = Vec::new();
+            let len = Response::meta_value(response).compose(&mut buffer);
+            assert_eq!(&buffer[..], expected);
+            assert_eq!(len, expected.len());
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            response(b"VA 5 f1 c2 t30 kkey\r\nvalue\r\n"),
+            Ok((
+                &b""[..],
+                Response::meta_value(
+                    MetaValue::new()
+                        .with_value(b"value")
+                        .with_flags(1)
+                        .with_cas(2)
+                        .with_ttl(30)
+                        .with_key(b"key")
+                ),
+            ))
+        );
+
+        assert_eq!(
+            response(b"HD c7 W\r\n"),
+            Ok((
+                &b""[..],
+                Response::meta_value(MetaValue::miss().with_win(7)),
+            ))
+        );
+
+        assert_eq!(
+            response(b"VA 3 c7 W X\r\nold\r\n"),
+            Ok((
+                &b""[..],
+                Response::meta_value(MetaValue::new().with_value(b"old").with_win(7).with_stale()),
+            ))
+        );
+
+        assert_eq!(
+            response(b"HD\r\n"),
+            Ok((&b""[..], Response::meta_value(MetaValue::new())))
+        );
+    }
+}
@@ -6,9 +6,11 @@ use crate::*;
 use protocol_common::{BufMut, Parse, ParseOk};
 
 mod client_error;
+mod debug_info;
 mod deleted;
 mod error;
 mod exists;
+mod meta_value;
 mod not_found;
 mod not_stored;
 mod numeric;
@@ -17,9 +19,11 @@ mod stored;
 mod values;
 
 pub use client_error::ClientError;
+pub use debug_info::DebugInfo;
 pub use deleted::Deleted;
 pub use error::Error;
 pub use exists::Exists;
+pub use meta_value::MetaValue;
 pub use not_found::NotFound;
 pub use not_stored::NotStored;
 pub use numeric::Numeric;
@@ -39,6 +43,8 @@ pub enum Response {
     Values(Values),
     Numeric(Numeric),
     Deleted(Deleted),
+    DebugInfo(DebugInfo),
+    MetaValue(MetaValue),
     Hangup,
 }
 
@@ -90,6 +96,14 @@ impl Response {
     pub fn deleted(noreply: bool) -> Self {
         Self::Deleted(Deleted::new(noreply))
     }
+
+    pub fn debug_info(info: DebugInfo) -> Self {
+        Self::DebugInfo(info)
+    }
+
+    pub fn meta_value(value: MetaValue) -> Self {
+        Self::MetaValue(value)
+    }
 }
 
 impl From<Values> for Response {
@@ -98,6 +112,18 @@ impl From<Values> for Response {
     }
 }
 
+impl From<DebugInfo> for Response {
+    fn from(other: DebugInfo) -> Self {
+        Self::DebugInfo(other)
+    }
+}
+
+impl From<MetaValue> for Response {
+    fn from(other: MetaValue) -> Self {
+        Self::MetaValue(other)
+    }
+}
+
 impl Compose for Response {
     fn compose(&self, session: &mut dyn BufMut) -> usize {
         match self {
@@ -111,6 +137,8 @@ impl Compose for Response {
             Self::Values(e) => e.compose(session),
             Self::Numeric(e) => e.compose(session),
             Self::Deleted(e) => e.compose(session),
+            Self::DebugInfo(e) => e.compose(session),
+            Self::MetaValue(e) => e.compose(session),
             Self::Hangup => 0,
         }
     }
@@ -133,6 +161,10 @@ pub enum ResponseType {
     Empty,
     Numeric(u64),
     Deleted,
+    DebugInfo,
+    DebugMiss,
+    MetaValue,
+    MetaHeader,
 }
 
 pub struct ResponseParser {}
@@ -150,6 +182,10 @@ pub(crate) fn response_type(input: &[u8]) -> IResult<&[u8], ResponseType> {
         b"VALUE" => ResponseType::Values,
         b"END" => ResponseType::Empty,
         b"DELETED" => ResponseType::Deleted,
+        b"ME" => ResponseType::DebugInfo,
+        b"EN" => ResponseType::DebugMiss,
+        b"VA" => ResponseType::MetaValue,
+        b"HD" => ResponseType::MetaHeader,
         _ => {
             if let Ok(s) = std::str::from_utf8(response_type_token) {
                 if let Ok(value) = s.parse::<u64>() {
@@ -219,6 +255,24 @@ pub(crate) fn response(input: &[u8]) -> IResult<&[u8], Response> {
             let (input, response) = deleted::parse(input)?;
             Ok((input, Response::Deleted(response)))
         }
+        (input, ResponseType::DebugInfo) => {
+            let (input, response) = debug_info::parse(input)?;
+            Ok((input, Response::DebugInfo(response)))
+        }
+        // this is for a meta debug or meta get request for a key which was not
+        // found, which are not distinguished
+        (input, ResponseType::DebugMiss) => {
+            let (input, response) = debug_info::parse_miss(input)?;
+            Ok((input, Response::DebugInfo(response)))
+        }
+        (input, ResponseType::MetaValue) => {
+            let (input, response) = meta_value::parse_value(input)?;
+            Ok((input, Response::MetaValue(response)))
+        }
+        (input, ResponseType::MetaHeader) => {
+            let (input, response) = meta_value::parse_header(input)?;
+            Ok((input, Response::MetaValue(response)))
+        }
     }
 }
 
@@ -14,6 +14,9 @@ pub trait Storage {
     fn get(&mut self, request: &Get) -> Response;
     fn gets(&mut self, request: &Gets) -> Response;
     fn incr(&mut self, request: &Incr) -> Response;
+    fn invalidate(&mut self, request: &Invalidate) -> Response;
+    fn meta_debug(&mut self, request: &MetaDebug) -> Response;
+    fn meta_get(&mut self, request: &MetaGet) -> Response;
     fn prepend(&mut self, request: &Prepend) -> Response;
     fn quit(&mut self, request: &Quit) -> Response;
     fn replace(&mut self, request: &Replace) -> Response;
@@ -14,10 +14,11 @@ mod test;
 use crate::Response;
 pub use keyword::Keyword;
 use logger::Klog;
+use protocol_common::Method;
 
 pub use parse::Parser as RequestParser;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 /// A collection of all possible `Ping` request types.
 pub enum Request {
     Ping,
@@ -32,3 +33,11 @@ impl Klog for Request {
         }
     }
 }
+
+impl Method for Request {
+    fn method(&self) -> Option<&str> {
+        match self {
+            Request::Ping => Some("ping"),
+        }
+    }
+}
@@ -12,9 +12,23 @@ mod parse;
 #[cfg(test)]
 mod test;
 
+use crate::Request;
+use protocol_common::{ErrorResponse, Method};
+
 pub use parse::Parser as ResponseParser;
 
 /// A collection of all possible `Ping` responses
 pub enum Response {
     Pong,
 }
+
+impl Method for Response {
+    fn method(&self) -> Option<&str> {
+        match self {
+            Response::Pong => Some("ping"),
+        }
+    }
+}
+
+/// The protocol has no error response, so the client connection is closed.
+impl ErrorResponse<Request> for Response {}
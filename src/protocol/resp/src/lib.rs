@@ -5,11 +5,13 @@
 mod message;
 mod request;
 mod response;
+mod storage;
 mod util;
 
 pub(crate) use util::*;
 
 pub use request::*;
 pub use response::*;
+pub use storage::*;
 
 common::metrics::test_no_duplicates!();
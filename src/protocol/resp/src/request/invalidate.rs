@@ -0,0 +1,101 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use super::*;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// Invalidates every item which was stored with the tag, see
+/// `SetRequest::tag()`. This is an extension to the protocol.
+/// format is: invalidate tag
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::redundant_allocation)]
+pub struct InvalidateRequest {
+    tag: Arc<Box<[u8]>>,
+}
+
+impl TryFrom<Message> for InvalidateRequest {
+    type Error = Error;
+
+    fn try_from(other: Message) -> Result<Self, Error> {
+        if let Message::Array(array) = other {
+            if array.inner.is_none() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let mut array = array.inner.unwrap();
+
+            if array.len() != 2 {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            let _command = take_bulk_string(&mut array)?;
+
+            let tag = take_bulk_string(&mut array)?
+                .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+            if tag.is_empty() {
+                return Err(Error::new(ErrorKind::Other, "malformed command"));
+            }
+
+            Ok(Self { tag })
+        } else {
+            Err(Error::new(ErrorKind::Other, "malformed command"))
+        }
+    }
+}
+
+impl InvalidateRequest {
+    pub fn new(tag: &[u8]) -> Self {
+        Self {
+            tag: Arc::new(tag.to_owned().into_boxed_slice()),
+        }
+    }
+
+    pub fn tag(&self) -> &[u8] {
+        &self.tag
+    }
+}
+
+impl From<&InvalidateRequest> for Message {
+    fn from(other: &InvalidateRequest) -> Message {
+        Message::Array(Array {
+            inner: Some(vec![
+                Message::BulkString(BulkString::new(b"INVALIDATE")),
+                Message::BulkString(BulkString::from(other.tag.clone())),
+            ]),
+        })
+    }
+}
+
+impl Compose for InvalidateRequest {
+    fn compose(&self, buf: &mut dyn BufMut) -> usize {
+        let message = Message::from(self);
+        message.compose(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser() {
+        let parser = RequestParser::new();
+        assert_eq!(
+            parser.parse(b"invalidate user:1\r\n").unwrap().into_inner(),
+            Request::Invalidate(InvalidateRequest::new(b"user:1"))
+        );
+
+        assert_eq!(
+            parser
+                .parse(b"*2\r\n$10\r\nINVALIDATE\r\n$6\r\nuser:1\r\n")
+                .unwrap()
+                .into_inner(),
+            Request::Invalidate(InvalidateRequest::new(b"user:1"))
+        );
+
+        assert!(parser.parse(b"invalidate\r\n").is_err());
+    }
+}
@@ -12,13 +12,15 @@ use std::sync::Arc;
 
 mod badd;
 mod get;
+mod invalidate;
 mod ping;
 mod set;
 
 pub use badd::BAddRequest;
 pub use get::GetRequest;
+pub use invalidate::InvalidateRequest;
 pub use ping::PingRequest;
-pub use set::SetRequest;
+pub use set::{SetMode, SetRequest};
 
 #[derive(Default)]
 pub struct RequestParser {
@@ -97,6 +99,9 @@ impl Parse<Request> for RequestParser {
                         Some(b"get") | Some(b"GET") => {
                             GetRequest::try_from(message).map(Request::from)
                         }
+                        Some(b"invalidate") | Some(b"INVALIDATE") => {
+                            InvalidateRequest::try_from(message).map(Request::from)
+                        }
                         Some(b"set") | Some(b"SET") => {
                             SetRequest::try_from(message).map(Request::from)
                         }
@@ -125,6 +130,7 @@ impl Compose for Request {
         match self {
             Self::BAdd(r) => r.compose(buf),
             Self::Get(r) => r.compose(buf),
+            Self::Invalidate(r) => r.compose(buf),
             Self::Set(r) => r.compose(buf),
             Self::Ping(r) => r.compose(buf),
         }
@@ -135,6 +141,7 @@ impl Compose for Request {
 pub enum Request {
     BAdd(BAddRequest),
     Get(GetRequest),
+    Invalidate(InvalidateRequest),
     Set(SetRequest),
     Ping(PingRequest),
 }
@@ -151,6 +158,12 @@ impl From<GetRequest> for Request {
     }
 }
 
+impl From<InvalidateRequest> for Request {
+    fn from(other: InvalidateRequest) -> Self {
+        Self::Invalidate(other)
+    }
+}
+
 impl From<SetRequest> for Request {
     fn from(other: SetRequest) -> Self {
         Self::Set(other)
@@ -167,6 +180,7 @@ impl From<PingRequest> for Request {
 pub enum Command {
     BAdd,
     Get,
+    Invalidate,
     Set,
     Ping,
 }
@@ -178,6 +192,7 @@ impl TryFrom<&[u8]> for Command {
         match other {
             b"badd" | b"BADD" => Ok(Command::BAdd),
             b"get" | b"GET" => Ok(Command::Get),
+            b"invalidate" | b"INVALIDATE" => Ok(Command::Invalidate),
             b"set" | b"SET" => Ok(Command::Set),
             b"ping" | b"PING" => Ok(Command::Ping),
             _ => Err(()),
@@ -21,6 +21,7 @@ pub struct SetRequest {
     expire_time: Option<ExpireTime>,
     mode: SetMode,
     get_old: bool,
+    tag: Option<Arc<Box<[u8]>>>,
 }
 
 impl SetRequest {
@@ -43,6 +44,12 @@ impl SetRequest {
     pub fn get_old(&self) -> bool {
         self.get_old
     }
+
+    /// The tag for the item, which is an extension to the protocol. The tag
+    /// is given as `TAG <tag>`.
+    pub fn tag(&self) -> Option<&[u8]> {
+        self.tag.as_ref().map(|tag| &***tag)
+    }
 }
 
 impl TryFrom<Message> for SetRequest {
@@ -75,6 +82,7 @@ impl TryFrom<Message> for SetRequest {
             let mut expire_time = None;
             let mut mode = SetMode::Set;
             let mut get_old = false;
+            let mut tag = None;
 
             while let Some(token) = take_bulk_string_as_utf8(&mut array)? {
                 match token.as_str() {
@@ -145,6 +153,20 @@ impl TryFrom<Message> for SetRequest {
 
                         get_old = true;
                     }
+                    "TAG" => {
+                        if tag.is_some() {
+                            return Err(Error::new(ErrorKind::Other, "malformed command"));
+                        }
+
+                        let t = take_bulk_string(&mut array)?
+                            .ok_or(Error::new(ErrorKind::Other, "malformed command"))?;
+
+                        if t.is_empty() {
+                            return Err(Error::new(ErrorKind::Other, "malformed command"));
+                        }
+
+                        tag = Some(t);
+                    }
                     _ => {
                         return Err(Error::new(ErrorKind::Other, "malformed command"));
                     }
@@ -157,6 +179,7 @@ impl TryFrom<Message> for SetRequest {
                 expire_time,
                 mode,
                 get_old,
+                tag,
             })
         } else {
             Err(Error::new(ErrorKind::Other, "malformed command"))
@@ -209,6 +232,11 @@ impl From<&SetRequest> for Message {
             v.push(Message::bulk_string(b"GET"));
         }
 
+        if let Some(tag) = &other.tag {
+            v.push(Message::bulk_string(b"TAG"));
+            v.push(Message::BulkString(BulkString::from(tag.clone())));
+        }
+
         Message::Array(Array { inner: Some(v) })
     }
 }
@@ -258,6 +286,17 @@ mod tests {
             panic!("invalid parse result");
         }
 
+        if let Request::Set(request) = parser
+            .parse(b"SET key value EX 1000 TAG user:1\r\n")
+            .unwrap()
+            .into_inner()
+        {
+            assert_eq!(request.key(), b"key");
+            assert_eq!(request.tag(), Some(&b"user:1"[..]));
+        } else {
+            panic!("invalid parse result");
+        }
+
         if let Request::Set(request) = parser
             .parse(b"*3\r\n$3\r\nset\r\n$1\r\n0\r\n$1\r\n1\r\n")
             .unwrap()
@@ -0,0 +1,11 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use crate::*;
+
+pub trait Storage {
+    fn get(&mut self, request: &GetRequest) -> Response;
+    fn invalidate(&mut self, request: &InvalidateRequest) -> Response;
+    fn set(&mut self, request: &SetRequest) -> Response;
+}
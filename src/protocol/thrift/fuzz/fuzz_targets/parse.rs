@@ -22,5 +22,9 @@ fuzz_target!(|data: &[u8]| {
 
         assert!(message.len() <= MAX_LEN);
         assert_eq!(message.len(), consumed - 4);
+
+        // decoding must not panic on arbitrary message bodies
+        let _ = message.header();
+        let _ = message.decode();
     }
 });
@@ -0,0 +1,390 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Decoding of Thrift structs for both the binary and compact protocols. This
+//! is a generic decoder which does not require an IDL, the decoded values are
+//! identified only by their field ids and wire types.
+
+use crate::Protocol;
+
+// limits the nesting of structs and containers so malformed messages cannot
+// exhaust the stack
+const MAX_DEPTH: usize = 64;
+
+// binary protocol type ids, which are also used as the canonical type ids
+// after translating the compact protocol type ids
+const T_STOP: u8 = 0;
+const T_BOOL: u8 = 2;
+const T_BYTE: u8 = 3;
+const T_DOUBLE: u8 = 4;
+const T_I16: u8 = 6;
+const T_I32: u8 = 8;
+const T_I64: u8 = 10;
+const T_STRING: u8 = 11;
+const T_STRUCT: u8 = 12;
+const T_MAP: u8 = 13;
+const T_SET: u8 = 14;
+const T_LIST: u8 = 15;
+
+// compact protocol type ids
+const C_STOP: u8 = 0;
+const C_BOOLEAN_TRUE: u8 = 1;
+const C_BOOLEAN_FALSE: u8 = 2;
+const C_BYTE: u8 = 3;
+const C_I16: u8 = 4;
+const C_I32: u8 = 5;
+const C_I64: u8 = 6;
+const C_DOUBLE: u8 = 7;
+const C_BINARY: u8 = 8;
+const C_LIST: u8 = 9;
+const C_SET: u8 = 10;
+const C_MAP: u8 = 11;
+const C_STRUCT: u8 = 12;
+
+/// A decoded Thrift value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Byte(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    Double(f64),
+    Binary(Box<[u8]>),
+    Struct(Struct),
+    Map(Vec<(Value, Value)>),
+    Set(Vec<Value>),
+    List(Vec<Value>),
+}
+
+/// A decoded Thrift struct, which is a sequence of fields.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Struct {
+    fields: Vec<Field>,
+}
+
+impl Struct {
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// Returns the value of the field with the provided id, if present.
+    pub fn field(&self, id: i16) -> Option<&Value> {
+        self.fields.iter().find(|f| f.id == id).map(|f| &f.value)
+    }
+}
+
+/// A single field within a decoded Thrift struct.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    id: i16,
+    value: Value,
+}
+
+impl Field {
+    pub fn id(&self) -> i16 {
+        self.id
+    }
+
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+}
+
+fn invalid_data() -> std::io::Error {
+    std::io::Error::from(std::io::ErrorKind::InvalidData)
+}
+
+/// A cursor over the bytes of a Thrift message.
+pub(crate) struct Decoder<'a> {
+    data: &'a [u8],
+    position: usize,
+    protocol: Protocol,
+    depth: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub(crate) fn new(data: &'a [u8], protocol: Protocol) -> Self {
+        Self {
+            data,
+            position: 0,
+            protocol,
+            depth: 0,
+        }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], std::io::Error> {
+        if len > self.remaining() {
+            return Err(invalid_data());
+        }
+        let bytes = &self.data[self.position..(self.position + len)];
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], std::io::Error> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.read_bytes(N)?);
+        Ok(array)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, std::io::Error> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub(crate) fn read_i32_be(&mut self) -> Result<i32, std::io::Error> {
+        Ok(i32::from_be_bytes(self.read_array()?))
+    }
+
+    /// Reads an unsigned LEB128 varint as used by the compact protocol.
+    pub(crate) fn read_varint(&mut self) -> Result<u64, std::io::Error> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_u8()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid_data())
+    }
+
+    /// Reads a zigzag encoded varint as used by the compact protocol.
+    fn read_zigzag(&mut self) -> Result<i64, std::io::Error> {
+        let value = self.read_varint()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    fn read_len(&mut self) -> Result<usize, std::io::Error> {
+        let len = match self.protocol {
+            Protocol::Binary => {
+                let len = self.read_i32_be()?;
+                if len < 0 {
+                    return Err(invalid_data());
+                }
+                len as usize
+            }
+            Protocol::Compact => self.read_varint()? as usize,
+        };
+
+        // every element occupies at least one byte, this makes sure that
+        // malformed lengths are rejected before we allocate
+        if len > self.remaining() {
+            return Err(invalid_data());
+        }
+
+        Ok(len)
+    }
+
+    fn read_binary(&mut self) -> Result<Box<[u8]>, std::io::Error> {
+        let len = self.read_len()?;
+        Ok(self.read_bytes(len)?.to_vec().into_boxed_slice())
+    }
+
+    pub(crate) fn read_utf8(&mut self, len: usize) -> Result<Box<str>, std::io::Error> {
+        let bytes = self.read_bytes(len)?;
+        std::str::from_utf8(bytes)
+            .map(|s| s.into())
+            .map_err(|_| invalid_data())
+    }
+
+    pub(crate) fn read_string(&mut self) -> Result<Box<str>, std::io::Error> {
+        let len = self.read_len()?;
+        self.read_utf8(len)
+    }
+
+    /// Translates a compact protocol type id into the canonical type id.
+    fn compact_type(ttype: u8) -> Result<u8, std::io::Error> {
+        match ttype {
+            C_STOP => Ok(T_STOP),
+            C_BOOLEAN_TRUE | C_BOOLEAN_FALSE => Ok(T_BOOL),
+            C_BYTE => Ok(T_BYTE),
+            C_I16 => Ok(T_I16),
+            C_I32 => Ok(T_I32),
+            C_I64 => Ok(T_I64),
+            C_DOUBLE => Ok(T_DOUBLE),
+            C_BINARY => Ok(T_STRING),
+            C_LIST => Ok(T_LIST),
+            C_SET => Ok(T_SET),
+            C_MAP => Ok(T_MAP),
+            C_STRUCT => Ok(T_STRUCT),
+            _ => Err(invalid_data()),
+        }
+    }
+
+    /// Reads the header for a list or set, returning the element type and the
+    /// number of elements.
+    fn read_list_header(&mut self) -> Result<(u8, usize), std::io::Error> {
+        match self.protocol {
+            Protocol::Binary => {
+                let ttype = self.read_u8()?;
+                let len = self.read_len()?;
+                Ok((ttype, len))
+            }
+            Protocol::Compact => {
+                let header = self.read_u8()?;
+                let ttype = Self::compact_type(header & 0x0f)?;
+                let len = if header >> 4 == 0x0f {
+                    self.read_len()?
+                } else {
+                    (header >> 4) as usize
+                };
+                Ok((ttype, len))
+            }
+        }
+    }
+
+    /// Reads the header for a map, returning the key type, the value type, and
+    /// the number of entries.
+    fn read_map_header(&mut self) -> Result<(u8, u8, usize), std::io::Error> {
+        match self.protocol {
+            Protocol::Binary => {
+                let ktype = self.read_u8()?;
+                let vtype = self.read_u8()?;
+                let len = self.read_len()?;
+                Ok((ktype, vtype, len))
+            }
+            Protocol::Compact => {
+                let len = self.read_len()?;
+                if len == 0 {
+                    return Ok((T_STOP, T_STOP, 0));
+                }
+                let types = self.read_u8()?;
+                let ktype = Self::compact_type(types >> 4)?;
+                let vtype = Self::compact_type(types & 0x0f)?;
+                Ok((ktype, vtype, len))
+            }
+        }
+    }
+
+    fn read_elements(&mut self, ttype: u8, len: usize) -> Result<Vec<Value>, std::io::Error> {
+        let mut values = Vec::with_capacity(len);
+        for _ in 0..len {
+            values.push(self.read_value(ttype)?);
+        }
+        Ok(values)
+    }
+
+    fn read_value(&mut self, ttype: u8) -> Result<Value, std::io::Error> {
+        let value = match (ttype, self.protocol) {
+            (T_BOOL, _) => Value::Bool(self.read_u8()? == 1),
+            (T_BYTE, _) => Value::Byte(self.read_u8()? as i8),
+            (T_I16, Protocol::Binary) => Value::I16(i16::from_be_bytes(self.read_array()?)),
+            (T_I32, Protocol::Binary) => Value::I32(self.read_i32_be()?),
+            (T_I64, Protocol::Binary) => Value::I64(i64::from_be_bytes(self.read_array()?)),
+            (T_DOUBLE, Protocol::Binary) => Value::Double(f64::from_be_bytes(self.read_array()?)),
+            (T_I16, Protocol::Compact) => Value::I16(self.read_zigzag()? as i16),
+            (T_I32, Protocol::Compact) => Value::I32(self.read_zigzag()? as i32),
+            (T_I64, Protocol::Compact) => Value::I64(self.read_zigzag()?),
+            (T_DOUBLE, Protocol::Compact) => Value::Double(f64::from_le_bytes(self.read_array()?)),
+            (T_STRING, _) => Value::Binary(self.read_binary()?),
+            (T_STRUCT, _) => Value::Struct(self.read_struct()?),
+            (T_MAP, _) => {
+                self.enter()?;
+                let (ktype, vtype, len) = self.read_map_header()?;
+                let mut entries = Vec::with_capacity(len);
+                for _ in 0..len {
+                    let key = self.read_value(ktype)?;
+                    let value = self.read_value(vtype)?;
+                    entries.push((key, value));
+                }
+                self.depth -= 1;
+                Value::Map(entries)
+            }
+            (T_SET, _) => {
+                self.enter()?;
+                let (ttype, len) = self.read_list_header()?;
+                let values = self.read_elements(ttype, len)?;
+                self.depth -= 1;
+                Value::Set(values)
+            }
+            (T_LIST, _) => {
+                self.enter()?;
+                let (ttype, len) = self.read_list_header()?;
+                let values = self.read_elements(ttype, len)?;
+                self.depth -= 1;
+                Value::List(values)
+            }
+            _ => {
+                return Err(invalid_data());
+            }
+        };
+
+        Ok(value)
+    }
+
+    fn enter(&mut self) -> Result<(), std::io::Error> {
+        if self.depth >= MAX_DEPTH {
+            return Err(invalid_data());
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Reads a struct, consuming all fields up to and including the stop
+    /// field.
+    pub(crate) fn read_struct(&mut self) -> Result<Struct, std::io::Error> {
+        self.enter()?;
+
+        let mut fields = Vec::new();
+        let mut last_id: i16 = 0;
+
+        loop {
+            let header = self.read_u8()?;
+
+            let field = match self.protocol {
+                Protocol::Binary => {
+                    if header == T_STOP {
+                        break;
+                    }
+                    let id = i16::from_be_bytes(self.read_array()?);
+                    let value = self.read_value(header)?;
+                    Field { id, value }
+                }
+                Protocol::Compact => {
+                    if header == C_STOP {
+                        break;
+                    }
+
+                    // the upper nibble is a delta from the last field id, or
+                    // zero if the field id follows as a zigzag varint
+                    let delta = (header >> 4) as i16;
+                    let id = if delta == 0 {
+                        self.read_zigzag()? as i16
+                    } else {
+                        last_id.wrapping_add(delta)
+                    };
+                    last_id = id;
+
+                    // booleans are encoded directly in the field type
+                    let value = match header & 0x0f {
+                        C_BOOLEAN_TRUE => Value::Bool(true),
+                        C_BOOLEAN_FALSE => Value::Bool(false),
+                        ttype => self.read_value(Self::compact_type(ttype)?)?,
+                    };
+                    Field { id, value }
+                }
+            };
+
+            fields.push(field);
+        }
+
+        self.depth -= 1;
+
+        Ok(Struct { fields })
+    }
+
+    /// Returns an error if there are any bytes remaining.
+    pub(crate) fn finish(&self) -> Result<(), std::io::Error> {
+        if self.remaining() == 0 {
+            Ok(())
+        } else {
+            Err(invalid_data())
+        }
+    }
+}
@@ -0,0 +1,203 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Encoding and decoding of the Thrift message header which precedes the
+//! message body. The header identifies the method being called, the type of the message, and a
+//! sequence id which is used to match replies to calls.
+
+use crate::decode::Decoder;
+
+// binary protocol header constants
+const BINARY_VERSION_MASK: u32 = 0xffff_0000;
+const BINARY_VERSION_1: u32 = 0x8001_0000;
+const BINARY_TYPE_MASK: u32 = 0x0000_00ff;
+
+// compact protocol header constants
+const COMPACT_PROTOCOL_ID: u8 = 0x82;
+const COMPACT_VERSION: u8 = 1;
+const COMPACT_VERSION_MASK: u8 = 0x1f;
+const COMPACT_TYPE_SHIFT: u8 = 5;
+const COMPACT_TYPE_MASK: u8 = 0x07;
+
+/// The Thrift protocol used to encode a message.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Protocol {
+    Binary,
+    Compact,
+}
+
+/// The type of a Thrift message.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MessageType {
+    Call,
+    Reply,
+    Exception,
+    Oneway,
+}
+
+impl From<MessageType> for u8 {
+    fn from(value: MessageType) -> Self {
+        match value {
+            MessageType::Call => 1,
+            MessageType::Reply => 2,
+            MessageType::Exception => 3,
+            MessageType::Oneway => 4,
+        }
+    }
+}
+
+impl TryFrom<u8> for MessageType {
+    type Error = std::io::Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Self::Call),
+            2 => Ok(Self::Reply),
+            3 => Ok(Self::Exception),
+            4 => Ok(Self::Oneway),
+            _ => Err(std::io::Error::from(std::io::ErrorKind::InvalidData)),
+        }
+    }
+}
+
+/// The decoded header of a Thrift message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageHeader {
+    protocol: Protocol,
+    method: Box<str>,
+    message_type: MessageType,
+    sequence_id: i32,
+}
+
+impl MessageHeader {
+    /// The protocol which was used to encode the message.
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// The name of the method for this message.
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    pub fn message_type(&self) -> MessageType {
+        self.message_type
+    }
+
+    pub fn sequence_id(&self) -> i32 {
+        self.sequence_id
+    }
+
+    /// Returns a header for a message which replies to this one with the
+    /// provided message type, using the same protocol, method, and sequence id.
+    pub(crate) fn reply(&self, message_type: MessageType) -> Self {
+        Self {
+            message_type,
+            ..self.clone()
+        }
+    }
+
+    /// Encodes the header onto the end of the buffer. The binary protocol
+    /// always uses the strict encoding.
+    pub(crate) fn encode(&self, buffer: &mut Vec<u8>) {
+        let message_type = u8::from(self.message_type);
+        match self.protocol {
+            Protocol::Binary => {
+                buffer.extend_from_slice(&(BINARY_VERSION_1 | message_type as u32).to_be_bytes());
+                buffer.extend_from_slice(&(self.method.len() as i32).to_be_bytes());
+                buffer.extend_from_slice(self.method.as_bytes());
+                buffer.extend_from_slice(&self.sequence_id.to_be_bytes());
+            }
+            Protocol::Compact => {
+                buffer.push(COMPACT_PROTOCOL_ID);
+                buffer.push((message_type << COMPACT_TYPE_SHIFT) | COMPACT_VERSION);
+                write_varint(buffer, self.sequence_id as u32 as u64);
+                write_varint(buffer, self.method.len() as u64);
+                buffer.extend_from_slice(self.method.as_bytes());
+            }
+        }
+    }
+
+    /// Decodes the header from the start of the message body, detecting the
+    /// protocol from the leading bytes. Returns the header along with a
+    /// `Decoder` which is positioned at the start of the message struct.
+    pub(crate) fn decode(data: &[u8]) -> Result<(Self, Decoder<'_>), std::io::Error> {
+        if data.first() == Some(&COMPACT_PROTOCOL_ID) {
+            Self::decode_compact(data)
+        } else {
+            Self::decode_binary(data)
+        }
+    }
+
+    fn decode_binary(data: &[u8]) -> Result<(Self, Decoder<'_>), std::io::Error> {
+        let mut decoder = Decoder::new(data, Protocol::Binary);
+
+        let first = decoder.read_i32_be()?;
+
+        let (method, message_type) = if first < 0 {
+            // strict encoding, the first word is the version and message type
+            let first = first as u32;
+            if first & BINARY_VERSION_MASK != BINARY_VERSION_1 {
+                return Err(std::io::Error::from(std::io::ErrorKind::InvalidData));
+            }
+            let message_type = MessageType::try_from((first & BINARY_TYPE_MASK) as u8)?;
+            let method = decoder.read_string()?;
+            (method, message_type)
+        } else {
+            // non-strict encoding, the first word is the length of the name
+            let method = decoder.read_utf8(first as usize)?;
+            let message_type = MessageType::try_from(decoder.read_u8()?)?;
+            (method, message_type)
+        };
+
+        let sequence_id = decoder.read_i32_be()?;
+
+        Ok((
+            Self {
+                protocol: Protocol::Binary,
+                method,
+                message_type,
+                sequence_id,
+            },
+            decoder,
+        ))
+    }
+
+    fn decode_compact(data: &[u8]) -> Result<(Self, Decoder<'_>), std::io::Error> {
+        let mut decoder = Decoder::new(data, Protocol::Compact);
+
+        // skip over the protocol id
+        let _ = decoder.read_u8()?;
+
+        let version_and_type = decoder.read_u8()?;
+        if version_and_type & COMPACT_VERSION_MASK != COMPACT_VERSION {
+            return Err(std::io::Error::from(std::io::ErrorKind::InvalidData));
+        }
+        let message_type =
+            MessageType::try_from((version_and_type >> COMPACT_TYPE_SHIFT) & COMPACT_TYPE_MASK)?;
+
+        // the sequence id is a varint, but it is not zigzag encoded
+        let sequence_id = decoder.read_varint()? as u32 as i32;
+        let method = decoder.read_string()?;
+
+        Ok((
+            Self {
+                protocol: Protocol::Compact,
+                method,
+                message_type,
+                sequence_id,
+            },
+            decoder,
+        ))
+    }
+}
+
+/// Writes an unsigned LEB128 varint as used by the compact protocol.
+pub(crate) fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
@@ -3,29 +3,155 @@
 // http://www.apache.org/licenses/LICENSE-2.0
 
 //! A protocol crate for Thrift binary protocol.
+//!
+//! Messages are framed by a 4-byte length. The message header (method name,
+//! message type, and sequence id) is decoded by the parser, while the message
+//! body is treated as opaque and may be decoded on demand. Both the binary and
+//! compact protocols are supported.
 
+#[macro_use]
+extern crate logger;
+
+mod decode;
+mod header;
+
+pub use decode::{Field, Struct, Value};
+pub use header::{MessageHeader, MessageType, Protocol};
+
+use logger::Klog;
 use protocol_common::BufMut;
 use protocol_common::Compose;
+use protocol_common::ErrorResponse;
+use protocol_common::Method;
 use protocol_common::Parse;
 use protocol_common::ParseOk;
 use rustcommon_metrics::*;
 
 const THRIFT_HEADER_LEN: usize = std::mem::size_of::<u32>();
 
+// the TApplicationException type for an internal error
+const INTERNAL_ERROR: i32 = 6;
+
 // Stats
 counter!(MESSAGES_PARSED);
 counter!(MESSAGES_COMPOSED);
 
-/// An opaque Thrift message
+/// A Thrift message with an opaque body
+#[derive(Clone)]
 pub struct Message {
     data: Box<[u8]>,
+    header: Option<MessageHeader>,
 }
 
 #[allow(clippy::len_without_is_empty)]
 impl Message {
+    fn new(data: Box<[u8]>) -> Self {
+        let header = MessageHeader::decode(&data).ok().map(|(header, _)| header);
+        Self { data, header }
+    }
+
     pub fn len(&self) -> usize {
         self.data.len()
     }
+
+    /// The message header, which identifies the method, the message type, and
+    /// the sequence id. Returns `None` if the header could not be decoded.
+    pub fn header(&self) -> Option<&MessageHeader> {
+        self.header.as_ref()
+    }
+
+    /// Decodes the full message, returning the header and the struct which
+    /// holds the arguments (for calls) or the result (for replies). Returns an
+    /// error if the message is malformed or has trailing bytes.
+    pub fn decode(&self) -> Result<(MessageHeader, Struct), std::io::Error> {
+        let (header, mut decoder) = MessageHeader::decode(&self.data)?;
+        let body = decoder.read_struct()?;
+        decoder.finish()?;
+        Ok((header, body))
+    }
+
+    /// Builds an exception reply to a call, with a `TApplicationException`
+    /// which carries the message and an internal error type.
+    pub fn exception(call: &MessageHeader, message: &str) -> Self {
+        let header = call.reply(MessageType::Exception);
+        let mut data = Vec::new();
+        header.encode(&mut data);
+
+        match header.protocol() {
+            Protocol::Binary => {
+                // field 1: message (string)
+                data.push(11);
+                data.extend_from_slice(&1_i16.to_be_bytes());
+                data.extend_from_slice(&(message.len() as i32).to_be_bytes());
+                data.extend_from_slice(message.as_bytes());
+                // field 2: type (i32)
+                data.push(8);
+                data.extend_from_slice(&2_i16.to_be_bytes());
+                data.extend_from_slice(&INTERNAL_ERROR.to_be_bytes());
+            }
+            Protocol::Compact => {
+                // field 1: message (binary), the field id delta is in the
+                // upper nibble
+                data.push(0x18);
+                header::write_varint(&mut data, message.len() as u64);
+                data.extend_from_slice(message.as_bytes());
+                // field 2: type (i32), zigzag encoded
+                data.push(0x15);
+                header::write_varint(&mut data, (INTERNAL_ERROR << 1) as u64);
+            }
+        }
+        // field stop
+        data.push(0);
+
+        Self {
+            data: data.into_boxed_slice(),
+            header: Some(header),
+        }
+    }
+}
+
+impl ErrorResponse<Message> for Message {
+    /// Replies with an exception, unless the request could not be decoded, in
+    /// which case there is no way to reply.
+    fn error_response(request: &Message, message: &str) -> Option<Self> {
+        request
+            .header()
+            .map(|header| Message::exception(header, message))
+    }
+}
+
+impl Klog for Message {
+    type Response = Message;
+
+    fn klog(&self, response: &Self::Response) {
+        let method = self.method().unwrap_or("-");
+        let status = match response.header().map(|h| h.message_type()) {
+            Some(MessageType::Reply) => "reply",
+            Some(MessageType::Exception) => "exception",
+            _ => "-",
+        };
+        klog!(
+            "\"{}\" {} {} {}",
+            method,
+            status,
+            self.len(),
+            response.len()
+        );
+    }
+}
+
+impl Method for Message {
+    fn method(&self) -> Option<&str> {
+        self.header.as_ref().map(|h| h.method())
+    }
+
+    /// Messages which cannot be decoded and exception replies are errors.
+    fn is_error(&self) -> bool {
+        !matches!(
+            self.header.as_ref().map(|h| h.message_type()),
+            Some(MessageType::Call | MessageType::Reply | MessageType::Oneway)
+        )
+    }
 }
 
 impl Compose for Message {
@@ -70,7 +196,7 @@ impl Parse<Message> for MessageParser {
             let data = buffer[THRIFT_HEADER_LEN..framed_len]
                 .to_vec()
                 .into_boxed_slice();
-            let message = Message { data };
+            let message = Message::new(data);
             Ok(ParseOk::new(message, framed_len))
         }
     }
@@ -97,6 +223,140 @@ mod tests {
         assert_eq!(consumed, body.len() + THRIFT_HEADER_LEN);
         assert_eq!(*parsed.data, body);
     }
+
+    fn new_message(data: &[u8]) -> Message {
+        Message::new(data.to_vec().into_boxed_slice())
+    }
+
+    #[test]
+    fn binary() {
+        let data = [
+            0x80, 0x01, 0x00, 0x01, // version and message type
+            0x00, 0x00, 0x00, 0x03, b'g', b'e', b't', // method name
+            0x00, 0x00, 0x00, 0x07, // sequence id
+            0x0b, 0x00, 0x01, 0x00, 0x00, 0x00, 0x03, b'k', b'e', b'y', // field 1
+            0x08, 0x00, 0x02, 0xff, 0xff, 0xff, 0xfe, // field 2
+            0x00, // stop
+        ];
+        let message = new_message(&data);
+
+        let header = message.header().expect("failed to decode header");
+        assert_eq!(header.protocol(), Protocol::Binary);
+        assert_eq!(header.method(), "get");
+        assert_eq!(header.message_type(), MessageType::Call);
+        assert_eq!(header.sequence_id(), 7);
+
+        assert_eq!(message.method(), Some("get"));
+        assert!(!message.is_error());
+
+        let (_, body) = message.decode().expect("failed to decode");
+        assert_eq!(body.fields().len(), 2);
+        assert_eq!(body.field(1), Some(&Value::Binary(b"key".to_vec().into())));
+        assert_eq!(body.field(2), Some(&Value::I32(-2)));
+
+        // trailing bytes are rejected
+        let mut trailing = data.to_vec();
+        trailing.push(0x00);
+        assert!(new_message(&trailing).decode().is_err());
+
+        // truncated messages are rejected
+        assert!(new_message(&data[0..10]).header().is_none());
+        assert!(new_message(&data[0..10]).is_error());
+        assert!(new_message(&data[0..20]).decode().is_err());
+    }
+
+    #[test]
+    fn binary_non_strict() {
+        let data = [
+            0x00, 0x00, 0x00, 0x03, b'g', b'e', b't', // method name
+            0x02, // message type
+            0x00, 0x00, 0x00, 0x07, // sequence id
+            0x00, // stop
+        ];
+        let message = new_message(&data);
+
+        let (header, body) = message.decode().expect("failed to decode");
+        assert_eq!(header.protocol(), Protocol::Binary);
+        assert_eq!(header.method(), "get");
+        assert_eq!(header.message_type(), MessageType::Reply);
+        assert_eq!(header.sequence_id(), 7);
+        assert!(body.fields().is_empty());
+    }
+
+    #[test]
+    fn compact() {
+        let data = [
+            0x82, // protocol id
+            0x21, // version and message type
+            0x07, // sequence id
+            0x03, b'g', b'e', b't', // method name
+            0x18, 0x03, b'k', b'e', b'y', // field 1
+            0x15, 0x03, // field 2
+            0x11, // field 3
+            0x19, 0x24, 0x02, 0x04, // field 4
+            0x00, // stop
+        ];
+        let message = new_message(&data);
+
+        let header = message.header().expect("failed to decode header");
+        assert_eq!(header.protocol(), Protocol::Compact);
+        assert_eq!(header.method(), "get");
+        assert_eq!(header.message_type(), MessageType::Call);
+        assert_eq!(header.sequence_id(), 7);
+
+        let (_, body) = message.decode().expect("failed to decode");
+        assert_eq!(body.fields().len(), 4);
+        assert_eq!(body.field(1), Some(&Value::Binary(b"key".to_vec().into())));
+        assert_eq!(body.field(2), Some(&Value::I32(-2)));
+        assert_eq!(body.field(3), Some(&Value::Bool(true)));
+        assert_eq!(
+            body.field(4),
+            Some(&Value::List(vec![Value::I16(1), Value::I16(2)]))
+        );
+
+        // bad version is rejected
+        let mut bad = data.to_vec();
+        bad[1] = 0x22;
+        assert!(new_message(&bad).header().is_none());
+    }
+
+    #[test]
+    fn exception() {
+        let binary = [
+            0x80, 0x01, 0x00, 0x01, // version and message type
+            0x00, 0x00, 0x00, 0x03, b'g', b'e', b't', // method name
+            0x00, 0x00, 0x00, 0x07, // sequence id
+            0x00, // stop
+        ];
+        let compact = [
+            0x82, // protocol id
+            0x21, // version and message type
+            0xff, 0xff, 0xff, 0xff, 0x0f, // sequence id
+            0x03, b'g', b'e', b't', // method name
+            0x00, // stop
+        ];
+
+        for data in [&binary[..], &compact[..]] {
+            let call = new_message(data);
+            let reply = Message::error_response(&call, "unavailable").expect("no reply");
+            assert!(reply.is_error());
+
+            let (header, body) = reply.decode().expect("failed to decode");
+            let expected = call.header().unwrap();
+            assert_eq!(header.protocol(), expected.protocol());
+            assert_eq!(header.method(), "get");
+            assert_eq!(header.message_type(), MessageType::Exception);
+            assert_eq!(header.sequence_id(), expected.sequence_id());
+            assert_eq!(
+                body.field(1),
+                Some(&Value::Binary(b"unavailable".to_vec().into()))
+            );
+            assert_eq!(body.field(2), Some(&Value::I32(INTERNAL_ERROR)));
+        }
+
+        // there is no reply to a message which can not be decoded
+        assert!(Message::error_response(&new_message(&binary[0..10]), "unavailable").is_none());
+    }
 }
 
 common::metrics::test_no_duplicates!();
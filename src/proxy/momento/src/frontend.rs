@@ -4,8 +4,8 @@
 
 use session::Buf;
 
-use crate::*;
 use crate::protocol::*;
+use crate::*;
 
 pub(crate) async fn handle_memcache_client(
     mut socket: tokio::net::TcpStream,
@@ -108,6 +108,12 @@ pub(crate) async fn handle_resp_client(
                             break;
                         }
                     }
+                    resp::Request::Invalidate(_) => {
+                        // tags are not stored by the backend
+                        if resp::unsupported(&mut socket, "tags").await.is_err() {
+                            break;
+                        }
+                    }
                     _ => {
                         println!("bad request");
                         let _ = socket.write_all(b"CLIENT_ERROR\r\n").await;
@@ -7,7 +7,9 @@ pub use protocol_resp::{Request, RequestParser};
 mod get;
 mod ping;
 mod set;
+mod unsupported;
 
 pub use get::*;
 pub use ping::*;
 pub use set::*;
+pub use unsupported::*;
@@ -16,6 +16,13 @@ pub async fn set(
 ) -> Result<(), Error> {
     SET.increment();
 
+    if request.tag().is_some() {
+        SET_EX.increment();
+        // the backend cannot invalidate by tag, so the item is not stored
+        // rather than being stored without the tag
+        return super::unsupported(socket, "tags").await;
+    }
+
     if let Ok(key) = std::str::from_utf8(request.key()) {
         let value = if let Ok(value) = std::str::from_utf8(request.value()) {
             value.to_owned()
@@ -0,0 +1,22 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+use crate::Error;
+use net::TCP_SEND_BYTE;
+use session::{SESSION_SEND, SESSION_SEND_BYTE, SESSION_SEND_EX};
+use tokio::io::AsyncWriteExt;
+
+/// Replies with an error for a request which uses a feature the backend does
+/// not provide. The connection is kept open.
+pub async fn unsupported(socket: &mut tokio::net::TcpStream, feature: &str) -> Result<(), Error> {
+    let response_buf = format!("-ERR {} are not supported\r\n", feature);
+    SESSION_SEND.increment();
+    SESSION_SEND_BYTE.add(response_buf.len() as _);
+    TCP_SEND_BYTE.add(response_buf.len() as _);
+    if let Err(e) = socket.write_all(response_buf.as_bytes()).await {
+        SESSION_SEND_EX.increment();
+        return Err(e);
+    }
+    Ok(())
+}
@@ -28,7 +28,7 @@ fn main() {
         .version_short("v")
         .long_about(
             "A Pelikan proxy server which speaks the Thrift binary protocol \
-            and routes messages to a backend server.",
+            and routes messages to backend servers by method.",
         )
         .arg(
             Arg::with_name("stats")
@@ -0,0 +1,121 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! A tool for working with Segcache dumps. It can ask a running Segcache
+//! instance to write a dump or load one through the admin port, and can verify
+//! and summarize a dump file offline.
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use seg::DumpReader;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const ADMIN_ADDRESS: &str = "127.0.0.1:9999";
+
+fn main() {
+    let admin = Arg::with_name("admin")
+        .long("admin")
+        .short("a")
+        .help("Address of the Segcache admin port")
+        .takes_value(true)
+        .default_value(ADMIN_ADDRESS);
+    let file = Arg::with_name("FILE")
+        .help("Path of the dump file")
+        .required(true)
+        .index(1);
+
+    let matches = App::new(env!("CARGO_BIN_NAME"))
+        .version(env!("CARGO_PKG_VERSION"))
+        .version_short("v")
+        .about("Writes, loads, and verifies Segcache dumps")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("dump")
+                .about("Ask a running instance to write a dump to the path")
+                .arg(admin.clone())
+                .arg(file.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("load")
+                .about("Ask a running instance to load the dump at the path")
+                .arg(admin)
+                .arg(file.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("inspect")
+                .about("Verify a dump file and print a summary")
+                .arg(file),
+        )
+        .get_matches();
+
+    let result = match matches.subcommand() {
+        ("dump", Some(args)) => admin_command("dump", args),
+        ("load", Some(args)) => admin_command("load", args),
+        ("inspect", Some(args)) => inspect(args),
+        _ => unreachable!(),
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Sends the command to the admin port and waits for its outcome, which the
+/// server replies with once the dump or load has completed. The path is made
+/// absolute, since it is resolved by the server rather than this tool.
+fn admin_command(command: &str, args: &ArgMatches) -> Result<(), std::io::Error> {
+    let mut path = PathBuf::from(args.value_of("FILE").unwrap());
+    if path.is_relative() {
+        path = std::env::current_dir()?.join(path);
+    }
+
+    let mut stream = TcpStream::connect(args.value_of("admin").unwrap())?;
+    stream.write_all(format!("{} {}\r\n", command, path.display()).as_bytes())?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response)?;
+    let response = response.trim_end();
+    if response == "OK" {
+        println!("{} of {} completed", command, path.display());
+        return Ok(());
+    }
+
+    let message = match response.strip_prefix("ERROR ") {
+        Some(message) => format!("{} of {} failed: {}", command, path.display(), message),
+        None if response.is_empty() => "connection closed without a response".to_string(),
+        None => format!("unexpected response: {}", response),
+    };
+    Err(std::io::Error::new(std::io::ErrorKind::Other, message))
+}
+
+/// Reads the entire dump, verifying the checksum, and prints a summary.
+fn inspect(args: &ArgMatches) -> Result<(), std::io::Error> {
+    let file = File::open(args.value_of("FILE").unwrap())?;
+    let mut dump = DumpReader::new(BufReader::new(file))?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let mut expired = 0;
+    let mut bytes = 0;
+    while let Some(record) = dump.read()? {
+        // items which do not expire have an expiry of 0
+        if record.expire_at() != 0 && record.expire_at() <= now {
+            expired += 1;
+        }
+        bytes += record.key().len() + record.value().len() + record.optional().len();
+    }
+
+    println!("items: {}", dump.count());
+    println!("expired: {}", expired);
+    println!("bytes: {}", bytes);
+    println!("checksum: ok");
+    Ok(())
+}
@@ -35,14 +35,21 @@ impl Segcache {
 
         // initialize parser
         let parser = Parser::new()
-            .max_value_size(config.seg().segment_size() as usize)
+            .max_value_size(config.seg().max_value_size())
             .time_type(config.time().time_type());
 
+        // the heap may be resized to hold between one segment and the max heap
+        // size
+        let seg = config.seg();
+        let heap_size_range = seg.segment_size() as usize
+            ..=std::cmp::max(seg.heap_size(), seg.max_heap_size().unwrap_or(0));
+
         // initialize process
         let process_builder = ProcessBuilder::<Parser, Request, Response, Storage>::new(
             &config, log_drain, parser, storage,
         )?
-        .version(env!("CARGO_PKG_VERSION"));
+        .version(env!("CARGO_PKG_VERSION"))
+        .heap_size_range(heap_size_range);
 
         // spawn threads
         let process = process_builder.spawn();
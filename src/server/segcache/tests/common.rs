@@ -195,11 +195,137 @@ pub fn tests() {
         ],
     );
 
-    // test unsupported commands
-    test("append", &[("append 7 0 0 1\r\n0\r\n", Some("ERROR\r\n"))]);
+    // test append
     test(
-        "prepend",
-        &[("prepend 8 0 0 1\r\n0\r\n", Some("ERROR\r\n"))],
+        "append not_stored",
+        &[("append 19 0 0 1\r\n0\r\n", Some("NOT_STORED\r\n"))],
+    );
+    test(
+        "append stored",
+        &[
+            // set the key
+            ("set 20 42 0 5\r\nhello\r\n", Some("STORED\r\n")),
+            // append to it
+            ("append 20 0 0 6\r\n world\r\n", Some("STORED\r\n")),
+            // flags are kept from the original item
+            (
+                "get 20\r\n",
+                Some("VALUE 20 42 11\r\nhello world\r\nEND\r\n"),
+            ),
+        ],
+    );
+    test(
+        "append numeric",
+        &[
+            // set the key
+            ("set 21 0 0 2\r\n10\r\n", Some("STORED\r\n")),
+            // append a digit to it
+            ("append 21 0 0 1\r\n5\r\n", Some("STORED\r\n")),
+            // the value is still numeric
+            ("incr 21 1\r\n", Some("106\r\n")),
+            // append a non-digit to it
+            ("append 21 0 0 1\r\na\r\n", Some("STORED\r\n")),
+            ("get 21\r\n", Some("VALUE 21 0 4\r\n106a\r\nEND\r\n")),
+            // the value is no longer numeric
+            ("incr 21 1\r\n", Some("ERROR\r\n")),
+        ],
+    );
+
+    // test prepend
+    test(
+        "prepend not_stored",
+        &[("prepend 22 0 0 1\r\n0\r\n", Some("NOT_STORED\r\n"))],
+    );
+    test(
+        "prepend stored",
+        &[
+            // set the key
+            ("set 23 42 0 5\r\nworld\r\n", Some("STORED\r\n")),
+            // prepend to it
+            ("prepend 23 0 0 6\r\nhello \r\n", Some("STORED\r\n")),
+            // flags are kept from the original item
+            (
+                "get 23\r\n",
+                Some("VALUE 23 42 11\r\nhello world\r\nEND\r\n"),
+            ),
+        ],
+    );
+    test(
+        "prepend numeric",
+        &[
+            // set the key
+            ("set 24 0 0 2\r\n10\r\n", Some("STORED\r\n")),
+            // prepend a digit to it
+            ("prepend 24 0 0 1\r\n5\r\n", Some("STORED\r\n")),
+            // the value is still numeric
+            ("decr 24 1\r\n", Some("509\r\n")),
+        ],
+    );
+
+    // test meta debug, a hit reports times which vary between runs
+    test("meta debug miss", &[("me 25\r\n", Some("EN\r\n"))]);
+    test(
+        "meta debug deleted",
+        &[
+            // set the key
+            ("set 26 0 0 1\r\n0\r\n", Some("STORED\r\n")),
+            // delete it
+            ("delete 26\r\n", Some("DELETED\r\n")),
+            // the key is not found
+            ("me 26\r\n", Some("EN\r\n")),
+        ],
+    );
+
+    // items stored without a ttl are reported with a ttl of -1
+    test(
+        "meta get no ttl",
+        &[
+            ("set 60 0 0 1\r\n0\r\n", Some("STORED\r\n")),
+            ("mg 60 t\r\n", Some("HD t-1\r\n")),
+        ],
+    );
+
+    // test tag invalidation
+    test(
+        "invalidate",
+        &[
+            // set two keys with the same tag and one without
+            ("set 27 0 0 1 tag=t1\r\n0\r\n", Some("STORED\r\n")),
+            ("set 28 0 0 1 tag=t1\r\n1\r\n", Some("STORED\r\n")),
+            ("set 29 0 0 1\r\n2\r\n", Some("STORED\r\n")),
+            // the tagged keys are found
+            ("get 27\r\n", Some("VALUE 27 0 1\r\n0\r\nEND\r\n")),
+            // invalidate the tag
+            ("invalidate t1\r\n", Some("DELETED\r\n")),
+            // only the untagged key remains
+            ("get 27\r\n", Some("END\r\n")),
+            ("get 28\r\n", Some("END\r\n")),
+            ("get 29\r\n", Some("VALUE 29 0 1\r\n2\r\nEND\r\n")),
+        ],
+    );
+
+    // test leases with meta get
+    test(
+        "lease",
+        &[
+            // the first miss is granted a lease, the token is the cas value
+            ("mg 30 v N30\r\n", Some("HD c1 W\r\n")),
+            // the following misses are told to wait
+            ("mg 30 v N30\r\n", Some("HD Z\r\n")),
+            // a set needs the token of the current lease
+            ("set 30 0 0 1 lease=2\r\n0\r\n", Some("NOT_STORED\r\n")),
+            ("set 30 0 0 1 lease=1\r\n0\r\n", Some("STORED\r\n")),
+            ("mg 30 v f k N30\r\n", Some("VA 1 f0 k30\r\n0\r\n")),
+            // the lease ended with the set
+            ("set 30 0 0 1 lease=1\r\n1\r\n", Some("NOT_STORED\r\n")),
+            // without a lease, a miss is returned
+            ("mg 31 v\r\n", Some("EN\r\n")),
+            // a lease timeout which is out of range is rejected
+            (
+                "mg 32 v N100000\r\n",
+                Some("CLIENT_ERROR lease timeout out of range\r\n"),
+            ),
+        ],
     );
 
     std::thread::sleep(Duration::from_millis(500));
@@ -272,7 +398,9 @@ fn test(name: &str, data: &[(&str, Option<&str>)]) {
     info!("status: passed\n");
 }
 
-pub fn admin_tests() {
+/// Runs the admin tests, `resizable` is false if the server rejects resizing
+/// the heap, as it does when the worker threads read from storage directly.
+pub fn admin_tests(resizable: bool) {
     debug!("beginning admin tests");
     println!();
 
@@ -283,6 +411,29 @@ pub fn admin_tests() {
             Some(&format!("VERSION {}\r\n", env!("CARGO_PKG_VERSION"))),
         )],
     );
+
+    // dump and load reply once they have completed
+    let path = std::env::temp_dir().join(format!("segcache-{}.dump", std::process::id()));
+    let dump = format!("dump {}\r\n", path.display());
+    let load = format!("load {}\r\n", path.display());
+    admin_test("dump", &[(&dump, Some("OK\r\n"))]);
+    admin_test("load", &[(&load, Some("OK\r\n"))]);
+    let _ = std::fs::remove_file(&path);
+    admin_test("load missing", &[(&load, Some("ERROR "))]);
+
+    // resize replies once the heap has been resized, and sizes outside of the
+    // configured range are rejected
+    if resizable {
+        admin_test("resize shrink", &[("resize 33554432\r\n", Some("OK\r\n"))]);
+        admin_test("resize grow", &[("resize 67108864\r\n", Some("OK\r\n"))]);
+    } else {
+        admin_test("resize shrink", &[("resize 33554432\r\n", Some("ERROR "))]);
+    }
+    admin_test("resize zero", &[("resize 0\r\n", Some("ERROR "))]);
+    admin_test(
+        "resize too large",
+        &[("resize 134217728\r\n", Some("ERROR "))],
+    );
 }
 
 // opens a new connection to the admin port, sends a request, and checks the response.
@@ -291,7 +442,7 @@ fn admin_test(name: &str, data: &[(&str, Option<&str>)]) {
     debug!("connecting to server");
     let mut stream = TcpStream::connect("127.0.0.1:9999").expect("failed to connect");
     stream
-        .set_read_timeout(Some(Duration::from_millis(250)))
+        .set_read_timeout(Some(Duration::from_millis(1000)))
         .expect("failed to set read timeout");
     stream
         .set_write_timeout(Some(Duration::from_millis(250)))
@@ -27,7 +27,7 @@ fn main() {
 
     tests();
 
-    admin_tests();
+    admin_tests(true);
 
     // shutdown server and join
     info!("shutdown...");
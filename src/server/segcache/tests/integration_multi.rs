@@ -29,7 +29,7 @@ fn main() {
 
     tests();
 
-    admin_tests();
+    admin_tests(true);
 
     // shutdown server and join
     info!("shutdown...");
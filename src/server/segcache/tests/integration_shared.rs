@@ -0,0 +1,40 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! This test module runs the integration test suite against a multi-threaded
+//! instance of Segcache which serves gets from the worker threads.
+
+#[macro_use]
+extern crate logger;
+
+mod common;
+
+use crate::common::*;
+
+use config::{SegConfig, SegcacheConfig, WorkerConfig};
+use pelikan_segcache_rs::Segcache;
+
+use std::time::Duration;
+
+fn main() {
+    debug!("launching multi-worker server with shared reads");
+    let mut config = SegcacheConfig::default();
+    config.worker_mut().set_threads(2);
+    config.seg_mut().set_shared_reads(true);
+    let server = Segcache::new(config).expect("failed to launch segcache");
+
+    // wait for server to startup. duration is chosen to be longer than we'd
+    // expect startup to take in a slow ci environment.
+    std::thread::sleep(Duration::from_secs(10));
+
+    tests();
+
+    admin_tests(false);
+
+    // shutdown server and join
+    info!("shutdown...");
+    server.shutdown();
+
+    info!("passed!");
+}
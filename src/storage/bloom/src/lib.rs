@@ -130,7 +130,8 @@ impl RawBloomFilter {
 pub struct BloomFilter<T: ?Sized> {
     raw: RawBloomFilter,
     seed: u64,
-    _dummy: PhantomData<*const T>,
+    // the filter never holds a `T`, so this keeps it `Send` and `Sync`
+    _dummy: PhantomData<fn(&T)>,
 }
 
 impl<T: Hash + ?Sized> BloomFilter<T> {
@@ -9,18 +9,32 @@ use std::fs::{File, OpenOptions};
 use std::io::{Error, ErrorKind, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 
+#[cfg(target_os = "linux")]
+use std::os::unix::ffi::OsStrExt;
+
 #[cfg(os = "linux")]
 use std::os::unix::fs::OpenOptionsExt;
 
 use memmap2::{MmapMut, MmapOptions};
 
 const PAGE_SIZE: usize = 4096;
-const HEADER_SIZE: usize = core::mem::size_of::<Header>();
+#[cfg(target_os = "linux")]
+const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+/// The size of the header at the start of a file-backed datapool
+pub const HEADER_SIZE: usize = core::mem::size_of::<Header>();
 const MAGIC: [u8; 8] = *b"PELIKAN!";
 
 // NOTE: this must be incremented if there are breaking changes to the on-disk
 // format
-const VERSION: u64 = 0;
+const VERSION: u64 = 2;
+
+// header flag which is set when the data matches the checksums in the file
+const FLAG_CLEAN: u64 = 0x1;
+
+// the data of a file-backed datapool is checksummed in chunks of this size, so
+// that a flush only needs to checksum the chunks which have changed
+const CHUNK_SIZE: usize = 1024 * 1024;
+const CHECKSUM_SIZE: usize = 32;
 
 /// The datapool trait defines the abstraction that each datapool implementation
 /// should conform to.
@@ -33,20 +47,73 @@ pub trait Datapool: Send {
     fn as_mut_slice(&mut self) -> &mut [u8];
 
     /// Performs any actions necessary to persist the data to the backing store.
-    /// This may be a no-op for datapools which cannot persist data.
+    /// This may be a no-op for datapools which cannot persist data. For
+    /// file-backed datapools, this writes a header with the checksum of the
+    /// data, after which the file may be reopened until the data is changed.
+    /// Any later change to the data causes the checksum to fail when the
+    /// datapool is reopened, unless it is followed by another flush.
     fn flush(&mut self) -> Result<(), std::io::Error>;
 
+    /// Persists the data, as for `flush()`, where every change to the data
+    /// since the last flush is within the provided ranges. This allows
+    /// file-backed datapools to only write and checksum the changed parts of
+    /// the data. The default implementation is the same as `flush()`.
+    fn flush_ranges(&mut self, _ranges: &[Range<usize>]) -> Result<(), std::io::Error> {
+        self.flush()
+    }
+
+    /// Persists the data, as for `flush()`, when the datapool is no longer in
+    /// use. The default implementation is the same as `flush()`.
+    fn close(&mut self) -> Result<(), std::io::Error> {
+        self.flush()
+    }
+
+    /// Returns the checksum written by the most recent flush, which identifies
+    /// the contents of the data at that point. Returns `None` for datapools
+    /// which are not persisted, or which have not been flushed.
+    fn checksum(&self) -> Option<[u8; 32]> {
+        None
+    }
+
+    /// Releases the memory which backs a range of the datapool to the
+    /// operating system, after which the contents of the range are undefined.
+    /// Only whole pages within the range are released. The default
+    /// implementation is a no-op for datapools which cannot release memory.
+    fn release(&mut self, _range: Range<usize>) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
     fn len(&self) -> usize {
         self.as_slice().len()
     }
 }
 
+/// Selects whether the memory of a datapool is backed by huge pages, which
+/// reduces TLB misses for large datapools.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum HugePages {
+    /// Use the default page size.
+    #[default]
+    None,
+    /// Advise the kernel to use transparent huge pages where possible.
+    Transparent,
+    /// Use explicitly reserved huge pages (hugetlb). The pages must be
+    /// reserved by the system, eg: through `vm.nr_hugepages`.
+    Explicit,
+}
+
 /// Represents volatile in-memory storage.
 pub struct Memory {
-    mmap: MmapMut,
+    region: Region,
     size: usize,
 }
 
+// The mapping which holds the memory for a `Memory` datapool
+enum Region {
+    Mmap(MmapMut),
+    HugeTlb(HugeTlb),
+}
+
 impl Memory {
     pub fn create(size: usize) -> Result<Self, std::io::Error> {
         // mmap an anonymous region
@@ -54,28 +121,256 @@ impl Memory {
 
         // causes the mmap'd region to be prefaulted by writing a zero at the
         // start of each page
-        let mut offset = 0;
-        while offset < size {
-            mmap[offset] = 0;
-            offset += PAGE_SIZE;
-        }
+        prefault(&mut mmap[..], PAGE_SIZE);
+
+        Ok(Self {
+            region: Region::Mmap(mmap),
+            size,
+        })
+    }
 
-        Ok(Self { mmap, size })
+    /// Create a new `Memory` datapool which is backed by huge pages. Returns
+    /// an error if huge pages are not available, in which case the caller may
+    /// fall back to `Memory::create()`.
+    pub fn create_huge(size: usize, huge_pages: HugePages) -> Result<Self, std::io::Error> {
+        match huge_pages {
+            HugePages::None => Self::create(size),
+            HugePages::Transparent => {
+                // the region is not populated when it is mapped, since the
+                // pages must be faulted after the advice is given
+                let mut mmap = MmapOptions::new().len(size).map_anon()?;
+                advise_huge_pages(&mut mmap[..])?;
+                prefault(&mut mmap[..], PAGE_SIZE);
+
+                Ok(Self {
+                    region: Region::Mmap(mmap),
+                    size,
+                })
+            }
+            HugePages::Explicit => {
+                let mut region = HugeTlb::create(size)?;
+                prefault(region.as_mut_slice(), PAGE_SIZE);
+
+                Ok(Self {
+                    region: Region::HugeTlb(region),
+                    size,
+                })
+            }
+        }
     }
 }
 
 impl Datapool for Memory {
     fn as_slice(&self) -> &[u8] {
-        &self.mmap[..self.size]
+        match &self.region {
+            Region::Mmap(mmap) => &mmap[..self.size],
+            Region::HugeTlb(region) => &region.as_slice()[..self.size],
+        }
     }
 
     fn as_mut_slice(&mut self) -> &mut [u8] {
-        &mut self.mmap[..self.size]
+        match &mut self.region {
+            Region::Mmap(mmap) => &mut mmap[..self.size],
+            Region::HugeTlb(region) => &mut region.as_mut_slice()[..self.size],
+        }
     }
 
     fn flush(&mut self) -> Result<(), std::io::Error> {
-        self.mmap.flush()
+        match &mut self.region {
+            Region::Mmap(mmap) => mmap.flush(),
+            // anonymous memory has no backing store
+            Region::HugeTlb(_) => Ok(()),
+        }
     }
+
+    fn release(&mut self, range: Range<usize>) -> Result<(), std::io::Error> {
+        let range = Range {
+            start: range.start,
+            end: range.end.min(self.size),
+        };
+        match &mut self.region {
+            Region::Mmap(mmap) => release_pages(&mut mmap[range], PAGE_SIZE),
+            Region::HugeTlb(region) => {
+                let page_size = region.page_size;
+                release_pages(&mut region.as_mut_slice()[range], page_size)
+            }
+        }
+    }
+}
+
+// Causes a region to be prefaulted by writing a zero at the start of each page
+fn prefault(region: &mut [u8], page_size: usize) {
+    let mut offset = 0;
+    while offset < region.len() {
+        region[offset] = 0;
+        offset += page_size;
+    }
+}
+
+// Releases the whole pages within the region to the operating system
+#[cfg(target_os = "linux")]
+fn release_pages(region: &mut [u8], page_size: usize) -> Result<(), std::io::Error> {
+    // page sizes are powers of two
+    let ptr = region.as_mut_ptr() as usize;
+    let start = (ptr + page_size - 1) & !(page_size - 1);
+    let end = (ptr + region.len()) & !(page_size - 1);
+    if start >= end {
+        return Ok(());
+    }
+
+    let result =
+        unsafe { libc::madvise(start as *mut libc::c_void, end - start, libc::MADV_DONTNEED) };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn release_pages(_region: &mut [u8], _page_size: usize) -> Result<(), std::io::Error> {
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn advise_huge_pages(region: &mut [u8]) -> Result<(), std::io::Error> {
+    // the kernel uses huge pages for the aligned portion of the region, so the
+    // region itself does not need to be aligned
+    let result = unsafe {
+        libc::madvise(
+            region.as_mut_ptr() as *mut libc::c_void,
+            region.len(),
+            libc::MADV_HUGEPAGE,
+        )
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn advise_huge_pages(_region: &mut [u8]) -> Result<(), std::io::Error> {
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        "transparent huge pages are not supported on this platform",
+    ))
+}
+
+// An anonymous mapping which is backed by explicitly reserved huge pages
+struct HugeTlb {
+    ptr: *mut u8,
+    len: usize,
+    page_size: usize,
+}
+
+// SAFETY: the mapping is owned by this struct and is only accessed through it
+unsafe impl Send for HugeTlb {}
+
+impl HugeTlb {
+    #[cfg(target_os = "linux")]
+    fn create(size: usize) -> Result<Self, std::io::Error> {
+        // the length of the mapping must be a whole number of huge pages
+        let page_size = huge_page_size();
+        let len = (size as f64 / page_size as f64).ceil() as usize * page_size;
+
+        // the huge pages are reserved when the region is mapped, so this fails
+        // rather than faulting later if not enough huge pages are available
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_HUGETLB | libc::MAP_POPULATE,
+                -1,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(Self {
+            ptr: ptr as *mut u8,
+            len,
+            page_size,
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn create(_size: usize) -> Result<Self, std::io::Error> {
+        Err(Error::new(
+            ErrorKind::Unsupported,
+            "explicit huge pages are not supported on this platform",
+        ))
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl Drop for HugeTlb {
+    fn drop(&mut self) {
+        #[cfg(target_os = "linux")]
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+// Returns the default huge page size, as reported by the kernel
+#[cfg(target_os = "linux")]
+fn huge_page_size() -> usize {
+    // eg: "Hugepagesize:       2048 kB"
+    std::fs::read_to_string("/proc/meminfo")
+        .ok()
+        .and_then(|meminfo| {
+            meminfo
+                .lines()
+                .find(|line| line.starts_with("Hugepagesize:"))
+                .and_then(|line| line.split_whitespace().nth(1))
+                .and_then(|kb| kb.parse::<usize>().ok())
+        })
+        .map(|kb| kb * 1024)
+        .unwrap_or(HUGE_PAGE_SIZE)
+}
+
+// Returns the huge page size of the hugetlbfs filesystem which holds the path,
+// or an error if the path is not on a hugetlbfs filesystem
+#[cfg(target_os = "linux")]
+fn hugetlbfs_page_size(path: &Path) -> Result<usize, std::io::Error> {
+    const HUGETLBFS_MAGIC: u32 = 0x958458f6;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "path contains a nul byte"))?;
+
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(Error::last_os_error());
+    }
+
+    if stat.f_type as u32 != HUGETLBFS_MAGIC {
+        return Err(Error::new(
+            ErrorKind::Other,
+            "path is not on a hugetlbfs filesystem",
+        ));
+    }
+
+    Ok(stat.f_bsize as usize)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn hugetlbfs_page_size(_path: &Path) -> Result<usize, std::io::Error> {
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        "hugetlbfs is not supported on this platform",
+    ))
 }
 
 // NOTE: make sure this is a whole number of pages and that all fields which are
@@ -91,7 +386,9 @@ pub struct Header {
     time_unix_ns: UnixInstant<Nanoseconds<u64>>,
     user_version: u64,
     options: u64,
-    _pad: [u8; 4008],
+    flags: u64,
+    data_size: u64,
+    _pad: [u8; 3992],
 }
 
 impl Header {
@@ -106,16 +403,45 @@ impl Header {
             time_unix_ns: UnixInstant::<Nanoseconds<u64>>::now(),
             user_version: 0,
             options: 0,
-            _pad: [0; 4008],
+            flags: 0,
+            data_size: 0,
+            _pad: [0; 3992],
         }
     }
 
+    // Creates a header which is written by a flush, with the checksum of the
+    // header and the table of chunk checksums which follows the data
+    fn flushed(user_version: u64, data_size: usize, table: &[u8]) -> Self {
+        let mut header = Header::new();
+        header.set_user_version(user_version);
+        header.data_size = data_size as u64;
+        header.set_clean(true);
+
+        // the checksum is calculated with a zero'd checksum in the header
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(header.as_bytes());
+        hasher.update(table);
+        header.set_checksum(hasher.finalize());
+
+        header
+    }
+
+    // Copies the header from the start of the bytes
+    fn from_bytes(bytes: &[u8]) -> Self {
+        assert!(bytes.len() >= HEADER_SIZE);
+
+        // SAFETY: the bytes are at least HEADER_SIZE and every bit pattern is
+        // a valid header
+        unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const Header) }
+    }
+
     fn as_bytes(&self) -> &[u8] {
         unsafe { std::slice::from_raw_parts((&*self as *const Header) as *const u8, HEADER_SIZE) }
     }
 
-    fn checksum(&self) -> &[u8; 32] {
-        &self.checksum
+    /// The checksum of the header and the table of chunk checksums
+    pub fn checksum(&self) -> [u8; 32] {
+        self.checksum
     }
 
     fn set_checksum(&mut self, hash: Hash) {
@@ -124,17 +450,60 @@ impl Header {
         }
     }
 
-    fn zero_checksum(&mut self) {
-        for byte in self.checksum.iter_mut() {
-            *byte = 0;
-        }
+    /// Reads the header of the datapool file at the given path, without
+    /// opening the datapool or checking the header.
+    pub fn read<T: AsRef<Path>>(path: T) -> Result<Self, std::io::Error> {
+        let mut header = [0; HEADER_SIZE];
+        File::open(path)?.read_exact(&mut header)?;
+        Ok(Self::from_bytes(&header))
     }
 
-    fn check(&self) -> Result<(), std::io::Error> {
+    /// Checks that the header has the expected magic and version.
+    pub fn check(&self) -> Result<(), std::io::Error> {
         self.check_magic()?;
         self.check_version()
     }
 
+    /// Returns true if the checksum in the header matches the header and the
+    /// provided table of chunk checksums.
+    pub fn verify_checksum(&self, table: &[u8]) -> bool {
+        // the checksum is calculated with a zero'd checksum in the header
+        let mut header = [0; HEADER_SIZE];
+        header.copy_from_slice(self.as_bytes());
+        header[0..32].fill(0);
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&header);
+        hasher.update(table);
+        let hash = hasher.finalize();
+
+        self.checksum[0..32] == hash.as_bytes()[0..32]
+    }
+
+    /// Checks the checksum in the header, and the checksum of each chunk of
+    /// the data, against the contents of the datapool file which starts with
+    /// this header.
+    pub fn verify(&self, file: &[u8]) -> Result<(), std::io::Error> {
+        let layout = Layout::new(self.data_size as usize, PAGE_SIZE);
+        if file.len() < layout.table.end {
+            return Err(Error::new(
+                ErrorKind::Other,
+                "file is smaller than the datapool",
+            ));
+        }
+
+        let table = &file[layout.table.clone()];
+        if !self.verify_checksum(table) {
+            return Err(Error::new(ErrorKind::Other, "checksum mismatch"));
+        }
+
+        layout.verify_chunks(&file[layout.data.clone()], table)
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
     fn check_version(&self) -> Result<(), std::io::Error> {
         if self.version != VERSION {
             Err(Error::new(
@@ -154,7 +523,7 @@ impl Header {
         }
     }
 
-    fn user_version(&self) -> u64 {
+    pub fn user_version(&self) -> u64 {
         self.user_version
     }
 
@@ -165,32 +534,169 @@ impl Header {
     pub fn options(&self) -> u64 {
         self.options
     }
+
+    /// The size of the data region, in bytes
+    pub fn data_size(&self) -> u64 {
+        self.data_size
+    }
+
+    /// The monotonic time, in seconds, when the header was written
+    pub fn time_monotonic_s(&self) -> Instant<Seconds<u32>> {
+        self.time_monotonic_s
+    }
+
+    /// The monotonic time, in nanoseconds, when the header was written
+    pub fn time_monotonic_ns(&self) -> Instant<Nanoseconds<u64>> {
+        self.time_monotonic_ns
+    }
+
+    /// The unix time, in seconds, when the header was written
+    pub fn time_unix_s(&self) -> UnixInstant<Seconds<u32>> {
+        self.time_unix_s
+    }
+
+    /// The unix time, in nanoseconds, when the header was written
+    pub fn time_unix_ns(&self) -> UnixInstant<Nanoseconds<u64>> {
+        self.time_unix_ns
+    }
+
+    /// Returns true if the header was written by a flush and the datapool has
+    /// not been opened since. An open datapool is not clean until it is next
+    /// flushed.
+    pub fn is_clean(&self) -> bool {
+        self.flags & FLAG_CLEAN != 0
+    }
+
+    fn set_clean(&mut self, clean: bool) {
+        if clean {
+            self.flags |= FLAG_CLEAN;
+        } else {
+            self.flags &= !FLAG_CLEAN;
+        }
+    }
+
+    fn check_clean(&self) -> Result<(), std::io::Error> {
+        if self.is_clean() {
+            Ok(())
+        } else {
+            Err(Error::new(
+                ErrorKind::Other,
+                "file was not flushed after it was last opened",
+            ))
+        }
+    }
+}
+
+// The layout of a file-backed datapool. The data follows the header, and is
+// followed by a table which holds the checksum of each chunk of the data. The
+// checksum in the header covers the header and the table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Layout {
+    data: Range<usize>,
+    table: Range<usize>,
+    // the size of the file, which is a whole number of pages
+    size: usize,
+}
+
+impl Layout {
+    fn new(data_size: usize, page_size: usize) -> Self {
+        let data = HEADER_SIZE..(HEADER_SIZE + data_size);
+
+        let start = data.end.next_multiple_of(PAGE_SIZE);
+        let table = start..(start + data_size.div_ceil(CHUNK_SIZE) * CHECKSUM_SIZE);
+
+        let size = table.end.next_multiple_of(page_size);
+
+        Self { data, table, size }
+    }
+
+    fn chunks(&self) -> usize {
+        self.table.len() / CHECKSUM_SIZE
+    }
+
+    // The range of the data which is covered by the chunk
+    fn chunk(&self, chunk: usize) -> Range<usize> {
+        let start = chunk * CHUNK_SIZE;
+        start..(start + CHUNK_SIZE).min(self.data.len())
+    }
+
+    // The range of the table which holds the checksum of the chunk
+    fn entry(&self, chunk: usize) -> Range<usize> {
+        let start = chunk * CHECKSUM_SIZE;
+        start..(start + CHECKSUM_SIZE)
+    }
+
+    // Returns the chunks which overlap any of the ranges of the data, in order
+    fn overlapping(&self, ranges: &[Range<usize>]) -> Vec<usize> {
+        let mut chunks: Vec<usize> = ranges
+            .iter()
+            .map(|range| range.start..range.end.min(self.data.len()))
+            .filter(|range| !range.is_empty())
+            .flat_map(|range| (range.start / CHUNK_SIZE)..range.end.div_ceil(CHUNK_SIZE))
+            .collect();
+        chunks.sort_unstable();
+        chunks.dedup();
+        chunks
+    }
+
+    // Checks the checksum of each chunk of the data against the table
+    fn verify_chunks(&self, data: &[u8], table: &[u8]) -> Result<(), std::io::Error> {
+        for chunk in 0..self.chunks() {
+            let hash = blake3::hash(&data[self.chunk(chunk)]);
+            if table[self.entry(chunk)] != hash.as_bytes()[..] {
+                return Err(Error::new(ErrorKind::Other, "chunk checksum mismatch"));
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Represents storage that primarily exists in a file. This is best used in
 /// combination with a DAX-aware filesystem on persistent memory to avoid page
 /// cache pollution and interference. It can be used for volatile storage or
-/// allow to resume from a clean shutdown.
+/// allow to resume from the most recent flush.
 pub struct MmapFile {
     mmap: MmapMut,
-    data: Range<usize>,
+    layout: Layout,
     user_version: u64,
+    // set when the table of chunk checksums may not match the data, in which
+    // case the next flush checksums all of the data
+    stale: bool,
 }
 
 impl MmapFile {
     /// Open an existing `MmapFile` datapool at the given path and with the
     /// specified size (in bytes). Returns an error if the file does not exist,
-    /// does not match the expected size, could not be mmap'd, or is otherwise
-    /// determined to be corrupt.
+    /// does not match the expected size, could not be mmap'd, was changed
+    /// after it was last flushed, or is otherwise determined to be corrupt.
     pub fn open<T: AsRef<Path>>(
         path: T,
         data_size: usize,
         user_version: u64,
     ) -> Result<Self, std::io::Error> {
-        // we need the data size to be a whole number of pages
-        let pages = ((HEADER_SIZE + data_size) as f64 / PAGE_SIZE as f64).ceil() as usize;
+        Self::open_with_page_size(path.as_ref(), data_size, user_version, PAGE_SIZE)
+    }
 
-        let total_size = pages * PAGE_SIZE;
+    /// Open an existing `MmapFile` datapool which was created on a hugetlbfs
+    /// filesystem by `MmapFile::create_huge()`. Returns an error if the path
+    /// is not on a hugetlbfs filesystem, or for any of the reasons that
+    /// `MmapFile::open()` would.
+    pub fn open_huge<T: AsRef<Path>>(
+        path: T,
+        data_size: usize,
+        user_version: u64,
+    ) -> Result<Self, std::io::Error> {
+        let page_size = hugetlbfs_page_size(path.as_ref())?;
+        Self::open_with_page_size(path.as_ref(), data_size, user_version, page_size)
+    }
+
+    fn open_with_page_size(
+        path: &Path,
+        data_size: usize,
+        user_version: u64,
+        page_size: usize,
+    ) -> Result<Self, std::io::Error> {
+        let layout = Layout::new(data_size, page_size);
 
         // open an existing file for read and write access
         let file = OpenOptions::new()
@@ -200,61 +706,88 @@ impl MmapFile {
             .open(path)?;
 
         // make sure the file size matches the expected size
-        if file.metadata()?.len() != total_size as u64 {
+        if file.metadata()?.len() != layout.size as u64 {
             return Err(Error::new(ErrorKind::Other, "filesize mismatch"));
         }
 
-        // data resides after a small header
-        let data = Range {
-            start: HEADER_SIZE,
-            end: HEADER_SIZE + data_size,
-        };
-
         // mmap the file
         let mmap = unsafe { MmapOptions::new().populate().map_mut(&file)? };
 
-        // load copy the header from the mmap'd file
-        let mut header = [0; HEADER_SIZE];
-        header.copy_from_slice(&mmap[0..HEADER_SIZE]);
-
-        // convert the header to a struct so we can check and manipulate it
-        let header = unsafe { &mut *(header.as_ptr() as *mut Header) };
+        // copy the header from the mmap'd file so we can check it
+        let header = Header::from_bytes(&mmap[0..HEADER_SIZE]);
 
         // check the header
         header.check()?;
 
-        // check the user version
+        // check the user version and the size of the data
         if header.user_version() != user_version {
             return Err(Error::new(ErrorKind::Other, "user version mismatch"));
         }
+        if header.data_size() != data_size as u64 {
+            return Err(Error::new(ErrorKind::Other, "data size mismatch"));
+        }
 
-        // zero out the checksum in the header copy
-        header.zero_checksum();
+        // a file which is in use may have been changed since it was flushed
+        header.check_clean()?;
 
-        // create a hasher
-        let mut hasher = blake3::Hasher::new();
+        // check the checksums of the table and of each chunk of the data, as a
+        // side effect this prefaults all the pages
+        header.verify(&mmap)?;
 
-        // hash the header with a zero'd checksum
-        hasher.update(header.as_bytes());
+        let mut datapool = Self {
+            mmap,
+            layout,
+            user_version,
+            stale: false,
+        };
 
-        // calculates the hash of the data region, as a side effect this
-        // prefaults all the pages
-        hasher.update(&mmap[data.start..data.end]);
+        // the data is changed in place once it is returned, so the file is no
+        // longer clean until it is flushed
+        datapool.mark_dirty()?;
 
-        // finalize the hash
-        let hash = hasher.finalize();
+        // return the loaded datapool
+        Ok(datapool)
+    }
 
-        // compare the stored checksum in the file to the calculated checksum
-        if mmap[0..32] != hash.as_bytes()[0..32] {
-            return Err(Error::new(ErrorKind::Other, "checksum mismatch"));
+    /// Open the existing `MmapFile` datapool at the given path if it was
+    /// flushed and passes its checks, otherwise any file at the path is
+    /// discarded and a new datapool is created.
+    pub fn open_or_create<T: AsRef<Path>>(
+        path: T,
+        data_size: usize,
+        user_version: u64,
+    ) -> Result<Self, std::io::Error> {
+        let path = path.as_ref();
+        match Self::open(path, data_size, user_version) {
+            Ok(datapool) => Ok(datapool),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                Self::create(path, data_size, user_version)
+            }
+            Err(_) => {
+                std::fs::remove_file(path)?;
+                Self::create(path, data_size, user_version)
+            }
         }
+    }
 
-        // return the loaded datapool
-        Ok(Self {
-            mmap,
-            data,
-            user_version,
-        })
+    /// As for `MmapFile::open_or_create()`, for datapools on a hugetlbfs
+    /// filesystem. See `MmapFile::create_huge()`.
+    pub fn open_or_create_huge<T: AsRef<Path>>(
+        path: T,
+        data_size: usize,
+        user_version: u64,
+    ) -> Result<Self, std::io::Error> {
+        let path = path.as_ref();
+        match Self::open_huge(path, data_size, user_version) {
+            Ok(datapool) => Ok(datapool),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                Self::create_huge(path, data_size, user_version)
+            }
+            Err(_) => {
+                std::fs::remove_file(path)?;
+                Self::create_huge(path, data_size, user_version)
+            }
+        }
     }
 
     /// Create a new `File` datapool at the given path and with the specified
@@ -266,16 +799,48 @@ impl MmapFile {
         data_size: usize,
         user_version: u64,
     ) -> Result<Self, std::io::Error> {
-        // we need the data size to be a whole number of pages
-        let pages = ((HEADER_SIZE + data_size) as f64 / PAGE_SIZE as f64).ceil() as usize;
+        Self::create_with_page_size(path.as_ref(), data_size, user_version, PAGE_SIZE)
+    }
 
-        let total_size = pages * PAGE_SIZE;
+    /// Create a new `MmapFile` datapool at a path on a hugetlbfs filesystem,
+    /// so that the datapool is backed by huge pages. The file is sized to a
+    /// whole number of the filesystem's huge pages. Returns an error if the
+    /// path is not on a hugetlbfs filesystem or if there are not enough huge
+    /// pages available, in which case no file is left behind.
+    pub fn create_huge<T: AsRef<Path>>(
+        path: T,
+        data_size: usize,
+        user_version: u64,
+    ) -> Result<Self, std::io::Error> {
+        let path = path.as_ref();
 
-        // data resides after a small header
-        let data = Range {
-            start: HEADER_SIZE,
-            end: total_size,
+        // the file does not exist yet, so check the filesystem which will
+        // hold it
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
         };
+        let page_size = hugetlbfs_page_size(parent)?;
+
+        let result = Self::create_with_page_size(path, data_size, user_version, page_size);
+
+        // a partially created file would keep its huge pages reserved
+        if let Err(e) = &result {
+            if e.kind() != ErrorKind::AlreadyExists {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+
+        result
+    }
+
+    fn create_with_page_size(
+        path: &Path,
+        data_size: usize,
+        user_version: u64,
+        page_size: usize,
+    ) -> Result<Self, std::io::Error> {
+        let layout = Layout::new(data_size, page_size);
 
         // create a new file with read and write access
         let file = OpenOptions::new()
@@ -285,34 +850,29 @@ impl MmapFile {
             .open(path)?;
 
         // grow the file to match the total size
-        file.set_len(total_size as u64)?;
+        file.set_len(layout.size as u64)?;
 
         // mmap the file
         let mut mmap = unsafe { MmapOptions::new().populate().map_mut(&file)? };
 
         // causes the mmap'd region to be prefaulted by writing a zero at the
         // start of each page
-        let mut offset = 0;
-        while offset < total_size {
-            mmap[offset] = 0;
-            offset += PAGE_SIZE;
-        }
+        prefault(&mut mmap[..], page_size);
         mmap.flush()?;
 
+        // the table is empty, so the first flush checksums all of the data
         Ok(Self {
             mmap,
-            data,
+            layout,
             user_version,
+            stale: true,
         })
     }
 
     pub fn header(&self) -> &Header {
-        // load copy the header from the mmap'd file
-        let mut header = [0; HEADER_SIZE];
-        header.copy_from_slice(&self.mmap[0..HEADER_SIZE]);
-
-        // convert the header to a struct
-        unsafe { &*(header.as_ptr() as *const Header) }
+        // SAFETY: the mapping is at least HEADER_SIZE and the header has an
+        // alignment of one
+        unsafe { &*(self.mmap.as_ptr() as *const Header) }
     }
 
     pub fn time_monotonic_s(&self) -> Instant<Seconds<u32>> {
@@ -330,61 +890,82 @@ impl MmapFile {
     pub fn time_unix_ns(&self) -> UnixInstant<Nanoseconds<u64>> {
         self.header().time_unix_ns
     }
-}
 
-impl Datapool for MmapFile {
-    fn as_slice(&self) -> &[u8] {
-        &self.mmap[self.data.start..self.data.end]
+    // Clears the clean flag in the header of the file
+    fn mark_dirty(&mut self) -> Result<(), std::io::Error> {
+        let mut header = Header::from_bytes(&self.mmap[0..HEADER_SIZE]);
+        header.set_clean(false);
+        self.mmap[0..HEADER_SIZE].copy_from_slice(header.as_bytes());
+        self.mmap.flush_range(0, HEADER_SIZE)
     }
 
-    fn as_mut_slice(&mut self) -> &mut [u8] {
-        &mut self.mmap[self.data.start..self.data.end]
-    }
+    // Persists the chunks of the data which overlap the ranges, or all of the
+    // data if the table is stale, and updates their checksums in the table.
+    // The header, which holds the checksum of the table, is only written once
+    // the data and the table are persisted, so a crash part way through leaves
+    // a file which fails its checks.
+    fn checkpoint(&mut self, ranges: &[Range<usize>]) -> Result<(), std::io::Error> {
+        let chunks = if self.stale {
+            (0..self.layout.chunks()).collect()
+        } else {
+            self.layout.overlapping(ranges)
+        };
 
-    fn flush(&mut self) -> Result<(), std::io::Error> {
-        // flush everything to the underlying file
-        self.mmap.flush()?;
+        // the table does not match the data until the flush completes
+        self.stale = true;
 
-        // initialize the hasher
-        let mut hasher = blake3::Hasher::new();
+        let data = self.layout.data.start;
+        let table = self.layout.table.start;
+        for chunk in chunks {
+            let range = self.layout.chunk(chunk);
+            self.mmap.flush_range(data + range.start, range.len())?;
 
-        // prepare the header
-        let mut header = Header::new();
+            let hash = blake3::hash(&self.mmap[(data + range.start)..(data + range.end)]);
+            let entry = self.layout.entry(chunk);
+            self.mmap[(table + entry.start)..(table + entry.end)].copy_from_slice(hash.as_bytes());
+        }
 
-        // set the user version
-        header.set_user_version(self.user_version);
+        // flush the table
+        self.mmap.flush_range(table, self.layout.table.len())?;
 
-        // hash the header
-        hasher.update(header.as_bytes());
+        // write the header with the checksum of the table and flush it
+        let header = Header::flushed(
+            self.user_version,
+            self.layout.data.len(),
+            &self.mmap[self.layout.table.clone()],
+        );
+        self.mmap[0..HEADER_SIZE].copy_from_slice(header.as_bytes());
+        self.mmap.flush_range(0, HEADER_SIZE)?;
 
-        // calculate the number of data pages to be copied
-        let data_pages = (self.mmap.len() - HEADER_SIZE) / PAGE_SIZE;
+        self.stale = false;
+        Ok(())
+    }
+}
 
-        // hash the data region
-        for page in 0..data_pages {
-            let start = page * PAGE_SIZE + HEADER_SIZE;
-            let end = start + PAGE_SIZE;
-            hasher.update(&self.mmap[start..end]);
-        }
+impl Datapool for MmapFile {
+    fn as_slice(&self) -> &[u8] {
+        &self.mmap[self.layout.data.clone()]
+    }
 
-        // finalize the hash
-        let hash = hasher.finalize();
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.mmap[self.layout.data.clone()]
+    }
 
-        // set the header checksum with the calculated hash
-        header.set_checksum(hash);
+    fn flush(&mut self) -> Result<(), std::io::Error> {
+        let all = 0..self.layout.data.len();
+        self.checkpoint(&[all])
+    }
 
-        // write the header to the file using memcpy
-        // SAFETY: we know the source is exactly HEADER_SIZE and that the
-        // destination is at least as large. We also know that they are both
-        // properly aligned and do not overlap.
-        unsafe {
-            let src = header.as_bytes().as_ptr();
-            let dst = self.mmap.as_mut_ptr();
-            std::ptr::copy_nonoverlapping(src, dst, HEADER_SIZE);
-        }
+    fn flush_ranges(&mut self, ranges: &[Range<usize>]) -> Result<(), std::io::Error> {
+        self.checkpoint(ranges)
+    }
 
-        // flush again
-        self.mmap.flush()
+    fn checksum(&self) -> Option<[u8; 32]> {
+        if self.stale {
+            None
+        } else {
+            Some(self.header().checksum())
+        }
     }
 }
 
@@ -403,9 +984,13 @@ impl Datapool for MmapFile {
 pub struct FileBackedMemory {
     memory: Memory,
     header: Box<[u8]>,
+    // a copy of the table of chunk checksums which is in the file
+    table: Box<[u8]>,
     file: File,
-    file_data: Range<usize>,
+    layout: Layout,
     user_version: u64,
+    // set when the table may not match the data, see `MmapFile`
+    stale: bool,
 }
 
 impl FileBackedMemory {
@@ -414,23 +999,9 @@ impl FileBackedMemory {
         data_size: usize,
         user_version: u64,
     ) -> Result<Self, std::io::Error> {
-        // we need the data size to be a whole number of pages for direct io
-        let pages = ((HEADER_SIZE + data_size) as f64 / PAGE_SIZE as f64).ceil() as usize;
-
-        // total size must be larger than the requested size to allow for the
-        // header
-        let file_total_size = Range {
-            start: 0,
-            end: pages * PAGE_SIZE,
-        };
+        let layout = Layout::new(data_size, PAGE_SIZE);
 
-        // data resides after a small header
-        let file_data = Range {
-            start: HEADER_SIZE,
-            end: HEADER_SIZE + data_size,
-        };
-
-        // create a new file with read and write access
+        // open an existing file with read and write access
         #[cfg(os = "linux")]
         let mut file = OpenOptions::new()
             .create_new(false)
@@ -447,88 +1018,57 @@ impl FileBackedMemory {
             .open(path)?;
 
         // make sure the file size matches the expected size
-        if file.metadata()?.len() != file_total_size.end as u64 {
+        if file.metadata()?.len() != layout.size as u64 {
             return Err(Error::new(ErrorKind::Other, "filesize mismatch"));
         }
 
-        // calculate the page range for the data region
-        let data_pages = (file_data.end - file_data.start) / PAGE_SIZE;
-
-        // reserve memory for the data
-        let mut memory = Memory::create(data_size)?;
-
-        // seek to start of header
-        file.seek(SeekFrom::Start(0))?;
-
-        // prepare the header to read from disk
-        let mut header = [0; HEADER_SIZE];
-
         // read the header from disk
-        loop {
-            if file.read(&mut header[0..PAGE_SIZE])? == PAGE_SIZE {
-                break;
-            }
-            file.seek(SeekFrom::Start(0))?;
-        }
-
-        // create a new hasher to checksum the file content, including the
-        // header with a zero'd checksum
-        let mut hasher = blake3::Hasher::new();
-
-        // turn the raw header into the struct
-        let header = unsafe { &mut *(header.as_ptr() as *mut Header) };
+        let mut bytes = [0; HEADER_SIZE];
+        read_at(&mut file, 0, &mut bytes)?;
+        let mut header = Header::from_bytes(&bytes);
 
         // check the header
         header.check()?;
 
-        // check the user version
+        // check the user version and the size of the data
         if header.user_version() != user_version {
             return Err(Error::new(ErrorKind::Other, "user version mismatch"));
         }
-
-        // copy the checksum out of the header and zero it in the header
-        let file_checksum = header.checksum().to_owned();
-        header.zero_checksum();
-
-        // hash the header with the zero'd checksum
-        hasher.update(header.as_bytes());
-
-        // seek to start of the data
-        file.seek(SeekFrom::Start(file_data.start as u64))?;
-
-        // read the data region from the file, copy it into memory and hash it
-        // in a single pass
-        for page in 0..data_pages {
-            // retry the read until a complete page is read
-            loop {
-                let start = page * PAGE_SIZE;
-                let end = start + PAGE_SIZE;
-
-                if file.read(&mut memory.as_mut_slice()[start..end])? == PAGE_SIZE {
-                    hasher.update(&memory.as_slice()[start..end]);
-                    break;
-                }
-                // if the read was incomplete, we seek back to the right spot in
-                // the file
-                file.seek(SeekFrom::Start((HEADER_SIZE + start) as u64))?;
-            }
+        if header.data_size() != data_size as u64 {
+            return Err(Error::new(ErrorKind::Other, "data size mismatch"));
         }
 
-        // finalize the hash
-        let hash = hasher.finalize();
+        // a file which is in use may have been changed since it was flushed
+        header.check_clean()?;
 
-        // compare the checksum agaianst what's in the header
-        if file_checksum[0..32] != hash.as_bytes()[0..32] {
+        // read the table and check its checksum
+        let mut table = vec![0; layout.table.len()].into_boxed_slice();
+        read_at(&mut file, layout.table.start, &mut table)?;
+        if !header.verify_checksum(&table) {
             return Err(Error::new(ErrorKind::Other, "checksum mismatch"));
         }
 
+        // read the data region into memory and check the checksum of each
+        // chunk
+        let mut memory = Memory::create(data_size)?;
+        read_at(&mut file, layout.data.start, memory.as_mut_slice())?;
+        layout.verify_chunks(memory.as_slice(), &table)?;
+
+        // the file would hold stale data once the memory is changed, so it is
+        // no longer clean until it is flushed
+        header.set_clean(false);
+        write_at(&mut file, 0, header.as_bytes())?;
+        file.sync_all()?;
+
         // return the loaded datapool
         Ok(Self {
             memory,
             header: header.as_bytes().to_owned().into_boxed_slice(),
+            table,
             file,
-            file_data,
+            layout,
             user_version,
+            stale: false,
         })
     }
 
@@ -537,21 +1077,7 @@ impl FileBackedMemory {
         data_size: usize,
         user_version: u64,
     ) -> Result<Self, std::io::Error> {
-        // we need the data size to be a whole number of pages for direct io
-        let pages = ((HEADER_SIZE + data_size) as f64 / PAGE_SIZE as f64).ceil() as usize;
-
-        // total size must be larger than the requested size to allow for the
-        // header
-        let file_total_size = Range {
-            start: 0,
-            end: pages * PAGE_SIZE,
-        };
-
-        // data resides after a small header
-        let file_data = Range {
-            start: HEADER_SIZE,
-            end: pages * PAGE_SIZE,
-        };
+        let layout = Layout::new(data_size, PAGE_SIZE);
 
         // create a new file with read and write access
         #[cfg(os = "linux")]
@@ -570,27 +1096,25 @@ impl FileBackedMemory {
             .open(path)?;
 
         // grow the file to match the total size
-        file.set_len(file_total_size.end as u64)?;
+        file.set_len(layout.size as u64)?;
 
         // causes file to be zeroed out
-        for page in 0..pages {
-            loop {
-                if file.write(&[0; PAGE_SIZE])? == PAGE_SIZE {
-                    break;
-                }
-                file.seek(SeekFrom::Start((page * PAGE_SIZE) as u64))?;
-            }
+        for page in 0..(layout.size / PAGE_SIZE) {
+            write_at(&mut file, page * PAGE_SIZE, &[0; PAGE_SIZE])?;
         }
         file.sync_all()?;
 
         let memory = Memory::create(data_size)?;
 
+        // the table is empty, so the first flush writes all of the data
         Ok(Self {
             memory,
             header: vec![0; HEADER_SIZE].into_boxed_slice(),
+            table: vec![0; layout.table.len()].into_boxed_slice(),
             file,
-            file_data,
+            layout,
             user_version,
+            stale: true,
         })
     }
 
@@ -613,6 +1137,43 @@ impl FileBackedMemory {
     pub fn time_unix_ns(&self) -> UnixInstant<Nanoseconds<u64>> {
         self.header().time_unix_ns
     }
+
+    // Writes the chunks of the data which overlap the ranges to the file, and
+    // then the table and header, see `MmapFile::checkpoint()`
+    fn checkpoint(&mut self, ranges: &[Range<usize>]) -> Result<(), std::io::Error> {
+        let chunks = if self.stale {
+            (0..self.layout.chunks()).collect()
+        } else {
+            self.layout.overlapping(ranges)
+        };
+
+        // the table does not match the data until the flush completes
+        self.stale = true;
+
+        // write each chunk to the file and update its checksum
+        for chunk in chunks {
+            let range = self.layout.chunk(chunk);
+            let data = &self.memory.as_slice()[range.clone()];
+            write_at(&mut self.file, self.layout.data.start + range.start, data)?;
+            let hash = blake3::hash(data);
+            self.table[self.layout.entry(chunk)].copy_from_slice(hash.as_bytes());
+        }
+        write_at(&mut self.file, self.layout.table.start, &self.table)?;
+
+        // the data and table must be persisted before the header which
+        // describes them
+        self.file.sync_all()?;
+
+        let header = Header::flushed(self.user_version, self.layout.data.len(), &self.table);
+        write_at(&mut self.file, 0, header.as_bytes())?;
+        self.file.sync_all()?;
+
+        // keep a copy of the header for the time accessors
+        self.header.copy_from_slice(header.as_bytes());
+
+        self.stale = false;
+        Ok(())
+    }
 }
 
 impl Datapool for FileBackedMemory {
@@ -625,55 +1186,33 @@ impl Datapool for FileBackedMemory {
     }
 
     fn flush(&mut self) -> Result<(), std::io::Error> {
-        // initialize the hasher
-        let mut hasher = blake3::Hasher::new();
-
-        // prepare the header
-        let mut header = Header::new();
-
-        // set the user version
-        header.set_user_version(self.user_version);
-
-        // hash the header with a zero'd checksum
-        hasher.update(header.as_bytes());
-
-        // calculate the number of data pages to be copied
-        let data_pages = (self.file_data.end - self.file_data.start) / PAGE_SIZE;
-
-        // write the data region to the file and hash it in one pass
-        self.file.seek(SeekFrom::Start(HEADER_SIZE as u64))?;
-        for page in 0..data_pages {
-            loop {
-                let start = page * PAGE_SIZE;
-                let end = start + PAGE_SIZE;
-                if self.file.write(&self.memory.as_slice()[start..end])? == PAGE_SIZE {
-                    hasher.update(&self.memory.as_slice()[start..end]);
-                    break;
-                }
-                self.file
-                    .seek(SeekFrom::Start((HEADER_SIZE + start) as u64))?;
-            }
-        }
-
-        // finalize the hash
-        let hash = hasher.finalize();
+        let all = 0..self.layout.data.len();
+        self.checkpoint(&[all])
+    }
 
-        // set the checksum in the header to the calculated hash
-        header.set_checksum(hash);
+    fn flush_ranges(&mut self, ranges: &[Range<usize>]) -> Result<(), std::io::Error> {
+        self.checkpoint(ranges)
+    }
 
-        // write the header to the file
-        self.file.seek(SeekFrom::Start(0))?;
-        loop {
-            if self.file.write(header.as_bytes())? == HEADER_SIZE {
-                break;
-            }
-            self.file.seek(SeekFrom::Start(0))?;
+    fn checksum(&self) -> Option<[u8; 32]> {
+        if self.stale {
+            None
+        } else {
+            Some(self.header().checksum())
         }
+    }
+}
 
-        self.file.sync_all()?;
+// Reads from the offset in the file until the buffer is filled
+fn read_at(file: &mut File, offset: usize, buf: &mut [u8]) -> Result<(), std::io::Error> {
+    file.seek(SeekFrom::Start(offset as u64))?;
+    file.read_exact(buf)
+}
 
-        Ok(())
-    }
+// Writes all of the buffer to the file at the offset
+fn write_at(file: &mut File, offset: usize, buf: &[u8]) -> Result<(), std::io::Error> {
+    file.seek(SeekFrom::Start(offset as u64))?;
+    file.write_all(buf)
 }
 
 #[cfg(test)]
@@ -693,6 +1232,49 @@ mod tests {
         assert_eq!(datapool.len(), 2 * PAGE_SIZE);
     }
 
+    #[test]
+    fn huge_page_memory_datapool() {
+        // huge pages may not be available, but the datapool is either created
+        // with the requested size or an error is returned
+        for huge_pages in [HugePages::None, HugePages::Transparent, HugePages::Explicit] {
+            if let Ok(mut datapool) = Memory::create_huge(2 * PAGE_SIZE, huge_pages) {
+                assert_eq!(datapool.len(), 2 * PAGE_SIZE);
+                datapool.as_mut_slice()[2 * PAGE_SIZE - 1] = 0xFF;
+                assert_eq!(datapool.as_slice()[2 * PAGE_SIZE - 1], 0xFF);
+            }
+        }
+        assert!(Memory::create_huge(2 * PAGE_SIZE, HugePages::None).is_ok());
+    }
+
+    #[test]
+    fn memory_datapool_release() {
+        let mut datapool = Memory::create(4 * PAGE_SIZE).expect("failed to create pool");
+        datapool.as_mut_slice()[0] = 0xFF;
+        datapool.as_mut_slice()[PAGE_SIZE] = 0xFF;
+
+        // only the released pages are affected
+        datapool
+            .release(PAGE_SIZE..4 * PAGE_SIZE)
+            .expect("failed to release");
+        assert_eq!(datapool.len(), 4 * PAGE_SIZE);
+        assert_eq!(datapool.as_slice()[0], 0xFF);
+        datapool.as_mut_slice()[PAGE_SIZE] = 0xAB;
+        assert_eq!(datapool.as_slice()[PAGE_SIZE], 0xAB);
+    }
+
+    #[test]
+    fn huge_page_mmapfile_datapool() {
+        let tempdir = TempDir::new().expect("failed to generate tempdir");
+        let mut path = tempdir.into_path();
+        path.push("mmap_test.data");
+
+        // a path which is not on hugetlbfs is rejected without creating a file
+        if hugetlbfs_page_size(path.parent().unwrap()).is_err() {
+            assert!(MmapFile::create_huge(&path, 2 * PAGE_SIZE, 0).is_err());
+            assert!(!path.exists());
+        }
+    }
+
     #[test]
     fn mmapfile_datapool() {
         let tempdir = TempDir::new().expect("failed to generate tempdir");
@@ -712,7 +1294,7 @@ mod tests {
             for (i, byte) in magic_a.iter().enumerate() {
                 datapool.as_mut_slice()[i] = *byte;
             }
-            datapool.flush().expect("failed to flush");
+            datapool.close().expect("failed to close");
         }
 
         // open the datapool and check the content, then update it
@@ -726,7 +1308,7 @@ mod tests {
             for (i, byte) in magic_b.iter().enumerate() {
                 datapool.as_mut_slice()[i] = *byte;
             }
-            datapool.flush().expect("failed to flush");
+            datapool.close().expect("failed to close");
         }
 
         // open the datapool again, and check that it has the new data
@@ -742,6 +1324,127 @@ mod tests {
         }
     }
 
+    #[test]
+    fn mmapfile_datapool_clean() {
+        let tempdir = TempDir::new().expect("failed to generate tempdir");
+        let mut path = tempdir.into_path();
+        path.push("mmap_test.data");
+
+        // a flush persists the data and writes a header which can be reopened
+        {
+            let mut datapool =
+                MmapFile::create(&path, 2 * PAGE_SIZE, 0).expect("failed to create pool");
+            assert!(datapool.checksum().is_none());
+            datapool.as_mut_slice()[0] = 0xFF;
+            datapool.flush().expect("failed to flush");
+            assert!(datapool.header().is_clean());
+            assert!(datapool.checksum().is_some());
+        }
+
+        // a reopened file is no longer clean until it is flushed again
+        {
+            let datapool = MmapFile::open(&path, 2 * PAGE_SIZE, 0).expect("failed to open pool");
+            assert_eq!(datapool.as_slice()[0], 0xFF);
+            assert!(!datapool.header().is_clean());
+        }
+        assert!(MmapFile::open(&path, 2 * PAGE_SIZE, 0).is_err());
+
+        // an unclean file is discarded and replaced
+        {
+            let mut datapool =
+                MmapFile::open_or_create(&path, 2 * PAGE_SIZE, 0).expect("failed to create pool");
+            assert_eq!(datapool.as_slice()[0], 0);
+            datapool.as_mut_slice()[0] = 0xAB;
+            datapool.close().expect("failed to close");
+            assert!(datapool.header().is_clean());
+        }
+
+        // the checksum identifies the data when the file was flushed
+        let checksum = {
+            let mut datapool =
+                MmapFile::open_or_create(&path, 2 * PAGE_SIZE, 0).expect("failed to open pool");
+            assert_eq!(datapool.as_slice()[0], 0xAB);
+            let checksum = datapool.checksum();
+            datapool.as_mut_slice()[0] = 0xAC;
+            datapool.flush().expect("failed to flush");
+            assert_ne!(datapool.checksum(), checksum);
+            datapool.checksum()
+        };
+        {
+            let datapool = MmapFile::open(&path, 2 * PAGE_SIZE, 0).expect("failed to open pool");
+            assert_eq!(datapool.checksum(), checksum);
+            assert_eq!(datapool.as_slice()[0], 0xAC);
+        }
+
+        // the data size must match the size it was flushed with
+        assert!(MmapFile::open(&path, PAGE_SIZE, 0).is_err());
+
+        // changes after the file is flushed cause the checksum to fail
+        {
+            let mut datapool =
+                MmapFile::open_or_create(&path, 2 * PAGE_SIZE, 0).expect("failed to create pool");
+            datapool.flush().expect("failed to flush");
+            datapool.as_mut_slice()[0] = 0xCD;
+        }
+        assert!(MmapFile::open(&path, 2 * PAGE_SIZE, 0).is_err());
+    }
+
+    #[test]
+    fn mmapfile_datapool_flush_ranges() {
+        let tempdir = TempDir::new().expect("failed to generate tempdir");
+        let mut path = tempdir.into_path();
+        path.push("mmap_test.data");
+
+        let size = 3 * CHUNK_SIZE;
+
+        // only the chunks which overlap the ranges are checksummed, which is
+        // enough when all the changes are within the ranges
+        {
+            let mut datapool = MmapFile::create(&path, size, 0).expect("failed to create pool");
+            datapool.flush().expect("failed to flush");
+            datapool.as_mut_slice()[CHUNK_SIZE + 1] = 0xFF;
+            let changed = CHUNK_SIZE..(CHUNK_SIZE + 2);
+            datapool.flush_ranges(&[changed]).expect("failed to flush");
+        }
+        {
+            let mut datapool = MmapFile::open(&path, size, 0).expect("failed to open pool");
+            assert_eq!(datapool.as_slice()[CHUNK_SIZE + 1], 0xFF);
+
+            // a change outside of the ranges is not checksummed
+            datapool.as_mut_slice()[2 * CHUNK_SIZE] = 0xFF;
+            let first = 0..CHUNK_SIZE;
+            datapool.flush_ranges(&[first]).expect("failed to flush");
+        }
+        assert!(MmapFile::open(&path, size, 0).is_err());
+    }
+
+    #[test]
+    fn header_read() {
+        let tempdir = TempDir::new().expect("failed to generate tempdir");
+        let mut path = tempdir.into_path();
+        path.push("header_test.data");
+
+        {
+            let mut datapool =
+                MmapFile::create(&path, 2 * PAGE_SIZE, 7).expect("failed to create pool");
+            datapool.as_mut_slice()[0] = 0xFF;
+            datapool.close().expect("failed to close");
+        }
+
+        // the header can be read and verified without opening the datapool
+        let mut data = std::fs::read(&path).expect("failed to read file");
+        let header = Header::read(&path).expect("failed to read header");
+        assert!(header.check().is_ok());
+        assert!(header.is_clean());
+        assert_eq!(header.user_version(), 7);
+        assert_eq!(header.data_size(), 2 * PAGE_SIZE as u64);
+        assert!(header.verify(&data).is_ok());
+        assert!(header.verify(&data[..(HEADER_SIZE + PAGE_SIZE)]).is_err());
+
+        data[HEADER_SIZE] = 0;
+        assert!(header.verify(&data).is_err());
+    }
+
     #[test]
     fn filebackedmemory_datapool() {
         let tempdir = TempDir::new().expect("failed to generate tempdir");
@@ -761,7 +1464,7 @@ mod tests {
             for (i, byte) in magic_a.iter().enumerate() {
                 datapool.as_mut_slice()[i] = *byte;
             }
-            datapool.flush().expect("failed to flush");
+            datapool.close().expect("failed to close");
         }
 
         // open the datapool and check the content, then update it
@@ -775,7 +1478,7 @@ mod tests {
             for (i, byte) in magic_b.iter().enumerate() {
                 datapool.as_mut_slice()[i] = *byte;
             }
-            datapool.flush().expect("failed to flush");
+            datapool.close().expect("failed to close");
         }
 
         // open the datapool again, and check that it has the new data
@@ -0,0 +1,93 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Admission policies decide whether a new key is stored when it is written.
+
+use crate::*;
+use bloom::BloomFilter;
+
+// the target false positive rate for the admission filter
+const FALSE_POSITIVE_RATE: f64 = 0.01;
+
+/// Admission policies control which new keys are stored. Writes to keys which
+/// are already in the cache are always admitted.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Admission {
+    /// Every write is admitted.
+    #[default]
+    None,
+    /// A new key is only admitted if it has been written, or was a miss,
+    /// within the current or previous window. This keeps one-hit-wonders from
+    /// displacing other items. Keys are tracked with a pair of bloom filters
+    /// which are each sized to hold `items` keys, and which rotate every
+    /// `window`.
+    Bloom {
+        items: usize,
+        window: std::time::Duration,
+    },
+}
+
+/// Tracks the keys which have recently been written or missed using a pair
+/// of bloom filters. New keys are recorded in the current filter, and on each
+/// rotation the previous filter is cleared and becomes the current filter.
+pub(crate) struct AdmissionFilter {
+    current: BloomFilter<[u8]>,
+    previous: BloomFilter<[u8]>,
+    window: Duration,
+    rotate_at: Instant,
+}
+
+impl AdmissionFilter {
+    /// Returns a filter for the policy, or `None` if every write is admitted.
+    pub fn new(policy: Admission) -> Option<Self> {
+        match policy {
+            Admission::None => None,
+            Admission::Bloom { items, window } => {
+                // optimal parameters for the target false positive rate, see
+                // the bloom crate documentation
+                let n = items.max(1) as f64;
+                let ln2 = std::f64::consts::LN_2;
+                let m = (-(n * FALSE_POSITIVE_RATE.ln()) / (ln2 * ln2)).ceil();
+                let k = ((m / n) * ln2).round().max(1.0);
+
+                let window = Duration::from_secs(window.as_secs().clamp(1, u32::MAX as u64) as u32);
+
+                Some(Self {
+                    current: BloomFilter::new(m as usize, k as usize),
+                    previous: BloomFilter::new(m as usize, k as usize),
+                    window,
+                    rotate_at: clock::recent() + window,
+                })
+            }
+        }
+    }
+
+    /// Returns true if a write of a new key should be admitted. A key which is
+    /// not admitted is recorded so that it will be admitted next time.
+    pub fn admit(&mut self, key: &[u8]) -> bool {
+        self.rotate();
+        if self.current.contains(key) || self.previous.contains(key) {
+            true
+        } else {
+            self.current.insert(key);
+            false
+        }
+    }
+
+    /// Records a key which was not found in the cache.
+    pub fn miss(&mut self, key: &[u8]) {
+        self.rotate();
+        self.current.insert(key);
+    }
+
+    // starts a new window if the current window has ended
+    fn rotate(&mut self) {
+        let now = clock::recent();
+        if now >= self.rotate_at {
+            std::mem::swap(&mut self.current, &mut self.previous);
+            self.current.clear();
+            self.rotate_at = now + self.window;
+        }
+    }
+}
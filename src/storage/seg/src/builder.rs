@@ -5,12 +5,19 @@
 //! A builder for configuring a new [`Seg`] instance.
 
 use crate::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// A builder that is used to construct a new [`Seg`] instance.
 pub struct Builder {
     hash_power: u8,
+    max_hash_power: Option<u8>,
     overflow_factor: f64,
+    compression: Option<usize>,
+    admission: Admission,
+    expire_budget: ExpireBudget,
+    item_cas: bool,
+    checkpoint_path: Option<PathBuf>,
+    stale_grace: std::time::Duration,
     segments_builder: SegmentsBuilder,
 }
 
@@ -19,7 +26,14 @@ impl Default for Builder {
     fn default() -> Self {
         Self {
             hash_power: 16,
+            max_hash_power: None,
             overflow_factor: 0.0,
+            compression: None,
+            admission: Admission::None,
+            expire_budget: ExpireBudget::default(),
+            item_cas: false,
+            checkpoint_path: None,
+            stale_grace: std::time::Duration::ZERO,
             segments_builder: SegmentsBuilder::default(),
         }
     }
@@ -48,6 +62,26 @@ impl Builder {
         self
     }
 
+    /// Specify the largest hash power the hashtable may grow to. By default,
+    /// the hashtable does not grow beyond the initial hash power. When a
+    /// larger power is provided, the hashtable doubles in size when it is
+    /// close to full, moving items into the larger hashtable a few buckets at
+    /// a time. The buckets for the larger hashtable are allocated a few at a
+    /// time ahead of growing, so up to three times the memory of the current
+    /// hashtable may be used.
+    ///
+    /// ```
+    /// use seg::Seg;
+    ///
+    /// // create a cache with a small hashtable which may grow to have room
+    /// // for ~1.8M items
+    /// let cache = Seg::builder().hash_power(17).max_hash_power(Some(21)).build();
+    /// ```
+    pub fn max_hash_power(mut self, max_hash_power: Option<u8>) -> Self {
+        self.max_hash_power = max_hash_power;
+        self
+    }
+
     /// Specify an overflow factor which is used to scale the hashtable and
     /// provide additional capacity for chaining item buckets. A factor of 1.0
     /// will result in a hash table that is 100% larger.
@@ -94,6 +128,28 @@ impl Builder {
         self
     }
 
+    /// Specify the largest heap size which the heap may be resized to at
+    /// runtime with `Seg::resize()`. By default, the heap may not grow beyond
+    /// its initial size. Memory is only mapped when the heap grows, but the
+    /// segment ids for the largest heap are reserved up front. Segments added
+    /// at runtime are held in memory, even if a datapool path is provided.
+    ///
+    /// ```
+    /// use seg::Seg;
+    ///
+    /// const MB: usize = 1024 * 1024;
+    ///
+    /// // create a cache with a 64MB heap which may grow to 256MB
+    /// let cache = Seg::builder()
+    ///     .heap_size(64 * MB)
+    ///     .max_heap_size(Some(256 * MB))
+    ///     .build();
+    /// ```
+    pub fn max_heap_size(mut self, bytes: Option<usize>) -> Self {
+        self.segments_builder = self.segments_builder.max_heap_size(bytes);
+        self
+    }
+
     /// Specify the segment size for item storage. The largest item which can be
     /// held is `size - 5` bytes for builds without the `debug` or `magic` build
     /// features enabled. Smaller segment sizes reduce the number of items which
@@ -117,8 +173,9 @@ impl Builder {
         self
     }
 
-    /// Specify the eviction policy to be used. See the `Policy` documentation
-    /// for more details about each strategy.
+    /// Specify the eviction policy to be used. This is either one of the
+    /// built-in strategies, see the `Policy` documentation for more details
+    /// about each, or an implementation of `EvictionPolicy`.
     ///
     /// ```
     /// use seg::{Policy, Seg};
@@ -130,21 +187,221 @@ impl Builder {
     /// let policy = Policy::Merge { max: 8, merge: 4, compact: 2};
     /// let cache = Seg::builder().eviction(policy).build();
     /// ```
-    pub fn eviction(mut self, policy: Policy) -> Self {
-        self.segments_builder = self.segments_builder.eviction_policy(policy);
+    pub fn eviction<P: Into<Box<dyn EvictionPolicy>>>(mut self, policy: P) -> Self {
+        self.segments_builder = self.segments_builder.eviction_policy(policy.into());
         self
     }
 
-    /// Specify a backing file to be used for segment storage.
-    ///
-    /// # Panics
-    ///
-    /// This will panic if the file already exists
+    /// Specify a backing file to be used for segment storage. The items are
+    /// restored from an existing file if it was flushed by the last call to
+    /// `Seg::checkpoint()` or `Seg::close()`, has not changed since, and was
+    /// created with the same segment size, heap size, and namespaces. The
+    /// metadata of the checkpoint is kept in a second file, which has the
+    /// same path with a `.meta` suffix. Otherwise, the file is discarded and
+    /// replaced.
     pub fn datapool_path<T: AsRef<Path>>(mut self, path: Option<T>) -> Self {
+        self.checkpoint_path = path.as_ref().map(|path| checkpoint::path(path.as_ref()));
         self.segments_builder = self.segments_builder.datapool_path(path);
         self
     }
 
+    /// Specify whether segment storage is backed by huge pages, which reduces
+    /// TLB misses for large heaps. Transparent huge pages only apply to
+    /// in-memory storage. Explicit huge pages must be reserved by the system
+    /// and, if a datapool path is provided, the path must be on a hugetlbfs
+    /// filesystem. If huge pages are unavailable, a warning is logged and the
+    /// default page size is used.
+    ///
+    /// ```
+    /// use seg::{HugePages, Seg};
+    ///
+    /// let cache = Seg::builder().huge_pages(HugePages::Transparent).build();
+    /// ```
+    pub fn huge_pages(mut self, huge_pages: HugePages) -> Self {
+        self.segments_builder = self.segments_builder.huge_pages(huge_pages);
+        self
+    }
+
+    /// Specify the size in bytes of the file-backed second tier. This has no
+    /// effect unless a path is provided with `tier2_path()`.
+    pub fn tier2_size(mut self, bytes: usize) -> Self {
+        self.segments_builder = self.segments_builder.tier2_size(bytes);
+        self
+    }
+
+    /// Specify a file to be used for a second tier of segment storage, which
+    /// is typically on fast local storage. Segments which the eviction policy
+    /// selects are moved to the second tier rather than discarded, and items
+    /// which are read from the second tier are moved back into memory. When
+    /// the second tier is full, its oldest segment is evicted.
+    ///
+    /// Merge based eviction evicts individual items rather than whole segments
+    /// and does not use the second tier.
+    ///
+    /// The second tier is not restored, so an existing file is always
+    /// replaced.
+    pub fn tier2_path<T: AsRef<Path>>(mut self, path: Option<T>) -> Self {
+        self.segments_builder = self.segments_builder.tier2_path(path);
+        self
+    }
+
+    /// Specify namespaces which partition the keyspace by key prefix. Each
+    /// namespace may occupy up to its quota of segments, and when eviction is
+    /// required the segments of the namespace which is furthest over its
+    /// quota are evicted first. Keys which do not belong to any namespace are
+    /// not limited by a quota.
+    ///
+    /// Item counts, bytes, hits, and evictions are reported for each
+    /// namespace, with the stats for keys outside of any namespace reported
+    /// as the `default` namespace.
+    ///
+    /// ```
+    /// use seg::{Namespace, Seg};
+    ///
+    /// // keys starting with "user" may use up to 16 segments and keys
+    /// // starting with "session" may use up to 32 segments
+    /// let cache = Seg::builder()
+    ///     .namespaces(vec![Namespace::new("user", 16), Namespace::new("session", 32)])
+    ///     .build();
+    /// ```
+    pub fn namespaces(mut self, namespaces: Vec<Namespace>) -> Self {
+        self.segments_builder = self.segments_builder.namespaces(namespaces);
+        self
+    }
+
+    /// Specify a delimiter which separates the namespace from the rest of the
+    /// key. When set, the namespace of a key is the portion of the key before
+    /// the first delimiter, which must exactly match a namespace name.
+    /// Otherwise, a key belongs to the namespace with the longest name which
+    /// is a prefix of the key.
+    ///
+    /// ```
+    /// use seg::{Namespace, Seg};
+    ///
+    /// // "user:1234" belongs to the "user" namespace, but "username" does not
+    /// let cache = Seg::builder()
+    ///     .namespaces(vec![Namespace::new("user", 16)])
+    ///     .namespace_delimiter(Some(b':'))
+    ///     .build();
+    /// ```
+    pub fn namespace_delimiter(mut self, delimiter: Option<u8>) -> Self {
+        self.segments_builder = self.segments_builder.namespace_delimiter(delimiter);
+        self
+    }
+
+    /// Enable compression for values which are at least the provided number
+    /// of bytes. Values are only stored compressed if that makes them smaller,
+    /// and are decompressed transparently when read. Compression is disabled
+    /// by default.
+    ///
+    /// ```
+    /// use seg::Seg;
+    ///
+    /// // compress values which are 1KB or larger
+    /// let cache = Seg::builder().compression(Some(1024)).build();
+    /// ```
+    pub fn compression(mut self, threshold: Option<usize>) -> Self {
+        self.compression = threshold;
+        self
+    }
+
+    /// Specify the admission policy, which controls whether new keys are
+    /// stored when they are written. See the `Admission` documentation for
+    /// more details. By default, every write is admitted.
+    ///
+    /// ```
+    /// use seg::{Admission, Seg};
+    /// use std::time::Duration;
+    ///
+    /// // only admit keys which are seen twice within a minute
+    /// let admission = Admission::Bloom {
+    ///     items: 100_000,
+    ///     window: Duration::from_secs(60),
+    /// };
+    /// let cache = Seg::builder().admission(admission).build();
+    /// ```
+    pub fn admission(mut self, policy: Admission) -> Self {
+        self.admission = policy;
+        self
+    }
+
+    /// Limit the number of segments removed by each call to `expire()`. When
+    /// many segments expire at once, removing them all in a single call can
+    /// delay the requests which follow. Any remaining expired segments are
+    /// removed by later calls to `expire()`. By default, there is no limit.
+    ///
+    /// ```
+    /// use seg::Seg;
+    ///
+    /// // remove at most 4 expired segments on each call to expire
+    /// let cache = Seg::builder().expire_max_segments(Some(4)).build();
+    /// ```
+    pub fn expire_max_segments(mut self, segments: Option<usize>) -> Self {
+        self.expire_budget.segments = segments;
+        self
+    }
+
+    /// Limit the time spent removing expired segments in each call to
+    /// `expire()`. The limit is checked after each segment is removed, so a
+    /// call may take slightly longer. Any remaining expired segments are
+    /// removed by later calls to `expire()`. By default, there is no limit.
+    ///
+    /// ```
+    /// use seg::Seg;
+    /// use std::time::Duration;
+    ///
+    /// // spend at most 100us removing expired segments on each call
+    /// let cache = Seg::builder()
+    ///     .expire_max_time(Some(Duration::from_micros(100)))
+    ///     .build();
+    /// ```
+    pub fn expire_max_time(mut self, time: Option<std::time::Duration>) -> Self {
+        self.expire_budget.time = time;
+        self
+    }
+
+    /// Store a unique 64 bit CAS value with each item. By default, the items
+    /// in a hash bucket share a 32 bit CAS value, which changes whenever any
+    /// of those items is written. This means that unrelated keys may have the
+    /// same CAS value, and that a CAS operation may fail due to a write to
+    /// another key.
+    ///
+    /// With per-item CAS values, every write and every increment or decrement
+    /// assigns the item a new value from a counter, matching the behavior of
+    /// Memcached. This adds 8 bytes to each item, which reduces the number of
+    /// small items that fit in the heap.
+    ///
+    /// ```
+    /// use seg::Seg;
+    ///
+    /// let cache = Seg::builder().item_cas(true).build();
+    /// ```
+    pub fn item_cas(mut self, enabled: bool) -> Self {
+        self.item_cas = enabled;
+        self
+    }
+
+    /// Retain items for this long after they expire, so that they may be
+    /// returned by `Seg::get_lease()` while another caller refills them.
+    /// Expired items are not returned by any other operation, and are not
+    /// available to shared readers, which return `SegError::Busy` for every
+    /// item once this is set. The items still occupy the heap until the grace
+    /// period ends. By default, items are removed as soon as they expire.
+    ///
+    /// ```
+    /// use seg::Seg;
+    /// use std::time::Duration;
+    ///
+    /// // serve expired items for up to 30 seconds while they are refilled
+    /// let cache = Seg::builder()
+    ///     .stale_grace(Duration::from_secs(30))
+    ///     .build();
+    /// ```
+    pub fn stale_grace(mut self, grace: std::time::Duration) -> Self {
+        self.stale_grace = grace;
+        self
+    }
+
     /// Consumes the builder and returns a fully-allocated `Seg` instance.
     ///
     /// ```
@@ -159,15 +416,41 @@ impl Builder {
     ///     .eviction(Policy::Random).build();
     /// ```
     pub fn build(self) -> Result<Seg, std::io::Error> {
-        let hashtable = HashTable::new(self.hash_power, self.overflow_factor);
+        let hashtable = HashTable::new(
+            self.hash_power,
+            self.max_hash_power.unwrap_or(self.hash_power),
+            self.overflow_factor,
+        );
         let segments = self.segments_builder.build()?;
-        let ttl_buckets = TtlBuckets::default();
+        let ttl_buckets = TtlBuckets::with_namespaces(segments.namespaces());
 
-        Ok(Seg {
+        let mut seg = Seg {
             hashtable,
             segments,
             ttl_buckets,
-            time: Instant::recent(),
-        })
+            time: clock::recent(),
+            next_large: 0,
+            cas: if self.item_cas { Some(0) } else { None },
+            tags: None,
+            leases: Leases::new(),
+            stale_grace: Duration::from_secs(self.stale_grace.as_secs() as u32),
+            checkpoint_path: self.checkpoint_path,
+            compression: self.compression,
+            admission: AdmissionFilter::new(self.admission),
+            expire_budget: self.expire_budget,
+            shared: false,
+        };
+
+        // the datapool was reopened, so restore the items from its checkpoint
+        if seg.segments.checksum().is_some() {
+            if let Some(path) = seg.checkpoint_path.clone() {
+                match checkpoint::restore(&mut seg, &path) {
+                    Ok(items) => info!("restored {} items from checkpoint", items),
+                    Err(e) => warn!("not restoring from checkpoint: {}", e),
+                }
+            }
+        }
+
+        Ok(seg)
     }
 }
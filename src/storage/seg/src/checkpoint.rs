@@ -0,0 +1,699 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Checkpoints of the cache metadata, which allow a cache backed by a
+//! file-backed datapool to be restored after a restart.
+//!
+//! The segment data is persisted in the datapool file itself. The segment
+//! headers, the ttl bucket chains, the tag generations and the item info for
+//! each item in the hashtable are held in memory, so a checkpoint writes them
+//! to a separate file next to the datapool. The checkpoint records the checksum
+//! of the datapool it was taken with, and is only restored if the datapool
+//! still matches that checksum when it is reopened.
+//!
+//! The file is little-endian and ends with a checksum of its contents:
+//! ```text
+//! header:   magic, version, datapool checksum, unix time, magic feature,
+//!           segment size, segments, namespace names
+//! state:    next large id, per-item cas, hashtable power
+//! segments: write offset, live bytes, live items, prev, next, create age,
+//!           merge age, ttl, accessible, evictable and namespace of each
+//! free:     head of the free queue, free segments, flush age
+//! buckets:  head, tail and next to merge of each ttl bucket
+//! tags:     number of slots, generation of each slot
+//! items:    item info of each item, followed by a zero
+//! checksum: blake3 of everything above
+//! ```
+//!
+//! Times are stored as ages relative to the time of the checkpoint, and the
+//! time elapsed until the cache is restored is added back, so that segments
+//! expire as if the cache had kept running. Items in the second tier are not
+//! checkpointed, and items which use the CAS value of their hash bucket get a
+//! new CAS value once restored.
+
+use crate::*;
+use core::num::NonZeroU32;
+use std::fs::File;
+use std::io::{BufWriter, Error, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"SEGCKPT\0";
+const VERSION: u32 = 1;
+
+/// Returns the path of the checkpoint for a datapool file
+pub(crate) fn path(datapool: &Path) -> PathBuf {
+    let mut path = datapool.as_os_str().to_owned();
+    path.push(".meta");
+    PathBuf::from(path)
+}
+
+/// Writes a checkpoint of the cache metadata to the path. The file is replaced
+/// atomically, so an existing checkpoint remains intact if this fails. The
+/// segments must already have been checkpointed, so that the checkpoint
+/// matches the checksum of the datapool.
+pub(crate) fn write(seg: &mut Seg, path: &Path) -> Result<(), Error> {
+    let checksum = seg
+        .segments
+        .checksum()
+        .ok_or_else(|| Error::other("datapool has not been flushed"))?;
+
+    let tmp = path.with_extension("tmp");
+    let mut writer = Writer::new(File::create(&tmp)?);
+    let now = clock::recent();
+
+    // header
+    writer.put(MAGIC);
+    writer.put_u32(VERSION);
+    writer.put(&checksum);
+    writer.put_u64(dump::unix_now());
+    writer.put_u8(cfg!(feature = "magic") as u8);
+    writer.put_u32(seg.segments.segment_size() as u32);
+    writer.put_u32(seg.segments.cap() as u32);
+    let names = seg.segments.namespace_names();
+    writer.put_u32(names.len() as u32);
+    for name in &names {
+        writer.put_u32(name.len() as u32);
+        writer.put(name);
+    }
+
+    // state
+    writer.put_u64(seg.next_large);
+    writer.put_u8(seg.cas.is_some() as u8);
+    writer.put_u64(seg.cas.unwrap_or(0));
+    writer.put_u8(seg.hashtable.power());
+
+    // segments
+    for header in seg.segments.headers() {
+        writer.put_u32(header.write_offset() as u32);
+        writer.put_u32(header.live_bytes() as u32);
+        writer.put_u32(header.live_items() as u32);
+        writer.put_id(header.prev_seg());
+        writer.put_id(header.next_seg());
+        writer.put_u32((now - header.create_at()).as_secs());
+        writer.put_u32((now - header.merge_at()).as_secs());
+        writer.put_u32(header.ttl().as_secs());
+        writer.put_u8(header.accessible() as u8);
+        writer.put_u8(header.evictable() as u8);
+        writer.put_u16(header.namespace());
+    }
+
+    // free
+    let (free_q, free) = seg.segments.free_q();
+    writer.put_id(free_q);
+    writer.put_u32(free);
+    writer.put_u32((now - seg.segments.flush_at()).as_secs());
+
+    // buckets
+    writer.put_u32(seg.ttl_buckets.buckets.len() as u32);
+    for bucket in seg.ttl_buckets.buckets.iter() {
+        writer.put_id(bucket.head());
+        writer.put_id(bucket.tail());
+        writer.put_id(bucket.next_to_merge());
+    }
+
+    // tags
+    let generations = seg.tags.as_ref().map(|tags| tags.generations());
+    writer.put_u32(generations.map(|g| g.len()).unwrap_or(0) as u32);
+    for generation in generations.unwrap_or(&[]) {
+        writer.put_u32(*generation);
+    }
+
+    // items, skipping any held in the second tier
+    let max = seg.segments.max() as u32;
+    seg.hashtable.for_each_item(|item_info| {
+        if get_seg_id(item_info)
+            .map(|id| id.get() <= max)
+            .unwrap_or(false)
+        {
+            writer.put_u64(item_info);
+        }
+    });
+    writer.put_u64(0);
+
+    writer.finish()?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Restores the cache metadata from the checkpoint at the path. This must be
+/// called on a cache which was just built, with segments which were reopened
+/// from the same datapool. Nothing is changed if the checkpoint does not match
+/// the datapool or the configuration of the cache. Returns the number of items
+/// restored.
+pub(crate) fn restore(seg: &mut Seg, path: &Path) -> Result<usize, Error> {
+    let snapshot = Snapshot::read(path)?;
+    snapshot.check(seg)?;
+    Ok(snapshot.apply(seg))
+}
+
+/// The checkpoint of a file-backed datapool, which is read without building a
+/// cache so that the files may be inspected offline. Neither file is changed.
+///
+/// ```no_run
+/// use seg::Checkpoint;
+///
+/// let checkpoint = Checkpoint::read("/path/to/datapool").expect("no checkpoint");
+/// for segment in checkpoint.segments().filter(|s| s.in_use()) {
+///     println!("{}: {} items", segment.id(), segment.live_items());
+/// }
+/// ```
+pub struct Checkpoint {
+    snapshot: Snapshot,
+}
+
+impl Checkpoint {
+    /// Reads the checkpoint for the datapool file at the path. Returns an
+    /// error if there is no checkpoint, if it fails its checksum, or if it
+    /// was not taken with the current contents of the datapool.
+    pub fn read<T: AsRef<Path>>(datapool: T) -> Result<Self, Error> {
+        let header = datapool::Header::read(datapool.as_ref())?;
+        let snapshot = Snapshot::read(&path(datapool.as_ref()))?;
+        if !header.is_clean() || header.checksum() != snapshot.checksum {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "checkpoint does not match the datapool",
+            ));
+        }
+        Ok(Self { snapshot })
+    }
+
+    /// The time the checkpoint was taken, in seconds since the unix epoch
+    pub fn written_at(&self) -> u64 {
+        self.snapshot.written_at
+    }
+
+    /// The size of each segment in bytes
+    pub fn segment_size(&self) -> usize {
+        self.snapshot.segment_size as usize
+    }
+
+    /// The names of the namespaces, in order of their ids starting from 1.
+    /// Namespace 0 holds the keys which are outside of any namespace.
+    pub fn namespaces(&self) -> &[Vec<u8>] {
+        &self.snapshot.names
+    }
+
+    /// The number of items in the hashtable
+    pub fn items(&self) -> usize {
+        self.snapshot.items.len()
+    }
+
+    /// Returns an iterator over the in-memory segments, in order of their ids
+    pub fn segments(&self) -> impl Iterator<Item = SegmentSummary<'_>> {
+        let base = if self.snapshot.magic {
+            std::mem::size_of_val(&SEG_MAGIC) as i32
+        } else {
+            0
+        };
+        self.snapshot
+            .headers
+            .iter()
+            .enumerate()
+            .map(move |(idx, header)| SegmentSummary {
+                id: idx as u32 + 1,
+                header,
+                base,
+            })
+    }
+
+    /// Calls the function with the segment id and key of each item, which
+    /// are read from the data of the datapool, see
+    /// `datapool::Header::data_size()`. Returns an error if the items were
+    /// written with a different item format than this build uses.
+    pub fn for_each_key(&self, data: &[u8], mut f: impl FnMut(u32, &[u8])) -> Result<(), Error> {
+        if self.snapshot.magic != cfg!(feature = "magic") {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "items were written with a different magic feature",
+            ));
+        }
+
+        let segment_size = self.segment_size();
+        for item_info in &self.snapshot.items {
+            let id = match get_seg_id(*item_info) {
+                Some(id) => id.get() as usize,
+                None => continue,
+            };
+            let start = (id - 1) * segment_size + get_offset(*item_info) as usize;
+            let end = std::cmp::min(id * segment_size, data.len());
+            if start + ITEM_HDR_SIZE + ITEM_EXT_SIZE > end {
+                continue;
+            }
+            // SAFETY: the item header and extended flags are within the data,
+            // and the item is only read
+            let item = RawItem::from_ptr(data[start..].as_ptr() as *mut u8);
+            if start + item.size() <= end {
+                f(id as u32, item.key());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A read-only view of a segment in a [`Checkpoint`]
+pub struct SegmentSummary<'a> {
+    id: u32,
+    header: &'a SavedHeader,
+    // the bytes at the start of the segment which do not hold items
+    base: i32,
+}
+
+impl SegmentSummary<'_> {
+    /// The id of the segment
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns true if the segment is in a ttl bucket and may hold items,
+    /// false if it is free
+    pub fn in_use(&self) -> bool {
+        self.header.evictable
+    }
+
+    /// The number of live items in the segment
+    pub fn live_items(&self) -> usize {
+        self.header.live_items as usize
+    }
+
+    /// The number of bytes used by live items in the segment
+    pub fn live_bytes(&self) -> usize {
+        (self.header.live_bytes - self.base).max(0) as usize
+    }
+
+    /// The number of bytes written to the segment, including the items which
+    /// have since been removed
+    pub fn written_bytes(&self) -> usize {
+        (self.header.write_offset - self.base).max(0) as usize
+    }
+
+    /// The TTL of the items in the segment, or `None` if they do not expire
+    pub fn ttl(&self) -> Option<std::time::Duration> {
+        if self.header.ttl >= MAX_TTL {
+            None
+        } else {
+            Some(std::time::Duration::from_secs(self.header.ttl as u64))
+        }
+    }
+
+    /// The time from when the segment was taken from the free queue until the
+    /// checkpoint was taken
+    pub fn age(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.header.create_age as u64)
+    }
+
+    /// The id of the namespace of the items in the segment
+    pub fn namespace(&self) -> u16 {
+        self.header.namespace
+    }
+}
+
+/// The state of a segment header as it was checkpointed
+struct SavedHeader {
+    write_offset: i32,
+    live_bytes: i32,
+    live_items: i32,
+    prev: Option<NonZeroU32>,
+    next: Option<NonZeroU32>,
+    create_age: u32,
+    merge_age: u32,
+    ttl: u32,
+    accessible: bool,
+    evictable: bool,
+    namespace: u16,
+}
+
+/// The contents of a checkpoint, which are checked against the cache before
+/// any of the cache is changed
+struct Snapshot {
+    checksum: [u8; 32],
+    written_at: u64,
+    elapsed: u32,
+    magic: bool,
+    segment_size: u32,
+    names: Vec<Vec<u8>>,
+    next_large: u64,
+    cas: Option<u64>,
+    hash_power: u8,
+    headers: Vec<SavedHeader>,
+    free_q: Option<NonZeroU32>,
+    free: u32,
+    flush_age: u32,
+    buckets: Vec<[Option<NonZeroU32>; 3]>,
+    generations: Vec<u32>,
+    items: Vec<u64>,
+}
+
+impl Snapshot {
+    fn read(path: &Path) -> Result<Self, Error> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        if bytes.len() < 32 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "checkpoint is truncated",
+            ));
+        }
+        let (contents, checksum) = bytes.split_at(bytes.len() - 32);
+        if blake3::hash(contents).as_bytes() != checksum {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "checkpoint checksum mismatch",
+            ));
+        }
+        Self::parse(&mut Reader { bytes: contents })
+    }
+
+    fn parse(reader: &mut Reader) -> Result<Self, Error> {
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "not a checkpoint"));
+        }
+        if reader.u32()? != VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "checkpoint version mismatch",
+            ));
+        }
+        let checksum = reader.take(32)?.try_into().unwrap();
+        let written_at = reader.u64()?;
+        let elapsed = dump::unix_now().saturating_sub(written_at);
+        let elapsed = std::cmp::min(elapsed, u32::MAX as u64) as u32;
+        let magic = reader.u8()? != 0;
+        let segment_size = reader.u32()?;
+        let segments = reader.u32()? as usize;
+        let mut names = Vec::new();
+        for _ in 0..reader.u32()? {
+            let len = reader.u32()? as usize;
+            names.push(reader.take(len)?.to_vec());
+        }
+
+        let next_large = reader.u64()?;
+        let has_cas = reader.u8()? != 0;
+        let cas = reader.u64()?;
+        let hash_power = reader.u8()?;
+
+        let mut headers = Vec::with_capacity(segments);
+        for _ in 0..segments {
+            headers.push(SavedHeader {
+                write_offset: reader.u32()? as i32,
+                live_bytes: reader.u32()? as i32,
+                live_items: reader.u32()? as i32,
+                prev: reader.id()?,
+                next: reader.id()?,
+                create_age: reader.u32()?,
+                merge_age: reader.u32()?,
+                ttl: reader.u32()?,
+                accessible: reader.u8()? != 0,
+                evictable: reader.u8()? != 0,
+                namespace: reader.u16()?,
+            });
+        }
+
+        let free_q = reader.id()?;
+        let free = reader.u32()?;
+        let flush_age = reader.u32()?;
+
+        let mut buckets = Vec::new();
+        for _ in 0..reader.u32()? {
+            buckets.push([reader.id()?, reader.id()?, reader.id()?]);
+        }
+
+        let mut generations = Vec::new();
+        for _ in 0..reader.u32()? {
+            generations.push(reader.u32()?);
+        }
+
+        let mut items = Vec::new();
+        loop {
+            match reader.u64()? {
+                0 => break,
+                item_info => items.push(item_info),
+            }
+        }
+
+        Ok(Self {
+            checksum,
+            written_at,
+            elapsed,
+            magic,
+            segment_size,
+            names,
+            next_large,
+            cas: if has_cas { Some(cas) } else { None },
+            hash_power,
+            headers,
+            free_q,
+            free,
+            flush_age,
+            buckets,
+            generations,
+            items,
+        })
+    }
+
+    /// Checks that the checkpoint was taken with the datapool and a matching
+    /// configuration
+    fn check(&self, seg: &Seg) -> Result<(), Error> {
+        let mismatch = |what| {
+            Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("checkpoint {} mismatch", what),
+            ))
+        };
+        if seg.segments.checksum() != Some(self.checksum) {
+            return mismatch("datapool checksum");
+        }
+        if self.magic != cfg!(feature = "magic") {
+            return mismatch("magic feature");
+        }
+        if self.segment_size != seg.segments.segment_size() as u32
+            || self.headers.len() != seg.segments.cap()
+        {
+            return mismatch("segment size");
+        }
+        if self.names != seg.segments.namespace_names()
+            || self.buckets.len() != seg.ttl_buckets.buckets.len()
+        {
+            return mismatch("namespace");
+        }
+        let cap = seg.segments.cap() as u32;
+        let ids = self
+            .headers
+            .iter()
+            .flat_map(|h| [h.prev, h.next])
+            .chain(self.buckets.iter().flatten().copied())
+            .chain([self.free_q]);
+        if ids.flatten().any(|id| id.get() > cap) || self.free > cap {
+            return mismatch("segment id");
+        }
+        Ok(())
+    }
+
+    /// Restores the cache from the checkpoint. Segments which were created
+    /// too long ago to be represented by the clock are discarded along with
+    /// their items. Returns the number of items restored.
+    fn apply(self, seg: &mut Seg) -> usize {
+        let now = clock::recent();
+        let restore_at =
+            |age: u32| now.checked_sub(Duration::from_secs(age.saturating_add(self.elapsed)));
+
+        if self.hash_power > seg.hashtable.power() {
+            seg.hashtable = seg.hashtable.with_power(self.hash_power);
+        }
+
+        let mut discard = vec![false; self.headers.len()];
+        for (idx, (header, saved)) in seg
+            .segments
+            .headers_mut()
+            .iter_mut()
+            .zip(&self.headers)
+            .enumerate()
+        {
+            let create_at = restore_at(saved.create_age);
+            discard[idx] = saved.evictable && create_at.is_none();
+            let create_at = create_at.unwrap_or(now);
+            let merge_at = restore_at(saved.merge_age).unwrap_or(create_at);
+
+            header.set_write_offset(saved.write_offset);
+            header.set_live(saved.live_items, saved.live_bytes);
+            header.set_prev_seg(saved.prev);
+            header.set_next_seg(saved.next);
+            header.set_create_at(create_at);
+            header.set_merge_at(merge_at);
+            header.set_ttl(Duration::from_secs(saved.ttl));
+            header.set_accessible(saved.accessible);
+            header.set_evictable(saved.evictable);
+            header.set_namespace(saved.namespace);
+        }
+        seg.segments.set_free_q(self.free_q, self.free);
+        let flush_at = restore_at(self.flush_age).unwrap_or(now);
+        seg.segments.set_flush_at(flush_at);
+
+        for (bucket, [head, tail, next_to_merge]) in
+            seg.ttl_buckets.buckets.iter_mut().zip(self.buckets)
+        {
+            bucket.set_head(head);
+            bucket.set_tail(tail);
+            bucket.set_next_to_merge(next_to_merge);
+        }
+
+        if !self.generations.is_empty() {
+            let mut tags = Tags::new();
+            if tags.restore(&self.generations) {
+                seg.tags = Some(tags);
+            }
+        }
+        seg.next_large = self.next_large;
+        if let (Some(cas), Some(saved)) = (&mut seg.cas, self.cas) {
+            *cas = std::cmp::max(*cas, saved);
+        }
+
+        // the namespaces track the segments in the ttl buckets in the order
+        // they were taken from the free queue, which is the order they were
+        // created
+        let mut held: Vec<usize> = (0..self.headers.len())
+            .filter(|idx| self.headers[*idx].evictable && !discard[*idx])
+            .collect();
+        held.sort_by_key(|idx| std::cmp::Reverse(self.headers[*idx].create_age));
+        for idx in held {
+            let id = NonZeroU32::new(idx as u32 + 1).unwrap();
+            seg.segments.set_namespace(id, self.headers[idx].namespace);
+        }
+
+        // items which cannot be linked are removed from their segment, as if
+        // they were deleted
+        let mut restored = 0;
+        for item_info in self.items {
+            let id = match get_seg_id(item_info) {
+                Some(id) if id.get() as usize <= discard.len() => id,
+                _ => continue,
+            };
+            let linked = !discard[id.get() as usize - 1]
+                && match seg.segments.get_item(item_info) {
+                    Some(item) => seg.hashtable.restore(item.key(), item_info),
+                    None => false,
+                };
+            if linked {
+                restored += 1;
+            } else if let Ok(mut segment) = seg.segments.get_mut(id) {
+                segment.remove_item_at(get_offset(item_info) as usize);
+            }
+        }
+
+        // restoring does not change the segment data, so only the segments
+        // which are changed from now on need to be written by the next
+        // checkpoint
+        seg.segments.mark_clean();
+
+        for (idx, _) in discard.iter().enumerate().filter(|(_, d)| **d) {
+            let id = NonZeroU32::new(idx as u32 + 1).unwrap();
+            seg.segments
+                .discard(id, &mut seg.ttl_buckets, &mut seg.hashtable);
+        }
+
+        let headers = seg.segments.headers().iter().filter(|h| h.accessible());
+        let (items, bytes) = headers.fold((0, 0), |(items, bytes), h| {
+            (
+                items + h.live_items() as i64,
+                bytes + (h.live_bytes() - first_item_offset() as i32) as i64,
+            )
+        });
+        ITEM_CURRENT.set(items);
+        ITEM_CURRENT_BYTES.set(bytes);
+
+        restored
+    }
+}
+
+/// Writes the checkpoint and the checksum of its contents
+struct Writer {
+    inner: BufWriter<File>,
+    hasher: blake3::Hasher,
+    result: Result<(), Error>,
+}
+
+impl Writer {
+    fn new(file: File) -> Self {
+        Self {
+            inner: BufWriter::new(file),
+            hasher: blake3::Hasher::new(),
+            result: Ok(()),
+        }
+    }
+
+    fn put(&mut self, bytes: &[u8]) {
+        if self.result.is_ok() {
+            self.hasher.update(bytes);
+            self.result = self.inner.write_all(bytes);
+        }
+    }
+
+    fn put_u8(&mut self, value: u8) {
+        self.put(&[value]);
+    }
+
+    fn put_u16(&mut self, value: u16) {
+        self.put(&value.to_le_bytes());
+    }
+
+    fn put_u32(&mut self, value: u32) {
+        self.put(&value.to_le_bytes());
+    }
+
+    fn put_u64(&mut self, value: u64) {
+        self.put(&value.to_le_bytes());
+    }
+
+    fn put_id(&mut self, id: Option<NonZeroU32>) {
+        self.put_u32(id.map(|id| id.get()).unwrap_or(0));
+    }
+
+    /// Writes the checksum and syncs the file
+    fn finish(mut self) -> Result<(), Error> {
+        let checksum = *self.hasher.finalize().as_bytes();
+        self.put(&checksum);
+        self.result?;
+        self.inner
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()
+    }
+}
+
+/// Reads the contents of a checkpoint
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() < len {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "checkpoint is truncated",
+            ));
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn id(&mut self) -> Result<Option<NonZeroU32>, Error> {
+        Ok(NonZeroU32::new(self.u32()?))
+    }
+}
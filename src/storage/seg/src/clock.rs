@@ -0,0 +1,79 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! The clock used for item and segment timestamps. It follows the coarse
+//! system clock. With the `sim` feature, the clock may be frozen, in which case
+//! time only moves forward when the clock is advanced. This allows traces to be
+//! replayed with virtual time, see `freeze_clock()`.
+
+use crate::*;
+#[cfg(feature = "sim")]
+use core::sync::atomic::{AtomicU32, Ordering};
+#[cfg(feature = "sim")]
+use std::sync::OnceLock;
+
+// the time at which the clock was frozen
+#[cfg(feature = "sim")]
+static FROZEN_AT: OnceLock<Instant> = OnceLock::new();
+
+// the number of seconds the frozen clock has been advanced by
+#[cfg(feature = "sim")]
+static ADVANCED: AtomicU32 = AtomicU32::new(0);
+
+/// Freezes the clock used by every `Seg` in the process, time then only moves
+/// forward with calls to `advance_clock()`. This is intended for simulations
+/// which replay a trace with virtual time, and should be called before any
+/// `Seg` is built. Once frozen, the clock can not be unfrozen.
+///
+/// ```
+/// use seg::*;
+/// use std::time::Duration;
+///
+/// seg::freeze_clock();
+///
+/// let mut cache = Seg::builder().build().expect("failed to create cache");
+/// assert!(cache.insert(b"coffee", b"strong", None, Duration::from_secs(60)).is_ok());
+///
+/// // time only moves forward when the clock is advanced
+/// seg::advance_clock(Duration::from_secs(30));
+/// cache.expire();
+/// assert!(cache.get(b"coffee").is_some());
+///
+/// seg::advance_clock(Duration::from_secs(60));
+/// cache.expire();
+/// assert!(cache.get(b"coffee").is_none());
+/// ```
+#[cfg(feature = "sim")]
+pub fn freeze_clock() {
+    let _ = FROZEN_AT.get_or_init(Instant::recent);
+}
+
+/// Moves the frozen clock forward by the duration, with a resolution of one
+/// second. Has no effect unless the clock is frozen, see `freeze_clock()`.
+#[cfg(feature = "sim")]
+pub fn advance_clock(duration: std::time::Duration) {
+    if FROZEN_AT.get().is_some() {
+        ADVANCED.fetch_add(duration.as_secs() as u32, Ordering::Relaxed);
+    }
+}
+
+/// Returns the current time, which is the recent coarse time unless the clock
+/// is frozen
+pub(crate) fn recent() -> Instant {
+    #[cfg(feature = "sim")]
+    if let Some(at) = FROZEN_AT.get() {
+        return *at + Duration::from_secs(ADVANCED.load(Ordering::Relaxed));
+    }
+    Instant::recent()
+}
+
+/// Returns the current time, which reads the system clock unless the clock is
+/// frozen
+pub(crate) fn now() -> Instant {
+    #[cfg(feature = "sim")]
+    if FROZEN_AT.get().is_some() {
+        return recent();
+    }
+    Instant::now()
+}
@@ -0,0 +1,425 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! A portable export format for the contents of the cache. A dump does not
+//! depend on the layout of the segments, so it can be loaded into a cache of a
+//! different size or version.
+//!
+//! The format is a header followed by a stream of records and a trailer. All
+//! integers are little-endian.
+//!
+//! ```text
+//! header:  magic: [u8; 8], version: u32
+//! item:    1u8, expire_at: u64, klen: u32, key, value, olen: u32, optional
+//!          (an expire_at of 0 is used for items which do not expire)
+//! value:   0u8, vlen: u32, bytes | 1u8, u64
+//! trailer: 0u8, count: u64, checksum: [u8; 32]
+//! ```
+//!
+//! The expiry is stored as an absolute UNIX time in seconds and the checksum
+//! is a BLAKE3 hash of every byte which precedes it.
+
+use crate::*;
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+use storage_types::OwnedValue;
+
+const MAGIC: [u8; 8] = *b"SEGDUMP\0";
+const FORMAT_VERSION: u32 = 1;
+
+const TAG_END: u8 = 0;
+const TAG_ITEM: u8 = 1;
+
+const VALUE_BYTES: u8 = 0;
+const VALUE_U64: u8 = 1;
+
+// the largest key, value, or optional data which will be read from a dump,
+// this protects against large allocations when reading a corrupted dump
+const MAX_FIELD_LEN: u32 = 1 << 30;
+
+/// Returns the current UNIX time in seconds.
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// maps the clock of the cache onto UNIX time. item ttls are relative to the
+// clock of the cache, which may be frozen, so the UNIX time is derived from it
+// rather than read from the system clock on each step
+struct UnixClock {
+    unix: u64,
+    at: Instant,
+}
+
+impl UnixClock {
+    fn new() -> Self {
+        Self {
+            unix: unix_now(),
+            at: clock::recent(),
+        }
+    }
+
+    // returns the current UNIX time in seconds
+    fn now(&self) -> u64 {
+        self.unix + (clock::recent() - self.at).as_secs() as u64
+    }
+}
+
+/// A single item read from a dump.
+pub struct Record {
+    key: Box<[u8]>,
+    value: OwnedValue,
+    optional: Box<[u8]>,
+    expire_at: u64,
+}
+
+impl Record {
+    /// Borrow the item key
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Borrow the item value
+    pub fn value(&self) -> Value<'_> {
+        self.value.as_value()
+    }
+
+    /// Borrow the optional data
+    pub fn optional(&self) -> &[u8] {
+        &self.optional
+    }
+
+    /// The UNIX time in seconds at which the item expires, or 0 if the item
+    /// does not expire
+    pub fn expire_at(&self) -> u64 {
+        self.expire_at
+    }
+}
+
+/// Writes items into a dump. The dump is not complete until `finish()` has
+/// been called.
+pub struct DumpWriter<W: Write> {
+    writer: W,
+    hasher: blake3::Hasher,
+    count: u64,
+}
+
+impl<W: Write> DumpWriter<W> {
+    /// Create a new `DumpWriter` and write the header.
+    pub fn new(writer: W) -> Result<Self, Error> {
+        let mut dump = Self {
+            writer,
+            hasher: blake3::Hasher::new(),
+            count: 0,
+        };
+        dump.write_all(&MAGIC)?;
+        dump.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        Ok(dump)
+    }
+
+    /// Write a single item which expires at the provided UNIX time.
+    pub fn write(
+        &mut self,
+        key: &[u8],
+        value: Value,
+        optional: &[u8],
+        expire_at: u64,
+    ) -> Result<(), Error> {
+        self.write_all(&[TAG_ITEM])?;
+        self.write_all(&expire_at.to_le_bytes())?;
+        self.write_field(key)?;
+        match value {
+            Value::Bytes(v) => {
+                self.write_all(&[VALUE_BYTES])?;
+                self.write_field(v)?;
+            }
+            Value::U64(v) => {
+                self.write_all(&[VALUE_U64])?;
+                self.write_all(&v.to_le_bytes())?;
+            }
+        }
+        self.write_field(optional)?;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Write the trailer and flush the writer, returning the number of items
+    /// written.
+    pub fn finish(mut self) -> Result<u64, Error> {
+        self.write_all(&[TAG_END])?;
+        self.write_all(&self.count.to_le_bytes())?;
+        let checksum = self.hasher.finalize();
+        self.writer.write_all(checksum.as_bytes())?;
+        self.writer.flush()?;
+        Ok(self.count)
+    }
+
+    fn write_field(&mut self, data: &[u8]) -> Result<(), Error> {
+        let len: u32 = data
+            .len()
+            .try_into()
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "field too large"))?;
+        self.write_all(&len.to_le_bytes())?;
+        self.write_all(data)
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.hasher.update(data);
+        self.writer.write_all(data)
+    }
+}
+
+/// Reads items from a dump. The checksum is verified once the trailer has
+/// been read, so the items must be treated as unverified until `read()` has
+/// returned `None`.
+pub struct DumpReader<R: Read> {
+    reader: R,
+    hasher: blake3::Hasher,
+    count: u64,
+    done: bool,
+}
+
+impl<R: Read> DumpReader<R> {
+    /// Create a new `DumpReader`, returning an error if the header is invalid
+    /// or the format version is not supported.
+    pub fn new(reader: R) -> Result<Self, Error> {
+        let mut dump = Self {
+            reader,
+            hasher: blake3::Hasher::new(),
+            count: 0,
+            done: false,
+        };
+
+        let mut magic = [0; 8];
+        dump.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "not a cache dump"));
+        }
+
+        let version = dump.read_u32()?;
+        if version != FORMAT_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unsupported dump version: {}", version),
+            ));
+        }
+
+        Ok(dump)
+    }
+
+    /// Read the next item, returning `None` once the trailer has been read and
+    /// the checksum has been verified.
+    pub fn read(&mut self) -> Result<Option<Record>, Error> {
+        if self.done {
+            return Ok(None);
+        }
+
+        match self.read_u8()? {
+            TAG_ITEM => {}
+            TAG_END => {
+                self.done = true;
+                let count = self.read_u64()?;
+                let expected = self.hasher.finalize();
+                let mut checksum = [0; 32];
+                self.reader.read_exact(&mut checksum)?;
+                if checksum != *expected.as_bytes() {
+                    return Err(Error::new(ErrorKind::InvalidData, "checksum mismatch"));
+                }
+                if count != self.count {
+                    return Err(Error::new(ErrorKind::InvalidData, "item count mismatch"));
+                }
+                return Ok(None);
+            }
+            _ => {
+                return Err(Error::new(ErrorKind::InvalidData, "bad record tag"));
+            }
+        }
+
+        let expire_at = self.read_u64()?;
+        let key = self.read_field()?;
+        let value = match self.read_u8()? {
+            VALUE_BYTES => OwnedValue::Bytes(self.read_field()?),
+            VALUE_U64 => OwnedValue::U64(self.read_u64()?),
+            _ => {
+                return Err(Error::new(ErrorKind::InvalidData, "bad value type"));
+            }
+        };
+        let optional = self.read_field()?;
+        self.count += 1;
+
+        Ok(Some(Record {
+            key,
+            value,
+            optional,
+            expire_at,
+        }))
+    }
+
+    /// The number of items which have been read.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_field(&mut self) -> Result<Box<[u8]>, Error> {
+        let len = self.read_u32()?;
+        if len > MAX_FIELD_LEN {
+            return Err(Error::new(ErrorKind::InvalidData, "field too large"));
+        }
+        let mut data = vec![0; len as usize];
+        self.read_exact(&mut data)?;
+        Ok(data.into_boxed_slice())
+    }
+
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        let mut buf = [0; 1];
+        self.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        let mut buf = [0; 4];
+        self.read_exact(&mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, Error> {
+        let mut buf = [0; 8];
+        self.read_exact(&mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        self.reader.read_exact(buf)?;
+        self.hasher.update(buf);
+        Ok(())
+    }
+}
+
+/// A dump of the cache which is written a few items at a time, so that it can
+/// be interleaved with other operations on the cache. Items which are written
+/// or removed while the dump is in progress may or may not be included.
+pub struct DumpTask<W: Write> {
+    writer: DumpWriter<W>,
+    cursor: Cursor,
+    clock: UnixClock,
+}
+
+impl<W: Write> DumpTask<W> {
+    /// Create a new `DumpTask` and write the header.
+    pub fn new(writer: W) -> Result<Self, Error> {
+        Ok(Self {
+            writer: DumpWriter::new(writer)?,
+            cursor: Cursor::default(),
+            clock: UnixClock::new(),
+        })
+    }
+
+    /// Writes up to `limit` items, returning true once every item has been
+    /// written, after which the dump is completed with `finish()`.
+    pub fn step(&mut self, cache: &mut Seg, limit: usize) -> Result<bool, Error> {
+        let now = self.clock.now();
+        let mut scan = cache.scan(self.cursor.clone());
+        for (item, ttl) in scan.by_ref().take(limit) {
+            self.writer.write(
+                item.key(),
+                item.value(),
+                item.optional().unwrap_or(&[]),
+                ttl.map(|ttl| now + ttl.as_secs()).unwrap_or(0),
+            )?;
+        }
+        self.cursor = scan.cursor();
+        Ok(self.cursor.is_done())
+    }
+
+    /// Write the trailer, returning the number of items written.
+    pub fn finish(self) -> Result<usize, Error> {
+        self.writer.finish().map(|count| count as usize)
+    }
+}
+
+/// A load of a dump into the cache which inserts a few items at a time. The
+/// whole dump is read and its checksum is verified before any item is
+/// inserted, so an invalid dump leaves the cache unchanged.
+pub struct LoadTask<R: Read + Seek> {
+    reader: Option<DumpReader<R>>,
+    verified: bool,
+    loaded: usize,
+    clock: UnixClock,
+}
+
+impl<R: Read + Seek> LoadTask<R> {
+    /// Create a new `LoadTask`, returning an error if the header is invalid.
+    pub fn new(reader: R) -> Result<Self, Error> {
+        Ok(Self {
+            reader: Some(DumpReader::new(reader)?),
+            verified: false,
+            loaded: 0,
+            clock: UnixClock::new(),
+        })
+    }
+
+    /// Reads up to `limit` items, which are inserted once the dump has been
+    /// verified. Returns the number of items loaded once the load is complete.
+    /// Items bypass the admission policy, and tags are not kept in the dump,
+    /// so the items are loaded without them. Items which cannot be inserted,
+    /// for example because they are too large for the segments of this cache,
+    /// are skipped.
+    pub fn step(&mut self, cache: &mut Seg, limit: usize) -> Result<Option<usize>, Error> {
+        let reader = match self.reader.as_mut() {
+            Some(reader) => reader,
+            None => return Ok(Some(self.loaded)),
+        };
+
+        let now = self.clock.now();
+        for _ in 0..limit {
+            let record = match reader.read()? {
+                Some(record) => record,
+                None => {
+                    let reader = self.reader.take().unwrap();
+                    if self.verified {
+                        return Ok(Some(self.loaded));
+                    }
+                    // the dump is valid, rewind and insert the items
+                    let mut inner = reader.into_inner();
+                    inner.seek(SeekFrom::Start(0))?;
+                    self.reader = Some(DumpReader::new(inner)?);
+                    self.verified = true;
+                    return Ok(None);
+                }
+            };
+
+            let ttl = match record.expire_at() {
+                0 => Duration::from_secs(0),
+                expire_at if expire_at > now => {
+                    Duration::from_secs(core::cmp::min(u32::MAX as u64, expire_at - now) as u32)
+                }
+                _ => continue,
+            };
+            if !self.verified {
+                continue;
+            }
+            if cache
+                .insert_with_ttl(
+                    record.key(),
+                    record.value(),
+                    Some(record.optional()),
+                    ttl,
+                    None,
+                )
+                .is_ok()
+            {
+                self.loaded += 1;
+            }
+        }
+
+        Ok(None)
+    }
+}
@@ -25,4 +25,10 @@ pub enum SegError {
     DataCorrupted,
     #[error("item is not numeric")]
     NotNumeric,
+    #[error("read conflicted with a write")]
+    Busy,
+    #[error("key is reserved")]
+    ReservedKey,
+    #[error("lease timeout out of range")]
+    LeaseTimeout,
 }
@@ -0,0 +1,198 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! The built-in implementations of [`EvictionPolicy`] which correspond to each
+//! [`Policy`].
+
+use super::*;
+use core::cmp::{max, Ordering};
+
+/// Never selects a segment, see [`Policy::None`].
+pub(crate) struct NoEviction;
+
+impl EvictionPolicy for NoEviction {
+    fn select(&mut self, _segments: &[SegmentStats]) -> Option<NonZeroU32> {
+        None
+    }
+}
+
+/// Selects a random segment, see [`Policy::Random`].
+pub(crate) struct Random {
+    rng: Box<crate::Random>,
+}
+
+impl Random {
+    pub fn new() -> Self {
+        Self {
+            rng: Box::new(rng()),
+        }
+    }
+}
+
+impl EvictionPolicy for Random {
+    fn select(&mut self, segments: &[SegmentStats]) -> Option<NonZeroU32> {
+        let start = self.rng.gen::<u32>() as usize % segments.len();
+
+        (0..segments.len())
+            .map(|i| &segments[(start + i) % segments.len()])
+            .find(|s| s.can_evict())
+            .map(|s| s.id())
+    }
+}
+
+/// Selects the oldest segment in a TTL bucket chosen at random, weighted by
+/// the number of segments in each bucket, see [`Policy::RandomFifo`].
+pub(crate) struct RandomFifo {
+    rng: Box<crate::Random>,
+}
+
+impl RandomFifo {
+    pub fn new() -> Self {
+        Self {
+            rng: Box::new(rng()),
+        }
+    }
+}
+
+impl EvictionPolicy for RandomFifo {
+    fn select(&mut self, segments: &[SegmentStats]) -> Option<NonZeroU32> {
+        // picking a random accessible segment and walking back to the head of
+        // its `TtlBucket` is equivalent to picking a bucket from a weighted
+        // distribution based on the number of segments per bucket
+        let start = self.rng.gen::<u32>() as usize % segments.len();
+
+        let mut segment = (0..segments.len())
+            .map(|i| &segments[(start + i) % segments.len()])
+            .find(|s| s.accessible())?;
+
+        for _ in 0..segments.len() {
+            match segment.prev() {
+                Some(prev) => segment = &segments[prev.get() as usize - 1],
+                None => break,
+            }
+        }
+
+        Some(segment.id())
+    }
+}
+
+/// The order used by a [`Ranked`] policy.
+pub(crate) enum Rank {
+    /// Oldest first, see [`Policy::Fifo`].
+    Fifo,
+    /// Closest to expiration first, see [`Policy::Cte`].
+    Cte,
+    /// Fewest live bytes first, see [`Policy::Util`].
+    Util,
+}
+
+/// Ranks all of the segments and then selects them in order, re-ranking
+/// periodically or once most of the ranked segments have been used.
+pub(crate) struct Ranked {
+    rank: Rank,
+    last_update_time: Instant,
+    ranked_segs: Vec<NonZeroU32>,
+    index: usize,
+}
+
+impl Ranked {
+    pub fn new(rank: Rank) -> Self {
+        Self {
+            rank,
+            last_update_time: clock::now(),
+            ranked_segs: Vec::new(),
+            index: 0,
+        }
+    }
+
+    fn should_rerank(&mut self) -> bool {
+        let now = clock::recent();
+        if self.ranked_segs.is_empty()
+            || (now - self.last_update_time).as_secs() > 1
+            || self.ranked_segs.len() < (self.index + 8)
+        {
+            self.last_update_time = now;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn rerank(&mut self, segments: &[SegmentStats]) {
+        let mut ids: Vec<NonZeroU32> = segments.iter().map(|s| s.id()).collect();
+        let compare = match self.rank {
+            Rank::Fifo => compare_fifo,
+            Rank::Cte => compare_cte,
+            Rank::Util => compare_util,
+        };
+        ids.sort_by(|a, b| {
+            compare(
+                &segments[a.get() as usize - 1],
+                &segments[b.get() as usize - 1],
+            )
+        });
+        self.ranked_segs = ids;
+        self.index = 0;
+    }
+}
+
+impl EvictionPolicy for Ranked {
+    fn select(&mut self, segments: &[SegmentStats]) -> Option<NonZeroU32> {
+        if self.should_rerank() {
+            self.rerank(segments);
+        }
+        let index = self.index;
+        self.index += 1;
+        self.ranked_segs.get(index).copied()
+    }
+}
+
+fn compare_fifo(lhs: &SegmentStats, rhs: &SegmentStats) -> Ordering {
+    if !lhs.can_evict() {
+        Ordering::Greater
+    } else if !rhs.can_evict() {
+        Ordering::Less
+    } else if max(lhs.0.create_at(), lhs.0.merge_at()) > max(rhs.0.create_at(), rhs.0.merge_at()) {
+        Ordering::Greater
+    } else {
+        Ordering::Less
+    }
+}
+
+fn compare_cte(lhs: &SegmentStats, rhs: &SegmentStats) -> Ordering {
+    if !lhs.can_evict() {
+        Ordering::Greater
+    } else if !rhs.can_evict() {
+        Ordering::Less
+    } else if (lhs.0.create_at() + lhs.0.ttl()) > (rhs.0.create_at() + rhs.0.ttl()) {
+        Ordering::Greater
+    } else {
+        Ordering::Less
+    }
+}
+
+fn compare_util(lhs: &SegmentStats, rhs: &SegmentStats) -> Ordering {
+    if !lhs.can_evict() {
+        Ordering::Greater
+    } else if !rhs.can_evict() {
+        Ordering::Less
+    } else if lhs.live_bytes() > rhs.live_bytes() {
+        Ordering::Greater
+    } else {
+        Ordering::Less
+    }
+}
+
+/// Evicts by merging segments, see [`Policy::Merge`].
+pub(crate) struct Merging(pub Merge);
+
+impl EvictionPolicy for Merging {
+    fn select(&mut self, _segments: &[SegmentStats]) -> Option<NonZeroU32> {
+        None
+    }
+
+    fn merge(&self) -> Option<Merge> {
+        Some(self.0)
+    }
+}
@@ -3,64 +3,50 @@
 // http://www.apache.org/licenses/LICENSE-2.0
 
 //! Eviction is used to select a segment to remove when the cache becomes full.
-//! An eviction [`Policy`] determines what data will be evicted from the cache.
+//! An [`EvictionPolicy`] determines what data will be evicted from the cache,
+//! and a [`Policy`] selects one of the built-in policies.
 
-use core::cmp::{max, Ordering};
 use core::num::NonZeroU32;
 
 use ::rand::Rng;
 
 use crate::rng;
-use crate::segments::*;
-use crate::Random;
 use crate::*;
 
+mod builtin;
 mod policy;
 
-pub use policy::Policy;
+pub use policy::{EvictionPolicy, Merge, Policy, SegmentStats};
 
-/// The `Eviction` struct is used to rank and return segments for eviction. It
-/// implements eviction strategies corresponding to the `Policy`.
+/// The `Eviction` struct holds the configured [`EvictionPolicy`] and the
+/// parameters for merge based eviction and compaction.
 pub struct Eviction {
-    policy: Policy,
-    last_update_time: Instant,
-    ranked_segs: Box<[Option<NonZeroU32>]>,
-    index: usize,
+    policy: Box<dyn EvictionPolicy>,
+    merge: Option<Merge>,
     rng: Box<Random>,
 }
 
 impl Eviction {
-    /// Creates a new `Eviction` struct which will handle up to `nseg` segments
-    /// using the specified eviction policy.
-    pub fn new(nseg: usize, policy: Policy) -> Self {
-        let mut ranked_segs = Vec::with_capacity(0);
-        ranked_segs.reserve_exact(nseg);
-        ranked_segs.resize_with(nseg, || None);
-        let ranked_segs = ranked_segs.into_boxed_slice();
+    /// Creates a new `Eviction` struct using the specified eviction policy.
+    pub fn new(policy: Box<dyn EvictionPolicy>) -> Self {
+        let merge = policy.merge();
 
         Self {
             policy,
-            last_update_time: Instant::now(),
-            ranked_segs,
-            index: 0,
+            merge,
             rng: Box::new(rng()),
         }
     }
 
+    /// Returns the merge parameters if the policy evicts by merging segments
     #[inline]
-    pub fn policy(&self) -> Policy {
-        self.policy
+    pub fn merge(&self) -> Option<Merge> {
+        self.merge
     }
 
-    /// Returns the segment id of the least valuable segment
-    pub fn least_valuable_seg(&mut self) -> Option<NonZeroU32> {
-        let index = self.index;
-        self.index += 1;
-        if index < self.ranked_segs.len() {
-            self.ranked_segs[index]
-        } else {
-            None
-        }
+    /// Asks the policy for the next segment to evict
+    pub fn select(&mut self, segments: &[SegmentStats]) -> Option<NonZeroU32> {
+        self.policy.select(segments)
     }
 
     /// Returns a random u32
@@ -69,103 +55,12 @@ impl Eviction {
         self.rng.gen()
     }
 
-    pub fn should_rerank(&mut self) -> bool {
-        let now = Instant::recent();
-        match self.policy {
-            Policy::None | Policy::Random | Policy::RandomFifo | Policy::Merge { .. } => false,
-            Policy::Fifo | Policy::Cte | Policy::Util => {
-                if self.ranked_segs[0].is_none()
-                    || (now - self.last_update_time).as_secs() > 1
-                    || self.ranked_segs.len() < (self.index + 8)
-                {
-                    self.last_update_time = now;
-                    true
-                } else {
-                    false
-                }
-            }
-        }
-    }
-
-    pub fn rerank(&mut self, headers: &[SegmentHeader]) {
-        let mut ids: Vec<NonZeroU32> = headers.iter().map(|h| h.id()).collect();
-        match self.policy {
-            Policy::None | Policy::Random | Policy::RandomFifo | Policy::Merge { .. } => {
-                return;
-            }
-            Policy::Fifo { .. } => {
-                ids.sort_by(|a, b| {
-                    Self::compare_fifo(
-                        &headers[a.get() as usize - 1],
-                        &headers[b.get() as usize - 1],
-                    )
-                });
-            }
-            Policy::Cte { .. } => {
-                ids.sort_by(|a, b| {
-                    Self::compare_cte(
-                        &headers[a.get() as usize - 1],
-                        &headers[b.get() as usize - 1],
-                    )
-                });
-            }
-            Policy::Util { .. } => {
-                ids.sort_by(|a, b| {
-                    Self::compare_util(
-                        &headers[a.get() as usize - 1],
-                        &headers[b.get() as usize - 1],
-                    )
-                });
-            }
-        }
-        for (i, id) in self.ranked_segs.iter_mut().enumerate() {
-            *id = Some(ids[i]);
-        }
-        self.index = 0;
-    }
-
-    fn compare_fifo(lhs: &SegmentHeader, rhs: &SegmentHeader) -> Ordering {
-        if !lhs.can_evict() {
-            Ordering::Greater
-        } else if !rhs.can_evict() {
-            Ordering::Less
-        } else if max(lhs.create_at(), lhs.merge_at()) > max(rhs.create_at(), rhs.merge_at()) {
-            Ordering::Greater
-        } else {
-            Ordering::Less
-        }
-    }
-
-    fn compare_cte(lhs: &SegmentHeader, rhs: &SegmentHeader) -> Ordering {
-        if !lhs.can_evict() {
-            Ordering::Greater
-        } else if !rhs.can_evict() {
-            Ordering::Less
-        } else if (lhs.create_at() + lhs.ttl()) > (rhs.create_at() + rhs.ttl()) {
-            Ordering::Greater
-        } else {
-            Ordering::Less
-        }
-    }
-
-    fn compare_util(lhs: &SegmentHeader, rhs: &SegmentHeader) -> Ordering {
-        if !lhs.can_evict() {
-            Ordering::Greater
-        } else if !rhs.can_evict() {
-            Ordering::Less
-        } else if lhs.live_bytes() > rhs.live_bytes() {
-            Ordering::Greater
-        } else {
-            Ordering::Less
-        }
-    }
-
     #[inline]
     /// Returns the maximum number of segments which can be merged during a
     /// single merge operation. Applies to both eviction and compaction merge
     /// passes.
     pub fn max_merge(&self) -> usize {
-        if let Policy::Merge { max, .. } = self.policy {
+        if let Some(Merge { max, .. }) = self.merge {
             max
         } else {
             8
@@ -176,7 +71,7 @@ impl Eviction {
     /// Returns the number of segments which should be combined during an
     /// eviction merge.
     pub fn n_merge(&self) -> usize {
-        if let Policy::Merge { merge, .. } = self.policy {
+        if let Some(Merge { merge, .. }) = self.merge {
             merge
         } else {
             4
@@ -187,7 +82,7 @@ impl Eviction {
     /// Returns the number of segments which should be combined during a
     /// compaction merge.
     pub fn n_compact(&self) -> usize {
-        if let Policy::Merge { compact, .. } = self.policy {
+        if let Some(Merge { compact, .. }) = self.merge {
             compact
         } else {
             2
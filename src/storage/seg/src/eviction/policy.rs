@@ -2,8 +2,13 @@
 // Licensed under the Apache License, Version 2.0
 // http://www.apache.org/licenses/LICENSE-2.0
 
+use crate::segments::SegmentHeader;
+use crate::*;
+use core::num::NonZeroU32;
+
 /// Policies define the eviction strategy to be used. All eviction strategies
-/// exclude segments which are currently accepting new items.
+/// exclude segments which are currently accepting new items. Each policy is a
+/// built-in implementation of [`EvictionPolicy`].
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Policy {
     /// No eviction. When all the segments are full, inserts will fail until
@@ -60,3 +65,167 @@ pub enum Policy {
         compact: usize,
     },
 }
+
+/// A strategy for choosing which segment to evict when the cache is full. This
+/// allows for segment selection strategies beyond the built-in [`Policy`]
+/// variants. A boxed implementation, or any type implementing this trait, can
+/// be provided to `Seg::builder().eviction(...)`.
+///
+/// ```
+/// use core::num::NonZeroU32;
+/// use seg::{EvictionPolicy, Seg, SegmentStats};
+///
+/// // evicts the segment with the fewest live items
+/// struct FewestItems;
+///
+/// impl EvictionPolicy for FewestItems {
+///     fn select(&mut self, segments: &[SegmentStats]) -> Option<NonZeroU32> {
+///         segments
+///             .iter()
+///             .filter(|s| s.can_evict())
+///             .min_by_key(|s| s.live_items())
+///             .map(|s| s.id())
+///     }
+/// }
+///
+/// let cache = Seg::builder().eviction(FewestItems).build();
+/// ```
+pub trait EvictionPolicy: Send {
+    /// Returns the id of the next segment to evict, or `None` if there is no
+    /// segment to evict. The stats for segment `id` are at index `id - 1`. A
+    /// segment is only evicted if it can be evicted, otherwise `select()` is
+    /// called again.
+    fn select(&mut self, segments: &[SegmentStats]) -> Option<NonZeroU32>;
+
+    /// Returns the merge parameters for policies which evict by merging
+    /// segments within each TTL bucket rather than by selecting a segment to
+    /// evict, in which case `select()` is never called.
+    fn merge(&self) -> Option<Merge> {
+        None
+    }
+}
+
+impl<T: EvictionPolicy + 'static> From<T> for Box<dyn EvictionPolicy> {
+    fn from(policy: T) -> Self {
+        Box::new(policy)
+    }
+}
+
+impl From<Policy> for Box<dyn EvictionPolicy> {
+    fn from(policy: Policy) -> Self {
+        match policy {
+            Policy::None => Box::new(super::builtin::NoEviction),
+            Policy::Random => Box::new(super::builtin::Random::new()),
+            Policy::RandomFifo => Box::new(super::builtin::RandomFifo::new()),
+            Policy::Fifo => Box::new(super::builtin::Ranked::new(super::builtin::Rank::Fifo)),
+            Policy::Cte => Box::new(super::builtin::Ranked::new(super::builtin::Rank::Cte)),
+            Policy::Util => Box::new(super::builtin::Ranked::new(super::builtin::Rank::Util)),
+            Policy::Merge {
+                max,
+                merge,
+                compact,
+            } => Box::new(super::builtin::Merging(Merge {
+                max,
+                merge,
+                compact,
+            })),
+        }
+    }
+}
+
+/// The parameters for merge based eviction, see [`Policy::Merge`] for details.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Merge {
+    pub max: usize,
+    pub merge: usize,
+    pub compact: usize,
+}
+
+/// A read-only view of the header of a segment, which is used by an
+/// [`EvictionPolicy`] to select a segment for eviction.
+#[repr(transparent)]
+pub struct SegmentStats(pub(crate) SegmentHeader);
+
+impl SegmentStats {
+    pub(crate) fn from_headers(headers: &[SegmentHeader]) -> &[SegmentStats] {
+        // safety: `SegmentStats` is a transparent wrapper around the header
+        unsafe { &*(headers as *const [SegmentHeader] as *const [SegmentStats]) }
+    }
+
+    /// The id of the segment
+    pub fn id(&self) -> NonZeroU32 {
+        self.0.id()
+    }
+
+    /// Returns true if the segment holds items and may be evicted. Segments
+    /// which are still accepting new items, or which have only recently been
+    /// created, cannot be evicted.
+    pub fn can_evict(&self) -> bool {
+        self.0.can_evict()
+    }
+
+    /// Returns true if the segment holds items, false if it is free
+    pub fn accessible(&self) -> bool {
+        self.0.accessible()
+    }
+
+    /// The time since the segment was taken from the free queue
+    pub fn age(&self) -> std::time::Duration {
+        std::time::Duration::from_secs((clock::recent() - self.0.create_at()).as_secs() as u64)
+    }
+
+    /// The time since the segment was last the target of a merge
+    pub fn merge_age(&self) -> std::time::Duration {
+        std::time::Duration::from_secs((clock::recent() - self.0.merge_at()).as_secs() as u64)
+    }
+
+    /// The TTL of the items held in the segment
+    pub fn ttl(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.0.ttl().as_secs() as u64)
+    }
+
+    /// The namespace of the items held in the segment, where 0 is the default
+    /// namespace and the configured namespaces are numbered from 1 in order
+    pub fn namespace(&self) -> u16 {
+        self.0.namespace()
+    }
+
+    /// The time until the segment expires
+    pub fn expires_in(&self) -> std::time::Duration {
+        let expire_at = self.0.create_at() + self.0.ttl();
+        let now = clock::recent();
+        if expire_at > now {
+            std::time::Duration::from_secs((expire_at - now).as_secs() as u64)
+        } else {
+            std::time::Duration::ZERO
+        }
+    }
+
+    /// The number of bytes held by live items in the segment
+    pub fn live_bytes(&self) -> usize {
+        self.0.live_bytes().max(0) as usize
+    }
+
+    /// The number of live items in the segment
+    pub fn live_items(&self) -> usize {
+        self.0.live_items().max(0) as usize
+    }
+
+    /// The number of bytes which have been written into the segment, including
+    /// items which have since been removed
+    pub fn write_offset(&self) -> usize {
+        self.0.write_offset().max(0) as usize
+    }
+
+    /// The previous segment in the same TTL bucket, `None` if this segment is
+    /// the oldest in the bucket
+    pub fn prev(&self) -> Option<NonZeroU32> {
+        self.0.prev_seg()
+    }
+
+    /// The next segment in the same TTL bucket, `None` if this segment is the
+    /// newest in the bucket
+    pub fn next(&self) -> Option<NonZeroU32> {
+        self.0.next_seg()
+    }
+}
@@ -64,6 +64,16 @@
 //! This works out so that we have capacity to store 7 items for every bucket
 //! allocated to a chain.
 //!
+//! If a maximum power larger than the initial power is provided, the
+//! [`HashTable`] will grow to twice the number of buckets when an insert fails,
+//! when a chain is near the maximum length, or when most of the overflow
+//! buckets have been chained. Growing allocates the new buckets and keeps the
+//! previous buckets until all of their items have been moved. The bucket for a
+//! key is moved before the key is accessed, and a few more buckets are moved
+//! on each insert, so that growing never blocks for long. Lookups which happen
+//! while items are being moved, such as those made when evicting items, check
+//! both sets of buckets.
+//!
 
 // hashtable
 
@@ -73,6 +83,17 @@ const N_BUCKET_SLOT: usize = 8;
 /// Maximum number of buckets in a chain. Must be <= 255.
 const MAX_CHAIN_LEN: u64 = 16;
 
+/// The hashtable grows once a chain is within this many buckets of the maximum
+/// chain length.
+const GROW_CHAIN_MARGIN: u64 = 2;
+
+/// Number of buckets moved on each insert while the hashtable is growing.
+const MIGRATE_STEP: usize = 8;
+
+/// Number of buckets allocated for the next, larger hashtable on each insert
+/// until it is ready.
+const PREPARE_STEP: usize = 8;
+
 use crate::*;
 use ahash::RandomState;
 use core::marker::PhantomData;
@@ -93,10 +114,9 @@ struct IterState {
 }
 
 impl IterState {
-    fn new(hashtable: &HashTable, hash: u64) -> Self {
-        let bucket_id = (hash & hashtable.mask) as usize;
-        let buckets_len = hashtable.data.len();
-        let bucket = hashtable.data[bucket_id];
+    fn new(data: &[HashBucket], bucket_id: usize) -> Self {
+        let buckets_len = data.len();
+        let bucket = data[bucket_id];
         let chain_len = chain_len(bucket.data[0]) as usize;
 
         Self {
@@ -132,10 +152,10 @@ struct IterMut<'a> {
 }
 
 impl<'a> IterMut<'a> {
-    fn new(hashtable: &'a mut HashTable, hash: u64) -> Self {
-        let state = IterState::new(hashtable, hash);
+    fn new(data: &'a mut [HashBucket], bucket_id: usize) -> Self {
+        let state = IterState::new(data, bucket_id);
 
-        let ptr = hashtable.data.as_mut_ptr();
+        let ptr = data.as_mut_ptr();
 
         Self {
             ptr,
@@ -187,24 +207,61 @@ impl<'a> Iterator for IterMut<'a> {
     }
 }
 
+/// The buckets of a hashtable which is growing. Items are moved from these
+/// buckets into the new buckets as keys are accessed and on each insert.
+struct Resize {
+    data: Box<[HashBucket]>,
+    mask: u64,
+    /// The next primary bucket to be moved on insert
+    next: u64,
+    /// A bit for each primary bucket, which is set once it has been moved
+    migrated: Box<[u64]>,
+}
+
+impl Resize {
+    fn is_migrated(&self, bucket_id: usize) -> bool {
+        self.migrated[bucket_id / 64] & (1 << (bucket_id % 64)) != 0
+    }
+
+    fn set_migrated(&mut self, bucket_id: usize) {
+        self.migrated[bucket_id / 64] |= 1 << (bucket_id % 64);
+    }
+}
+
+/// The state used to grow a hashtable, which is kept out of line so that the
+/// hashtable fits within a single cache line.
+#[derive(Default)]
+struct Growth {
+    /// The previous buckets while the hashtable is growing
+    resize: Option<Resize>,
+    /// Buckets for the next, larger hashtable. These are allocated a few at a
+    /// time ahead of growing, so that growing does not allocate the whole
+    /// hashtable within a single insert.
+    spare: Vec<HashBucket>,
+}
+
 /// Main structure for performing item lookup. Contains a contiguous allocation
 /// of [`HashBucket`]s which are used to store item info and metadata.
 #[repr(C)]
 pub(crate) struct HashTable {
     hash_builder: Box<RandomState>,
-    power: u64,
     mask: u64,
     data: Box<[HashBucket]>,
     started: Instant,
+    power: u8,
+    max_power: u8,
     next_to_chain: u64,
+    growth: Box<Growth>,
     _pad: [u8; 8],
 }
 
 impl HashTable {
     /// Creates a new hashtable with a specified power and overflow factor. The
     /// hashtable will have the capacity to store up to
-    /// `7 * 2^(power - 3) * (1 + overflow_factor)` items.
-    pub fn new(power: u8, overflow_factor: f64) -> HashTable {
+    /// `7 * 2^(power - 3) * (1 + overflow_factor)` items. If the max power is
+    /// larger than the power, the hashtable may grow until it reaches the max
+    /// power.
+    pub fn new(power: u8, max_power: u8, overflow_factor: f64) -> HashTable {
         if overflow_factor < 0.0 {
             fatal!("hashtable overflow factor must be >= 0.0");
         }
@@ -235,20 +292,66 @@ impl HashTable {
             0x4feb29c1fbbd59d0,
         );
 
+        HASH_POWER.set(power as _);
+
         Self {
             hash_builder: Box::new(hash_builder),
-            power: power.into(),
             mask,
             data: data.into_boxed_slice(),
-            started: Instant::now(),
+            started: clock::now(),
+            power,
+            max_power: std::cmp::max(power, max_power),
             next_to_chain: buckets as u64,
+            growth: Box::default(),
             _pad: [0; 8],
         }
     }
 
+    /// Returns the current power of the hashtable
+    pub fn power(&self) -> u8 {
+        self.power
+    }
+
+    /// Returns true if items are being moved into a larger hashtable
+    #[cfg(test)]
+    pub fn is_growing(&self) -> bool {
+        self.growth.resize.is_some()
+    }
+
+    /// Starts growing the hashtable, leaving no overflow buckets for the items
+    /// which are moved into the larger hashtable
+    #[cfg(test)]
+    pub fn grow_without_overflow(&mut self) -> bool {
+        let grown = self.grow();
+        self.next_to_chain = self.data.len() as u64;
+        grown
+    }
+
+    /// Returns true if the hashtable may grow, which replaces the buckets
+    pub fn can_grow(&self) -> bool {
+        self.growth.resize.is_some() || self.power < self.max_power
+    }
+
+    /// Returns a read-only view of the buckets, see [`BucketsView`].
+    pub(crate) fn view(&self) -> BucketsView {
+        BucketsView {
+            hash_builder: (*self.hash_builder).clone(),
+            ptr: self.data.as_ptr(),
+            len: self.data.len(),
+            mask: self.mask,
+        }
+    }
+
     /// Lookup an item by key and return it
-    pub fn get(&mut self, key: &[u8], time: Instant, segments: &mut Segments) -> Option<Item> {
+    pub fn get(
+        &mut self,
+        key: &[u8],
+        time: Instant,
+        ttl_buckets: &mut TtlBuckets,
+        segments: &mut Segments,
+    ) -> Option<Item> {
         let hash = self.hash(key);
+        self.migrate_hash(hash, ttl_buckets, segments);
         let tag = tag_from_hash(hash);
         let bucket_id = hash & self.mask;
 
@@ -259,13 +362,13 @@ impl HashTable {
         if curr_ts != get_ts(bucket_info) as u32 {
             self.data[bucket_id as usize].data[0] = (bucket_info & !TS_MASK) | (curr_ts as u64);
 
-            let iter = IterMut::new(self, hash);
+            let iter = self.iter_mut(hash);
             for item_info in iter {
                 *item_info &= CLEAR_FREQ_SMOOTH_MASK;
             }
         }
 
-        let iter = IterMut::new(self, hash);
+        let iter = self.iter_mut(hash);
 
         for item_info in iter {
             if get_tag(*item_info) == tag {
@@ -285,9 +388,14 @@ impl HashTable {
                         *item_info = (*item_info & !FREQ_MASK) | freq;
                     }
 
+                    let item_info = *item_info;
+                    let (create_at, expire_at) = segments.item_lifetime(item_info)?;
                     let item = Item::new(
                         current_item,
                         get_cas(self.data[(hash & self.mask) as usize].data[0]),
+                        create_at,
+                        expire_at,
+                        (get_freq(item_info) & 0x7F) as u8,
                     );
                     item.check_magic();
 
@@ -302,10 +410,16 @@ impl HashTable {
     /// Lookup an item by key and return it without incrementing the item
     /// frequency. This may be used to compose higher-level functions which do
     /// not want a successful item lookup to count as a hit for that item.
-    pub fn get_no_freq_incr(&mut self, key: &[u8], segments: &mut Segments) -> Option<Item> {
+    pub fn get_no_freq_incr(
+        &mut self,
+        key: &[u8],
+        ttl_buckets: &mut TtlBuckets,
+        segments: &mut Segments,
+    ) -> Option<Item> {
         let hash = self.hash(key);
+        self.migrate_hash(hash, ttl_buckets, segments);
 
-        let iter = IterMut::new(self, hash);
+        let iter = self.iter_mut(hash);
 
         let tag = tag_from_hash(hash);
 
@@ -315,9 +429,14 @@ impl HashTable {
                 if current_item.key() != key {
                     HASH_TAG_COLLISION.increment();
                 } else {
+                    let item_info = *item_info;
+                    let (create_at, expire_at) = segments.item_lifetime(item_info)?;
                     let item = Item::new(
                         current_item,
                         get_cas(self.data[(hash & self.mask) as usize].data[0]),
+                        create_at,
+                        expire_at,
+                        (get_freq(item_info) & 0x7F) as u8,
                     );
                     item.check_magic();
 
@@ -329,12 +448,41 @@ impl HashTable {
         None
     }
 
+    /// Lookup the item info for the item with the key. This may be used to
+    /// locate the segment which currently holds the item.
+    pub(crate) fn get_item_info(
+        &mut self,
+        key: &[u8],
+        ttl_buckets: &mut TtlBuckets,
+        segments: &mut Segments,
+    ) -> Option<u64> {
+        let hash = self.hash(key);
+        self.migrate_hash(hash, ttl_buckets, segments);
+
+        let iter = self.iter_mut(hash);
+
+        let tag = tag_from_hash(hash);
+
+        for item_info in iter {
+            if get_tag(*item_info) == tag {
+                let current_item = segments.get_item(*item_info).unwrap();
+                if current_item.key() != key {
+                    HASH_TAG_COLLISION.increment();
+                } else {
+                    return Some(*item_info);
+                }
+            }
+        }
+
+        None
+    }
+
     /// Return the frequency for the item with the key
     pub fn get_freq(&mut self, key: &[u8], segment: &mut Segment, offset: u64) -> Option<u64> {
         let hash = self.hash(key);
         let tag = tag_from_hash(hash);
 
-        let iter = IterMut::new(self, hash);
+        let iter = self.iter_all(hash);
 
         for item_info in iter {
             if get_tag(*item_info) == tag
@@ -361,7 +509,7 @@ impl HashTable {
         let hash = self.hash(key);
         let tag = tag_from_hash(hash);
 
-        let iter = IterMut::new(self, hash);
+        let iter = self.iter_all(hash);
 
         for item_info in iter {
             if get_tag(*item_info) == tag {
@@ -381,7 +529,7 @@ impl HashTable {
     pub(crate) fn is_item_at(&mut self, key: &[u8], seg: NonZeroU32, offset: u64) -> bool {
         let hash = self.hash(key);
         let tag = tag_from_hash(hash);
-        let iter = IterMut::new(self, hash);
+        let iter = self.iter_all(hash);
 
         for item_info in iter {
             if get_tag(*item_info) == tag {
@@ -412,6 +560,13 @@ impl HashTable {
         let hash = self.hash(item.key());
         let tag = tag_from_hash(hash);
 
+        self.migrate_hash(hash, ttl_buckets, segments);
+        self.migrate(MIGRATE_STEP, ttl_buckets, segments);
+        self.prepare(PREPARE_STEP);
+        if self.should_grow(hash) && self.grow() {
+            self.migrate_hash(hash, ttl_buckets, segments);
+        }
+
         // check the item magic
         item.check_magic();
 
@@ -419,7 +574,7 @@ impl HashTable {
 
         let mut removed: Option<u64> = None;
 
-        let iter = IterMut::new(self, hash);
+        let iter = self.iter_mut(hash);
 
         for item_info in iter {
             if get_tag(*item_info) != tag {
@@ -446,26 +601,15 @@ impl HashTable {
             let _ = segments.remove_item(removed_item, ttl_buckets, self);
         }
 
-        if insert_item_info != 0 {
-            let mut bucket_id = (hash & self.mask) as usize;
-            let chain_len = chain_len(self.data[bucket_id].data[0]);
-
-            if chain_len < MAX_CHAIN_LEN && (self.next_to_chain as usize) < self.data.len() {
-                // we need to chase through the buckets to get the id of the last
-                // bucket in the chain
-                for _ in 0..chain_len {
-                    bucket_id = self.data[bucket_id].data[N_BUCKET_SLOT - 1] as usize;
-                }
-
-                let next_id = self.next_to_chain as usize;
-                self.next_to_chain += 1;
+        if insert_item_info != 0 && self.chain(hash, insert_item_info) {
+            insert_item_info = 0;
+        }
 
-                self.data[next_id].data[0] = self.data[bucket_id].data[N_BUCKET_SLOT - 1];
-                self.data[next_id].data[1] = insert_item_info;
+        // if the chain is full, grow the hashtable and try again
+        if insert_item_info != 0 && self.grow() {
+            self.migrate_hash(hash, ttl_buckets, segments);
+            if self.place(hash, insert_item_info) {
                 insert_item_info = 0;
-                self.data[bucket_id].data[N_BUCKET_SLOT - 1] = next_id as u64;
-
-                self.data[(hash & self.mask) as usize].data[0] += 0x0000_0000_0001_0000;
             }
         }
 
@@ -482,21 +626,25 @@ impl HashTable {
     /// item by key and checks if the CAS value matches the provided value.
     ///
     /// A success indicates that the item was found with the CAS value provided
-    /// and that the CAS value has now been updated to a new value.
+    /// and that the CAS value has now been updated to a new value. Items which
+    /// are stored with their own CAS value are compared against that value
+    /// instead of the CAS value of the bucket.
     ///
     /// A failure indicates that the CAS value did not match or there was no
     /// matching item for that key.
     pub fn try_update_cas<'a>(
         &mut self,
         key: &'a [u8],
-        cas: u32,
+        cas: u64,
+        ttl_buckets: &mut TtlBuckets,
         segments: &mut Segments,
     ) -> Result<(), SegError> {
         let hash = self.hash(key);
+        self.migrate_hash(hash, ttl_buckets, segments);
         let tag = tag_from_hash(hash);
         let bucket_id = hash & self.mask;
 
-        let iter = IterMut::new(self, hash);
+        let iter = self.iter_mut(hash);
 
         for item_info in iter {
             if get_tag(*item_info) == tag {
@@ -516,7 +664,10 @@ impl HashTable {
                         *item_info = (*item_info & !FREQ_MASK) | freq;
                     }
 
-                    if cas == get_cas(self.data[bucket_id as usize].data[0]) {
+                    let current = item
+                        .cas()
+                        .unwrap_or(get_cas(self.data[bucket_id as usize].data[0]) as u64);
+                    if cas == current {
                         self.data[bucket_id as usize].data[0] += 1 << CAS_BIT_SHIFT;
                         return Ok(());
                     } else {
@@ -537,9 +688,10 @@ impl HashTable {
         segments: &mut Segments,
     ) -> bool {
         let hash = self.hash(key);
+        self.migrate_hash(hash, ttl_buckets, segments);
         let tag = tag_from_hash(hash);
 
-        let iter = IterMut::new(self, hash);
+        let iter = self.iter_mut(hash);
 
         let mut removed: Option<u64> = None;
 
@@ -591,7 +743,7 @@ impl HashTable {
         let tag = tag_from_hash(hash);
         let evict_item_info = build_item_info(tag, segment.id(), offset as u64);
 
-        let iter = IterMut::new(self, hash);
+        let iter = self.iter_all(hash);
 
         for item_info in iter {
             let current_item_info = clear_freq(*item_info);
@@ -616,11 +768,349 @@ impl HashTable {
         false
     }
 
+    /// Moves up to the provided number of buckets into the larger hashtable
+    /// while the hashtable is growing, otherwise allocates up to the provided
+    /// number of buckets for the next, larger hashtable. Returns true if there
+    /// are buckets which remain to be moved.
+    pub fn migrate(
+        &mut self,
+        buckets: usize,
+        ttl_buckets: &mut TtlBuckets,
+        segments: &mut Segments,
+    ) -> bool {
+        if self.growth.resize.is_none() {
+            self.prepare(buckets);
+            return false;
+        }
+
+        for _ in 0..buckets {
+            let (bucket_id, done) = match &mut self.growth.resize {
+                Some(resize) => {
+                    let bucket_id = resize.next as usize;
+                    resize.next += 1;
+                    (bucket_id, resize.next > resize.mask)
+                }
+                None => return false,
+            };
+
+            self.migrate_bucket(bucket_id, ttl_buckets, segments);
+
+            if done {
+                self.growth.resize = None;
+                return false;
+            }
+        }
+        self.growth.resize.is_some()
+    }
+
+    /// Calls the function with the item info of every item in the hashtable,
+    /// including the items in buckets which have not yet been moved into the
+    /// larger hashtable.
+    pub(crate) fn for_each_item(&mut self, mut f: impl FnMut(u64)) {
+        if let Some(resize) = &mut self.growth.resize {
+            for bucket_id in 0..=resize.mask as usize {
+                if resize.is_migrated(bucket_id) {
+                    continue;
+                }
+                for item_info in IterMut::new(&mut resize.data, bucket_id) {
+                    if *item_info != 0 {
+                        f(*item_info);
+                    }
+                }
+            }
+        }
+        for bucket_id in 0..=self.mask as usize {
+            for item_info in IterMut::new(&mut self.data, bucket_id) {
+                if *item_info != 0 {
+                    f(*item_info);
+                }
+            }
+        }
+    }
+
+    /// Links the item info for a key which is being restored from a
+    /// checkpoint. Returns false if the item info does not belong to the key
+    /// or there is no room for it.
+    pub(crate) fn restore(&mut self, key: &[u8], item_info: u64) -> bool {
+        let hash = self.hash(key);
+        get_tag(item_info) == tag_from_hash(hash) && self.place(hash, item_info)
+    }
+
+    /// Returns an empty hashtable with the same configuration and the
+    /// provided power, which is limited to the max power
+    pub(crate) fn with_power(&self, power: u8) -> HashTable {
+        let primary = (self.mask + 1) as f64;
+        let overflow_factor = self.data.len() as f64 / primary - 1.0;
+        let power = std::cmp::min(power, self.max_power);
+        HashTable::new(power, self.max_power, overflow_factor)
+    }
+
+    /// Returns an iterator over the item info slots in the bucket chain for
+    /// the hash
+    fn iter_mut(&mut self, hash: u64) -> IterMut<'_> {
+        IterMut::new(&mut self.data, (hash & self.mask) as usize)
+    }
+
+    /// Returns an iterator over the item info slots in the bucket chain for
+    /// the hash, including the chain in the previous buckets if the hashtable
+    /// is growing and the bucket has not yet been moved
+    fn iter_all(&mut self, hash: u64) -> impl Iterator<Item = &mut u64> + '_ {
+        let previous = match &mut self.growth.resize {
+            Some(resize) if !resize.is_migrated((hash & resize.mask) as usize) => {
+                let bucket_id = (hash & resize.mask) as usize;
+                Some(IterMut::new(&mut resize.data, bucket_id))
+            }
+            _ => None,
+        };
+        previous
+            .into_iter()
+            .flatten()
+            .chain(IterMut::new(&mut self.data, (hash & self.mask) as usize))
+    }
+
+    /// Stores the item info in an empty slot in the bucket chain for the hash,
+    /// chaining another bucket if there are no empty slots. Returns false if
+    /// the item info could not be stored.
+    fn place(&mut self, hash: u64, item_info: u64) -> bool {
+        for slot in self.iter_mut(hash) {
+            if *slot == 0 {
+                *slot = item_info;
+                return true;
+            }
+        }
+        self.chain(hash, item_info)
+    }
+
+    /// Chains an overflow bucket onto the end of the bucket chain for the hash
+    /// and stores the item info in it. Returns false if the chain is at the
+    /// maximum length or there are no more overflow buckets.
+    fn chain(&mut self, hash: u64, item_info: u64) -> bool {
+        let mut bucket_id = (hash & self.mask) as usize;
+        let chain_len = chain_len(self.data[bucket_id].data[0]);
+
+        if chain_len >= MAX_CHAIN_LEN || (self.next_to_chain as usize) >= self.data.len() {
+            return false;
+        }
+
+        // we need to chase through the buckets to get the id of the last
+        // bucket in the chain
+        for _ in 0..chain_len {
+            bucket_id = self.data[bucket_id].data[N_BUCKET_SLOT - 1] as usize;
+        }
+
+        let next_id = self.next_to_chain as usize;
+        self.next_to_chain += 1;
+
+        self.data[next_id].data[0] = self.data[bucket_id].data[N_BUCKET_SLOT - 1];
+        self.data[next_id].data[1] = item_info;
+        self.data[bucket_id].data[N_BUCKET_SLOT - 1] = next_id as u64;
+
+        self.data[(hash & self.mask) as usize].data[0] += 0x0000_0000_0001_0000;
+        true
+    }
+
+    /// Returns true if the chain for the hash is near the maximum length or
+    /// most of the overflow buckets have been chained
+    fn should_grow(&self, hash: u64) -> bool {
+        if self.growth.resize.is_some() || self.power >= self.max_power {
+            return false;
+        }
+
+        let chain_len = chain_len(self.data[(hash & self.mask) as usize].data[0]);
+        let primary = self.mask + 1;
+        let overflow = self.data.len() as u64 - primary;
+        let chained = self.next_to_chain - primary;
+
+        chain_len + GROW_CHAIN_MARGIN >= MAX_CHAIN_LEN
+            || (overflow > 0 && chained * 8 >= overflow * 7)
+    }
+
+    /// Allocates up to the provided number of buckets for the next, larger
+    /// hashtable, unless the hashtable is growing or is at the max power.
+    fn prepare(&mut self, buckets: usize) {
+        if self.growth.resize.is_some() || self.power >= self.max_power {
+            return;
+        }
+
+        let total_buckets = self.data.len() * 2;
+        let spare = &mut self.growth.spare;
+        if spare.capacity() < total_buckets {
+            spare.reserve_exact(total_buckets - spare.len());
+        }
+        let len = std::cmp::min(total_buckets, spare.len() + buckets);
+        spare.resize(len, HashBucket::new());
+    }
+
+    /// Starts growing the hashtable to twice the number of buckets. Returns
+    /// false if the hashtable is already growing or is at the max power.
+    fn grow(&mut self) -> bool {
+        if self.growth.resize.is_some() || self.power >= self.max_power {
+            return false;
+        }
+
+        let primary = self.mask + 1;
+        let total_buckets = self.data.len() * 2;
+
+        // any buckets which were not allocated ahead of time are allocated now
+        self.prepare(total_buckets);
+        let data = std::mem::take(&mut self.growth.spare);
+
+        let previous = std::mem::replace(&mut self.data, data.into_boxed_slice());
+        self.growth.resize = Some(Resize {
+            data: previous,
+            mask: self.mask,
+            next: 0,
+            migrated: vec![0; (primary as usize).div_ceil(64)].into_boxed_slice(),
+        });
+
+        self.power += 1;
+        self.mask = primary * 2 - 1;
+        self.next_to_chain = primary * 2;
+
+        debug!(
+            "growing hashtable to {} primary buckets and {} total buckets",
+            primary * 2,
+            total_buckets
+        );
+        HASH_GROW.increment();
+        HASH_POWER.set(self.power as _);
+        true
+    }
+
+    /// Moves the bucket for the hash into the larger hashtable if the
+    /// hashtable is growing. This must be done before the bucket chain for the
+    /// hash is used to insert items or update the bucket info.
+    fn migrate_hash(&mut self, hash: u64, ttl_buckets: &mut TtlBuckets, segments: &mut Segments) {
+        if let Some(resize) = &self.growth.resize {
+            self.migrate_bucket((hash & resize.mask) as usize, ttl_buckets, segments);
+        }
+    }
+
+    /// Moves the items in a primary bucket of the previous hashtable, and the
+    /// buckets chained to it, into the buckets of the larger hashtable.
+    fn migrate_bucket(
+        &mut self,
+        bucket_id: usize,
+        ttl_buckets: &mut TtlBuckets,
+        segments: &mut Segments,
+    ) {
+        let resize = match &mut self.growth.resize {
+            Some(resize) if !resize.is_migrated(bucket_id) => resize,
+            _ => return,
+        };
+        resize.set_migrated(bucket_id);
+
+        let mut items = [0; N_BUCKET_SLOT * (MAX_CHAIN_LEN as usize + 1)];
+        let mut count = 0;
+        for item_info in IterMut::new(&mut resize.data, bucket_id) {
+            if *item_info != 0 {
+                items[count] = *item_info;
+                count += 1;
+            }
+        }
+
+        // the items are split between two buckets which share the cas value
+        // and timestamp of the previous bucket
+        let bucket_info = resize.data[bucket_id].data[0] & !BUCKET_CHAIN_LEN_MASK;
+        let high = bucket_id + resize.mask as usize + 1;
+        resize.data[bucket_id] = HashBucket::new();
+        self.data[bucket_id].data[0] = bucket_info;
+        self.data[high].data[0] = bucket_info;
+
+        let mut dropped = 0;
+        for idx in 0..count {
+            let item_info = items[idx];
+            let hash = match segments.get_item(item_info) {
+                Some(item) => self.hash(item.key()),
+                None => continue,
+            };
+            if !self.place(hash, item_info) {
+                items[dropped] = item_info;
+                dropped += 1;
+            }
+        }
+        HASH_MIGRATE.add(count as _);
+
+        // there is no room for these items in the larger hashtable, so they
+        // are removed the same way as deleted items once the bucket is moved
+        for item_info in &items[..dropped] {
+            HASH_INSERT_EX.increment();
+            let _ = segments.remove_item(*item_info, ttl_buckets, self);
+        }
+    }
+
     /// Internal function used to calculate a hash value for a key
-    fn hash(&self, key: &[u8]) -> u64 {
+    pub(crate) fn hash(&self, key: &[u8]) -> u64 {
         HASH_LOOKUP.increment();
         let mut hasher = self.hash_builder.build_hasher();
         hasher.write(key);
         hasher.finish()
     }
 }
+
+/// A read-only view of the buckets of a [`HashTable`] which may be used from
+/// other threads while the hashtable is being modified. The bucket memory is
+/// never freed as long as the hashtable does not grow, but the item info read
+/// through this view may be inconsistent if the hashtable was modified during
+/// the read. Callers must validate the result, see `crate::shared`.
+#[derive(Clone)]
+pub(crate) struct BucketsView {
+    hash_builder: RandomState,
+    ptr: *const HashBucket,
+    len: usize,
+    mask: u64,
+}
+
+impl BucketsView {
+    /// Calculates the hash for a key, matching the hashtable
+    pub fn hash(&self, key: &[u8]) -> u64 {
+        HASH_LOOKUP.increment();
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write(key);
+        hasher.finish()
+    }
+
+    fn read(&self, bucket_id: usize, slot: usize) -> u64 {
+        debug_assert!(bucket_id < self.len && slot < N_BUCKET_SLOT);
+        // SAFETY: the bucket id is checked by the caller and the buckets live
+        // as long as the hashtable, which outlives this view. The read may race
+        // with a write, so it is volatile.
+        unsafe { std::ptr::read_volatile(&(*self.ptr.add(bucket_id)).data[slot]) }
+    }
+
+    /// Calls `f` with the item info of each item matching the tag for the
+    /// hash, along with the CAS value of the bucket, until it returns a result.
+    pub fn find<T>(&self, hash: u64, mut f: impl FnMut(u64, u32) -> Option<T>) -> Option<T> {
+        let tag = tag_from_hash(hash);
+        let mut bucket_id = (hash & self.mask) as usize;
+        let bucket_info = self.read(bucket_id, 0);
+        let cas = get_cas(bucket_info);
+        let chain_len = chain_len(bucket_info);
+
+        for chain_idx in 0..=chain_len {
+            let first = if chain_idx == 0 { 1 } else { 0 };
+            let last = if chain_idx == chain_len {
+                N_BUCKET_SLOT
+            } else {
+                N_BUCKET_SLOT - 1
+            };
+            for slot in first..last {
+                let item_info = self.read(bucket_id, slot);
+                if item_info != 0 && get_tag(item_info) == tag {
+                    if let Some(result) = f(item_info, cas) {
+                        return Some(result);
+                    }
+                }
+            }
+            if chain_idx < chain_len {
+                bucket_id = self.read(bucket_id, N_BUCKET_SLOT - 1) as usize;
+                // a concurrent write may leave the chain inconsistent
+                if bucket_id >= self.len {
+                    return None;
+                }
+            }
+        }
+
+        None
+    }
+}
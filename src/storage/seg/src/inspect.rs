@@ -0,0 +1,211 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! A tool which checks a file-backed datapool and prints what it holds,
+//! without opening the datapool, so the files are left unchanged.
+//!
+//! The header of the datapool and its checksums are always checked. If the
+//! datapool was flushed by a checkpoint, the segment headers and hashtable are
+//! read from the checkpoint which is kept next to the datapool, see
+//! `seg::Checkpoint`, and the tool prints the utilization, TTL, and item count
+//! of each segment, a summary of the TTLs, and optionally the keys.
+//!
+//! The tool is built with the `inspect` feature:
+//!
+//! ```text
+//! cargo run --release -p seg --features inspect --bin datapool-inspect -- datapool
+//! ```
+
+use clap::{App, Arg};
+use common::time::{DateTime, SecondsFormat};
+use datapool::{Header, HEADER_SIZE};
+use memmap2::Mmap;
+use seg::Checkpoint;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Error;
+use std::time::Duration;
+
+fn main() {
+    let matches = App::new(env!("CARGO_BIN_NAME"))
+        .version(env!("CARGO_PKG_VERSION"))
+        .version_short("v")
+        .about("Verifies a datapool file and summarizes the segments it holds")
+        .arg(
+            Arg::with_name("segments")
+                .long("segments")
+                .short("s")
+                .help("Print the utilization, TTL, and item count of each segment"),
+        )
+        .arg(
+            Arg::with_name("keys")
+                .long("keys")
+                .short("k")
+                .help("Print the segment id and key of each item"),
+        )
+        .arg(
+            Arg::with_name("FILE")
+                .help("Path of the datapool file")
+                .required(true)
+                .index(1),
+        )
+        .get_matches();
+
+    let options = Options {
+        segments: matches.is_present("segments"),
+        keys: matches.is_present("keys"),
+    };
+
+    if let Err(e) = inspect(matches.value_of("FILE").unwrap(), options) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+struct Options {
+    segments: bool,
+    keys: bool,
+}
+
+/// Prints the header fields and the results of checking the magic, version,
+/// and checksums, followed by the contents of the checkpoint. Returns an error
+/// if any of the checks fail.
+fn inspect(path: &str, options: Options) -> Result<(), Error> {
+    let header = Header::read(path)?;
+
+    let file = File::open(path)?;
+    let file_size = file.metadata()?.len() as usize;
+
+    // SAFETY: the file is only read, it may still be changed by a running
+    // instance, in which case the checksum is expected to fail
+    let mmap = unsafe { Mmap::map(&file)? };
+
+    println!("file size: {}", file_size);
+    println!("data size: {}", header.data_size());
+    println!("version: {}", header.version());
+    println!("user version: {}", header.user_version());
+    println!(
+        "written at: {}",
+        DateTime::from(header.time_unix_ns()).to_rfc3339_opts(SecondsFormat::Millis, false)
+    );
+    println!("clean: {}", header.is_clean());
+
+    header.check()?;
+    println!("header: ok");
+
+    header.verify(&mmap)?;
+    println!("checksum: ok");
+
+    let checkpoint = match Checkpoint::read(path) {
+        Ok(checkpoint) => checkpoint,
+        Err(e) => {
+            println!("checkpoint: none ({})", e);
+            return Ok(());
+        }
+    };
+    println!("checkpoint: ok");
+    print_summary(&checkpoint);
+
+    if options.segments {
+        print_segments(&checkpoint);
+    }
+
+    if options.keys {
+        let data = &mmap[HEADER_SIZE..(HEADER_SIZE + header.data_size() as usize)];
+        println!();
+        println!("{:>8} key", "segment");
+        checkpoint.for_each_key(data, |id, key| {
+            println!("{:>8} {}", id, key.escape_ascii());
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Prints the totals for the cache and for each TTL
+fn print_summary(checkpoint: &Checkpoint) {
+    let segment_size = checkpoint.segment_size();
+    let segments: Vec<_> = checkpoint.segments().collect();
+    let used: Vec<_> = segments.iter().filter(|s| s.in_use()).collect();
+    let live_bytes: usize = used.iter().map(|s| s.live_bytes()).sum();
+
+    println!("segment size: {}", segment_size);
+    println!(
+        "segments: {} ({} free)",
+        segments.len(),
+        segments.len() - used.len()
+    );
+    println!("items: {}", checkpoint.items());
+    println!("live bytes: {}", live_bytes);
+    println!(
+        "utilization: {:.1}%",
+        percent(live_bytes, used.len() * segment_size)
+    );
+    for (id, name) in checkpoint.namespaces().iter().enumerate() {
+        println!("namespace {}: {}", id + 1, name.escape_ascii());
+    }
+
+    // segments, items, and live bytes for each ttl
+    let mut ttls: BTreeMap<Option<Duration>, (usize, usize, usize)> = BTreeMap::new();
+    for segment in &used {
+        let entry = ttls.entry(segment.ttl()).or_default();
+        entry.0 += 1;
+        entry.1 += segment.live_items();
+        entry.2 += segment.live_bytes();
+    }
+
+    println!();
+    println!(
+        "{:>10} {:>8} {:>10} {:>12} {:>6}",
+        "ttl", "segments", "items", "live bytes", "util"
+    );
+    for (ttl, (count, items, bytes)) in ttls {
+        println!(
+            "{:>10} {:>8} {:>10} {:>12} {:>5.1}%",
+            format_ttl(ttl),
+            count,
+            items,
+            bytes,
+            percent(bytes, count * segment_size)
+        );
+    }
+}
+
+/// Prints a line for each segment which holds items
+fn print_segments(checkpoint: &Checkpoint) {
+    let segment_size = checkpoint.segment_size();
+
+    println!();
+    println!(
+        "{:>8} {:>9} {:>10} {:>8} {:>12} {:>6} {:>10}",
+        "segment", "namespace", "ttl", "items", "live bytes", "util", "age"
+    );
+    for segment in checkpoint.segments().filter(|s| s.in_use()) {
+        println!(
+            "{:>8} {:>9} {:>10} {:>8} {:>12} {:>5.1}% {:>9}s",
+            segment.id(),
+            segment.namespace(),
+            format_ttl(segment.ttl()),
+            segment.live_items(),
+            segment.live_bytes(),
+            percent(segment.live_bytes(), segment_size),
+            segment.age().as_secs()
+        );
+    }
+}
+
+fn format_ttl(ttl: Option<Duration>) -> String {
+    match ttl {
+        Some(ttl) => format!("{}s", ttl.as_secs()),
+        None => "none".to_string(),
+    }
+}
+
+fn percent(part: usize, whole: usize) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        100.0 * part as f64 / whole as f64
+    }
+}
@@ -19,19 +19,45 @@
 //! Flags:
 //! ```text
 //! ┌──────────────┬──────────────┬──────────────────────────────┐
-//! │    TYPED?    │   PADDING    │             OLEN             │
+//! │    TYPED?    │     EXT?     │             OLEN             │
 //! │              │              │                              │
 //! │    1 bit     │    1 bit     │            6 bit             │
 //! │              │              │                              │
 //! │      64      │      65      │  66                      71  │
 //! └──────────────┴──────────────┴──────────────────────────────┘
 //! ```
+//!
+//! If the ext flag is set, the header is followed by a byte of extended flags.
+//! Items which are not large, compressed, stored with a CAS value, or tagged do
+//! not have this byte.
+//!
+//! Extended flags:
+//! ```text
+//! ┌───────────┬───────────┬───────────┬───────────┬────────────────────┐
+//! │  LARGE?   │COMPRESSED?│   CAS?    │  TAGGED?  │      PADDING       │
+//! │           │           │           │           │                    │
+//! │   1 bit   │   1 bit   │   1 bit   │   1 bit   │       4 bit        │
+//! │           │           │           │           │                    │
+//! │     0     │     1     │     2     │     3     │  4               7 │
+//! └───────────┴───────────┴───────────┴───────────┴────────────────────┘
+//! ```
+//!
+//! If the CAS flag is set, the extended flags are followed by the 64 bit CAS
+//! value of the item. Otherwise the item uses the CAS value of its hash bucket.
+//!
+//! If the tagged flag is set, the 64 bit tag of the item follows the extended
+//! flags and the CAS value, if any. This holds the slot of the tag and its
+//! generation when the item was written.
 
 // item constants
 
 /// The size of the item header in bytes
 pub const ITEM_HDR_SIZE: usize = std::mem::size_of::<crate::item::ItemHeader>();
 
+/// The size of the extended flags in bytes, which follow the header of items
+/// which use them
+pub const ITEM_EXT_SIZE: usize = std::mem::size_of::<u8>();
+
 #[cfg(feature = "magic")]
 /// The magic bytes to store at the start of the item
 pub const ITEM_MAGIC: u32 = 0xDECAFBAD;
@@ -66,6 +92,26 @@ const OLEN_MASK: u8 = 0b00111111;
 /// A mask to get the bit indicating the item value should be treated as a
 /// typed value from the item header's flags field
 const TYPED_MASK: u8 = 0b10000000;
+/// A mask to get the bit indicating the header is followed by the extended
+/// flags from the item header's flags field
+const EXT_MASK: u8 = 0b01000000;
+
+// large/compressed/cas/tagged
+/// A mask to get the bit indicating the item value is a manifest for a value
+/// which is stored in chunks, from the item's extended flags
+pub(super) const LARGE_MASK: u8 = 0b10000000;
+/// A mask to get the bit indicating the item value is compressed from the
+/// item's extended flags
+pub(super) const COMPRESSED_MASK: u8 = 0b01000000;
+/// A mask to get the bit indicating the item is stored with its own CAS value
+/// from the item's extended flags
+pub(super) const CAS_MASK: u8 = 0b00100000;
+/// A mask to get the bit indicating the item is stored with a tag from the
+/// item's extended flags
+pub(super) const TAGGED_MASK: u8 = 0b00010000;
+
+/// The maximum length of the optional data in bytes
+pub const MAX_OLEN: usize = OLEN_MASK as usize;
 
 use core::convert::TryFrom;
 
@@ -112,7 +158,7 @@ pub struct ItemHeader {
     #[cfg(feature = "magic")]
     magic: u32,
     len: u32,  // packs vlen:24 klen:8
-    flags: u8, // packs is_num:1, deleted:1, olen:6
+    flags: u8, // packs is_num:1, has_ext:1, olen:6
 }
 
 impl ItemHeader {
@@ -137,6 +183,20 @@ impl ItemHeader {
         assert_eq!(self.magic(), ITEM_MAGIC);
     }
 
+    /// Returns false if the magic bytes do not match
+    #[cfg(feature = "magic")]
+    #[inline]
+    pub fn has_magic(&self) -> bool {
+        self.magic() == ITEM_MAGIC
+    }
+
+    /// Without the magic bytes, every header is treated as valid
+    #[cfg(not(feature = "magic"))]
+    #[inline]
+    pub fn has_magic(&self) -> bool {
+        true
+    }
+
     /// Get the item's key length
     #[inline]
     pub fn klen(&self) -> u8 {
@@ -165,6 +225,18 @@ impl ItemHeader {
         self.flags & TYPED_MASK != 0
     }
 
+    /// Is the header followed by the extended flags?
+    #[inline]
+    pub fn has_ext(&self) -> bool {
+        self.flags & EXT_MASK != 0
+    }
+
+    /// Mark the header as followed by the extended flags
+    #[inline]
+    pub fn set_ext(&mut self) {
+        self.flags |= EXT_MASK;
+    }
+
     pub(super) fn value_type(&self) -> Option<ValueType> {
         if self.is_typed() {
             if let Ok(t) = ValueType::try_from((self.len >> TYPE_SHIFT) as u8) {
@@ -236,6 +308,7 @@ impl std::fmt::Debug for ItemHeader {
             .field("klen", &self.klen())
             .field("vlen", &self.vlen())
             .field("type", &self.value_type())
+            .field("ext", &self.has_ext())
             .field("olen", &self.olen())
             .finish()
     }
@@ -250,6 +323,7 @@ impl std::fmt::Debug for ItemHeader {
             .field("klen", &self.klen())
             .field("vlen", &self.vlen())
             .field("typed", &self.is_typed())
+            .field("ext", &self.has_ext())
             .field("olen", &self.olen())
             .finish()
     }
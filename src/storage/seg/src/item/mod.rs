@@ -11,23 +11,71 @@ mod reserved;
 #[cfg(any(feature = "magic", feature = "debug"))]
 pub(crate) use header::ITEM_MAGIC_SIZE;
 
+use crate::tags::Tag;
 use crate::SegError;
 use crate::Value;
+use crate::{clock, Duration, Instant};
+use std::time::SystemTime;
 
-pub(crate) use header::{ItemHeader, ITEM_HDR_SIZE};
-pub(crate) use raw::RawItem;
+pub(crate) use header::{ItemHeader, ITEM_EXT_SIZE, ITEM_HDR_SIZE, MAX_OLEN};
+pub(crate) use raw::{RawItem, CAS_SIZE};
 pub(crate) use reserved::ReservedItem;
 
 /// Items are the base unit of data stored within the cache.
 pub struct Item {
+    // the CAS value of the hash bucket, which is used unless the item is
+    // stored with its own CAS value
     cas: u32,
     raw: RawItem,
+    create_at: Instant,
+    expire_at: Option<Instant>,
+    freq: u8,
+    // the value of a large or compressed item, which is reassembled from its
+    // chunks and decompressed when the item is read
+    value: Option<Box<[u8]>>,
 }
 
 impl Item {
-    /// Creates a new `Item` from its parts
-    pub(crate) fn new(raw: RawItem, cas: u32) -> Self {
-        Item { cas, raw }
+    /// Creates a new `Item` from its parts. The creation and expiration times
+    /// are those of the segment which holds the item.
+    pub(crate) fn new(
+        raw: RawItem,
+        cas: u32,
+        create_at: Instant,
+        expire_at: Option<Instant>,
+        freq: u8,
+    ) -> Self {
+        Item {
+            cas,
+            raw,
+            create_at,
+            expire_at,
+            freq,
+            value: None,
+        }
+    }
+
+    /// Returns the item with the stored value replaced by the value which is
+    /// returned to the caller
+    pub(crate) fn with_value(mut self, value: Box<[u8]>) -> Self {
+        self.value = Some(value);
+        self
+    }
+
+    /// Returns true if the stored value is a manifest for a large item
+    pub(crate) fn is_large(&self) -> bool {
+        self.raw.is_large()
+    }
+
+    /// Returns true if the stored value is compressed
+    pub(crate) fn is_compressed(&self) -> bool {
+        self.raw.is_compressed()
+    }
+
+    /// Borrow the stored value, which may be the manifest for a large item or
+    /// a compressed value
+    pub(crate) fn raw_value(&self) -> Value<'_> {
+        self.raw.value()
     }
 
     /// If the `magic` or `debug` features are enabled, this allows for checking
@@ -48,12 +96,27 @@ impl Item {
 
     /// Borrow the item value
     pub fn value(&self) -> Value {
-        self.raw.value()
+        match &self.value {
+            Some(value) => Value::Bytes(value),
+            None => self.raw.value(),
+        }
+    }
+
+    /// CAS value for the item. This is unique to the item if per-item CAS
+    /// values are enabled, otherwise it is shared by the items in the same
+    /// hash bucket.
+    pub fn cas(&self) -> u64 {
+        self.raw.cas().unwrap_or(self.cas as u64)
+    }
+
+    /// Replaces the CAS value which is stored with the item, if any
+    pub(crate) fn set_cas(&mut self, cas: u64) {
+        self.raw.set_cas(cas)
     }
 
-    /// CAS value for the item
-    pub fn cas(&self) -> u32 {
-        self.cas
+    /// Returns the tag which is stored with the item, if any
+    pub(crate) fn tag(&self) -> Option<Tag> {
+        self.raw.tag()
     }
 
     /// Borrow the optional data
@@ -61,6 +124,54 @@ impl Item {
         self.raw.optional()
     }
 
+    /// The time remaining until the item expires, or `None` if the item does
+    /// not expire. Items expire along with the segment which holds them,
+    /// which may be sooner than the TTL the item was written with.
+    pub fn ttl(&self) -> Option<std::time::Duration> {
+        let now = clock::recent();
+        self.expire_at.map(|expire_at| {
+            if expire_at > now {
+                std::time::Duration::from_secs((expire_at - now).as_secs() as u64)
+            } else {
+                std::time::Duration::ZERO
+            }
+        })
+    }
+
+    /// The time at which the item expires, or `None` if the item does not
+    /// expire, see `ttl()`
+    pub fn expire_at(&self) -> Option<SystemTime> {
+        self.ttl().map(|ttl| SystemTime::now() + ttl)
+    }
+
+    /// Returns true if the item is past its TTL but is still retained, see
+    /// `Builder::stale_grace()`
+    pub(crate) fn is_expired(&self, now: Instant) -> bool {
+        self.expire_at
+            .map(|expire_at| expire_at <= now)
+            .unwrap_or(false)
+    }
+
+    /// The time since the segment which holds the item was created. Items are
+    /// written into a segment after it is created, so the item may be newer.
+    pub fn age(&self) -> std::time::Duration {
+        let age: Duration = clock::recent() - self.create_at;
+        std::time::Duration::from_secs(age.as_secs() as u64)
+    }
+
+    /// The time at which the segment which holds the item was created, see
+    /// `age()`
+    pub fn create_at(&self) -> SystemTime {
+        SystemTime::now() - self.age()
+    }
+
+    /// An approximate count of the accesses to the item, which is used by
+    /// merge eviction to decide which items to keep. The count is between 0
+    /// and 127 and grows more slowly after the first 16 accesses.
+    pub fn freq(&self) -> u8 {
+        self.freq
+    }
+
     /// Perform a wrapping addition on the value. Returns an error if the item
     /// is not a numeric type.
     pub fn wrapping_add(&mut self, rhs: u64) -> Result<(), SegError> {
@@ -78,6 +189,9 @@ impl std::fmt::Debug for Item {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
         f.debug_struct("Item")
             .field("cas", &self.cas())
+            .field("ttl", &self.ttl())
+            .field("age", &self.age())
+            .field("freq", &self.freq())
             .field("raw", &self.raw)
             .finish()
     }
@@ -5,13 +5,18 @@
 //! A raw byte-level representation of an item.
 //!
 //! Unlike an [`Item`], the [`RawItem`] does not contain any fields which are
-//! shared within a hash bucket such as the CAS value.
+//! shared within a hash bucket such as the CAS value. Items which are stored
+//! with their own CAS value are the exception.
 
-use super::header::ValueType;
+use super::header::{ValueType, CAS_MASK, COMPRESSED_MASK, LARGE_MASK, TAGGED_MASK};
 use crate::item::*;
+use crate::tags::Tag;
 use crate::SegError;
 use crate::Value;
 
+/// The size of the per-item CAS value in bytes
+pub(crate) const CAS_SIZE: usize = std::mem::size_of::<u64>();
+
 /// The raw byte-level representation of an item
 #[repr(C)]
 #[derive(Clone, Copy)]
@@ -101,17 +106,148 @@ impl RawItem {
         }
     }
 
+    /// Returns the extended flags, which are empty if the header is not
+    /// followed by them
+    #[inline]
+    fn ext(&self) -> u8 {
+        if self.header().has_ext() {
+            unsafe { *self.data.add(ITEM_HDR_SIZE) }
+        } else {
+            0
+        }
+    }
+
+    /// Sets an extended flag. The item must have been defined with the
+    /// extended flags.
+    fn set_ext(&mut self, mask: u8) {
+        debug_assert!(self.header().has_ext());
+        unsafe {
+            *self.data.add(ITEM_HDR_SIZE) |= mask;
+        }
+    }
+
+    /// Returns true if the item is stored with its own CAS value
+    #[inline]
+    pub(crate) fn has_cas(&self) -> bool {
+        self.ext() & CAS_MASK != 0
+    }
+
+    /// Returns true if the item is stored with a tag
+    #[inline]
+    pub(crate) fn is_tagged(&self) -> bool {
+        self.ext() & TAGGED_MASK != 0
+    }
+
+    /// Returns the CAS value which is stored with the item, or `None` if the
+    /// item uses the CAS value of its hash bucket
+    pub(crate) fn cas(&self) -> Option<u64> {
+        if self.has_cas() {
+            // the CAS value follows the extended flags, so it may be unaligned
+            unsafe {
+                Some(std::ptr::read_unaligned(
+                    self.data.add(self.cas_offset()) as *const u64
+                ))
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Replaces the CAS value which is stored with the item. Items which use
+    /// the CAS value of their hash bucket are unchanged.
+    pub(crate) fn set_cas(&mut self, cas: u64) {
+        if self.has_cas() {
+            unsafe {
+                std::ptr::write_unaligned(self.data.add(self.cas_offset()) as *mut u64, cas);
+            }
+        }
+    }
+
+    /// Returns the tag which is stored with the item, or `None` if the item
+    /// is not tagged
+    pub(crate) fn tag(&self) -> Option<Tag> {
+        if self.is_tagged() {
+            // the tag follows the header and CAS value, so it may be unaligned
+            unsafe {
+                Some(Tag::from_bits(std::ptr::read_unaligned(
+                    self.data.add(self.tag_offset()) as *const u64,
+                )))
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the value is a manifest for a large value
+    #[inline]
+    pub(crate) fn is_large(&self) -> bool {
+        self.ext() & LARGE_MASK != 0
+    }
+
+    /// Mark the value as a manifest for a large value. The item must have
+    /// been defined with the extended flags.
+    pub(crate) fn set_large(&mut self) {
+        self.set_ext(LARGE_MASK)
+    }
+
+    /// Returns true if the value is compressed
+    #[inline]
+    pub(crate) fn is_compressed(&self) -> bool {
+        self.ext() & COMPRESSED_MASK != 0
+    }
+
+    /// Mark the value as compressed. The item must have been defined with the
+    /// extended flags.
+    pub(crate) fn set_compressed(&mut self) {
+        self.set_ext(COMPRESSED_MASK)
+    }
+
     /// Check the header magic bytes
     #[inline]
     pub(crate) fn check_magic(&self) {
         self.header().check_magic()
     }
 
-    /// Copy data into the item
-    pub(crate) fn define(&mut self, key: &[u8], value: Value, optional: &[u8]) {
+    /// Returns false if the header magic bytes are enabled and do not match
+    #[inline]
+    pub(crate) fn has_magic(&self) -> bool {
+        self.header().has_magic()
+    }
+
+    /// Copy data into the item, storing the CAS value and the tag with the
+    /// item if they are provided. The extended flags are stored if `ext` is
+    /// true or the item has a CAS value or tag.
+    pub(crate) fn define(
+        &mut self,
+        key: &[u8],
+        value: Value,
+        optional: &[u8],
+        cas: Option<u64>,
+        tag: Option<Tag>,
+        ext: bool,
+    ) {
         unsafe {
             (*self.header_mut()).init();
         }
+        if ext || cas.is_some() || tag.is_some() {
+            unsafe {
+                (*self.header_mut()).set_ext();
+                *self.data.add(ITEM_HDR_SIZE) = 0;
+            }
+        }
+        if let Some(cas) = cas {
+            self.set_ext(CAS_MASK);
+            self.set_cas(cas);
+        }
+        if let Some(tag) = tag {
+            self.set_ext(TAGGED_MASK);
+            unsafe {
+                std::ptr::write_unaligned(
+                    self.data.add(self.tag_offset()) as *mut u64,
+                    tag.to_bits(),
+                );
+            }
+        }
         match value {
             Value::Bytes(value) => unsafe {
                 (*self.header_mut()).set_type(None);
@@ -158,10 +294,37 @@ impl RawItem {
         }
     }
 
-    // Gets the offset to the optional data
+    // Gets the offset to the CAS value, which follows the extended flags if
+    // the item has them
+    #[inline]
+    fn cas_offset(&self) -> usize {
+        if self.header().has_ext() {
+            ITEM_HDR_SIZE + ITEM_EXT_SIZE
+        } else {
+            ITEM_HDR_SIZE
+        }
+    }
+
+    // Gets the offset to the tag, which follows the CAS value if the item has
+    // one
+    #[inline]
+    fn tag_offset(&self) -> usize {
+        if self.has_cas() {
+            self.cas_offset() + CAS_SIZE
+        } else {
+            self.cas_offset()
+        }
+    }
+
+    // Gets the offset to the optional data, which follows the tag if the item
+    // has one
     #[inline]
     fn optional_offset(&self) -> usize {
-        ITEM_HDR_SIZE
+        if self.is_tagged() {
+            self.tag_offset() + Tag::SIZE
+        } else {
+            self.tag_offset()
+        }
     }
 
     // Gets the offset to the key
@@ -178,7 +341,10 @@ impl RawItem {
 
     /// Returns item size, rounded up for alignment
     pub(crate) fn size(&self) -> usize {
-        (((ITEM_HDR_SIZE + self.olen() as usize + self.klen() as usize + self.vlen() as usize)
+        (((self.optional_offset()
+            + self.olen() as usize
+            + self.klen() as usize
+            + self.vlen() as usize)
             >> 3)
             + 1)
             << 3
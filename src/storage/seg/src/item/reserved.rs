@@ -5,6 +5,7 @@
 //! A reserved item is an item which has been allocated, but has not been
 //! defined or linked in the hashtable.
 
+use crate::tags::Tag;
 use crate::RawItem;
 use crate::Value;
 use core::num::NonZeroU32;
@@ -24,9 +25,28 @@ impl ReservedItem {
         Self { item, seg, offset }
     }
 
-    /// Store the key, value, and optional data into the item
-    pub fn define(&mut self, key: &[u8], value: Value, optional: &[u8]) {
-        self.item.define(key, value, optional)
+    /// Store the key, value, optional data, CAS value, and tag into the item,
+    /// with the extended flags if `ext` is true, see `RawItem::define()`
+    pub fn define(
+        &mut self,
+        key: &[u8],
+        value: Value,
+        optional: &[u8],
+        cas: Option<u64>,
+        tag: Option<Tag>,
+        ext: bool,
+    ) {
+        self.item.define(key, value, optional, cas, tag, ext)
+    }
+
+    /// Mark the item value as a manifest for a large value
+    pub fn set_large(&mut self) {
+        self.item.set_large()
+    }
+
+    /// Mark the item value as compressed
+    pub fn set_compressed(&mut self) {
+        self.item.set_compressed()
     }
 
     /// Get the `RawItem` that backs the `ReservedItem`
@@ -0,0 +1,103 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Support for values which are larger than a single segment.
+//!
+//! A large value is split into chunks which are each stored as an ordinary
+//! item in the same `TtlBucket`, keyed by a unique id and the chunk index.
+//! The item for the user key is then stored with the large flag set and a
+//! manifest as its value. Because the chunks are ordinary items, they are
+//! expired and evicted like any other item. A read which finds that a chunk
+//! is missing treats the item as a miss and removes it.
+//!
+//! Manifest:
+//! ```text
+//! ┌──────────────────────┬──────────────────────┬────────────┐
+//! │          ID          │         VLEN         │   CHUNKS   │
+//! │                      │                      │            │
+//! │        64 bit        │        64 bit        │   32 bit   │
+//! │                      │                      │            │
+//! │0                   63│64                 127│128      159│
+//! └──────────────────────┴──────────────────────┴────────────┘
+//! ```
+
+/// The prefix for the key of each chunk. The leading NUL byte keeps the chunk
+/// keys out of the keyspace used by the text protocols, and the prefix is
+/// reserved so that clients of binary protocols cannot use it either.
+const CHUNK_PREFIX: &[u8] = b"\0seg:chunk:";
+
+/// The length of the key of each chunk
+pub(crate) const CHUNK_KEY_LEN: usize = CHUNK_PREFIX.len() + 8 + 4;
+
+/// The length of an encoded manifest
+const MANIFEST_LEN: usize = 8 + 8 + 4;
+
+/// Describes where to find the chunks of a large value.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Manifest {
+    id: u64,
+    len: u64,
+    chunks: u32,
+}
+
+impl Manifest {
+    pub fn new(id: u64, len: usize, chunks: u32) -> Self {
+        Self {
+            id,
+            len: len as u64,
+            chunks,
+        }
+    }
+
+    /// Decode a manifest from the value of an item with the large flag set
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != MANIFEST_LEN {
+            return None;
+        }
+        Some(Self {
+            id: u64::from_le_bytes(bytes[0..8].try_into().ok()?),
+            len: u64::from_le_bytes(bytes[8..16].try_into().ok()?),
+            chunks: u32::from_le_bytes(bytes[16..20].try_into().ok()?),
+        })
+    }
+
+    pub fn encode(&self) -> [u8; MANIFEST_LEN] {
+        let mut bytes = [0; MANIFEST_LEN];
+        bytes[0..8].copy_from_slice(&self.id.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.len.to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.chunks.to_le_bytes());
+        bytes
+    }
+
+    /// The length of the value in bytes
+    pub fn value_len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Returns an iterator over the keys of each chunk, in order
+    pub fn chunk_keys(&self) -> impl Iterator<Item = [u8; CHUNK_KEY_LEN]> {
+        let id = self.id;
+        (0..self.chunks).map(move |index| chunk_key(id, index))
+    }
+}
+
+/// Returns the key for a chunk of the large value with the provided id
+pub(crate) fn chunk_key(id: u64, index: u32) -> [u8; CHUNK_KEY_LEN] {
+    let mut key = [0; CHUNK_KEY_LEN];
+    key[..CHUNK_PREFIX.len()].copy_from_slice(CHUNK_PREFIX);
+    key[CHUNK_PREFIX.len()..(CHUNK_PREFIX.len() + 8)].copy_from_slice(&id.to_le_bytes());
+    key[(CHUNK_PREFIX.len() + 8)..].copy_from_slice(&index.to_le_bytes());
+    key
+}
+
+/// Returns true if the key uses the prefix which is reserved for chunks. Such
+/// keys cannot be read or written by clients.
+pub(crate) fn is_reserved_key(key: &[u8]) -> bool {
+    key.starts_with(CHUNK_PREFIX)
+}
+
+/// Returns true if the key belongs to a chunk of a large value
+pub(crate) fn is_chunk_key(key: &[u8]) -> bool {
+    key.len() == CHUNK_KEY_LEN && key.starts_with(CHUNK_PREFIX)
+}
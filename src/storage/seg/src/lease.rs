@@ -0,0 +1,133 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Leases prevent a thundering herd of requests to the backing store when a
+//! popular item is missing or expires. The first reader to miss is granted a
+//! lease, which is a token that allows it to refill the item. The readers
+//! which follow are told that the item is being refilled, and are given the
+//! expired value if it is still retained, see `Builder::stale_grace()`.
+//!
+//! A lease ends when the item is written or removed, or when it times out so
+//! that a reader which failed to refill the item does not block the others
+//! forever. A refill should only be written if the lease was still held when
+//! it is released, which prevents a slow refill from overwriting a newer
+//! value.
+
+use crate::*;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+/// The longest timeout of a lease, see `Seg::get_lease()`
+pub const MAX_LEASE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
+/// The result of `Seg::get_lease()`
+#[derive(Debug)]
+pub enum LeaseResult {
+    /// The item is current
+    Hit(Item),
+    /// The item is missing or expired and the caller was granted a lease to
+    /// refill it, see `Seg::release_lease()`. The expired item is included if
+    /// it is still retained.
+    Win { token: u64, stale: Option<Item> },
+    /// Another caller holds the lease and the expired item is still retained
+    Stale(Item),
+    /// Another caller holds the lease and there is no item to return
+    Wait,
+}
+
+struct Lease {
+    token: u64,
+    expire_at: Instant,
+}
+
+// the time a lease times out, with its token and key, ordered soonest first
+type Timeout = Reverse<(Instant, u64, Box<[u8]>)>;
+
+/// The outstanding leases, by key
+pub(crate) struct Leases {
+    // the most recent token, zero if no leases have been granted
+    token: u64,
+    leases: HashMap<Box<[u8]>, Lease>,
+    // the time each lease times out, soonest first. an entry is left in place
+    // when its lease ends early, and is skipped once it is reached if the
+    // token no longer matches the lease for the key
+    timeouts: BinaryHeap<Timeout>,
+}
+
+impl Leases {
+    pub fn new() -> Self {
+        Self {
+            token: 0,
+            leases: HashMap::new(),
+            timeouts: BinaryHeap::new(),
+        }
+    }
+
+    /// Grants a lease for the key which lasts for the timeout and returns the
+    /// token, or returns `None` if there is an outstanding lease for the key
+    pub fn acquire(&mut self, key: &[u8], now: Instant, timeout: Duration) -> Option<u64> {
+        if let Some(lease) = self.leases.get(key) {
+            if lease.expire_at > now {
+                return None;
+            }
+        }
+        self.token += 1;
+        let expire_at = now + timeout;
+        let key = key.to_owned().into_boxed_slice();
+        self.timeouts
+            .push(Reverse((expire_at, self.token, key.clone())));
+        self.leases.insert(
+            key,
+            Lease {
+                token: self.token,
+                expire_at,
+            },
+        );
+        Some(self.token)
+    }
+
+    /// Ends the lease for the key if it is held with the token, returns true
+    /// if it was held and has not timed out
+    pub fn release(&mut self, key: &[u8], token: u64, now: Instant) -> bool {
+        match self.leases.get(key) {
+            Some(lease) if lease.token == token => {
+                let current = lease.expire_at > now;
+                self.leases.remove(key);
+                current
+            }
+            _ => false,
+        }
+    }
+
+    /// Ends the lease for the key, if any
+    pub fn remove(&mut self, key: &[u8]) {
+        if !self.leases.is_empty() {
+            self.leases.remove(key);
+        }
+    }
+
+    /// Removes the leases which have timed out
+    pub fn expire(&mut self, now: Instant) {
+        while let Some(Reverse((expire_at, token, _))) = self.timeouts.peek() {
+            if *expire_at > now {
+                break;
+            }
+            let token = *token;
+            let Reverse((_, _, key)) = self.timeouts.pop().unwrap();
+            if self.leases.get(&key).map(|lease| lease.token) == Some(token) {
+                self.leases.remove(&key);
+            }
+        }
+
+        // the entries for leases which ended early are dropped once they
+        // outnumber the outstanding leases, so that frequent leases with long
+        // timeouts do not grow the queue without bound
+        if self.timeouts.len() > 2 * self.leases.len() + 64 {
+            let leases = &self.leases;
+            self.timeouts.retain(|Reverse((_, token, key))| {
+                leases.get(key).map(|lease| lease.token) == Some(*token)
+            });
+        }
+    }
+}
@@ -18,8 +18,11 @@
 //! * eager expiration of items
 //! * low metadata overhead
 //!
+//! * lookups from multiple threads alongside a single writer, see
+//!   [`Seg::into_shared()`]
+//!
 //! Non-goals:
-//! * not designed for concurrent access
+//! * not designed for concurrent writes
 //!
 
 // macro includes
@@ -35,18 +38,28 @@ use std::convert::TryInto;
 
 // NOTE: this represents the versioning of the internal data layout and must be
 // incremented when breaking changes are made to the datastructures
-const VERSION: u64 = 0;
+const VERSION: u64 = 3;
 
 // submodules
+mod admission;
 mod builder;
+mod checkpoint;
+mod clock;
+mod dump;
 mod error;
 mod eviction;
 mod hashtable;
 mod item;
+mod large;
+mod lease;
 mod metrics;
+mod namespace;
 mod rand;
+mod scan;
 mod seg;
 mod segments;
+mod shared;
+mod tags;
 mod ttl_buckets;
 
 // tests
@@ -55,10 +68,20 @@ mod tests;
 
 // publicly exported items from submodules
 pub use crate::seg::Seg;
+pub use admission::Admission;
 pub use builder::Builder;
+pub use checkpoint::{Checkpoint, SegmentSummary};
+#[cfg(feature = "sim")]
+pub use clock::{advance_clock, freeze_clock};
+pub use datapool::HugePages;
+pub use dump::{DumpReader, DumpTask, DumpWriter, LoadTask, Record};
 pub use error::SegError;
-pub use eviction::Policy;
+pub use eviction::{EvictionPolicy, Merge, Policy, SegmentStats};
 pub use item::Item;
+pub use lease::{LeaseResult, MAX_LEASE_TIMEOUT};
+pub use namespace::Namespace;
+pub use scan::{Cursor, Scan};
+pub use shared::{SegReader, SegWriter, SharedItem, WriteGuard};
 
 // publicly exported items from external crates
 pub use storage_types::Value;
@@ -69,10 +92,14 @@ pub(crate) type Instant = common::time::Instant<Seconds<u32>>;
 
 // items from submodules which are imported for convenience to the crate level
 pub(crate) use crate::rand::*;
+pub(crate) use admission::AdmissionFilter;
 pub(crate) use hashtable::*;
 pub(crate) use item::*;
+pub(crate) use large::*;
+pub(crate) use lease::Leases;
 pub(crate) use metrics::*;
 pub(crate) use segments::*;
+pub(crate) use tags::{Tag, Tags};
 pub(crate) use ttl_buckets::*;
 
 common::metrics::test_no_duplicates!();
@@ -36,9 +36,17 @@ counter!(
     EXPIRE_TIME,
     "amount of time, in nanoseconds, spent expiring segments"
 );
+gauge!(
+    EXPIRE_BACKLOG,
+    "number of expired segments waiting to be removed"
+);
 gauge!(EVICT_TIME, "time, in nanoseconds, spent evicting segments");
 gauge!(SEGMENT_FREE, "current number of free segments");
 gauge!(SEGMENT_CURRENT, "current number of segments");
+gauge!(
+    SEGMENT_TARGET,
+    "number of segments the heap is being resized to"
+);
 
 // hash table related
 counter!(HASH_TAG_COLLISION, "number of partial hash collisions");
@@ -51,6 +59,12 @@ counter!(
     HASH_REMOVE,
     "number of hash table entries which have been removed"
 );
+counter!(HASH_GROW, "number of times the hash table has grown");
+counter!(
+    HASH_MIGRATE,
+    "number of items moved into a larger hash table while growing"
+);
+gauge!(HASH_POWER, "current power of the hash table");
 counter!(
     HASH_LOOKUP,
     "total number of lookups against the hash table"
@@ -67,6 +81,82 @@ counter!(ITEM_DELETE, "number of items removed from the hash table");
 counter!(ITEM_EXPIRE, "number of items removed due to expiration");
 counter!(ITEM_EVICT, "number of items removed due to eviction");
 counter!(ITEM_COMPACTED, "number of items which have been compacted");
+counter!(
+    ITEM_TAG_INVALIDATE,
+    "number of times a tag has been invalidated"
+);
+counter!(
+    ITEM_TAG_INVALIDATED,
+    "number of items removed on read because their tag was invalidated"
+);
+counter!(
+    ITEM_LARGE_INSERT,
+    "number of items inserted which were split into chunks"
+);
+counter!(
+    ITEM_LARGE_MISSING,
+    "number of large items removed because a chunk was missing"
+);
+
+// lease related
+counter!(
+    LEASE_GRANT,
+    "number of leases granted for a missing or expired item"
+);
+counter!(
+    LEASE_STALE,
+    "number of expired items returned while another caller held the lease"
+);
+counter!(
+    LEASE_WAIT,
+    "number of lookups told to wait while another caller held the lease"
+);
+counter!(
+    LEASE_INVALID,
+    "number of leased inserts dropped because the lease was not held"
+);
+
+// admission related
+counter!(
+    ADMISSION_ACCEPT,
+    "number of inserts admitted by the admission filter"
+);
+counter!(
+    ADMISSION_REJECT,
+    "number of inserts rejected by the admission filter"
+);
+
+// compression related
+counter!(ITEM_COMPRESS, "number of values stored compressed");
+counter!(
+    ITEM_COMPRESS_SKIP,
+    "number of values above the threshold which were stored uncompressed because they did not compress"
+);
+counter!(
+    ITEM_COMPRESS_BYTES_IN,
+    "total number of bytes in values before they were compressed"
+);
+counter!(
+    ITEM_COMPRESS_BYTES_OUT,
+    "total number of bytes in values after they were compressed"
+);
+gauge!(
+    ITEM_COMPRESS_RATIO,
+    "ratio of bytes before to bytes after compression, in hundredths"
+);
+counter!(
+    ITEM_COMPRESS_TIME,
+    "amount of time, in nanoseconds, spent compressing values"
+);
+counter!(ITEM_DECOMPRESS, "number of values decompressed on read");
+counter!(
+    ITEM_DECOMPRESS_EX,
+    "number of compressed values which could not be decompressed"
+);
+counter!(
+    ITEM_DECOMPRESS_TIME,
+    "amount of time, in nanoseconds, spent decompressing values"
+);
 gauge!(ITEM_CURRENT, "current number of live items");
 gauge!(
     ITEM_CURRENT_BYTES,
@@ -77,3 +167,68 @@ gauge!(
     ITEM_DEAD_BYTES,
     "current number of dead bytes for storing items"
 );
+
+// tiered storage related
+gauge!(TIER1_SIZE, "size, in bytes, of the in-memory tier");
+gauge!(TIER2_SIZE, "size, in bytes, of the file-backed tier");
+gauge!(
+    TIER2_ITEMS,
+    "number of live items in the file-backed tier, as of the last expiration"
+);
+gauge!(
+    TIER2_BYTES,
+    "number of live bytes in the file-backed tier, as of the last expiration"
+);
+counter!(TIER1_HIT, "number of reads which found the item in memory");
+counter!(
+    TIER2_HIT,
+    "number of reads which found the item in the file-backed tier"
+);
+counter!(
+    TIER2_DEMOTE,
+    "number of items moved from memory to the file-backed tier"
+);
+counter!(
+    TIER2_PROMOTE,
+    "number of items moved from the file-backed tier back into memory"
+);
+counter!(
+    TIER2_SEGMENT_EVICT,
+    "number of segments evicted from the file-backed tier"
+);
+counter!(
+    TIER2_SEGMENT_EXPIRE,
+    "number of segments expired from the file-backed tier"
+);
+
+// shared reader related
+counter!(
+    READER_GET,
+    "number of get operations made by shared readers"
+);
+counter!(
+    READER_HIT,
+    "number of get operations by shared readers which hit"
+);
+counter!(
+    READER_RETRY,
+    "number of reads by shared readers retried due to a concurrent write"
+);
+counter!(
+    READER_BUSY,
+    "number of get operations by shared readers which returned busy"
+);
+
+// datapool related
+counter!(
+    DATAPOOL_CHECKPOINT,
+    "number of checkpoints of the file-backed datapool"
+);
+counter!(
+    DATAPOOL_CHECKPOINT_EX,
+    "number of checkpoints of the file-backed datapool which failed"
+);
+counter!(
+    DATAPOOL_CHECKPOINT_TIME,
+    "amount of time, in nanoseconds, spent checkpointing the file-backed datapool"
+);
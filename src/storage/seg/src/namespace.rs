@@ -0,0 +1,270 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Namespaces partition the keyspace by key prefix so that the segments used
+//! by each namespace can be limited to a quota. Each segment only holds items
+//! from a single namespace. When eviction is required, segments are taken
+//! from the namespace which is furthest over its quota.
+//!
+//! Keys which do not match any namespace belong to the default namespace,
+//! which has no quota.
+
+use crate::*;
+use core::num::NonZeroU32;
+use rustcommon_metrics::{Counter, DynBoxedMetric, Gauge};
+use std::collections::BTreeMap;
+
+/// The name used for the stats of keys which do not match any namespace.
+const DEFAULT_NAMESPACE: &str = "default";
+
+/// A namespace is a set of keys which share a common prefix, and which may
+/// occupy up to a limited number of segments.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Namespace {
+    name: Vec<u8>,
+    quota: usize,
+}
+
+impl Namespace {
+    /// Creates a new namespace for keys starting with `name`, which may use
+    /// up to `quota` segments before its segments are preferred for eviction.
+    ///
+    /// ```
+    /// use seg::Namespace;
+    ///
+    /// // keys starting with "profile" may occupy up to 64 segments
+    /// let namespace = Namespace::new("profile", 64);
+    /// ```
+    pub fn new<T: AsRef<[u8]>>(name: T, quota: usize) -> Self {
+        Self {
+            name: name.as_ref().to_vec(),
+            quota,
+        }
+    }
+
+    /// The key prefix which identifies this namespace
+    pub fn name(&self) -> &[u8] {
+        &self.name
+    }
+
+    /// The number of segments this namespace may use
+    pub fn quota(&self) -> usize {
+        self.quota
+    }
+}
+
+/// Per-namespace quota and stats
+struct Entry {
+    name: Vec<u8>,
+    quota: Option<usize>,
+    /// The segments which hold items for the namespace, keyed by the sequence
+    /// number of their allocation so that the oldest segment is first
+    held: BTreeMap<u64, NonZeroU32>,
+    items: DynBoxedMetric<Gauge>,
+    bytes: DynBoxedMetric<Gauge>,
+    segments: DynBoxedMetric<Gauge>,
+    hit: DynBoxedMetric<Counter>,
+    evict: DynBoxedMetric<Counter>,
+}
+
+impl Entry {
+    fn new(name: &[u8], quota: Option<usize>) -> Self {
+        let stat = String::from_utf8_lossy(if name.is_empty() {
+            DEFAULT_NAMESPACE.as_bytes()
+        } else {
+            name
+        })
+        .into_owned();
+
+        Self {
+            name: name.to_vec(),
+            quota,
+            held: BTreeMap::new(),
+            items: DynBoxedMetric::new(Gauge::new(), format!("namespace_{}_items", stat)),
+            bytes: DynBoxedMetric::new(Gauge::new(), format!("namespace_{}_bytes", stat)),
+            segments: DynBoxedMetric::new(Gauge::new(), format!("namespace_{}_segments", stat)),
+            hit: DynBoxedMetric::new(Counter::new(), format!("namespace_{}_hit", stat)),
+            evict: DynBoxedMetric::new(Counter::new(), format!("namespace_{}_evict", stat)),
+        }
+    }
+}
+
+/// Maps keys to namespace ids and tracks the usage of each namespace. The
+/// default namespace always has id 0.
+pub(crate) struct NamespaceTable {
+    delimiter: Option<u8>,
+    entries: Vec<Entry>,
+    /// The namespace and allocation sequence number for each segment id which
+    /// is held by a namespace
+    allocated: Vec<Option<(u16, u64)>>,
+    /// The sequence number for the next allocation
+    sequence: u64,
+    /// The usage of each namespace, kept so that an update does not allocate
+    usage: Vec<(i64, i64, i64)>,
+    /// Time the usage stats were last updated
+    updated_at: Instant,
+}
+
+impl NamespaceTable {
+    /// Creates a table for the namespaces. If a delimiter is provided, the
+    /// namespace of a key is the portion of the key before the first
+    /// delimiter. Otherwise, the longest matching namespace is used. Segment
+    /// ids up to `segments` may be held by the namespaces.
+    pub fn new(namespaces: Vec<Namespace>, delimiter: Option<u8>, segments: usize) -> Self {
+        assert!(
+            namespaces.len() < u16::MAX as usize,
+            "too many namespaces, at most {} are supported",
+            u16::MAX - 1
+        );
+
+        let mut entries = Vec::with_capacity(namespaces.len() + 1);
+        entries.push(Entry::new(&[], None));
+        for namespace in namespaces {
+            assert!(
+                !namespace.name.is_empty(),
+                "namespace name must not be empty"
+            );
+            assert!(
+                !entries.iter().any(|e| e.name == namespace.name),
+                "duplicate namespace: {}",
+                String::from_utf8_lossy(&namespace.name)
+            );
+            entries.push(Entry::new(&namespace.name, Some(namespace.quota)));
+        }
+
+        Self {
+            delimiter,
+            usage: vec![(0, 0, 0); entries.len()],
+            entries,
+            allocated: vec![None; segments],
+            sequence: 0,
+            updated_at: clock::recent(),
+        }
+    }
+
+    /// Returns the number of namespaces, including the default namespace
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns the names of the namespaces in order of their ids, excluding
+    /// the default namespace
+    pub fn names(&self) -> impl Iterator<Item = &[u8]> {
+        self.entries
+            .iter()
+            .skip(1)
+            .map(|entry| entry.name.as_slice())
+    }
+
+    /// Returns the id of the namespace the key belongs to
+    pub fn lookup(&self, key: &[u8]) -> u16 {
+        let mut best = 0;
+        if let Some(delimiter) = self.delimiter {
+            if let Some(end) = key.iter().position(|b| *b == delimiter) {
+                let prefix = &key[..end];
+                if let Some(id) = self.entries.iter().skip(1).position(|e| e.name == prefix) {
+                    best = id + 1;
+                }
+            }
+        } else {
+            for (id, entry) in self.entries.iter().enumerate().skip(1) {
+                if key.starts_with(&entry.name) && entry.name.len() > self.entries[best].name.len()
+                {
+                    best = id;
+                }
+            }
+        }
+        best as u16
+    }
+
+    /// Returns the number of segments held by the namespace
+    #[cfg(test)]
+    pub fn segments(&self, namespace: u16) -> usize {
+        self.entries
+            .get(namespace as usize)
+            .map(|entry| entry.held.len())
+            .unwrap_or(0)
+    }
+
+    /// Records that the segment now holds items for the namespace
+    pub fn acquire(&mut self, namespace: u16, id: NonZeroU32) {
+        self.release(id);
+        if let Some(entry) = self.entries.get_mut(namespace as usize) {
+            let sequence = self.sequence;
+            self.sequence += 1;
+            entry.held.insert(sequence, id);
+            self.allocated[id.get() as usize - 1] = Some((namespace, sequence));
+        }
+    }
+
+    /// Records that the segment no longer holds items for the namespace which
+    /// acquired it, if any
+    pub fn release(&mut self, id: NonZeroU32) {
+        if let Some((namespace, sequence)) = self.allocated[id.get() as usize - 1].take() {
+            self.entries[namespace as usize].held.remove(&sequence);
+        }
+    }
+
+    /// Returns the oldest evictable segment from the namespace which is
+    /// furthest over its quota, if any namespace is over quota.
+    pub fn over_quota(&self, headers: &[SegmentHeader]) -> Option<NonZeroU32> {
+        let mut best: Option<(usize, NonZeroU32)> = None;
+        for entry in self.entries.iter() {
+            let over = match entry.quota {
+                Some(quota) if entry.held.len() > quota => entry.held.len() - quota,
+                _ => continue,
+            };
+            if best.map(|(most, _)| over <= most).unwrap_or(false) {
+                continue;
+            }
+            let oldest = entry
+                .held
+                .values()
+                .find(|id| headers[id.get() as usize - 1].can_evict());
+            if let Some(id) = oldest {
+                best = Some((over, *id));
+            }
+        }
+        best.map(|(_, id)| id)
+    }
+
+    /// Records a hit for the namespace
+    pub fn hit(&self, namespace: u16) {
+        if let Some(entry) = self.entries.get(namespace as usize) {
+            entry.hit.increment();
+        }
+    }
+
+    /// Records evicted items for the namespace
+    pub fn evict(&self, namespace: u16, items: usize) {
+        if let Some(entry) = self.entries.get(namespace as usize) {
+            entry.evict.add(items as _);
+        }
+    }
+
+    /// Updates the usage stats for each namespace from the segment headers.
+    /// This scans every segment, so the stats are updated at most once per
+    /// second.
+    pub fn update(&mut self, headers: &[SegmentHeader]) {
+        let now = clock::recent();
+        if now == self.updated_at {
+            return;
+        }
+        self.updated_at = now;
+
+        self.usage.fill((0, 0, 0));
+        for header in headers.iter().filter(|h| h.accessible()) {
+            if let Some(usage) = self.usage.get_mut(header.namespace() as usize) {
+                usage.0 += header.live_items() as i64;
+                usage.1 += (header.live_bytes() - first_item_offset() as i32) as i64;
+                usage.2 += 1;
+            }
+        }
+        for (entry, (items, bytes, segments)) in self.entries.iter().zip(&self.usage) {
+            entry.items.set(*items);
+            entry.bytes.set(*bytes);
+            entry.segments.set(*segments);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Cursor-based iteration over the live items in the cache.
+
+use crate::*;
+use core::num::NonZeroU32;
+
+/// A resumable position within a scan of the cache. A default `Cursor` starts
+/// a new scan from the first segment.
+///
+/// A scan walks the segments in order and returns every item which is live
+/// for the duration of the scan at least once. Items which are written,
+/// moved, or removed while the scan is in progress may be returned more than
+/// once or not at all.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cursor {
+    // the id of the segment being scanned, zero before the scan has started
+    seg: u32,
+    // the offset of the next item to read from the segment
+    offset: usize,
+    // the offset and key of the last item returned, used to detect that the
+    // segment has been cleared or compacted since the cursor was taken
+    last: Option<(usize, Box<[u8]>)>,
+    done: bool,
+}
+
+impl Cursor {
+    /// Returns true once the scan has visited every segment.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    // moves the cursor to the first item of the next segment
+    fn next_segment(&mut self) {
+        self.seg += 1;
+        self.offset = first_item_offset();
+        self.last = None;
+    }
+}
+
+/// An iterator over the live, unexpired items in the cache, along with the
+/// remaining TTL for each item, which is `None` for items that do not expire.
+/// Created by `Seg::scan()`.
+pub struct Scan<'a> {
+    cache: &'a mut Seg,
+    cursor: Cursor,
+    // whether the cursor position has been checked against the segment
+    checked: bool,
+}
+
+impl<'a> Scan<'a> {
+    pub(crate) fn new(cache: &'a mut Seg, cursor: Cursor) -> Self {
+        Self {
+            cache,
+            cursor,
+            checked: false,
+        }
+    }
+
+    /// Returns the cursor which resumes the scan after the last item
+    /// returned by this iterator.
+    pub fn cursor(&self) -> Cursor {
+        self.cursor.clone()
+    }
+}
+
+impl<'a> Iterator for Scan<'a> {
+    type Item = (Item, Option<std::time::Duration>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let now = clock::recent();
+
+        while !self.cursor.done {
+            if self.cursor.seg == 0 {
+                self.cursor.next_segment();
+            }
+
+            let seg_id = NonZeroU32::new(self.cursor.seg).unwrap();
+            let flush_at = self.cache.segments.flush_at();
+            let (write_offset, expire_at, live) = match self.cache.segments.get_mut(seg_id) {
+                Ok(segment) => {
+                    let expire_at = segment.expire_at();
+                    let live = segment.accessible()
+                        && expire_at.map(|e| e > now).unwrap_or(true)
+                        && segment.create_at() >= flush_at;
+                    (segment.write_offset() as usize, expire_at, live)
+                }
+                Err(_) => {
+                    self.cursor.done = true;
+                    return None;
+                }
+            };
+
+            if !live {
+                self.cursor.next_segment();
+                continue;
+            }
+
+            // when resuming, restart the segment if the last item returned
+            // is no longer where the cursor left it
+            if !self.checked {
+                self.checked = true;
+                if let Some((offset, key)) = &self.cursor.last {
+                    if !self.cache.hashtable.is_item_at(key, seg_id, *offset as u64) {
+                        self.cursor.offset = first_item_offset();
+                    }
+                }
+            }
+
+            while self.cursor.offset < write_offset {
+                let offset = self.cursor.offset;
+                let raw = match self.cache.segments.get_item_at(Some(seg_id), offset) {
+                    Some(raw) => raw,
+                    None => break,
+                };
+                if raw.klen() == 0 {
+                    break;
+                }
+                raw.check_magic();
+                self.cursor.offset += raw.size();
+
+                // chunks are returned as part of their large item
+                if is_chunk_key(raw.key())
+                    || !self
+                        .cache
+                        .hashtable
+                        .is_item_at(raw.key(), seg_id, offset as u64)
+                {
+                    continue;
+                }
+
+                if let Some(item) = self
+                    .cache
+                    .hashtable
+                    .get_no_freq_incr(
+                        raw.key(),
+                        &mut self.cache.ttl_buckets,
+                        &mut self.cache.segments,
+                    )
+                    .and_then(|item| self.cache.assemble(item, false))
+                {
+                    self.cursor.last = Some((offset, raw.key().into()));
+                    let ttl = expire_at
+                        .map(|e| std::time::Duration::from_secs((e - now).as_secs() as u64));
+                    return Some((item, ttl));
+                }
+            }
+
+            self.cursor.next_segment();
+        }
+
+        None
+    }
+}
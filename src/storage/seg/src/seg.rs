@@ -10,6 +10,13 @@ use std::cmp::min;
 
 const RESERVE_RETRIES: usize = 3;
 
+// number of hashtable buckets moved into a larger hashtable on each expire
+const MIGRATE_EXPIRE_BUCKETS: usize = 1024;
+
+// number of segments holding items which are evicted to shrink the heap on
+// each expire
+const SHRINK_EXPIRE_SEGMENTS: usize = 16;
+
 /// A pre-allocated key-value store with eager expiration. It uses a
 /// segment-structured design that stores data in fixed-size segments, grouping
 /// objects with nearby expiration time into the same segment, and lifting most
@@ -19,6 +26,28 @@ pub struct Seg {
     pub(crate) segments: Segments,
     pub(crate) ttl_buckets: TtlBuckets,
     pub(crate) time: Instant,
+    // the id of the most recent large item, zero if there have been none
+    pub(crate) next_large: u64,
+    // the most recent per-item CAS value, or `None` if items use the CAS
+    // value of their hash bucket
+    pub(crate) cas: Option<u64>,
+    // the generations of the item tags, created when the first tagged item is
+    // inserted
+    pub(crate) tags: Option<Tags>,
+    pub(crate) leases: Leases,
+    // segments are kept for this long after they expire, so that their items
+    // can be returned by `get_lease()`
+    pub(crate) stale_grace: Duration,
+    // the metadata is checkpointed to this path, if the datapool is backed by
+    // a file
+    pub(crate) checkpoint_path: Option<std::path::PathBuf>,
+    // values of at least this many bytes are compressed
+    pub(crate) compression: Option<usize>,
+    pub(crate) admission: Option<AdmissionFilter>,
+    pub(crate) expire_budget: ExpireBudget,
+    // readers may be using the segments, so the heap is not resized, see
+    // `into_shared()`
+    pub(crate) shared: bool,
 }
 
 impl Seg {
@@ -54,7 +83,38 @@ impl Seg {
     #[cfg(any(test, feature = "debug"))]
     pub fn items(&mut self) -> usize {
         trace!("getting segment item counts");
-        self.segments.items()
+        // the chunks of a large value are counted as part of its item
+        self.segments.items() - self.chunk_items()
+    }
+
+    /// Returns the number of live items which hold a chunk of a large value
+    #[cfg(any(test, feature = "debug"))]
+    fn chunk_items(&mut self) -> usize {
+        if self.next_large == 0 {
+            return 0;
+        }
+
+        let mut chunks = 0;
+        for seg_id in self.segments.ids() {
+            let write_offset = match self.segments.get_mut(seg_id) {
+                Ok(segment) if segment.accessible() => segment.write_offset() as usize,
+                _ => continue,
+            };
+            let mut offset = first_item_offset();
+            while offset < write_offset {
+                let raw = match self.segments.get_item_at(Some(seg_id), offset) {
+                    Some(raw) if raw.klen() > 0 => raw,
+                    _ => break,
+                };
+                if is_chunk_key(raw.key())
+                    && self.hashtable.is_item_at(raw.key(), seg_id, offset as u64)
+                {
+                    chunks += 1;
+                }
+                offset += raw.size();
+            }
+        }
+        chunks
     }
 
     /// Get the item in the `Seg` with the provided key
@@ -71,7 +131,34 @@ impl Seg {
     /// assert_eq!(item.value(), b"strong");
     /// ```
     pub fn get(&mut self, key: &[u8]) -> Option<Item> {
-        self.hashtable.get(key, self.time, &mut self.segments)
+        self.read(key, false)
+    }
+
+    /// Get the item with the provided key, including an expired item which
+    /// is retained for leases if `stale` is true
+    fn read(&mut self, key: &[u8], stale: bool) -> Option<Item> {
+        if is_reserved_key(key) {
+            return None;
+        }
+        let tier2 = self.segments.has_tier2() && self.promote(key);
+        let item = self
+            .hashtable
+            .get(key, self.time, &mut self.ttl_buckets, &mut self.segments)
+            .and_then(|item| self.assemble(item, true))
+            .filter(|item| stale || !self.is_stale(item));
+        if item.is_none() {
+            if let Some(admission) = &mut self.admission {
+                admission.miss(key);
+            }
+            return None;
+        }
+        if tier2 {
+            TIER2_HIT.increment();
+        } else {
+            TIER1_HIT.increment();
+        }
+        self.segments.namespace_hit(key);
+        item
     }
 
     /// Get the item in the `Seg` with the provided key without
@@ -84,11 +171,88 @@ impl Seg {
     /// assert!(cache.get_no_freq_incr(b"coffee").is_none());
     /// ```
     pub fn get_no_freq_incr(&mut self, key: &[u8]) -> Option<Item> {
-        self.hashtable.get_no_freq_incr(key, &mut self.segments)
+        if is_reserved_key(key) {
+            return None;
+        }
+        let item =
+            self.hashtable
+                .get_no_freq_incr(key, &mut self.ttl_buckets, &mut self.segments)?;
+        self.assemble(item, false)
+            .filter(|item| !self.is_stale(item))
+    }
+
+    /// Get the item with the provided key, or a lease to refill it if it is
+    /// missing or expired. Only one caller at a time is granted a lease for a
+    /// key, and the others are given the expired item if it is retained, see
+    /// `Builder::stale_grace()`, or are told to wait. The lease ends when the
+    /// key is written or deleted, or after the timeout, at which point the
+    /// next caller is granted a lease. The timeout is rounded down to whole
+    /// seconds, with a minimum of one second, and a timeout longer than
+    /// `MAX_LEASE_TIMEOUT` returns an error.
+    ///
+    /// ```
+    /// use seg::{LeaseResult, Seg};
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = Seg::builder().build().expect("failed to create cache");
+    ///
+    /// // the first miss is granted a lease
+    /// let timeout = Duration::from_secs(10);
+    /// let token = match cache.get_lease(b"coffee", timeout) {
+    ///     Ok(LeaseResult::Win { token, .. }) => token,
+    ///     _ => panic!("expected a lease"),
+    /// };
+    ///
+    /// // while others wait for the item to be refilled
+    /// assert!(matches!(cache.get_lease(b"coffee", timeout), Ok(LeaseResult::Wait)));
+    ///
+    /// if cache.release_lease(b"coffee", token) {
+    ///     cache.insert(b"coffee", b"strong", None, Duration::ZERO);
+    /// }
+    /// assert!(matches!(cache.get_lease(b"coffee", timeout), Ok(LeaseResult::Hit(_))));
+    /// ```
+    pub fn get_lease(
+        &mut self,
+        key: &[u8],
+        timeout: std::time::Duration,
+    ) -> Result<LeaseResult, SegError> {
+        if timeout > MAX_LEASE_TIMEOUT {
+            return Err(SegError::LeaseTimeout);
+        }
+        let now = clock::recent();
+        let stale = match self.read(key, true) {
+            Some(item) if !self.is_stale(&item) => return Ok(LeaseResult::Hit(item)),
+            item => item,
+        };
+        let timeout = Duration::from_secs(std::cmp::max(1, timeout.as_secs()) as u32);
+        let result = match (self.leases.acquire(key, now, timeout), stale) {
+            (Some(token), stale) => {
+                LEASE_GRANT.increment();
+                LeaseResult::Win { token, stale }
+            }
+            (None, Some(item)) => {
+                LEASE_STALE.increment();
+                LeaseResult::Stale(item)
+            }
+            (None, None) => {
+                LEASE_WAIT.increment();
+                LeaseResult::Wait
+            }
+        };
+        Ok(result)
     }
 
     /// Insert a new item into the cache. May return an error indicating that
     /// the insert was not successful.
+    ///
+    /// Values which do not fit within a single segment are split into chunks
+    /// which are stored across multiple segments in the same `TtlBucket`.
+    /// Reading such a value requires it to be reassembled, so only values
+    /// which fit within a segment can be read without a copy.
+    ///
+    /// If an admission policy is configured, a write of a new key which is not
+    /// admitted is dropped and `Ok` is returned, as if the item had been
+    /// stored and then immediately evicted.
     /// ```
     /// use seg::{Policy, Seg};
     /// use std::time::Duration;
@@ -111,15 +275,295 @@ impl Seg {
         optional: Option<&[u8]>,
         ttl: std::time::Duration,
     ) -> Result<(), SegError> {
-        let value: Value = value.into();
+        if is_reserved_key(key) {
+            return Err(SegError::ReservedKey);
+        }
+        if !self.admit(key) {
+            return Ok(());
+        }
+        let ttl = Duration::from_secs(min(u32::MAX as u64, ttl.as_secs()) as u32);
+        self.insert_with_ttl(key, value.into(), optional, ttl, None)
+    }
+
+    /// Insert a new item into the cache with a tag, as `insert()` does. All the
+    /// items with the same tag may be removed at once with `invalidate_tag()`.
+    /// The tag adds 8 bytes to the item.
+    ///
+    /// ```
+    /// use seg::Seg;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = Seg::builder().build().expect("failed to create cache");
+    ///
+    /// cache.insert_tagged(b"user:123:name", b"alice", None, Duration::ZERO, b"user:123");
+    /// cache.insert_tagged(b"user:123:email", b"a@b.c", None, Duration::ZERO, b"user:123");
+    /// assert!(cache.get(b"user:123:name").is_some());
+    ///
+    /// cache.invalidate_tag(b"user:123");
+    /// assert!(cache.get(b"user:123:name").is_none());
+    /// assert!(cache.get(b"user:123:email").is_none());
+    /// ```
+    pub fn insert_tagged<'a, T: Into<Value<'a>>>(
+        &mut self,
+        key: &'a [u8],
+        value: T,
+        optional: Option<&[u8]>,
+        ttl: std::time::Duration,
+        tag: &[u8],
+    ) -> Result<(), SegError> {
+        if is_reserved_key(key) {
+            return Err(SegError::ReservedKey);
+        }
+        if !self.admit(key) {
+            return Ok(());
+        }
+        let ttl = Duration::from_secs(min(u32::MAX as u64, ttl.as_secs()) as u32);
+        let tag = self.tags.get_or_insert_with(Tags::new).tag(tag);
+        self.insert_with_ttl(key, value.into(), optional, ttl, Some(tag))
+    }
+
+    /// Ends a lease which was granted by `get_lease()`, returns true if it
+    /// was still held with the token. The refilled item should only be
+    /// written if this returns true, as otherwise the key was written or
+    /// deleted since the lease was granted, or the lease timed out, and the
+    /// refilled value may be older than the one in the cache.
+    pub fn release_lease(&mut self, key: &[u8], token: u64) -> bool {
+        if !is_reserved_key(key) && self.leases.release(key, token, clock::recent()) {
+            true
+        } else {
+            LEASE_INVALID.increment();
+            false
+        }
+    }
+
+    /// Invalidates every item which was inserted with the tag. This takes
+    /// constant time, as the items are only removed once they are next read.
+    /// Tags are hashed into a fixed number of slots, so items with a
+    /// different tag may also be invalidated.
+    pub fn invalidate_tag(&mut self, tag: &[u8]) {
+        ITEM_TAG_INVALIDATE.increment();
+        if let Some(tags) = &mut self.tags {
+            tags.invalidate(tag);
+        }
+    }
+
+    /// Returns true if a write to the key should be stored. Keys which are
+    /// already in the cache are always admitted.
+    fn admit(&mut self, key: &[u8]) -> bool {
+        let admission = match &mut self.admission {
+            Some(admission) => admission,
+            None => return true,
+        };
+
+        if self
+            .hashtable
+            .get_item_info(key, &mut self.ttl_buckets, &mut self.segments)
+            .is_some()
+            || admission.admit(key)
+        {
+            ADMISSION_ACCEPT.increment();
+            true
+        } else {
+            ADMISSION_REJECT.increment();
+            false
+        }
+    }
+
+    /// Inserts the item into the `TtlBucket` for the provided ttl, compressing
+    /// the value if enabled and splitting the value into chunks if it does not
+    /// fit within a single segment.
+    pub(crate) fn insert_with_ttl(
+        &mut self,
+        key: &[u8],
+        value: Value,
+        optional: Option<&[u8]>,
+        ttl: Duration,
+        tag: Option<Tag>,
+    ) -> Result<(), SegError> {
+        // a write ends any lease for the key, as the leaseholder may be
+        // refilling it with an older value
+        self.leases.remove(key);
 
         // default optional data is empty
         let optional = optional.unwrap_or(&[]);
+        let cas = self.next_cas();
+        if optional.len() > MAX_OLEN {
+            return Err(SegError::ItemOversized {
+                size: item_size(
+                    key.len(),
+                    size_of(&value),
+                    optional.len(),
+                    cas.is_some(),
+                    tag.is_some(),
+                    false,
+                ),
+            });
+        }
+
+        let compressed = match value {
+            Value::Bytes(value) => self.compress(value),
+            Value::U64(_) => None,
+        };
+        let value = match &compressed {
+            Some(compressed) => Value::Bytes(compressed),
+            None => value,
+        };
 
         // calculate size for item
-        let size = (((ITEM_HDR_SIZE + key.len() + size_of(&value) + optional.len()) >> 3) + 1) << 3;
+        let size = item_size(
+            key.len(),
+            size_of(&value),
+            optional.len(),
+            cas.is_some(),
+            tag.is_some(),
+            compressed.is_some(),
+        );
 
-        let ttl = Duration::from_secs(min(u32::MAX as u64, ttl.as_secs()) as u32);
+        // the chunks of a large item are removed once it has been replaced,
+        // this lookup is skipped until a large item has been inserted
+        let replaced = if self.next_large > 0 {
+            self.manifest(key)
+        } else {
+            None
+        };
+
+        let result = match value {
+            Value::Bytes(value) if size > self.max_item_size() => {
+                self.insert_large(key, value, optional, ttl, compressed.is_some(), cas, tag)
+            }
+            _ => {
+                let namespace = self.segments.namespace(key);
+                self.link(
+                    key,
+                    value,
+                    optional,
+                    ttl,
+                    namespace,
+                    false,
+                    compressed.is_some(),
+                    cas,
+                    tag,
+                )
+            }
+        };
+
+        if result.is_ok() {
+            if let Some(manifest) = replaced {
+                self.remove_chunks(&manifest);
+            }
+        }
+
+        result
+    }
+
+    /// Stores each chunk of a large value as an item and then links the item
+    /// for the key with a manifest describing the chunks. Only the item for
+    /// the key is stored with the CAS value and tag.
+    #[allow(clippy::too_many_arguments)]
+    fn insert_large(
+        &mut self,
+        key: &[u8],
+        value: &[u8],
+        optional: &[u8],
+        ttl: Duration,
+        compressed: bool,
+        cas: Option<u64>,
+        tag: Option<Tag>,
+    ) -> Result<(), SegError> {
+        let chunk_len = self.max_item_size() - ITEM_HDR_SIZE - CHUNK_KEY_LEN - 8;
+        let chunks = value.len().div_ceil(chunk_len);
+
+        // each chunk fills most of a segment, so a value which would need more
+        // than half of the segments cannot be stored without evicting itself
+        if chunks > self.segments.cap() / 2 {
+            return Err(SegError::ItemOversized {
+                size: item_size(
+                    key.len(),
+                    value.len(),
+                    optional.len(),
+                    cas.is_some(),
+                    tag.is_some(),
+                    compressed,
+                ),
+            });
+        }
+
+        self.next_large += 1;
+        let manifest = Manifest::new(self.next_large, value.len(), chunks as u32);
+
+        // the chunks are stored in the namespace of the key which owns them
+        let namespace = self.segments.namespace(key);
+
+        for (chunk_key, chunk) in manifest.chunk_keys().zip(value.chunks(chunk_len)) {
+            let chunk = Value::Bytes(chunk);
+            if let Err(e) = self.link(
+                &chunk_key,
+                chunk,
+                &[],
+                ttl,
+                namespace,
+                false,
+                false,
+                None,
+                None,
+            ) {
+                self.remove_chunks(&manifest);
+                return Err(e);
+            }
+        }
+
+        // reserving space for the later chunks may have evicted the segments
+        // holding the earlier chunks
+        if manifest.chunk_keys().any(|k| {
+            self.hashtable
+                .get_item_info(&k, &mut self.ttl_buckets, &mut self.segments)
+                .is_none()
+        }) {
+            self.remove_chunks(&manifest);
+            return Err(SegError::NoFreeSegments);
+        }
+
+        let encoded = manifest.encode();
+        let value = Value::Bytes(&encoded);
+        match self.link(
+            key, value, optional, ttl, namespace, true, compressed, cas, tag,
+        ) {
+            Ok(()) => {
+                ITEM_LARGE_INSERT.increment();
+                Ok(())
+            }
+            Err(e) => {
+                self.remove_chunks(&manifest);
+                Err(e)
+            }
+        }
+    }
+
+    /// Reserves space for the item in the `TtlBucket` for the provided ttl and
+    /// namespace and links it into the hashtable. The item is stored with its
+    /// own CAS value and a tag if they are provided.
+    #[allow(clippy::too_many_arguments)]
+    fn link(
+        &mut self,
+        key: &[u8],
+        value: Value,
+        optional: &[u8],
+        ttl: Duration,
+        namespace: u16,
+        large: bool,
+        compressed: bool,
+        cas: Option<u64>,
+        tag: Option<Tag>,
+    ) -> Result<(), SegError> {
+        // calculate size for item
+        let size = item_size(
+            key.len(),
+            size_of(&value),
+            optional.len(),
+            cas.is_some(),
+            tag.is_some(),
+            large || compressed,
+        );
 
         // try to get a `ReservedItem`
         let mut retries = RESERVE_RETRIES;
@@ -127,11 +571,17 @@ impl Seg {
         loop {
             match self
                 .ttl_buckets
-                .get_mut_bucket(ttl)
+                .get_mut_bucket(namespace, ttl)
                 .reserve(size, &mut self.segments)
             {
                 Ok(mut reserved_item) => {
-                    reserved_item.define(key, value, optional);
+                    reserved_item.define(key, value, optional, cas, tag, large || compressed);
+                    if large {
+                        reserved_item.set_large();
+                    }
+                    if compressed {
+                        reserved_item.set_compressed();
+                    }
                     reserved = reserved_item;
                     break;
                 }
@@ -191,7 +641,10 @@ impl Seg {
     }
 
     /// Performs a CAS operation, inserting the item only if the CAS value
-    /// matches the current value for that item.
+    /// matches the current value for that item. By default, the CAS value is
+    /// shared by the items in the same hash bucket, so a write to another key
+    /// may cause the operation to fail. See `Builder::item_cas()` for storing
+    /// a unique CAS value with each item.
     ///
     /// ```
     /// use seg::{Policy, Seg, SegError};
@@ -225,14 +678,145 @@ impl Seg {
         value: T,
         optional: Option<&[u8]>,
         ttl: std::time::Duration,
-        cas: u32,
+        cas: u64,
     ) -> Result<(), SegError> {
-        match self.hashtable.try_update_cas(key, cas, &mut self.segments) {
+        if is_reserved_key(key) {
+            return Err(SegError::NotFound);
+        }
+        // an item with an invalidated tag or which has expired is treated as
+        // missing
+        if self.tags.is_some() || self.stale_grace.as_secs() > 0 {
+            if let Some(item) =
+                self.hashtable
+                    .get_no_freq_incr(key, &mut self.ttl_buckets, &mut self.segments)
+            {
+                self.check_tag(item)
+                    .filter(|item| !self.is_stale(item))
+                    .ok_or(SegError::NotFound)?;
+            }
+        }
+        match self
+            .hashtable
+            .try_update_cas(key, cas, &mut self.ttl_buckets, &mut self.segments)
+        {
             Ok(()) => self.insert(key, value, optional, ttl),
             Err(e) => Err(e),
         }
     }
 
+    /// Appends the provided bytes to the value of an existing item. The flags
+    /// (optional data) and tag of the item are retained and the new item is stored in
+    /// the same `TtlBucket` as the existing item. Numeric values are treated
+    /// as their decimal representation, and the result remains numeric if the
+    /// concatenated value is itself a valid `u64`.
+    ///
+    /// ```
+    /// use seg::{Policy, Seg, SegError};
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = Seg::builder().build().expect("failed to create cache");
+    ///
+    /// // If the item is not in the cache, append will fail as 'NotFound'
+    /// assert_eq!(cache.append(b"drink", b" with milk"), Err(SegError::NotFound));
+    ///
+    /// cache.insert(b"drink", b"coffee", None, Duration::ZERO);
+    /// assert!(cache.append(b"drink", b" with milk").is_ok());
+    /// let item = cache.get(b"drink").expect("not found");
+    /// assert_eq!(item.value(), b"coffee with milk");
+    /// ```
+    pub fn append(&mut self, key: &[u8], value: &[u8]) -> Result<(), SegError> {
+        self.concat(key, value, false)
+    }
+
+    /// Prepends the provided bytes to the value of an existing item. The flags
+    /// (optional data) and tag of the item are retained and the new item is stored in
+    /// the same `TtlBucket` as the existing item. Numeric values are treated
+    /// as their decimal representation, and the result remains numeric if the
+    /// concatenated value is itself a valid `u64`.
+    ///
+    /// ```
+    /// use seg::{Policy, Seg, SegError};
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = Seg::builder().build().expect("failed to create cache");
+    ///
+    /// // If the item is not in the cache, prepend will fail as 'NotFound'
+    /// assert_eq!(cache.prepend(b"drink", b"iced "), Err(SegError::NotFound));
+    ///
+    /// cache.insert(b"drink", b"coffee", None, Duration::ZERO);
+    /// assert!(cache.prepend(b"drink", b"iced ").is_ok());
+    /// let item = cache.get(b"drink").expect("not found");
+    /// assert_eq!(item.value(), b"iced coffee");
+    /// ```
+    pub fn prepend(&mut self, key: &[u8], value: &[u8]) -> Result<(), SegError> {
+        self.concat(key, value, true)
+    }
+
+    /// Replaces an existing item with one whose value is the concatenation of
+    /// the current value and the provided bytes.
+    fn concat(&mut self, key: &[u8], value: &[u8], prepend: bool) -> Result<(), SegError> {
+        if is_reserved_key(key) {
+            return Err(SegError::NotFound);
+        }
+        let item_info = self
+            .hashtable
+            .get_item_info(key, &mut self.ttl_buckets, &mut self.segments)
+            .ok_or(SegError::NotFound)?;
+
+        // the item keeps the time remaining until the segment holding it
+        // expires, rather than the full ttl of its `TtlBucket`
+        let seg_id = get_seg_id(item_info).ok_or(SegError::NotFound)?;
+        let segment = self
+            .segments
+            .get_mut(seg_id)
+            .map_err(|_| SegError::DataCorrupted)?;
+        let ttl = match segment.expire_at() {
+            Some(expire_at) => {
+                let now = clock::recent();
+                if expire_at <= now {
+                    return Err(SegError::NotFound);
+                }
+                expire_at - now
+            }
+            None => segment.ttl(),
+        };
+
+        // copy out the current value and optional data, the reservation for
+        // the new item may cause the segment holding the old item to be
+        // evicted and reused
+        let item = self.get_no_freq_incr(key).ok_or(SegError::NotFound)?;
+        let current = match item.value() {
+            Value::Bytes(b) => b.to_vec(),
+            Value::U64(v) => format!("{}", v).into_bytes(),
+        };
+        let optional = item.optional().map(|o| o.to_vec());
+        let tag = item.tag();
+
+        let mut concatenated = Vec::with_capacity(current.len() + value.len());
+        if prepend {
+            concatenated.extend_from_slice(value);
+            concatenated.extend_from_slice(&current);
+        } else {
+            concatenated.extend_from_slice(&current);
+            concatenated.extend_from_slice(value);
+        }
+
+        let numeric = std::str::from_utf8(&concatenated)
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok());
+
+        match numeric {
+            Some(v) => self.insert_with_ttl(key, v.into(), optional.as_deref(), ttl, tag),
+            None => self.insert_with_ttl(
+                key,
+                concatenated.as_slice().into(),
+                optional.as_deref(),
+                ttl,
+                tag,
+            ),
+        }
+    }
+
     /// Remove the item with the given key, returns a bool indicating if it was
     /// removed.
     /// ```
@@ -252,12 +836,26 @@ impl Seg {
     /// ```
     // TODO(bmartin): a result would be better here
     pub fn delete(&mut self, key: &[u8]) -> bool {
+        if is_reserved_key(key) {
+            return false;
+        }
+        self.leases.remove(key);
+        if self.next_large > 0 {
+            if let Some(manifest) = self.manifest(key) {
+                self.remove_chunks(&manifest);
+            }
+        }
         self.hashtable
             .delete(key, &mut self.ttl_buckets, &mut self.segments)
     }
 
     /// Loops through the TTL Buckets to handle eager expiration, returns the
-    /// number of segments expired
+    /// number of segments expired. If an expiration budget is configured, the
+    /// remaining expired segments are left for the following calls, see
+    /// `expire_pending()`. Segments are kept for the stale grace period past
+    /// their TTL, see `Builder::stale_grace()`, and leases which have timed
+    /// out are removed. If the hashtable is growing, this also moves a batch
+    /// of items into the larger hashtable.
     /// ```
     /// use seg::{Policy, Seg, SegError};
     /// use std::time::Duration;
@@ -279,16 +877,137 @@ impl Seg {
     /// ```
     pub fn expire(&mut self) -> usize {
         common::time::refresh_clock();
-        self.time = Instant::recent();
-        self.ttl_buckets
-            .expire(&mut self.hashtable, &mut self.segments)
+        self.time = clock::recent();
+        let expired = self.ttl_buckets.expire(
+            &mut self.hashtable,
+            &mut self.segments,
+            self.expire_budget,
+            self.stale_grace,
+        ) + self.segments.expire_tier2(&mut self.hashtable);
+        self.leases.expire(self.time);
+        self.segments.update_namespaces();
+        self.hashtable.migrate(
+            MIGRATE_EXPIRE_BUCKETS,
+            &mut self.ttl_buckets,
+            &mut self.segments,
+        );
+        self.segments.shrink(
+            SHRINK_EXPIRE_SEGMENTS,
+            &mut self.ttl_buckets,
+            &mut self.hashtable,
+        );
+        expired
+    }
+
+    /// Persists the segments of a file-backed datapool along with the
+    /// metadata needed to restore the items when the cache is next built with
+    /// the same datapool, see `Builder::datapool_path()`. Only the segments
+    /// which have been used since the last checkpoint are written and
+    /// checksummed, so the time taken depends on the amount of writes rather
+    /// than the heap size. This is a no-op for in-memory datapools.
+    ///
+    /// The items cannot be restored once the heap has been resized, in which
+    /// case only the segments are persisted and any earlier checkpoint of the
+    /// metadata is removed.
+    ///
+    /// ```
+    /// use seg::Seg;
+    ///
+    /// let mut cache = Seg::builder().build().expect("failed to create cache");
+    /// assert!(cache.checkpoint().is_ok());
+    /// ```
+    pub fn checkpoint(&mut self) -> Result<(), std::io::Error> {
+        let path = match self.checkpoint_path.clone() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let start = std::time::Instant::now();
+        let mut result = self.segments.checkpoint();
+        if result.is_ok() {
+            result = if self.segments.is_restorable() {
+                checkpoint::write(self, &path)
+            } else {
+                match std::fs::remove_file(&path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                    _ => Ok(()),
+                }
+            };
+        }
+        DATAPOOL_CHECKPOINT_TIME.add(start.elapsed().as_nanos() as _);
+        if result.is_ok() {
+            DATAPOOL_CHECKPOINT.increment();
+        } else {
+            DATAPOOL_CHECKPOINT_EX.increment();
+        }
+        result
+    }
+
+    /// Checkpoints the cache, as `checkpoint()` does. This should be called
+    /// on graceful shutdown, so that the items are restored when the cache is
+    /// next built with the same datapool.
+    pub fn close(&mut self) -> Result<(), std::io::Error> {
+        self.checkpoint()
+    }
+
+    /// Returns true if the last call to `expire()` ran out of budget before
+    /// all the expired segments were removed, or if the heap is still being
+    /// shrunk, in which case `expire()` should be called again soon.
+    pub fn expire_pending(&self) -> bool {
+        self.ttl_buckets.expire_pending() || self.segments.cap() > self.segments.target()
+    }
+
+    /// Resize the heap to the provided size in bytes, which may be up to the
+    /// max heap size the cache was built with. Growing the heap maps more
+    /// memory and takes effect immediately. Shrinking the heap removes
+    /// segments from the end of the heap and releases their memory. Free
+    /// segments are removed immediately, while segments which hold items are
+    /// evicted a few at a time by `expire()`. Returns an error if the cache is
+    /// shared, see `into_shared()`.
+    ///
+    /// ```
+    /// use seg::Seg;
+    ///
+    /// const MB: usize = 1024 * 1024;
+    ///
+    /// let mut cache = Seg::builder()
+    ///     .heap_size(64 * MB)
+    ///     .max_heap_size(Some(256 * MB))
+    ///     .build()
+    ///     .expect("failed to create cache");
+    ///
+    /// cache.resize(256 * MB).expect("failed to grow heap");
+    /// assert_eq!(cache.heap_size(), 256 * MB);
+    ///
+    /// cache.resize(32 * MB).expect("failed to shrink heap");
+    /// assert_eq!(cache.heap_size(), 32 * MB);
+    /// ```
+    pub fn resize(&mut self, heap_size: usize) -> Result<(), std::io::Error> {
+        if self.shared {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "the heap cannot be resized while the cache is shared",
+            ));
+        }
+        let segments = heap_size / self.segments.segment_size() as usize;
+        self.segments.resize(segments)?;
+        self.segments
+            .shrink(0, &mut self.ttl_buckets, &mut self.hashtable);
+        Ok(())
+    }
+
+    /// Returns the current size of the heap in bytes, which may be larger than
+    /// the size passed to `resize()` while the heap is shrinking.
+    pub fn heap_size(&self) -> usize {
+        self.segments.cap() * self.segments.segment_size() as usize
     }
 
     pub fn clear(&mut self) -> usize {
         common::time::refresh_clock();
-        self.time = Instant::recent();
+        self.time = clock::recent();
         self.ttl_buckets
             .clear(&mut self.hashtable, &mut self.segments)
+            + self.segments.clear_tier2(&mut self.hashtable)
     }
 
     /// Checks the integrity of all segments
@@ -302,27 +1021,355 @@ impl Seg {
         }
     }
 
+    /// Returns an iterator over the live, unexpired items in the cache along
+    /// with their remaining TTL, starting from the provided `Cursor`. The
+    /// iterator may be dropped at any point and the scan resumed later using
+    /// the cursor returned by `Scan::cursor()`, which allows a scan to be
+    /// interleaved with other operations on the cache.
+    ///
+    /// ```
+    /// use seg::{Cursor, Seg};
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = Seg::builder().build().expect("failed to create cache");
+    /// cache.insert(b"coffee", b"strong", None, Duration::ZERO);
+    /// cache.insert(b"tea", b"green", None, Duration::ZERO);
+    ///
+    /// // take one item and then resume the scan
+    /// let mut scan = cache.scan(Cursor::default());
+    /// assert!(scan.next().is_some());
+    /// let cursor = scan.cursor();
+    ///
+    /// let mut scan = cache.scan(cursor);
+    /// assert!(scan.next().is_some());
+    /// assert!(scan.next().is_none());
+    /// assert!(scan.cursor().is_done());
+    /// ```
+    pub fn scan(&mut self, cursor: Cursor) -> Scan<'_> {
+        Scan::new(self, cursor)
+    }
+
+    /// Writes every live item to the writer using the portable dump format,
+    /// returning the number of items written. The expiry of each item is
+    /// recorded as an absolute time so that it is preserved when loaded. See
+    /// `DumpTask` for a dump which can be interleaved with other operations.
+    ///
+    /// ```
+    /// use seg::Seg;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = Seg::builder().build().expect("failed to create cache");
+    /// cache.insert(b"coffee", b"strong", Some(b"flag"), Duration::from_secs(60));
+    ///
+    /// let mut dump = Vec::new();
+    /// assert_eq!(cache.dump(&mut dump).unwrap(), 1);
+    ///
+    /// let mut other = Seg::builder().build().expect("failed to create cache");
+    /// assert_eq!(other.load(std::io::Cursor::new(dump)).unwrap(), 1);
+    /// let item = other.get(b"coffee").expect("didn't get item back");
+    /// assert_eq!(item.value(), b"strong");
+    /// assert_eq!(item.optional(), Some(&b"flag"[..]));
+    /// ```
+    pub fn dump<W: std::io::Write>(&mut self, writer: W) -> Result<usize, std::io::Error> {
+        let mut dump = DumpTask::new(writer)?;
+        while !dump.step(self, usize::MAX)? {}
+        dump.finish()
+    }
+
+    /// Inserts every item from a dump which has not yet expired, returning the
+    /// number of items loaded. The dump is verified before any item is
+    /// inserted, so an invalid dump returns an error and leaves the cache
+    /// unchanged. See `LoadTask` for a load which can be interleaved with
+    /// other operations.
+    pub fn load<R: std::io::Read + std::io::Seek>(
+        &mut self,
+        reader: R,
+    ) -> Result<usize, std::io::Error> {
+        let mut load = LoadTask::new(reader)?;
+        loop {
+            if let Some(loaded) = load.step(self, usize::MAX)? {
+                return Ok(loaded);
+            }
+        }
+    }
+
     /// Perform a wrapping addition on the value stored at the supplied key.
     /// Returns an error if the key is invalid, the item is not found, or the
-    /// stored value is not a numeric type.
+    /// stored value is not a numeric type. An item which is stored with its
+    /// own CAS value is given a new one.
     pub fn wrapping_add(&mut self, key: &[u8], rhs: u64) -> Result<Item, SegError> {
+        if is_reserved_key(key) {
+            return Err(SegError::NotFound);
+        }
         let mut item = self
             .hashtable
-            .get(key, self.time, &mut self.segments)
+            .get(key, self.time, &mut self.ttl_buckets, &mut self.segments)
+            .and_then(|item| self.check_tag(item))
+            .filter(|item| !self.is_stale(item))
             .ok_or(SegError::NotFound)?;
         item.wrapping_add(rhs)?;
+        if let Some(cas) = self.next_cas() {
+            item.set_cas(cas);
+        }
         Ok(item)
     }
 
     /// Perform a saturating subtraction on the value stored at the supplied
     /// key. Returns an error if the key is invalid, the item is not found, or
-    /// the stored value is not a numeric type.
+    /// the stored value is not a numeric type. An item which is stored with
+    /// its own CAS value is given a new one.
     pub fn saturating_sub(&mut self, key: &[u8], rhs: u64) -> Result<Item, SegError> {
+        if is_reserved_key(key) {
+            return Err(SegError::NotFound);
+        }
         let mut item = self
             .hashtable
-            .get(key, self.time, &mut self.segments)
+            .get(key, self.time, &mut self.ttl_buckets, &mut self.segments)
+            .and_then(|item| self.check_tag(item))
+            .filter(|item| !self.is_stale(item))
             .ok_or(SegError::NotFound)?;
         item.saturating_sub(rhs)?;
+        if let Some(cas) = self.next_cas() {
+            item.set_cas(cas);
+        }
         Ok(item)
     }
+
+    /// Returns a new per-item CAS value, or `None` if items use the CAS value
+    /// of their hash bucket
+    fn next_cas(&mut self) -> Option<u64> {
+        self.cas.as_mut().map(|cas| {
+            *cas += 1;
+            *cas
+        })
+    }
+
+    /// Returns the item unless it was stored with a tag which has since been
+    /// invalidated, in which case the item is removed
+    fn check_tag(&mut self, item: Item) -> Option<Item> {
+        let tag = match item.tag() {
+            Some(tag) => tag,
+            None => return Some(item),
+        };
+        if self.tags.as_ref().map(|tags| tags.is_current(tag)) == Some(true) {
+            return Some(item);
+        }
+        ITEM_TAG_INVALIDATED.increment();
+        self.delete(item.key());
+        None
+    }
+
+    /// Returns true if the item has expired and is only retained so that it
+    /// can be returned by `get_lease()`
+    fn is_stale(&self, item: &Item) -> bool {
+        self.stale_grace.as_secs() > 0 && item.is_expired(clock::recent())
+    }
+
+    /// The size of the largest item which fits within a single segment
+    fn max_item_size(&self) -> usize {
+        self.segments.segment_size() as usize - first_item_offset()
+    }
+
+    /// Returns the manifest if the item for the key is a large item
+    pub(crate) fn manifest(&mut self, key: &[u8]) -> Option<Manifest> {
+        let item =
+            self.hashtable
+                .get_no_freq_incr(key, &mut self.ttl_buckets, &mut self.segments)?;
+        if !item.is_large() {
+            return None;
+        }
+        match item.raw_value() {
+            Value::Bytes(manifest) => Manifest::decode(manifest),
+            Value::U64(_) => None,
+        }
+    }
+
+    /// Removes the chunks of a large item
+    fn remove_chunks(&mut self, manifest: &Manifest) {
+        for chunk_key in manifest.chunk_keys() {
+            self.hashtable
+                .delete(&chunk_key, &mut self.ttl_buckets, &mut self.segments);
+        }
+    }
+
+    /// Compresses the value if it is at least the compression threshold,
+    /// returning `None` if the value should be stored uncompressed.
+    fn compress(&self, value: &[u8]) -> Option<Vec<u8>> {
+        if value.len() < self.compression? {
+            return None;
+        }
+
+        let start = std::time::Instant::now();
+        let compressed = lz4_flex::compress_prepend_size(value);
+        ITEM_COMPRESS_TIME.add(start.elapsed().as_nanos() as _);
+
+        if compressed.len() >= value.len() {
+            ITEM_COMPRESS_SKIP.increment();
+            return None;
+        }
+
+        ITEM_COMPRESS.increment();
+        let bytes_in = ITEM_COMPRESS_BYTES_IN.add(value.len() as _) + value.len() as u64;
+        let bytes_out =
+            ITEM_COMPRESS_BYTES_OUT.add(compressed.len() as _) + compressed.len() as u64;
+        ITEM_COMPRESS_RATIO.set((bytes_in * 100 / bytes_out) as _);
+
+        Some(compressed)
+    }
+
+    /// Moves the item with the key from the second tier back into memory.
+    /// The item keeps the remaining TTL of the segment it was held in. Returns
+    /// true if the item was found in the second tier.
+    fn promote(&mut self, key: &[u8]) -> bool {
+        let item_info =
+            match self
+                .hashtable
+                .get_item_info(key, &mut self.ttl_buckets, &mut self.segments)
+            {
+                Some(item_info) => item_info,
+                None => return false,
+            };
+        let ttl = match self.segments.tier2_ttl(item_info) {
+            Some(ttl) => ttl,
+            None => return false,
+        };
+        let item = match self.segments.get_item(item_info) {
+            Some(item) => item,
+            None => return false,
+        };
+
+        // the item must be copied out, as linking may cause the segment which
+        // holds it to be reused
+        let (bytes, number) = match item.value() {
+            Value::Bytes(value) => (value.to_vec(), None),
+            Value::U64(value) => (Vec::new(), Some(value)),
+        };
+        let value = match number {
+            Some(value) => Value::U64(value),
+            None => Value::Bytes(&bytes),
+        };
+        let optional = item.optional().unwrap_or(&[]).to_vec();
+        let (large, compressed) = (item.is_large(), item.is_compressed());
+
+        // the item keeps its CAS value and tag, as it has not been modified
+        let cas = item.cas();
+        let tag = item.tag();
+
+        // a zero TTL would never expire, so an item in a segment which is due
+        // to expire is kept for at least a second
+        let ttl = std::cmp::max(ttl, Duration::from_secs(1));
+        let namespace = self.segments.namespace(key);
+        if self
+            .link(
+                key, value, &optional, ttl, namespace, large, compressed, cas, tag,
+            )
+            .is_ok()
+        {
+            TIER2_PROMOTE.increment();
+        }
+        true
+    }
+
+    /// Returns the item with the value that should be returned to the caller,
+    /// reassembling a large value from its chunks and decompressing it if
+    /// necessary. If the value cannot be recovered, such as when a chunk is
+    /// missing, or if the tag of the item was invalidated, the item is removed
+    /// and `None` is returned.
+    pub(crate) fn assemble(&mut self, item: Item, freq: bool) -> Option<Item> {
+        let item = self.check_tag(item)?;
+        if !item.is_large() {
+            if !item.is_compressed() {
+                return Some(item);
+            }
+            let value = match item.raw_value() {
+                Value::Bytes(value) => decompress(value),
+                Value::U64(_) => None,
+            };
+            return match value {
+                Some(value) => Some(item.with_value(value)),
+                None => {
+                    self.delete(item.key());
+                    None
+                }
+            };
+        }
+
+        let manifest = match item.raw_value() {
+            Value::Bytes(manifest) => Manifest::decode(manifest),
+            Value::U64(_) => None,
+        };
+
+        let mut value = Vec::new();
+        if let Some(manifest) = manifest {
+            value.reserve_exact(manifest.value_len());
+            for chunk_key in manifest.chunk_keys() {
+                // chunks are read with the same frequency accounting as the
+                // item so that they are retained alongside it
+                let chunk = if freq {
+                    self.hashtable.get(
+                        &chunk_key,
+                        self.time,
+                        &mut self.ttl_buckets,
+                        &mut self.segments,
+                    )
+                } else {
+                    self.hashtable.get_no_freq_incr(
+                        &chunk_key,
+                        &mut self.ttl_buckets,
+                        &mut self.segments,
+                    )
+                };
+                match chunk.as_ref().map(|c| c.value()) {
+                    Some(Value::Bytes(chunk)) => value.extend_from_slice(chunk),
+                    _ => break,
+                }
+            }
+        }
+
+        if manifest.map(|m| m.value_len()) != Some(value.len()) {
+            ITEM_LARGE_MISSING.increment();
+            self.delete(item.key());
+            return None;
+        }
+
+        if item.is_compressed() {
+            match decompress(&value) {
+                Some(value) => Some(item.with_value(value)),
+                None => {
+                    self.delete(item.key());
+                    None
+                }
+            }
+        } else {
+            Some(item.with_value(value.into_boxed_slice()))
+        }
+    }
+}
+
+// decompress a value which was stored compressed
+pub(crate) fn decompress(value: &[u8]) -> Option<Box<[u8]>> {
+    let start = std::time::Instant::now();
+    let result = lz4_flex::decompress_size_prepended(value);
+    ITEM_DECOMPRESS_TIME.add(start.elapsed().as_nanos() as _);
+
+    match result {
+        Ok(value) => {
+            ITEM_DECOMPRESS.increment();
+            Some(value.into_boxed_slice())
+        }
+        Err(_) => {
+            ITEM_DECOMPRESS_EX.increment();
+            None
+        }
+    }
+}
+
+// calculate the size of an item, rounded up for alignment. The extended flags
+// are stored for items which are large or compressed, as given by `ext`, and
+// for items with a CAS value or tag.
+fn item_size(klen: usize, vlen: usize, olen: usize, cas: bool, tag: bool, ext: bool) -> usize {
+    let ext = if ext || cas || tag { ITEM_EXT_SIZE } else { 0 };
+    let cas = if cas { CAS_SIZE } else { 0 };
+    let tag = if tag { Tag::SIZE } else { 0 };
+    (((ITEM_HDR_SIZE + ext + cas + tag + klen + vlen + olen) >> 3) + 1) << 3
 }
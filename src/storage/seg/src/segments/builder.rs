@@ -7,15 +7,23 @@
 use crate::eviction::*;
 use crate::item::*;
 use crate::segments::*;
+use crate::Namespace;
+use datapool::HugePages;
 
 use std::path::{Path, PathBuf};
 
 /// The `SegmentsBuilder` allows for the configuration of the segment storage.
 pub(crate) struct SegmentsBuilder {
     pub(super) heap_size: usize,
+    pub(super) max_heap_size: Option<usize>,
     pub(super) segment_size: i32,
-    pub(super) evict_policy: Policy,
+    pub(super) evict_policy: Box<dyn EvictionPolicy>,
     pub(super) datapool_path: Option<PathBuf>,
+    pub(super) huge_pages: HugePages,
+    pub(super) tier2_size: usize,
+    pub(super) tier2_path: Option<PathBuf>,
+    pub(super) namespaces: Vec<Namespace>,
+    pub(super) namespace_delimiter: Option<u8>,
 }
 
 impl Default for SegmentsBuilder {
@@ -23,8 +31,14 @@ impl Default for SegmentsBuilder {
         Self {
             segment_size: 1024 * 1024,
             heap_size: 64 * 1024 * 1024,
-            evict_policy: Policy::Random,
+            max_heap_size: None,
+            evict_policy: Policy::Random.into(),
             datapool_path: None,
+            huge_pages: HugePages::None,
+            tier2_size: 0,
+            tier2_path: None,
+            namespaces: Vec::new(),
+            namespace_delimiter: None,
         }
     }
 }
@@ -55,9 +69,17 @@ impl<'a> SegmentsBuilder {
         self
     }
 
-    /// Specify the eviction [`Policy`] which will be used when item allocation
+    /// Specify the largest heap size in bytes which the heap may be resized to
+    /// at runtime. Segment ids are reserved for the segments of the largest
+    /// heap, but memory is only mapped for the current heap size.
+    pub fn max_heap_size(mut self, bytes: Option<usize>) -> Self {
+        self.max_heap_size = bytes;
+        self
+    }
+
+    /// Specify the [`EvictionPolicy`] which will be used when item allocation
     /// fails due to memory pressure.
-    pub fn eviction_policy(mut self, policy: Policy) -> Self {
+    pub fn eviction_policy(mut self, policy: Box<dyn EvictionPolicy>) -> Self {
         self.evict_policy = policy;
         self
     }
@@ -70,6 +92,45 @@ impl<'a> SegmentsBuilder {
         self
     }
 
+    /// Specify whether the segment storage is backed by huge pages. If huge
+    /// pages are unavailable, the default page size is used instead.
+    pub fn huge_pages(mut self, huge_pages: HugePages) -> Self {
+        self.huge_pages = huge_pages;
+        self
+    }
+
+    /// Specify the size in bytes of the file-backed second tier. The size will
+    /// be divided by the segment size to determine the number of segments in
+    /// the second tier.
+    pub fn tier2_size(mut self, bytes: usize) -> Self {
+        self.tier2_size = bytes;
+        self
+    }
+
+    /// Specify the file used for the second tier. If provided, a file will be
+    /// created at the corresponding path and segments which would be evicted
+    /// have their items moved to it.
+    pub fn tier2_path<T: AsRef<Path>>(mut self, path: Option<T>) -> Self {
+        self.tier2_path = path.map(|p| p.as_ref().to_owned());
+        self
+    }
+
+    /// Specify the namespaces which partition the keyspace. Each namespace
+    /// may occupy up to its quota of segments before its segments are
+    /// preferred for eviction.
+    pub fn namespaces(mut self, namespaces: Vec<Namespace>) -> Self {
+        self.namespaces = namespaces;
+        self
+    }
+
+    /// Specify a delimiter which separates the namespace from the rest of the
+    /// key. If not provided, keys belong to the namespace with the longest
+    /// matching prefix.
+    pub fn namespace_delimiter(mut self, delimiter: Option<u8>) -> Self {
+        self.namespace_delimiter = delimiter;
+        self
+    }
+
     /// Construct the [`Segments`] from the builder
     pub fn build(self) -> Result<Segments, std::io::Error> {
         Segments::from_builder(self)
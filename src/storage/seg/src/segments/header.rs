@@ -15,11 +15,11 @@
 //! │   PREV SEG   │   NEXT SEG   │  CREATE AT   │   MERGE AT   │
 //! │              │              │              │              │
 //! │    32 bit    │    32 bit    │    32 bit    │    32 bit    │
-//! ├──────────────┼──┬──┬────────┴──────────────┴──────────────┤
-//! │     TTL      │  │  │               PADDING                │   Accessible
-//! │              │  │◀─┼──────────────────────────────────────┼──    8 bit
-//! │    32 bit    │8b│8b│                80 bit                │
-//! ├──────────────┴──┴──┴──────────────────────────────────────┤    Evictable
+//! ├──────────────┼──┬──┬────────┼──────────────┴──────────────┤
+//! │     TTL      │  │  │   NS   │           PADDING           │   Accessible
+//! │              │  │◀─┼────────┼─────────────────────────────┼──    8 bit
+//! │    32 bit    │8b│8b│ 16 bit │           64 bit            │
+//! ├──────────────┴──┴──┴────────┴─────────────────────────────┤    Evictable
 //! │                          PADDING                          │      8 bit
 //! │                                                           │
 //! │                          128 bit                          │
@@ -60,7 +60,9 @@ pub struct SegmentHeader {
     accessible: bool,
     /// Is the segment evictable?
     evictable: bool,
-    _pad: [u8; 25],
+    /// The namespace of the items in the segment
+    namespace: u16,
+    _pad: [u8; 23],
 }
 
 impl SegmentHeader {
@@ -72,12 +74,13 @@ impl SegmentHeader {
             live_items: 0,
             prev_seg: None,
             next_seg: None,
-            create_at: Instant::recent(),
+            create_at: clock::recent(),
             ttl: 0,
-            merge_at: Instant::recent(),
+            merge_at: clock::recent(),
             accessible: false,
             evictable: false,
-            _pad: [0; 25],
+            namespace: 0,
+            _pad: [0; 23],
         }
     }
 
@@ -93,8 +96,8 @@ impl SegmentHeader {
         self.prev_seg = None;
         self.next_seg = None;
         self.live_items = 0;
-        self.create_at = Instant::recent();
-        self.merge_at = Instant::recent();
+        self.create_at = clock::recent();
+        self.merge_at = clock::recent();
         self.accessible = true;
     }
 
@@ -150,6 +153,18 @@ impl SegmentHeader {
         self.accessible = accessible;
     }
 
+    #[inline]
+    /// The namespace of the items in the segment
+    pub fn namespace(&self) -> u16 {
+        self.namespace
+    }
+
+    #[inline]
+    /// Set the namespace of the items in the segment
+    pub fn set_namespace(&mut self, namespace: u16) {
+        self.namespace = namespace;
+    }
+
     #[inline]
     /// Is the segment evictable?
     pub fn evictable(&self) -> bool {
@@ -180,6 +195,14 @@ impl SegmentHeader {
         self.live_items -= 1;
     }
 
+    #[inline]
+    /// Sets the number of live items and bytes, which is used when the
+    /// segment is restored from a checkpoint.
+    pub fn set_live(&mut self, items: i32, bytes: i32) {
+        self.live_items = items;
+        self.live_bytes = bytes;
+    }
+
     #[inline]
     /// Returns the TTL for the segment.
     pub fn ttl(&self) -> Duration {
@@ -249,7 +272,25 @@ impl SegmentHeader {
     #[inline]
     /// Update the created time
     pub fn mark_created(&mut self) {
-        self.create_at = Instant::recent();
+        self.create_at = clock::recent();
+    }
+
+    #[inline]
+    /// Set the created time, which is used when the segment is restored from
+    /// a checkpoint.
+    pub fn set_create_at(&mut self, instant: Instant) {
+        self.create_at = instant;
+    }
+
+    #[inline]
+    /// Returns the instant at which the segment expires, or `None` if the
+    /// segment has the max TTL and does not expire
+    pub fn expire_at(&self) -> Option<Instant> {
+        if self.ttl >= MAX_TTL {
+            None
+        } else {
+            Some(self.create_at() + self.ttl())
+        }
     }
 
     #[inline]
@@ -261,7 +302,14 @@ impl SegmentHeader {
     #[inline]
     /// Update the created time
     pub fn mark_merged(&mut self) {
-        self.merge_at = Instant::recent();
+        self.merge_at = clock::recent();
+    }
+
+    #[inline]
+    /// Set the merged time, which is used when the segment is restored from a
+    /// checkpoint.
+    pub fn set_merge_at(&mut self, instant: Instant) {
+        self.merge_at = instant;
     }
 
     #[inline]
@@ -273,6 +321,6 @@ impl SegmentHeader {
     pub fn can_evict(&self) -> bool {
         self.evictable()
             && self.next_seg().is_some()
-            && (self.create_at() + self.ttl()) >= (Instant::recent() + SEG_MATURE_TIME)
+            && (self.create_at() + self.ttl()) >= (clock::recent() + SEG_MATURE_TIME)
     }
 }
@@ -0,0 +1,179 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! The memory which holds the in-memory segments.
+
+use core::ops::Range;
+use datapool::*;
+
+/// The `Heap` is made of one or more datapool regions, each of which holds a
+/// contiguous range of segments. Additional regions may be mapped to hold more
+/// segments, and the memory of segments at the end of the heap may be released,
+/// without moving any of the other segments.
+pub(crate) struct Heap {
+    regions: Vec<Region>,
+    segment_size: usize,
+    huge_pages: HugePages,
+    /// A flag for each segment in the first region, which is set once the
+    /// segment has been borrowed since the last checkpoint
+    dirty: Vec<bool>,
+}
+
+struct Region {
+    /// Index of the first segment in the region
+    first: usize,
+    /// Number of segments in the region
+    segments: usize,
+    data: Box<dyn Datapool>,
+}
+
+impl Heap {
+    /// Creates a heap with the datapool holding the initial segments. Regions
+    /// which are added later are in-memory, using the provided huge pages
+    /// setting.
+    pub fn new(
+        data: Box<dyn Datapool>,
+        segments: usize,
+        segment_size: usize,
+        huge_pages: HugePages,
+    ) -> Self {
+        Self {
+            regions: vec![Region {
+                first: 0,
+                segments,
+                data,
+            }],
+            segment_size,
+            huge_pages,
+            dirty: vec![false; segments],
+        }
+    }
+
+    /// Returns the number of segments which are mapped
+    pub fn segments(&self) -> usize {
+        self.regions
+            .last()
+            .map(|region| region.first + region.segments)
+            .unwrap_or(0)
+    }
+
+    /// Returns the number of segments in the first region
+    pub fn first_region(&self) -> usize {
+        self.regions[0].segments
+    }
+
+    /// Returns a pointer to the start of the first region
+    pub fn as_ptr(&self) -> *const u8 {
+        self.regions[0].data.as_slice().as_ptr()
+    }
+
+    /// Borrow the data for the segment at the index. Items may be changed in
+    /// place through any borrow, so the segment is included in the next
+    /// checkpoint.
+    pub fn segment(&mut self, idx: usize) -> &mut [u8] {
+        if let Some(dirty) = self.dirty.get_mut(idx) {
+            *dirty = true;
+        }
+        let segment_size = self.segment_size;
+        let region = self.region(idx);
+        let start = (idx - region.first) * segment_size;
+        &mut region.data.as_mut_slice()[start..(start + segment_size)]
+    }
+
+    /// Returns a pointer to the data for the segment at the index, which is
+    /// used to borrow the data of two segments at once
+    pub fn segment_ptr(&mut self, idx: usize) -> *mut u8 {
+        self.segment(idx).as_mut_ptr()
+    }
+
+    fn region(&mut self, idx: usize) -> &mut Region {
+        self.regions
+            .iter_mut()
+            .rev()
+            .find(|region| region.first <= idx)
+            .filter(|region| idx < region.first + region.segments)
+            .expect("segment is not mapped")
+    }
+
+    /// Persists the segments of the first region, which is the only one that
+    /// may be backed by a file. Only the segments which have been borrowed
+    /// since the last checkpoint are written and checksummed.
+    pub fn checkpoint(&mut self) -> Result<(), std::io::Error> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for (idx, _) in self.dirty.iter().enumerate().filter(|(_, dirty)| **dirty) {
+            let start = idx * self.segment_size;
+            let end = start + self.segment_size;
+            match ranges.last_mut() {
+                Some(range) if range.end == start => range.end = end,
+                _ => ranges.push(start..end),
+            }
+        }
+
+        self.regions[0].data.flush_ranges(&ranges)?;
+        self.dirty.fill(false);
+        Ok(())
+    }
+
+    /// Marks every segment in the first region as unchanged since the last
+    /// checkpoint
+    pub fn mark_clean(&mut self) {
+        self.dirty.fill(false);
+    }
+
+    /// Returns the checksum of the first region as of the last checkpoint, or
+    /// when it was opened, see `Datapool::checksum()`
+    pub fn checksum(&self) -> Option<[u8; 32]> {
+        self.regions[0].data.checksum()
+    }
+
+    /// Maps a new region so that the heap holds at least the provided number
+    /// of segments.
+    pub fn grow(&mut self, segments: usize) -> Result<(), std::io::Error> {
+        let first = self.segments();
+        if segments <= first {
+            return Ok(());
+        }
+
+        let size = (segments - first) * self.segment_size;
+        let data: Box<dyn Datapool> = match self.huge_pages {
+            HugePages::None => Box::new(Memory::create(size)?),
+            huge_pages => match Memory::create_huge(size, huge_pages) {
+                Ok(datapool) => Box::new(datapool),
+                Err(e) => {
+                    warn!("failed to create datapool using huge pages: {}", e);
+                    Box::new(Memory::create(size)?)
+                }
+            },
+        };
+
+        self.regions.push(Region {
+            first,
+            segments: segments - first,
+            data,
+        });
+
+        Ok(())
+    }
+
+    /// Releases the memory for the segments starting from the provided index.
+    /// Regions which only hold released segments are unmapped, the first
+    /// region is always kept.
+    pub fn release(&mut self, from: usize) {
+        while self.regions.len() > 1 && self.regions.last().unwrap().first >= from {
+            self.regions.pop();
+        }
+
+        let segment_size = self.segment_size;
+        let region = self.regions.last_mut().unwrap();
+        if from < region.first + region.segments {
+            let range = Range {
+                start: (from.max(region.first) - region.first) * segment_size,
+                end: region.segments * segment_size,
+            };
+            if let Err(e) = region.data.release(range) {
+                debug!("failed to release segment memory: {}", e);
+            }
+        }
+    }
+}
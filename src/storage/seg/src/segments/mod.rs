@@ -6,20 +6,33 @@
 
 use crate::*;
 
-const SEG_MAGIC: u64 = 0xBADC0FFEEBADCAFE;
+pub(crate) const SEG_MAGIC: u64 = 0xBADC0FFEEBADCAFE;
+
+/// Returns the offset of the first item in a segment
+pub(crate) fn first_item_offset() -> usize {
+    if cfg!(feature = "magic") {
+        std::mem::size_of_val(&SEG_MAGIC)
+    } else {
+        0
+    }
+}
 
 mod builder;
 mod error;
 mod header;
+mod heap;
 mod segment;
 #[allow(clippy::module_inception)]
 mod segments;
+mod tier;
 
 pub(crate) use builder::SegmentsBuilder;
 pub(crate) use error::SegmentsError;
 pub(crate) use header::SegmentHeader;
+pub(crate) use heap::Heap;
 pub(crate) use segment::Segment;
 pub(crate) use segments::Segments;
+pub(crate) use tier::Tier;
 
 #[cfg(test)]
 mod test {
@@ -183,6 +183,12 @@ impl<'a> Segment<'a> {
         self.header.set_evictable(evictable)
     }
 
+    /// Returns the namespace of the items in the segment
+    #[inline]
+    pub fn namespace(&self) -> u16 {
+        self.header.namespace()
+    }
+
     /// Performs some checks to determine if the segment can actually be evicted
     #[inline]
     pub fn can_evict(&self) -> bool {
@@ -207,6 +213,13 @@ impl<'a> Segment<'a> {
         self.header.create_at()
     }
 
+    /// Returns the instant at which the segment expires, or `None` if it does
+    /// not expire
+    #[inline]
+    pub fn expire_at(&self) -> Option<Instant> {
+        self.header.expire_at()
+    }
+
     /// Mark that the segment has been merged
     #[inline]
     pub fn mark_merged(&mut self) {
@@ -4,6 +4,7 @@
 
 use crate::eviction::*;
 use crate::item::*;
+use crate::namespace::*;
 use crate::segments::*;
 use core::num::NonZeroU32;
 use datapool::*;
@@ -14,20 +15,29 @@ use datapool::*;
 pub(crate) struct Segments {
     /// Pointer to slice of headers
     headers: Box<[SegmentHeader]>,
-    /// Pointer to raw data
-    data: Box<dyn Datapool>,
+    /// Memory holding the segment data
+    data: Heap,
     /// Segment size in bytes
     segment_size: i32,
     /// Number of free segments
     free: u32,
     /// Total number of segments
     cap: u32,
+    /// Number of segments the heap is being resized to
+    target: u32,
+    /// Number of segment ids reserved for in-memory segments, which is the
+    /// largest number of segments the heap may be resized to
+    max: u32,
     /// Head of the free segment queue
     free_q: Option<NonZeroU32>,
     /// Time last flushed
     flush_at: Instant,
     /// Eviction configuration and state
     evict: Box<Eviction>,
+    /// File-backed second tier, which holds items from evicted segments
+    tier2: Option<Box<Tier>>,
+    /// Quotas and stats for each namespace, if namespaces are configured
+    namespaces: Option<Box<NamespaceTable>>,
 }
 
 impl Segments {
@@ -36,24 +46,26 @@ impl Segments {
     pub(super) fn from_builder(builder: SegmentsBuilder) -> Result<Self, std::io::Error> {
         let segment_size = builder.segment_size;
         let segments = builder.heap_size / (builder.segment_size as usize);
+        let max = std::cmp::max(
+            segments,
+            builder.max_heap_size.unwrap_or(0) / (builder.segment_size as usize),
+        );
 
         debug!(
-            "heap size: {} seg size: {} segments: {}",
-            builder.heap_size, segment_size, segments
+            "heap size: {} seg size: {} segments: {} max segments: {}",
+            builder.heap_size, segment_size, segments, max
         );
 
         assert!(
-            segments < (1 << 24), // we use just 24 bits to store the seg id
+            max < (1 << 24), // we use just 24 bits to store the seg id
             "heap size requires too many segments, reduce heap size or increase segment size"
         );
 
         let evict_policy = builder.evict_policy;
 
-        debug!("eviction policy: {:?}", evict_policy);
-
         let mut headers = Vec::with_capacity(0);
-        headers.reserve_exact(segments);
-        for id in 0..segments {
+        headers.reserve_exact(max);
+        for id in 0..max {
             // safety: we start iterating from 1 and seg id is constrained to < 2^24
             let header = SegmentHeader::new(unsafe { NonZeroU32::new_unchecked(id as u32 + 1) });
             headers.push(header);
@@ -62,22 +74,40 @@ impl Segments {
 
         let heap_size = segments * segment_size as usize;
 
-        // TODO(bmartin): we will need to make additional changes before we
-        // allow restoring state from an existing datapool file, for now this
-        // retains the previous behavior and always creates a new file to mmap
-        // if a datapool path is provided.
-        let mut data: Box<dyn Datapool> = if let Some(file) = builder.datapool_path {
-            Box::new(MmapFile::create(file, heap_size, crate::VERSION)?)
-        } else {
-            Box::new(Memory::create(heap_size)?)
+        // an existing file is reused if it was flushed and still matches its
+        // checksums, and is otherwise discarded. The segments always start out
+        // empty, and are restored afterwards if there is a checkpoint which
+        // matches the file, see `Seg::checkpoint()`.
+        let data: Box<dyn Datapool> = match (builder.datapool_path, builder.huge_pages) {
+            (Some(file), HugePages::Explicit) => {
+                match MmapFile::open_or_create_huge(&file, heap_size, crate::VERSION) {
+                    Ok(datapool) => Box::new(datapool),
+                    Err(e) => {
+                        warn!("failed to create datapool using huge pages: {}", e);
+                        Box::new(MmapFile::open_or_create(file, heap_size, crate::VERSION)?)
+                    }
+                }
+            }
+            (Some(file), huge_pages) => {
+                if huge_pages == HugePages::Transparent {
+                    warn!("transparent huge pages are not used for file-backed datapools");
+                }
+                Box::new(MmapFile::open_or_create(file, heap_size, crate::VERSION)?)
+            }
+            (None, HugePages::None) => Box::new(Memory::create(heap_size)?),
+            (None, huge_pages) => match Memory::create_huge(heap_size, huge_pages) {
+                Ok(datapool) => Box::new(datapool),
+                Err(e) => {
+                    warn!("failed to create datapool using huge pages: {}", e);
+                    Box::new(Memory::create(heap_size)?)
+                }
+            },
         };
 
-        for idx in 0..segments {
-            let begin = segment_size as usize * idx;
-            let end = begin + segment_size as usize;
+        let mut data = Heap::new(data, segments, segment_size as usize, builder.huge_pages);
 
-            let mut segment =
-                Segment::from_raw_parts(&mut headers[idx], &mut data.as_mut_slice()[begin..end]);
+        for idx in 0..segments {
+            let mut segment = Segment::from_raw_parts(&mut headers[idx], data.segment(idx));
             segment.init();
 
             let id = idx as u32 + 1; // we index segments from 1
@@ -87,18 +117,46 @@ impl Segments {
             }
         }
 
+        let tier2 = match builder.tier2_path {
+            Some(path) if builder.tier2_size >= segment_size as usize => {
+                let tier2 = Tier::create(path, builder.tier2_size, segment_size, max as u32 + 1)?;
+                assert!(
+                    max + tier2.cap() < (1 << 24), // we use just 24 bits to store the seg id
+                    "tier2 size requires too many segments, reduce tier2 size or increase segment size"
+                );
+                Some(Box::new(tier2))
+            }
+            _ => None,
+        };
+
+        let namespaces = if builder.namespaces.is_empty() {
+            None
+        } else {
+            Some(Box::new(NamespaceTable::new(
+                builder.namespaces,
+                builder.namespace_delimiter,
+                max,
+            )))
+        };
+
         SEGMENT_CURRENT.set(segments as _);
+        SEGMENT_TARGET.set(segments as _);
         SEGMENT_FREE.set(segments as _);
+        TIER1_SIZE.set(heap_size as _);
 
         Ok(Self {
             headers,
             segment_size,
             cap: segments as u32,
+            target: segments as u32,
+            max: max as u32,
             free: segments as u32,
             free_q: NonZeroU32::new(1),
             data,
-            flush_at: Instant::now(),
-            evict: Box::new(Eviction::new(segments, evict_policy)),
+            flush_at: clock::now(),
+            evict: Box::new(Eviction::new(evict_policy)),
+            tier2,
+            namespaces,
         })
     }
 
@@ -108,6 +166,39 @@ impl Segments {
         self.segment_size
     }
 
+    /// Return the total number of segments
+    #[inline]
+    pub fn cap(&self) -> usize {
+        self.cap as usize
+    }
+
+    /// Returns the number of segments the heap is being resized to
+    pub fn target(&self) -> usize {
+        self.target as usize
+    }
+
+    /// Returns the largest number of segments the heap may be resized to
+    pub fn max(&self) -> usize {
+        self.max as usize
+    }
+
+    /// Returns true if segments may be held outside of the initial allocation,
+    /// which happens when the heap grows beyond its initial size
+    pub fn can_grow(&self) -> bool {
+        self.max as usize > self.data.first_region()
+    }
+
+    /// Returns a pointer to the start of the initial allocation, which holds
+    /// all the segments unless the heap can grow
+    pub(crate) fn heap_ptr(&self) -> *const u8 {
+        self.data.as_ptr()
+    }
+
+    /// Returns a pointer to the segment headers, which are never reallocated
+    pub(crate) fn headers_ptr(&self) -> *const SegmentHeader {
+        self.headers.as_ptr()
+    }
+
     /// Returns the number of free segments
     #[cfg(test)]
     pub fn free(&self) -> usize {
@@ -119,11 +210,88 @@ impl Segments {
         self.flush_at
     }
 
+    /// Returns a pointer to the flush time, for readers which do not hold a
+    /// reference to the segments
+    pub(crate) fn flush_at_ptr(&self) -> *const Instant {
+        &self.flush_at
+    }
+
     /// Mark the segments as flushed at a given instant
     pub fn set_flush_at(&mut self, instant: Instant) {
         self.flush_at = instant;
     }
 
+    /// Persists the in-memory segments if they are backed by a file, see
+    /// `Datapool::flush_ranges()`. Only the segments which have been used since
+    /// the last checkpoint are written.
+    pub(crate) fn checkpoint(&mut self) -> Result<(), std::io::Error> {
+        self.data.checkpoint()
+    }
+
+    /// Returns the checksum of the file backing the in-memory segments, as of
+    /// the last checkpoint or when the file was opened. This is `None` if the
+    /// segments are not backed by a file or the file has not been flushed.
+    pub(crate) fn checksum(&self) -> Option<[u8; 32]> {
+        self.data.checksum()
+    }
+
+    /// Returns true if every in-memory segment is held in the file, so that
+    /// the segments may be restored from a checkpoint. This is not the case
+    /// once the heap has been resized.
+    pub(crate) fn is_restorable(&self) -> bool {
+        self.cap == self.target && self.cap as usize == self.data.first_region()
+    }
+
+    /// Returns the headers of the in-memory segments
+    pub(crate) fn headers(&self) -> &[SegmentHeader] {
+        &self.headers[..self.cap as usize]
+    }
+
+    /// Returns the headers of the in-memory segments, which are changed
+    /// directly only to restore a checkpoint
+    pub(crate) fn headers_mut(&mut self) -> &mut [SegmentHeader] {
+        &mut self.headers[..self.cap as usize]
+    }
+
+    /// Returns the head of the free queue and the number of free segments
+    pub(crate) fn free_q(&self) -> (Option<NonZeroU32>, u32) {
+        (self.free_q, self.free)
+    }
+
+    /// Replaces the free queue, which is used to restore a checkpoint
+    pub(crate) fn set_free_q(&mut self, head: Option<NonZeroU32>, free: u32) {
+        self.free_q = head;
+        self.free = free;
+        SEGMENT_FREE.set(free as _);
+    }
+
+    /// Marks every segment as unchanged since the last checkpoint, which is
+    /// used once the segments are restored from the file
+    pub(crate) fn mark_clean(&mut self) {
+        self.data.mark_clean();
+    }
+
+    /// Evicts the items of a segment which is in a ttl bucket and returns the
+    /// segment to the free queue
+    pub(crate) fn discard(
+        &mut self,
+        id: NonZeroU32,
+        ttl_buckets: &mut TtlBuckets,
+        hashtable: &mut HashTable,
+    ) {
+        self.remove_from_bucket(id, ttl_buckets, hashtable);
+        self.push_free(id);
+    }
+
+    /// Returns the names of the configured namespaces, excluding the default
+    /// namespace
+    pub(crate) fn namespace_names(&self) -> Vec<Vec<u8>> {
+        match &self.namespaces {
+            Some(namespaces) => namespaces.names().map(|name| name.to_vec()).collect(),
+            None => Vec::new(),
+        }
+    }
+
     /// Retrieve a `RawItem` from the segment id and offset encoded in the
     /// item info.
     pub(crate) fn get_item(&mut self, item_info: u64) -> Option<RawItem> {
@@ -141,18 +309,38 @@ impl Segments {
     ) -> Option<RawItem> {
         let seg_id = seg_id.map(|v| v.get())?;
         trace!("getting item from: seg: {} offset: {}", seg_id, offset);
-        assert!(seg_id <= self.cap as u32);
+        if seg_id > self.max {
+            return self
+                .tier2
+                .as_mut()?
+                .get_mut(NonZeroU32::new(seg_id)?)?
+                .get_item_at(offset);
+        } else if seg_id > self.cap {
+            return None;
+        }
 
-        let seg_begin = self.segment_size() as usize * (seg_id as usize - 1);
-        let seg_end = seg_begin + self.segment_size() as usize;
         let mut segment = Segment::from_raw_parts(
             &mut self.headers[seg_id as usize - 1],
-            &mut self.data.as_mut_slice()[seg_begin..seg_end],
+            self.data.segment(seg_id as usize - 1),
         );
 
         segment.get_item_at(offset)
     }
 
+    /// Returns the time the segment holding the item was created and the time
+    /// at which it expires
+    pub(crate) fn item_lifetime(&self, item_info: u64) -> Option<(Instant, Option<Instant>)> {
+        let id = get_seg_id(item_info)?;
+        let header = if id.get() <= self.cap {
+            &self.headers[id.get() as usize - 1]
+        } else if id.get() > self.max {
+            self.tier2.as_ref()?.header(id)?
+        } else {
+            return None;
+        };
+        Some((header.create_at(), header.expire_at()))
+    }
+
     /// Tries to clear a segment by id
     fn clear_segment(
         &mut self,
@@ -182,73 +370,88 @@ impl Segments {
         ttl_buckets: &mut TtlBuckets,
         hashtable: &mut HashTable,
     ) -> Result<(), SegmentsError> {
-        let now = Instant::now();
-        match self.evict.policy() {
-            Policy::Merge { .. } => {
-                SEGMENT_EVICT.increment();
-
-                let mut seg_idx = self.evict.random();
-
-                seg_idx %= self.cap;
-                let ttl = self.headers[seg_idx as usize].ttl();
-                let offset = ttl_buckets.get_bucket_index(ttl);
-                let buckets = ttl_buckets.buckets.len();
-
-                // since merging starts in the middle of a segment chain, we may
-                // need to loop back around to the first ttl bucket we checked
-                for i in 0..=buckets {
-                    let bucket_id = (offset + i) % buckets;
-                    let ttl_bucket = &mut ttl_buckets.buckets[bucket_id];
-                    if let Some(first_seg) = ttl_bucket.head() {
-                        let start = ttl_bucket.next_to_merge().unwrap_or(first_seg);
-                        match self.merge_evict(start, hashtable) {
-                            Ok(next_to_merge) => {
-                                debug!("merged ttl_bucket: {} seg: {}", bucket_id, start);
-                                ttl_bucket.set_next_to_merge(next_to_merge);
-                                EVICT_TIME.add(now.elapsed().as_nanos() as _);
-                                return Ok(());
-                            }
-                            Err(_) => {
-                                SEGMENT_EVICT_EX.increment();
-                                ttl_bucket.set_next_to_merge(None);
-                                continue;
+        let now = clock::now();
+        if self.evict.merge().is_some() {
+            SEGMENT_EVICT.increment();
+
+            // start with a namespace which is over quota, if there is one
+            let seg_idx = match self.over_quota_seg() {
+                Some(id) => id.get() - 1,
+                None => self.evict.random() % self.cap,
+            };
+            let namespace = self.headers[seg_idx as usize].namespace();
+            let ttl = self.headers[seg_idx as usize].ttl();
+            let offset = ttl_buckets.get_namespace_bucket_index(namespace, ttl);
+            let buckets = ttl_buckets.buckets.len();
+
+            // since merging starts in the middle of a segment chain, we may
+            // need to loop back around to the first ttl bucket we checked
+            for i in 0..=buckets {
+                let bucket_id = (offset + i) % buckets;
+                let ttl_bucket = &mut ttl_buckets.buckets[bucket_id];
+                if let Some(first_seg) = ttl_bucket.head() {
+                    let start = ttl_bucket.next_to_merge().unwrap_or(first_seg);
+                    let evicted = ITEM_EVICT.value();
+                    match self.merge_evict(start, hashtable) {
+                        Ok(next_to_merge) => {
+                            debug!("merged ttl_bucket: {} seg: {}", bucket_id, start);
+                            if let Some(namespaces) = &self.namespaces {
+                                namespaces.evict(
+                                    self.headers[start.get() as usize - 1].namespace(),
+                                    (ITEM_EVICT.value() - evicted) as usize,
+                                );
                             }
+                            ttl_bucket.set_next_to_merge(next_to_merge);
+                            EVICT_TIME.add(now.elapsed().as_nanos() as _);
+                            return Ok(());
+                        }
+                        Err(_) => {
+                            SEGMENT_EVICT_EX.increment();
+                            ttl_bucket.set_next_to_merge(None);
+                            continue;
                         }
                     }
                 }
-                SEGMENT_EVICT_EX.increment();
-                EVICT_TIME.add(now.elapsed().as_nanos() as _);
-                Err(SegmentsError::NoEvictableSegments)
-            }
-            Policy::None => {
-                EVICT_TIME.add(now.elapsed().as_nanos() as _);
-                Err(SegmentsError::NoEvictableSegments)
             }
-            _ => {
-                SEGMENT_EVICT.increment();
-                if let Some(id) = self.least_valuable_seg(ttl_buckets) {
-                    let result = self
-                        .clear_segment(id, hashtable, false)
-                        .map_err(|_| SegmentsError::EvictFailure);
-
-                    if result.is_err() {
-                        EVICT_TIME.add(now.elapsed().as_nanos() as _);
-                        return result;
+            SEGMENT_EVICT_EX.increment();
+            EVICT_TIME.add(now.elapsed().as_nanos() as _);
+            Err(SegmentsError::NoEvictableSegments)
+        } else {
+            SEGMENT_EVICT.increment();
+            if let Some(id) = self.least_valuable_seg() {
+                self.demote(id, hashtable);
+
+                if let Some(namespaces) = &self.namespaces {
+                    let header = &self.headers[id.get() as usize - 1];
+                    if header.next_seg().is_some() {
+                        namespaces.evict(header.namespace(), header.live_items() as usize);
                     }
+                }
 
-                    let id_idx = id.get() as usize - 1;
-                    if self.headers[id_idx].prev_seg().is_none() {
-                        let ttl_bucket = ttl_buckets.get_mut_bucket(self.headers[id_idx].ttl());
-                        ttl_bucket.set_head(self.headers[id_idx].next_seg());
-                    }
-                    self.push_free(id);
-                    EVICT_TIME.add(now.elapsed().as_nanos() as _);
-                    Ok(())
-                } else {
-                    SEGMENT_EVICT_EX.increment();
+                let result = self
+                    .clear_segment(id, hashtable, false)
+                    .map_err(|_| SegmentsError::EvictFailure);
+
+                if result.is_err() {
                     EVICT_TIME.add(now.elapsed().as_nanos() as _);
-                    Err(SegmentsError::NoEvictableSegments)
+                    return result;
+                }
+
+                let id_idx = id.get() as usize - 1;
+                if self.headers[id_idx].prev_seg().is_none() {
+                    let ttl_bucket = ttl_buckets.get_mut_bucket(
+                        self.headers[id_idx].namespace(),
+                        self.headers[id_idx].ttl(),
+                    );
+                    ttl_bucket.set_head(self.headers[id_idx].next_seg());
                 }
+                self.push_free(id);
+                EVICT_TIME.add(now.elapsed().as_nanos() as _);
+                Ok(())
+            } else {
+                SEGMENT_EVICT_EX.increment();
+                EVICT_TIME.add(now.elapsed().as_nanos() as _);
+                Err(SegmentsError::NoEvictableSegments)
             }
         }
     }
@@ -256,17 +459,19 @@ impl Segments {
     /// Returns a mutable `Segment` view for the segment with the specified id
     pub(crate) fn get_mut(&mut self, id: NonZeroU32) -> Result<Segment, SegmentsError> {
         let id = id.get() as usize - 1;
-        if id < self.headers.len() {
+        if id < self.cap as usize {
             let header = self.headers.get_mut(id).unwrap();
-
-            let seg_start = self.segment_size as usize * id;
-            let seg_end = self.segment_size as usize * (id + 1);
-
-            let seg_data = &mut self.data.as_mut_slice()[seg_start..seg_end];
+            let seg_data = self.data.segment(id);
 
             let segment = Segment::from_raw_parts(header, seg_data);
             segment.check_magic();
             Ok(segment)
+        } else if id < self.max as usize {
+            Err(SegmentsError::BadSegmentId)
+        } else if let Some(tier2) = &mut self.tier2 {
+            tier2
+                .get_mut(NonZeroU32::new(id as u32 + 1).unwrap())
+                .ok_or(SegmentsError::BadSegmentId)
         } else {
             Err(SegmentsError::BadSegmentId)
         }
@@ -284,7 +489,7 @@ impl Segments {
         } else {
             let a = a.get() as usize - 1;
             let b = b.get() as usize - 1;
-            if a >= self.headers.len() || b >= self.headers.len() {
+            if a >= self.cap as usize || b >= self.cap as usize {
                 return Err(SegmentsError::BadSegmentId);
             }
             // we have already guaranteed that 'a' and 'b' are not the same, so
@@ -296,29 +501,8 @@ impl Segments {
                 let header_a = &mut self.headers[a] as *mut _;
                 let header_b = &mut self.headers[b] as *mut _;
 
-                let data = self.data.as_mut_slice();
-
-                // split the borrowed data
-                let split = (std::cmp::min(a, b) + 1) * seg_size;
-                let (first, second) = data.split_at_mut(split);
-
-                let (data_a, data_b) = if a < b {
-                    let start_a = seg_size * a;
-                    let end_a = seg_size * (a + 1);
-
-                    let start_b = (seg_size * b) - first.len();
-                    let end_b = (seg_size * (b + 1)) - first.len();
-
-                    (&mut first[start_a..end_a], &mut second[start_b..end_b])
-                } else {
-                    let start_a = (seg_size * a) - first.len();
-                    let end_a = (seg_size * (a + 1)) - first.len();
-
-                    let start_b = seg_size * b;
-                    let end_b = seg_size * (b + 1);
-
-                    (&mut second[start_a..end_a], &mut first[start_b..end_b])
-                };
+                let data_a = std::slice::from_raw_parts_mut(self.data.segment_ptr(a), seg_size);
+                let data_b = std::slice::from_raw_parts_mut(self.data.segment_ptr(b), seg_size);
 
                 let segment_a = Segment::from_raw_parts(&mut *header_a, data_a);
                 let segment_b = Segment::from_raw_parts(&mut *header_b, data_b);
@@ -375,6 +559,9 @@ impl Segments {
         let id_idx = id.get() as usize - 1;
         assert!(!self.headers[id_idx].evictable());
         self.headers[id_idx].set_accessible(false);
+        if let Some(namespaces) = &mut self.namespaces {
+            namespaces.release(id);
+        }
 
         self.headers[id_idx].reset();
 
@@ -427,67 +614,262 @@ impl Segments {
         }
     }
 
-    // TODO(bmartin): use a result here, not option
-    /// Returns the least valuable segment based on the configured eviction
-    /// policy. An eviction attempt should be made for the corresponding segment
-    /// before moving on to the next least valuable segment.
-    pub(crate) fn least_valuable_seg(
+    /// Sets the number of segments the heap is being resized to. Segments are
+    /// added immediately, by mapping more memory if needed. When the heap is
+    /// shrinking, segments are removed from the end of the heap by calls to
+    /// `shrink()`.
+    pub(crate) fn resize(&mut self, segments: usize) -> Result<(), std::io::Error> {
+        if segments == 0 || segments > self.max as usize {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "heap size must hold at least one segment and no more than the max heap size",
+            ));
+        }
+
+        self.target = segments as u32;
+        SEGMENT_TARGET.set(segments as _);
+
+        if segments > self.cap as usize {
+            self.data.grow(segments)?;
+
+            // new segments are added to the free queue
+            for idx in self.cap as usize..segments {
+                let mut segment =
+                    Segment::from_raw_parts(&mut self.headers[idx], self.data.segment(idx));
+                segment.init();
+                segment.set_accessible(false);
+
+                let id = segment.id();
+                self.push_front(id, self.free_q);
+                self.free_q = Some(id);
+                self.free += 1;
+                SEGMENT_FREE.increment();
+            }
+
+            self.set_cap(segments as u32);
+        }
+
+        Ok(())
+    }
+
+    /// Removes segments from the end of the heap while the heap is larger than
+    /// the target size. Free segments are removed immediately, and up to
+    /// `limit` segments which hold items are evicted and removed. Returns true
+    /// if there are segments which remain to be removed.
+    pub(crate) fn shrink(
         &mut self,
+        limit: usize,
         ttl_buckets: &mut TtlBuckets,
-    ) -> Option<NonZeroU32> {
-        match self.evict.policy() {
-            Policy::None => None,
-            Policy::Random => {
-                let mut start: u32 = self.evict.random();
-
-                start %= self.cap;
-
-                for i in 0..self.cap {
-                    let idx = (start + i) % self.cap;
-                    if self.headers[idx as usize].can_evict() {
-                        // safety: we are always adding 1 to the index
-                        return Some(unsafe { NonZeroU32::new_unchecked(idx + 1) });
-                    }
+        hashtable: &mut HashTable,
+    ) -> bool {
+        let cap = self.cap;
+        let mut evicted = 0;
+
+        while self.cap > self.target {
+            let id_idx = self.cap as usize - 1;
+            // safety: the id is at least one, since the target is non-zero
+            let id = unsafe { NonZeroU32::new_unchecked(self.cap) };
+
+            if self.headers[id_idx].evictable() {
+                // the segment is in a ttl bucket and holds items
+                if evicted >= limit {
+                    break;
                 }
-
-                None
-            }
-            Policy::RandomFifo => {
-                // This strategy is implemented by picking a random accessible
-                // segment and looking up the head of the corresponding
-                // `TtlBucket` and evicting that segment. This is functionally
-                // equivalent to picking a `TtlBucket` from a weighted
-                // distribution based on the number of segments per bucket.
-
-                let mut start: u32 = self.evict.random();
-
-                start %= self.cap;
-
-                for i in 0..self.cap {
-                    let idx = (start + i) % self.cap;
-                    if self.headers[idx as usize].accessible() {
-                        let ttl = self.headers[idx as usize].ttl();
-                        let ttl_bucket = ttl_buckets.get_mut_bucket(ttl);
-                        return ttl_bucket.head();
-                    }
+                evicted += 1;
+                self.remove_from_bucket(id, ttl_buckets, hashtable);
+            } else {
+                // the segment is in the free queue
+                let next = self.headers[id_idx].next_seg();
+                let prev = self.headers[id_idx].prev_seg();
+                if let Some(next) = next {
+                    self.headers[next.get() as usize - 1].set_prev_seg(prev);
                 }
-
-                None
-            }
-            _ => {
-                if self.evict.should_rerank() {
-                    self.evict.rerank(&self.headers);
+                match prev {
+                    Some(prev) => self.headers[prev.get() as usize - 1].set_next_seg(next),
+                    None => self.free_q = next,
                 }
-                while let Some(id) = self.evict.least_valuable_seg() {
-                    if let Ok(seg) = self.get_mut(id) {
-                        if seg.can_evict() {
-                            return Some(id);
-                        }
-                    }
+                self.free -= 1;
+                SEGMENT_FREE.decrement();
+            }
+
+            let header = &mut self.headers[id_idx];
+            header.set_accessible(false);
+            header.set_prev_seg(None);
+            header.set_next_seg(None);
+            header.reset();
+            self.set_cap(self.cap - 1);
+        }
+
+        if self.cap < cap {
+            self.data.release(self.cap as usize);
+        }
+
+        self.cap > self.target
+    }
+
+    /// Evicts the items of a segment and removes it from its ttl bucket
+    fn remove_from_bucket(
+        &mut self,
+        id: NonZeroU32,
+        ttl_buckets: &mut TtlBuckets,
+        hashtable: &mut HashTable,
+    ) {
+        let id_idx = id.get() as usize - 1;
+        let header = &self.headers[id_idx];
+        let prev = header.prev_seg();
+        let next = header.next_seg();
+
+        let ttl_bucket = ttl_buckets.get_mut_bucket(header.namespace(), header.ttl());
+        if prev.is_none() {
+            ttl_bucket.set_head(next);
+        }
+        if next.is_none() {
+            ttl_bucket.set_tail(prev);
+        }
+        if ttl_bucket.next_to_merge() == Some(id) {
+            ttl_bucket.set_next_to_merge(None);
+        }
+
+        if let Some(namespaces) = &self.namespaces {
+            namespaces.evict(header.namespace(), header.live_items() as usize);
+        }
+
+        // items are kept in the second tier, if there is one
+        self.demote(id, hashtable);
+
+        SEGMENT_EVICT.increment();
+        let mut segment = self.get_mut(id).unwrap();
+        segment.clear(hashtable, false);
+        self.unlink(id);
+        if let Some(namespaces) = &mut self.namespaces {
+            namespaces.release(id);
+        }
+    }
+
+    fn set_cap(&mut self, cap: u32) {
+        self.cap = cap;
+        SEGMENT_CURRENT.set(cap as _);
+        TIER1_SIZE.set(cap as i64 * self.segment_size as i64);
+    }
+
+    // TODO(bmartin): use a result here, not option
+    /// Returns the least valuable segment based on the configured eviction
+    /// policy. An eviction attempt should be made for the corresponding segment
+    /// before moving on to the next least valuable segment.
+    pub(crate) fn least_valuable_seg(&mut self) -> Option<NonZeroU32> {
+        if let Some(id) = self.over_quota_seg() {
+            return Some(id);
+        }
+        for _ in 0..self.cap {
+            let id = self.evict.select(SegmentStats::from_headers(
+                &self.headers[..self.cap as usize],
+            ))?;
+            if let Ok(seg) = self.get_mut(id) {
+                if seg.can_evict() {
+                    return Some(id);
                 }
-                None
             }
         }
+        None
+    }
+
+    /// Moves the items in a segment which is about to be evicted into the
+    /// second tier, if there is one. The newest segment in a `TtlBucket` is
+    /// skipped, as it cannot be evicted.
+    fn demote(&mut self, id: NonZeroU32, hashtable: &mut HashTable) {
+        let tier2 = match &mut self.tier2 {
+            Some(tier2) => tier2,
+            None => return,
+        };
+
+        let id_idx = id.get() as usize - 1;
+        if self.headers[id_idx].next_seg().is_none() {
+            return;
+        }
+
+        let mut segment =
+            Segment::from_raw_parts(&mut self.headers[id_idx], self.data.segment(id_idx));
+        tier2.demote(&mut segment, hashtable);
+    }
+
+    /// Returns the remaining TTL of the item if it is held in the second tier
+    pub(crate) fn tier2_ttl(&self, item_info: u64) -> Option<Duration> {
+        self.tier2.as_ref()?.ttl(get_seg_id(item_info)?)
+    }
+
+    /// Returns true if there is a second tier
+    pub(crate) fn has_tier2(&self) -> bool {
+        self.tier2.is_some()
+    }
+
+    /// Expires segments in the second tier, returning the number expired
+    pub(crate) fn expire_tier2(&mut self, hashtable: &mut HashTable) -> usize {
+        self.tier2
+            .as_mut()
+            .map(|tier2| tier2.expire(hashtable))
+            .unwrap_or(0)
+    }
+
+    /// Clears all segments in the second tier, returning the number cleared
+    pub(crate) fn clear_tier2(&mut self, hashtable: &mut HashTable) -> usize {
+        self.tier2
+            .as_mut()
+            .map(|tier2| tier2.clear(hashtable))
+            .unwrap_or(0)
+    }
+
+    /// Returns the number of namespaces, including the default namespace
+    pub(crate) fn namespaces(&self) -> u16 {
+        self.namespaces
+            .as_ref()
+            .map(|namespaces| namespaces.len() as u16)
+            .unwrap_or(1)
+    }
+
+    /// Returns the id of the namespace the key belongs to
+    pub(crate) fn namespace(&self, key: &[u8]) -> u16 {
+        self.namespaces
+            .as_ref()
+            .map(|namespaces| namespaces.lookup(key))
+            .unwrap_or(0)
+    }
+
+    /// Records a hit for the namespace of the key
+    pub(crate) fn namespace_hit(&self, key: &[u8]) {
+        if let Some(namespaces) = &self.namespaces {
+            namespaces.hit(namespaces.lookup(key));
+        }
+    }
+
+    /// Updates the usage stats for each namespace, at most once per second
+    pub(crate) fn update_namespaces(&mut self) {
+        if let Some(namespaces) = &mut self.namespaces {
+            namespaces.update(&self.headers);
+        }
+    }
+
+    /// Sets the namespace of the items in a segment which has been taken from
+    /// the free queue
+    pub(crate) fn set_namespace(&mut self, id: NonZeroU32, namespace: u16) {
+        self.headers[id.get() as usize - 1].set_namespace(namespace);
+        if let Some(namespaces) = &mut self.namespaces {
+            namespaces.acquire(namespace, id);
+        }
+    }
+
+    /// Returns the number of segments which hold items for the namespace
+    #[cfg(test)]
+    pub(crate) fn namespace_segments(&self, namespace: u16) -> usize {
+        self.namespaces
+            .as_ref()
+            .map(|namespaces| namespaces.segments(namespace))
+            .unwrap_or(0)
+    }
+
+    /// Returns the oldest evictable segment from the namespace which is
+    /// furthest over its quota, if any namespace is over quota.
+    fn over_quota_seg(&self) -> Option<NonZeroU32> {
+        self.namespaces.as_ref()?.over_quota(&self.headers)
     }
 
     /// Remove a single item from a segment based on the item_info
@@ -515,9 +897,15 @@ impl Segments {
     ) -> Result<(), SegmentsError> {
         // remove the item
         {
+            let tier2 = seg_id.get() > self.max;
             let mut segment = self.get_mut(seg_id)?;
             segment.remove_item_at(offset);
 
+            // segments in the second tier are reclaimed in FIFO order
+            if tier2 {
+                return Ok(());
+            }
+
             // regardless of eviction policy, we can evict the segment if its now
             // empty and would be evictable. if we evict, we must return early
             if segment.live_items() == 0 && segment.can_evict() {
@@ -529,7 +917,7 @@ impl Segments {
                 // if it's the head of a ttl bucket, we need to manually relink
                 // the bucket head while we have access to the ttl buckets
                 if segment.prev_seg().is_none() {
-                    let ttl_bucket = ttl_buckets.get_mut_bucket(segment.ttl());
+                    let ttl_bucket = ttl_buckets.get_mut_bucket(segment.namespace(), segment.ttl());
                     ttl_bucket.set_head(segment.next_seg());
                 }
                 self.push_free(seg_id);
@@ -540,7 +928,7 @@ impl Segments {
         // for merge eviction, we check if the segment is now below the target
         // ratio which serves as a low watermark for occupancy. if it is, we do
         // a no-evict merge (compaction only, no-pruning)
-        if let Policy::Merge { .. } = self.evict.policy() {
+        if self.evict.merge().is_some() {
             let target_ratio = self.evict.compact_ratio();
 
             let id_idx = seg_id.get() as usize - 1;
@@ -578,7 +966,10 @@ impl Segments {
                     let _ = self.merge_compact(seg_id, hashtable);
                     // we need to make sure the ttl bucket doesn't have a pointer to
                     // any of the segments we removed through merging.
-                    let ttl_bucket = ttl_buckets.get_mut_bucket(self.headers[id_idx].ttl());
+                    let ttl_bucket = ttl_buckets.get_mut_bucket(
+                        self.headers[id_idx].namespace(),
+                        self.headers[id_idx].ttl(),
+                    );
                     ttl_bucket.set_next_to_merge(None);
                 }
             }
@@ -588,6 +979,17 @@ impl Segments {
     }
 
     // mostly for testing, probably never want to run this otherwise
+    /// Returns the ids of every segment in the heap, followed by the segments
+    /// of the second tier
+    #[cfg(any(test, feature = "debug"))]
+    pub(crate) fn ids(&self) -> Vec<NonZeroU32> {
+        let tier2 = self.tier2.as_ref().map(|tier2| tier2.cap()).unwrap_or(0);
+        (1..=self.cap)
+            .chain((self.max + 1)..=(self.max + tier2 as u32))
+            .filter_map(NonZeroU32::new)
+            .collect()
+    }
+
     #[cfg(any(test, feature = "debug"))]
     pub(crate) fn items(&mut self) -> usize {
         let mut total = 0;
@@ -601,6 +1003,9 @@ impl Segments {
             debug!("{} items in segment {} segment: {:?}", count, id, segment);
             total += segment.live_items() as usize;
         }
+        if let Some(tier2) = &self.tier2 {
+            total += tier2.items();
+        }
         total
     }
 
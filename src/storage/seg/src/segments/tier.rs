@@ -0,0 +1,262 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! A second tier of segments which is backed by a file. Segments which would
+//! be evicted from memory have their live items copied into this tier, with
+//! the hashtable updated to point at their new location. Segments in this
+//! tier are written in FIFO order, and the oldest is evicted to make room.
+//!
+//! Items are copied from many segments, so each segment in this tier expires
+//! at the earliest expiration of any item copied into it.
+
+use crate::segments::*;
+use core::num::NonZeroU32;
+use datapool::*;
+use std::path::Path;
+
+pub(crate) struct Tier {
+    /// Pointer to slice of headers
+    headers: Box<[SegmentHeader]>,
+    /// Pointer to raw data
+    data: Box<dyn Datapool>,
+    /// Segment size in bytes
+    segment_size: usize,
+    /// Id of the first segment in this tier, which follows the segments in
+    /// memory
+    first: u32,
+    /// Index of the segment which is receiving items
+    current: Option<usize>,
+    /// Index of the next segment to receive items
+    next: usize,
+    /// Time last expired
+    last_expired: Instant,
+}
+
+impl Tier {
+    /// Creates a new tier in a file at the path, replacing any existing file.
+    /// The hashtable entries for items in this tier are not checkpointed, so
+    /// the tier always starts empty. The segments are given ids starting from
+    /// `first`.
+    pub fn create<T: AsRef<Path>>(
+        path: T,
+        size: usize,
+        segment_size: i32,
+        first: u32,
+    ) -> Result<Self, std::io::Error> {
+        let segments = size / segment_size as usize;
+
+        let mut headers = Vec::with_capacity(0);
+        headers.reserve_exact(segments);
+        for id in 0..segments {
+            // safety: `first` is non-zero and the ids are checked by the caller
+            let header =
+                SegmentHeader::new(unsafe { NonZeroU32::new_unchecked(first + id as u32) });
+            headers.push(header);
+        }
+        let mut headers = headers.into_boxed_slice();
+
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        let mut data: Box<dyn Datapool> = Box::new(MmapFile::create(
+            path,
+            segments * segment_size as usize,
+            crate::VERSION,
+        )?);
+
+        for (idx, header) in headers.iter_mut().enumerate() {
+            let begin = segment_size as usize * idx;
+            let end = begin + segment_size as usize;
+
+            let mut segment = Segment::from_raw_parts(header, &mut data.as_mut_slice()[begin..end]);
+            segment.init();
+            segment.set_accessible(false);
+        }
+
+        TIER2_SIZE.set((segments * segment_size as usize) as _);
+
+        Ok(Self {
+            headers,
+            data,
+            segment_size: segment_size as usize,
+            first,
+            current: None,
+            next: 0,
+            last_expired: clock::recent(),
+        })
+    }
+
+    /// Returns the number of segments in this tier
+    pub fn cap(&self) -> usize {
+        self.headers.len()
+    }
+
+    /// Returns true if the segment id belongs to this tier
+    pub fn contains(&self, id: NonZeroU32) -> bool {
+        id.get() >= self.first && ((id.get() - self.first) as usize) < self.headers.len()
+    }
+
+    /// Returns a mutable `Segment` view for the segment with the specified id
+    pub fn get_mut(&mut self, id: NonZeroU32) -> Option<Segment<'_>> {
+        if !self.contains(id) {
+            return None;
+        }
+        let segment = self.segment((id.get() - self.first) as usize);
+        segment.check_magic();
+        Some(segment)
+    }
+
+    /// Returns the header for the segment with the specified id
+    pub fn header(&self, id: NonZeroU32) -> Option<&SegmentHeader> {
+        if !self.contains(id) {
+            return None;
+        }
+        Some(&self.headers[(id.get() - self.first) as usize])
+    }
+
+    /// Returns the time remaining until the segment expires, which is the max
+    /// TTL if the segment does not expire
+    pub fn ttl(&self, id: NonZeroU32) -> Option<Duration> {
+        let header = self.header(id)?;
+        let now = clock::recent();
+        match header.expire_at() {
+            Some(expire_at) if expire_at > now => Some(expire_at - now),
+            Some(_) => Some(Duration::from_secs(0)),
+            None => Some(Duration::from_secs(MAX_TTL)),
+        }
+    }
+
+    /// Copies the live items from a segment in memory into this tier. Items
+    /// which cannot be copied remain in the source segment. Returns the number
+    /// of items copied.
+    pub fn demote(&mut self, src: &mut Segment, hashtable: &mut HashTable) -> usize {
+        let expire_at = src.expire_at();
+        let items = src.live_items();
+        if items <= 0 || expire_at.map(|e| e <= clock::recent()).unwrap_or(false) {
+            return 0;
+        }
+
+        // a segment in memory fits within two segments of this tier, unless
+        // there is an item which would fill a segment exactly
+        for _ in 0..std::cmp::min(3, self.cap()) {
+            let idx = match self.current {
+                Some(idx) => idx,
+                None => self.advance(hashtable, expire_at),
+            };
+
+            let mut dst = self.segment(idx);
+            if let Some(expire_at) = expire_at {
+                if dst.expire_at().map(|e| expire_at < e).unwrap_or(true) {
+                    dst.set_ttl(expire_at - dst.create_at());
+                }
+            }
+
+            let _ = src.copy_into(&mut dst, hashtable);
+            if src.live_items() == 0 {
+                break;
+            }
+
+            // the segment is full, continue with the next one
+            self.current = None;
+        }
+
+        let demoted = (items - src.live_items()) as usize;
+        TIER2_DEMOTE.add(demoted as _);
+        demoted
+    }
+
+    /// Expires any segments in this tier which have reached their expiration
+    /// time. Returns the number of segments expired.
+    pub fn expire(&mut self, hashtable: &mut HashTable) -> usize {
+        let now = clock::recent();
+        if now == self.last_expired {
+            return 0;
+        }
+        self.last_expired = now;
+
+        let mut expired = 0;
+        let mut items = 0;
+        let mut bytes = 0;
+        for idx in 0..self.cap() {
+            let mut segment = self.segment(idx);
+            if !segment.accessible() {
+                continue;
+            }
+            if segment.expire_at().map(|e| e <= now).unwrap_or(false) {
+                segment.clear(hashtable, true);
+                if self.current == Some(idx) {
+                    self.current = None;
+                }
+                expired += 1;
+            } else {
+                items += segment.live_items() as i64;
+                bytes += (segment.live_bytes() - first_item_offset() as i32) as i64;
+            }
+        }
+
+        TIER2_SEGMENT_EXPIRE.add(expired as _);
+        TIER2_ITEMS.set(items);
+        TIER2_BYTES.set(bytes);
+        expired
+    }
+
+    /// Removes all items from this tier. Returns the number of segments which
+    /// were cleared.
+    pub fn clear(&mut self, hashtable: &mut HashTable) -> usize {
+        let mut cleared = 0;
+        for idx in 0..self.cap() {
+            let mut segment = self.segment(idx);
+            if segment.accessible() {
+                segment.clear(hashtable, true);
+                cleared += 1;
+            }
+        }
+        self.current = None;
+
+        TIER2_ITEMS.set(0);
+        TIER2_BYTES.set(0);
+        cleared
+    }
+
+    /// Returns the number of live items in this tier
+    #[cfg(any(test, feature = "debug"))]
+    pub fn items(&self) -> usize {
+        self.headers
+            .iter()
+            .filter(|h| h.accessible())
+            .map(|h| h.live_items() as usize)
+            .sum()
+    }
+
+    // starts writing into the oldest segment in this tier, evicting the items
+    // it holds
+    fn advance(&mut self, hashtable: &mut HashTable, expire_at: Option<Instant>) -> usize {
+        let idx = self.next;
+        self.next = (idx + 1) % self.cap();
+
+        let mut segment = self.segment(idx);
+        if segment.accessible() {
+            segment.clear(hashtable, false);
+            TIER2_SEGMENT_EVICT.increment();
+        }
+        segment.init();
+        match expire_at {
+            Some(expire_at) => segment.set_ttl(expire_at - segment.create_at()),
+            None => segment.set_ttl(Duration::from_secs(MAX_TTL)),
+        }
+
+        self.current = Some(idx);
+        idx
+    }
+
+    fn segment(&mut self, idx: usize) -> Segment<'_> {
+        let begin = self.segment_size * idx;
+        let end = begin + self.segment_size;
+        Segment::from_raw_parts(
+            &mut self.headers[idx],
+            &mut self.data.as_mut_slice()[begin..end],
+        )
+    }
+}
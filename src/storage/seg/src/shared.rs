@@ -0,0 +1,468 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Shared access to a [`Seg`] from multiple threads. A `Seg` may be split into
+//! a single [`SegWriter`], which has exclusive use of all the operations, and
+//! any number of [`SegReader`]s which may look up items at the same time as
+//! the writer and each other.
+//!
+//! Reads are optimistic and validated with a sequence lock. The writer
+//! increments a sequence number before and after each write, and a reader
+//! retries when the sequence number changed while it was reading. Since the
+//! segments and the hashtable buckets are allocated up front and are neither
+//! freed nor resized while the cache is shared, a reader which races with the
+//! writer may see inconsistent data but never reads outside of them. Items are
+//! copied out and checked before they are returned, so a reader never returns
+//! a partially written item.
+//!
+//! A reader cannot modify the cache, so reads made through a reader do not
+//! count towards the item frequency used by merge eviction, and items in the
+//! second tier are not moved back into memory. Tagged items are only read by
+//! the writer, which checks that their tag is still current. Likewise, when
+//! expired items are retained for leases, only the writer can tell whether an
+//! item has expired, so every item is read by the writer. When a reader is
+//! unable to return a result, it returns `SegError::Busy` and the read should
+//! be made by the writer instead.
+
+use crate::seg::decompress;
+use crate::*;
+use core::sync::atomic::{fence, AtomicU64, Ordering};
+use std::sync::Arc;
+
+// number of attempts a reader makes before returning an error
+const READ_RETRIES: usize = 16;
+
+struct Shared {
+    seq: AtomicU64,
+    seg: core::cell::UnsafeCell<Seg>,
+}
+
+// SAFETY: the `Seg` is only accessed mutably through the single `SegWriter`.
+// Readers do not create references to the `Seg` and only read from the bucket
+// and segment memory, validating what they read with the sequence number.
+unsafe impl Sync for Shared {}
+
+/// The single writer for a shared [`Seg`], see the [module level
+/// documentation](crate::shared) for details.
+pub struct SegWriter {
+    shared: Arc<Shared>,
+}
+
+impl SegWriter {
+    /// Begin a write. All operations on the `Seg` are available through the
+    /// returned guard, and readers retry until it is dropped, so it should
+    /// only be held for a single operation.
+    ///
+    /// ```
+    /// use seg::Seg;
+    /// use std::time::Duration;
+    ///
+    /// let cache = Seg::builder().build().expect("failed to create cache");
+    /// let (mut writer, reader) = cache.into_shared();
+    ///
+    /// writer
+    ///     .write()
+    ///     .insert(b"coffee", b"hot", None, Duration::ZERO)
+    ///     .expect("failed to insert");
+    ///
+    /// let item = reader.get(b"coffee").expect("read failed");
+    /// assert_eq!(item.expect("missing").value(), b"hot");
+    /// ```
+    pub fn write(&mut self) -> WriteGuard<'_> {
+        let seq = self.shared.seq.load(Ordering::Relaxed);
+        self.shared.seq.store(seq + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        WriteGuard { writer: self }
+    }
+}
+
+/// Exclusive access to a shared [`Seg`], which is returned by
+/// `SegWriter::write()`. Readers are able to continue once it is dropped.
+pub struct WriteGuard<'a> {
+    writer: &'a mut SegWriter,
+}
+
+impl Drop for WriteGuard<'_> {
+    fn drop(&mut self) {
+        let seq = self.writer.shared.seq.load(Ordering::Relaxed);
+        self.writer.shared.seq.store(seq + 1, Ordering::Release);
+    }
+}
+
+impl core::ops::Deref for WriteGuard<'_> {
+    type Target = Seg;
+
+    fn deref(&self) -> &Seg {
+        // SAFETY: the writer is unique and the guard borrows it mutably
+        unsafe { &*self.writer.shared.seg.get() }
+    }
+}
+
+impl core::ops::DerefMut for WriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Seg {
+        // SAFETY: the writer is unique and the guard borrows it mutably
+        unsafe { &mut *self.writer.shared.seg.get() }
+    }
+}
+
+/// A reader for a shared [`Seg`], which may be cloned for use by any number of
+/// threads. See the [module level documentation](crate::shared) for details.
+#[derive(Clone)]
+pub struct SegReader {
+    shared: Arc<Shared>,
+    buckets: BucketsView,
+    heap: *const u8,
+    headers: *const SegmentHeader,
+    flush_at: *const Instant,
+    segment_size: usize,
+    cap: u32,
+    // expired items are retained and must not be returned
+    stale: bool,
+}
+
+// SAFETY: the pointers are to the bucket and segment memory and the segment
+// metadata owned by the shared `Seg`, which is kept alive by the `Arc`
+unsafe impl Send for SegReader {}
+unsafe impl Sync for SegReader {}
+
+// The result of a single unvalidated lookup
+enum Lookup {
+    Hit(SharedItem),
+    Miss,
+    // the item cannot be read by a reader
+    Unsupported,
+}
+
+impl SegReader {
+    /// Get a copy of the item with the provided key. Returns `SegError::Busy`
+    /// if the read repeatedly conflicted with the writer, or if the item can
+    /// only be read by the writer.
+    pub fn get(&self, key: &[u8]) -> Result<Option<SharedItem>, SegError> {
+        READER_GET.increment();
+        if is_reserved_key(key) {
+            return Ok(None);
+        }
+        let hash = self.buckets.hash(key);
+
+        for _ in 0..READ_RETRIES {
+            let seq = self.shared.seq.load(Ordering::Acquire);
+            if seq & 1 == 1 {
+                // a write is in progress
+                READER_RETRY.increment();
+                std::hint::spin_loop();
+                continue;
+            }
+
+            let result = self.lookup(key, hash);
+
+            fence(Ordering::Acquire);
+            if self.shared.seq.load(Ordering::Relaxed) != seq {
+                READER_RETRY.increment();
+                continue;
+            }
+
+            return match result {
+                Lookup::Hit(item) => {
+                    let item = self.finish(item);
+                    if item.is_some() {
+                        READER_HIT.increment();
+                    } else {
+                        READER_BUSY.increment();
+                    }
+                    item.map(Some).ok_or(SegError::Busy)
+                }
+                Lookup::Miss => Ok(None),
+                Lookup::Unsupported => {
+                    READER_BUSY.increment();
+                    Err(SegError::Busy)
+                }
+            };
+        }
+
+        READER_BUSY.increment();
+        Err(SegError::Busy)
+    }
+
+    // Finds and copies the item, including the chunks of a large value. The
+    // result must be validated before it is used.
+    fn lookup(&self, key: &[u8], hash: u64) -> Lookup {
+        let now = clock::recent();
+        // SAFETY: the flush time is owned by the shared `Seg`, and a racing
+        // read is discarded by the sequence number check
+        let flush_at = unsafe { std::ptr::read_volatile(self.flush_at) };
+
+        let mut unsupported = false;
+        let found = self.buckets.find(hash, |item_info, cas| {
+            // the writer removes the items of expired and flushed segments
+            // when it next expires, and they must not be returned until then
+            if self.is_expired(item_info, now, flush_at) {
+                return None;
+            }
+            match self.copy_item(item_info, cas) {
+                Some(item) if item.key() == key => Some(item),
+                Some(_) => {
+                    HASH_TAG_COLLISION.increment();
+                    None
+                }
+                None => {
+                    // the item is in the second tier or is inconsistent
+                    unsupported = true;
+                    None
+                }
+            }
+        });
+
+        let mut item = match found {
+            Some(item) => item,
+            None if unsupported => return Lookup::Unsupported,
+            None => return Lookup::Miss,
+        };
+
+        // the tag generations and the item expiry are only available to the
+        // writer
+        if item.raw().tag().is_some() || self.stale {
+            return Lookup::Unsupported;
+        }
+
+        if item.raw().is_large() {
+            let manifest = match item.raw().value() {
+                Value::Bytes(manifest) => Manifest::decode(manifest),
+                Value::U64(_) => None,
+            };
+            let manifest = match manifest {
+                Some(manifest) => manifest,
+                None => return Lookup::Unsupported,
+            };
+            // the manifest is not validated yet, so the value length is not
+            // used to reserve space
+            let mut value = Vec::new();
+            for chunk_key in manifest.chunk_keys() {
+                match self.lookup(&chunk_key, self.buckets.hash(&chunk_key)) {
+                    Lookup::Hit(chunk) => match chunk.raw().value() {
+                        Value::Bytes(chunk) => value.extend_from_slice(chunk),
+                        Value::U64(_) => return Lookup::Unsupported,
+                    },
+                    _ => return Lookup::Unsupported,
+                }
+            }
+            if value.len() != manifest.value_len() {
+                return Lookup::Unsupported;
+            }
+            item.value = Some(value.into_boxed_slice());
+        }
+
+        Lookup::Hit(item)
+    }
+
+    // Returns true if the item is in a segment which has expired or which was
+    // created before the cache was flushed, as checked by `TtlBucket::expire()`
+    fn is_expired(&self, item_info: u64, now: Instant, flush_at: Instant) -> bool {
+        let seg_id = match get_seg_id(item_info) {
+            Some(seg_id) if seg_id.get() <= self.cap => seg_id.get(),
+            _ => return false,
+        };
+        // SAFETY: the id is within the in-memory segments, and a racing read
+        // is discarded by the sequence number check
+        let header = unsafe { std::ptr::read_volatile(self.headers.add(seg_id as usize - 1)) };
+        header.expire_at().map(|e| e <= now).unwrap_or(false) || header.create_at() < flush_at
+    }
+
+    // Copies the item at the location in the item info, returns `None` if the
+    // item is not in memory or the copy is not a valid item
+    fn copy_item(&self, item_info: u64, cas: u32) -> Option<SharedItem> {
+        let seg_id = get_seg_id(item_info)?.get();
+        if seg_id > self.cap {
+            return None;
+        }
+        let offset = get_offset(item_info) as usize;
+        if offset + ITEM_HDR_SIZE + ITEM_EXT_SIZE > self.segment_size {
+            return None;
+        }
+        let start = self.segment_size * (seg_id as usize - 1) + offset;
+
+        // copy the header and extended flags to find the size of the item
+        let mut header = [0_u64; (ITEM_HDR_SIZE + ITEM_EXT_SIZE + 7) >> 3];
+        self.copy(start, &mut header, ITEM_HDR_SIZE + ITEM_EXT_SIZE);
+        let raw = RawItem::from_ptr(header.as_mut_ptr() as *mut u8);
+        if !raw.has_magic() {
+            return None;
+        }
+        let size = raw.size();
+        if offset + size > self.segment_size {
+            return None;
+        }
+
+        let mut data = vec![0_u64; size / 8].into_boxed_slice();
+        self.copy(start, &mut data, size);
+
+        Some(SharedItem {
+            data,
+            value: None,
+            cas,
+        })
+    }
+
+    // Copies `len` bytes of segment memory starting at `start` into `dst`
+    fn copy(&self, start: usize, dst: &mut [u64], len: usize) {
+        assert!(len <= dst.len() * 8);
+        // SAFETY: the caller checks that the range is within a segment. The
+        // memory may be written concurrently, so every read is volatile and a
+        // racing copy is discarded by the sequence number check.
+        unsafe {
+            let src = self.heap.add(start);
+            let words = if (src as usize).is_multiple_of(8) {
+                len / 8
+            } else {
+                0
+            };
+            for (word, dst) in dst.iter_mut().enumerate().take(words) {
+                *dst = std::ptr::read_volatile((src as *const u64).add(word));
+            }
+            let dst = dst.as_mut_ptr() as *mut u8;
+            for byte in (words * 8)..len {
+                *dst.add(byte) = std::ptr::read_volatile(src.add(byte));
+            }
+        }
+    }
+
+    // Decompresses the value of a validated item if needed, returns `None` if
+    // the value cannot be decompressed
+    fn finish(&self, mut item: SharedItem) -> Option<SharedItem> {
+        if !item.raw().is_compressed() {
+            return Some(item);
+        }
+        let value = match item.value.as_deref() {
+            Some(value) => decompress(value)?,
+            None => match item.raw().value() {
+                Value::Bytes(value) => decompress(value)?,
+                Value::U64(_) => return None,
+            },
+        };
+        item.value = Some(value);
+        Some(item)
+    }
+}
+
+/// A copy of an item which was read by a [`SegReader`]
+pub struct SharedItem {
+    data: Box<[u64]>,
+    value: Option<Box<[u8]>>,
+    // the CAS value of the hash bucket, see `Item`
+    cas: u32,
+}
+
+impl SharedItem {
+    fn raw(&self) -> RawItem {
+        RawItem::from_ptr(self.data.as_ptr() as *mut u8)
+    }
+
+    /// Borrow the key
+    pub fn key(&self) -> &[u8] {
+        // SAFETY: the raw item borrows from `data`, which lives as long as
+        // `self`
+        unsafe { std::slice::from_raw_parts(self.raw().key().as_ptr(), self.raw().klen() as _) }
+    }
+
+    /// Borrow the value
+    pub fn value(&self) -> Value<'_> {
+        if let Some(ref value) = self.value {
+            return Value::Bytes(value);
+        }
+        match self.raw().value() {
+            // SAFETY: as for `key()`
+            Value::Bytes(value) => {
+                Value::Bytes(unsafe { std::slice::from_raw_parts(value.as_ptr(), value.len()) })
+            }
+            Value::U64(value) => Value::U64(value),
+        }
+    }
+
+    /// Borrow the optional data
+    pub fn optional(&self) -> Option<&[u8]> {
+        let raw = self.raw();
+        // SAFETY: as for `key()`
+        raw.optional()
+            .map(|o| unsafe { std::slice::from_raw_parts(o.as_ptr(), o.len()) })
+    }
+
+    /// The CAS value of the item
+    pub fn cas(&self) -> u64 {
+        self.raw().cas().unwrap_or(self.cas as u64)
+    }
+}
+
+impl std::fmt::Debug for SharedItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("SharedItem")
+            .field("cas", &self.cas())
+            .field("raw", &self.raw())
+            .finish()
+    }
+}
+
+impl Seg {
+    /// Returns true if the `Seg` may be split with `into_shared()`, which
+    /// requires that neither the hashtable nor the heap can change size.
+    pub fn shareable(&self) -> bool {
+        !self.hashtable.can_grow()
+            && !self.segments.can_grow()
+            && self.segments.cap() == self.segments.target()
+    }
+
+    /// Split the `Seg` into a writer and a reader which may be used from
+    /// different threads, see the [module level documentation](crate::shared)
+    /// for details.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the hashtable may grow, since growing frees the buckets
+    /// which readers may be using, if the heap may grow beyond its initial
+    /// size, since the new segments are not visible to readers, or if the heap
+    /// is still shrinking, since shrinking releases segments which readers may
+    /// be using, see `shareable()`. Once shared, `resize()` returns an error.
+    pub fn into_shared(mut self) -> (SegWriter, SegReader) {
+        assert!(
+            !self.hashtable.can_grow(),
+            "shared access requires a hashtable which does not grow"
+        );
+        assert!(
+            !self.segments.can_grow(),
+            "shared access requires a heap which does not grow"
+        );
+        assert!(
+            self.segments.cap() == self.segments.target(),
+            "shared access requires a heap which is not shrinking"
+        );
+        self.shared = true;
+
+        let buckets = self.hashtable.view();
+        let heap = self.segments.heap_ptr();
+        let headers = self.segments.headers_ptr();
+        let segment_size = self.segments.segment_size() as usize;
+        let cap = self.segments.cap() as u32;
+        let stale = self.stale_grace.as_secs() > 0;
+
+        let shared = Arc::new(Shared {
+            seq: AtomicU64::new(0),
+            seg: core::cell::UnsafeCell::new(self),
+        });
+
+        // SAFETY: there is no writer yet. The `Seg` is not moved again, so
+        // the pointer remains valid for as long as the `Arc` is held.
+        let flush_at = unsafe { (*shared.seg.get()).segments.flush_at_ptr() };
+
+        let reader = SegReader {
+            shared: shared.clone(),
+            buckets,
+            heap,
+            headers,
+            flush_at,
+            segment_size,
+            cap,
+            stale,
+        };
+
+        (SegWriter { shared }, reader)
+    }
+}
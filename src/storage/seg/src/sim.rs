@@ -0,0 +1,795 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! A simulator which replays a trace of requests against `Seg` so that
+//! eviction policies and heap sizes can be compared on a real workload before
+//! changing the configuration of a running cache.
+//!
+//! Each combination of policy and heap size replays the whole trace against a
+//! new cache. Time is virtual, see `seg::freeze_clock()`, and only moves
+//! forward with the timestamps in the trace, so a trace which covers hours of
+//! traffic replays in however long it takes to process the requests. Keys,
+//! value sizes, and TTLs are taken from the trace, values are filled with
+//! zeros.
+//!
+//! Two trace formats are supported:
+//! * `klog` - the command log written by the memcache protocol, one request
+//!   per line, prefixed with an RFC 3339 timestamp. Reads are `get`, `gets`,
+//!   and `mg`, writes are `set`, `add`, `replace`, and `cas`, and removals are
+//!   `delete`. Other commands are skipped. The value size of a read is only
+//!   known if it was a hit, and for `mg` only if the value was returned.
+//! * `binary` - fixed size records of 20 bytes, with all fields little endian:
+//!   the timestamp in seconds as a `u32`, the key as a `u64`, the key size
+//!   (upper 10 bits) and value size (lower 22 bits) packed into a `u32`, and
+//!   the operation (upper 8 bits) and TTL in seconds (lower 24 bits) packed
+//!   into a `u32`. The operations are 1 `get`, 2 `gets`, 3 `set`, 4 `add`,
+//!   5 `cas`, 6 `replace`, and 9 `delete`, others are skipped. This follows
+//!   the layout of the binary form of the public Twitter cache traces.
+//!
+//! Eviction counts are read from the seg metrics, which are process-wide, so
+//! the runs are made one after another.
+//!
+//! The simulator is built with the `sim` feature, which also enables the
+//! virtual clock in seg:
+//!
+//! ```text
+//! cargo run --release -p seg --features sim --bin seg-sim -- trace.log
+//! ```
+
+use clap::{App, Arg};
+use rustcommon_metrics::*;
+use seg::{Policy, Seg};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read};
+use std::time::Duration;
+
+const KB: usize = 1024;
+const MB: usize = 1024 * KB;
+const GB: usize = 1024 * MB;
+
+// the size of a record in the binary trace format
+const RECORD_SIZE: usize = 20;
+
+// the policies which are simulated when none are specified
+const POLICIES: &[&str] = &["random", "randomfifo", "fifo", "cte", "util", "merge"];
+
+fn main() {
+    let matches = App::new(env!("CARGO_BIN_NAME"))
+        .version(env!("CARGO_PKG_VERSION"))
+        .version_short("v")
+        .about("Replays a cache trace against seg to compare eviction policies")
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .short("f")
+                .help("Format of the trace")
+                .possible_values(&["klog", "binary"])
+                .default_value("klog"),
+        )
+        .arg(
+            Arg::with_name("heap-size")
+                .long("heap-size")
+                .short("s")
+                .help(
+                    "Heap sizes to simulate, in bytes with an optional K, M, or G \
+                    suffix. Provide several to produce miss ratio curves",
+                )
+                .use_delimiter(true)
+                .default_value("64M"),
+        )
+        .arg(
+            Arg::with_name("policy")
+                .long("policy")
+                .short("p")
+                .help("Eviction policies to simulate, defaults to all but none")
+                .possible_values(&[
+                    "none",
+                    "random",
+                    "randomfifo",
+                    "fifo",
+                    "cte",
+                    "util",
+                    "merge",
+                ])
+                .use_delimiter(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("segment-size")
+                .long("segment-size")
+                .help("Size of each segment, in bytes with an optional K or M suffix")
+                .default_value("1M"),
+        )
+        .arg(
+            Arg::with_name("hash-power")
+                .long("hash-power")
+                .help("Initial power of the hash table, it grows as needed")
+                .default_value("20"),
+        )
+        .arg(
+            Arg::with_name("max-hash-power")
+                .long("max-hash-power")
+                .help("Power the hash table may grow to")
+                .default_value("28"),
+        )
+        .arg(
+            Arg::with_name("merge-max")
+                .long("merge-max")
+                .help("Maximum number of segments to merge in a pass of the merge policy")
+                .default_value("8"),
+        )
+        .arg(
+            Arg::with_name("merge-target")
+                .long("merge-target")
+                .help("Number of segments the merge policy merges on eviction")
+                .default_value("4"),
+        )
+        .arg(
+            Arg::with_name("compact-target")
+                .long("compact-target")
+                .help("Number of segments the merge policy compacts on eviction")
+                .default_value("2"),
+        )
+        .arg(
+            Arg::with_name("fill")
+                .long("fill")
+                .help(
+                    "Inserts the item on a read miss with this TTL in seconds, \
+                    if the trace has the value size",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("TRACE")
+                .help("Path of the trace file")
+                .required(true)
+                .index(1),
+        )
+        .get_matches();
+
+    let exit = |message: &str| -> ! {
+        eprintln!("error: {}", message);
+        std::process::exit(1);
+    };
+
+    let format = match matches.value_of("format").unwrap() {
+        "binary" => Format::Binary,
+        _ => Format::Klog,
+    };
+    let heap_sizes: Vec<usize> = matches
+        .values_of("heap-size")
+        .unwrap()
+        .map(|v| parse_size(v).unwrap_or_else(|| exit("heap size must be a number of bytes")))
+        .collect();
+    let policies: Vec<&str> = matches
+        .values_of("policy")
+        .map(|v| v.collect())
+        .unwrap_or_else(|| POLICIES.to_vec());
+    let segment_size = parse_size(matches.value_of("segment-size").unwrap())
+        .filter(|size| *size <= i32::MAX as usize)
+        .unwrap_or_else(|| exit("segment size must be a number of bytes"));
+    let hash_power = matches
+        .value_of("hash-power")
+        .unwrap()
+        .parse::<u8>()
+        .unwrap_or_else(|_| exit("hash power must be a number"));
+    let max_hash_power = matches
+        .value_of("max-hash-power")
+        .unwrap()
+        .parse::<u8>()
+        .unwrap_or_else(|_| exit("max hash power must be a number"));
+    let merge = ["merge-max", "merge-target", "compact-target"].map(|name| {
+        matches
+            .value_of(name)
+            .unwrap()
+            .parse::<usize>()
+            .unwrap_or_else(|_| exit(&format!("{} must be a number of segments", name)))
+    });
+    let fill = matches.value_of("fill").map(|v| {
+        v.parse::<u64>()
+            .map(Duration::from_secs)
+            .unwrap_or_else(|_| exit("fill TTL must be a number of seconds"))
+    });
+
+    let options = Options {
+        format,
+        segment_size: segment_size as i32,
+        hash_power,
+        max_hash_power,
+        merge: Policy::Merge {
+            max: merge[0],
+            merge: merge[1],
+            compact: merge[2],
+        },
+        fill,
+    };
+
+    seg::freeze_clock();
+
+    let mut results = Vec::new();
+    for policy in &policies {
+        for heap_size in &heap_sizes {
+            match simulate(
+                matches.value_of("TRACE").unwrap(),
+                &options,
+                policy,
+                *heap_size,
+            ) {
+                Ok(stats) => results.push((*policy, *heap_size, stats)),
+                Err(e) => exit(&e.to_string()),
+            }
+        }
+    }
+
+    report(&results, &policies, &heap_sizes);
+}
+
+/// The format of a trace file, see the module docs
+#[derive(Copy, Clone)]
+enum Format {
+    Klog,
+    Binary,
+}
+
+/// The settings which are shared by all the runs
+struct Options {
+    format: Format,
+    segment_size: i32,
+    hash_power: u8,
+    max_hash_power: u8,
+    // the merge policy with the configured parameters
+    merge: Policy,
+    fill: Option<Duration>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Op {
+    Get,
+    Set,
+    Add,
+    Replace,
+    Delete,
+}
+
+/// A single request from the trace
+#[derive(Debug, PartialEq, Eq)]
+struct Record {
+    // seconds since an arbitrary epoch
+    time: u64,
+    op: Op,
+    key: Box<[u8]>,
+    // zero for reads when the size is not known
+    value_size: usize,
+    ttl: u32,
+}
+
+/// The results of replaying the trace against one cache
+#[derive(Default)]
+struct Stats {
+    requests: u64,
+    skipped: u64,
+    gets: u64,
+    hits: u64,
+    store_failures: u64,
+    segments_evicted: u64,
+    segments_merged: u64,
+    items_evicted: u64,
+    items_expired: u64,
+}
+
+impl Stats {
+    fn hit_ratio(&self) -> f64 {
+        if self.gets == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.gets as f64
+        }
+    }
+
+    fn miss_ratio(&self) -> f64 {
+        if self.gets == 0 {
+            0.0
+        } else {
+            1.0 - self.hit_ratio()
+        }
+    }
+}
+
+/// Reads records from a trace file, counting the entries which are skipped
+struct Trace {
+    format: Format,
+    reader: BufReader<File>,
+    line: String,
+    skipped: u64,
+}
+
+impl Trace {
+    fn open(path: &str, format: Format) -> Result<Self, Error> {
+        Ok(Self {
+            format,
+            reader: BufReader::new(File::open(path)?),
+            line: String::new(),
+            skipped: 0,
+        })
+    }
+
+    fn next_klog(&mut self) -> Result<Option<Record>, Error> {
+        loop {
+            self.line.clear();
+            if self.reader.read_line(&mut self.line)? == 0 {
+                return Ok(None);
+            }
+            let line = self.line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+            match parse_klog(line) {
+                Some(record) => return Ok(Some(record)),
+                None => self.skipped += 1,
+            }
+        }
+    }
+
+    fn next_binary(&mut self) -> Result<Option<Record>, Error> {
+        let mut buf = [0; RECORD_SIZE];
+        loop {
+            match self.reader.read_exact(&mut buf) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e),
+            }
+            match decode_binary(&buf) {
+                Some(record) => return Ok(Some(record)),
+                None => self.skipped += 1,
+            }
+        }
+    }
+}
+
+impl Iterator for Trace {
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = match self.format {
+            Format::Klog => self.next_klog(),
+            Format::Binary => self.next_binary(),
+        };
+        record.transpose()
+    }
+}
+
+/// Parses a klog line, returns `None` for commands which are not replayed
+fn parse_klog(line: &str) -> Option<Record> {
+    let (timestamp, rest) = line.split_once(' ')?;
+    let time = parse_timestamp(timestamp)?;
+
+    // the request is quoted and followed by the response code and length
+    let (request, response) = rest.strip_prefix('"')?.rsplit_once('"')?;
+    let len: usize = response.split_whitespace().nth(1)?.parse().ok()?;
+
+    let mut request = request.split(' ');
+    let verb = request.next()?;
+    let key = request.next()?.as_bytes().to_owned().into_boxed_slice();
+
+    let (op, value_size, ttl) = match verb {
+        // the length is the value length for reads
+        "get" | "gets" | "mg" => (Op::Get, len, 0),
+        "set" | "add" | "replace" | "cas" => {
+            // skip the flags
+            let _ = request.next()?;
+            let ttl = request.next()?.parse().ok()?;
+            let value_size = request.next()?.parse().ok()?;
+            let op = match verb {
+                "set" => Op::Set,
+                "add" => Op::Add,
+                _ => Op::Replace,
+            };
+            (op, value_size, ttl)
+        }
+        "delete" => (Op::Delete, 0, 0),
+        _ => return None,
+    };
+
+    Some(Record {
+        time,
+        op,
+        key,
+        value_size,
+        ttl,
+    })
+}
+
+/// Parses an RFC 3339 timestamp into seconds since the UNIX epoch, ignoring
+/// any fractional seconds
+fn parse_timestamp(timestamp: &str) -> Option<u64> {
+    let (date, time) = timestamp.split_once('T')?;
+
+    let mut date = date.splitn(3, '-').map(|v| v.parse::<i64>().ok());
+    let year = date.next()??;
+    let month = date.next()??;
+    let day = date.next()??;
+
+    // the time is followed by either `Z` or an offset from UTC
+    let (time, offset) = match time.find(['Z', '+', '-']) {
+        Some(idx) => time.split_at(idx),
+        None => (time, ""),
+    };
+    let offset = match offset.as_bytes().first() {
+        None | Some(b'Z') => 0,
+        Some(sign) => {
+            let (hours, minutes) = offset[1..].split_once(':')?;
+            let offset = hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60;
+            if *sign == b'-' {
+                -offset
+            } else {
+                offset
+            }
+        }
+    };
+
+    let mut time = time.splitn(3, ':');
+    let hours = time.next()?.parse::<i64>().ok()?;
+    let minutes = time.next()?.parse::<i64>().ok()?;
+    let seconds = time.next()?.split('.').next()?.parse::<i64>().ok()?;
+
+    let seconds =
+        days_from_civil(year, month, day) * 86400 + hours * 3600 + minutes * 60 + seconds - offset;
+    u64::try_from(seconds).ok()
+}
+
+/// Returns the number of days since the UNIX epoch for a date in the proleptic
+/// Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Decodes a binary record, returns `None` for operations which are not
+/// replayed
+fn decode_binary(buf: &[u8; RECORD_SIZE]) -> Option<Record> {
+    let time = u32::from_le_bytes(buf[0..4].try_into().unwrap()) as u64;
+    let key = u64::from_le_bytes(buf[4..12].try_into().unwrap());
+    let sizes = u32::from_le_bytes(buf[12..16].try_into().unwrap());
+    let op_ttl = u32::from_le_bytes(buf[16..20].try_into().unwrap());
+
+    let op = match op_ttl >> 24 {
+        1 | 2 => Op::Get,
+        3 => Op::Set,
+        4 => Op::Add,
+        5 | 6 => Op::Replace,
+        9 => Op::Delete,
+        _ => return None,
+    };
+
+    // the key is padded with zeros to the key size from the trace
+    let key_size = (sizes >> 22) as usize;
+    let mut key = key.to_le_bytes().to_vec();
+    if key_size > key.len() {
+        key.resize(key_size, 0);
+    }
+
+    Some(Record {
+        time,
+        op,
+        key: key.into_boxed_slice(),
+        value_size: (sizes & 0x3f_ffff) as usize,
+        ttl: op_ttl & 0xff_ffff,
+    })
+}
+
+/// Parses a size in bytes with an optional K, M, or G suffix
+fn parse_size(size: &str) -> Option<usize> {
+    let (size, unit) = match size.char_indices().last()? {
+        (idx, 'k' | 'K') => (&size[..idx], KB),
+        (idx, 'm' | 'M') => (&size[..idx], MB),
+        (idx, 'g' | 'G') => (&size[..idx], GB),
+        _ => (size, 1),
+    };
+    size.parse::<usize>().ok()?.checked_mul(unit)
+}
+
+fn format_size(size: usize) -> String {
+    for (unit, suffix) in [(GB, "G"), (MB, "M"), (KB, "K")] {
+        if size >= unit && size / unit * unit == size {
+            return format!("{}{}", size / unit, suffix);
+        }
+    }
+    size.to_string()
+}
+
+fn policy(name: &str, options: &Options) -> Policy {
+    match name {
+        "none" => Policy::None,
+        "random" => Policy::Random,
+        "randomfifo" => Policy::RandomFifo,
+        "fifo" => Policy::Fifo,
+        "cte" => Policy::Cte,
+        "util" => Policy::Util,
+        _ => options.merge,
+    }
+}
+
+/// Returns the current value of a seg counter by name
+fn counter(name: &str) -> u64 {
+    for metric in &rustcommon_metrics::metrics() {
+        if metric.name() == name {
+            if let Some(counter) = metric.as_any().and_then(|a| a.downcast_ref::<Counter>()) {
+                return counter.value();
+            }
+        }
+    }
+    0
+}
+
+/// Replays the trace against a new cache with the policy and heap size
+fn simulate(
+    path: &str,
+    options: &Options,
+    policy_name: &str,
+    heap_size: usize,
+) -> Result<Stats, Error> {
+    let mut cache = Seg::builder()
+        .heap_size(heap_size)
+        .segment_size(options.segment_size)
+        .hash_power(options.hash_power)
+        .max_hash_power(Some(options.max_hash_power))
+        .eviction(policy(policy_name, options))
+        .build()?;
+
+    let segments_evicted = counter("segment_evict");
+    let segments_merged = counter("segment_merge");
+    let items_evicted = counter("item_evict");
+    let items_expired = counter("item_expire");
+
+    let mut stats = Stats::default();
+    let mut value = Vec::new();
+    let mut now = None;
+
+    let mut trace = Trace::open(path, options.format)?;
+    for record in trace.by_ref() {
+        let record = record?;
+        stats.requests += 1;
+
+        // time only moves forward, out of order records are replayed at the
+        // current time
+        match now {
+            Some(now) if record.time <= now => {}
+            Some(previous) => {
+                seg::advance_clock(Duration::from_secs(record.time - previous));
+                cache.expire();
+                now = Some(record.time);
+            }
+            None => now = Some(record.time),
+        }
+
+        if value.len() < record.value_size {
+            value.resize(record.value_size, 0);
+        }
+        let value = &value[..record.value_size];
+        let ttl = Duration::from_secs(record.ttl.into());
+
+        let store = match record.op {
+            Op::Get => {
+                stats.gets += 1;
+                if cache.get(&record.key).is_some() {
+                    stats.hits += 1;
+                    None
+                } else {
+                    options.fill.filter(|_| record.value_size > 0)
+                }
+            }
+            Op::Set => Some(ttl),
+            Op::Add => cache.get_no_freq_incr(&record.key).is_none().then_some(ttl),
+            Op::Replace => cache.get_no_freq_incr(&record.key).is_some().then_some(ttl),
+            Op::Delete => {
+                cache.delete(&record.key);
+                None
+            }
+        };
+
+        if let Some(ttl) = store {
+            if cache.insert(&record.key, value, None, ttl).is_err() {
+                stats.store_failures += 1;
+            }
+        }
+    }
+
+    stats.skipped = trace.skipped;
+    stats.segments_evicted = counter("segment_evict") - segments_evicted;
+    stats.segments_merged = counter("segment_merge") - segments_merged;
+    stats.items_evicted = counter("item_evict") - items_evicted;
+    stats.items_expired = counter("item_expire") - items_expired;
+
+    Ok(stats)
+}
+
+fn report(results: &[(&str, usize, Stats)], policies: &[&str], heap_sizes: &[usize]) {
+    if let Some((_, _, stats)) = results.first() {
+        println!(
+            "requests: {} replayed, {} skipped",
+            stats.requests, stats.skipped
+        );
+        println!();
+    }
+
+    println!(
+        "{:<12} {:>10} {:>12} {:>10} {:>12} {:>12} {:>12} {:>12} {:>12}",
+        "POLICY",
+        "HEAP SIZE",
+        "GETS",
+        "HIT RATIO",
+        "STORE FAIL",
+        "SEG EVICT",
+        "SEG MERGE",
+        "ITEM EVICT",
+        "ITEM EXPIRE"
+    );
+    for (policy, heap_size, stats) in results {
+        println!(
+            "{:<12} {:>10} {:>12} {:>10.4} {:>12} {:>12} {:>12} {:>12} {:>12}",
+            policy,
+            format_size(*heap_size),
+            stats.gets,
+            stats.hit_ratio(),
+            stats.store_failures,
+            stats.segments_evicted,
+            stats.segments_merged,
+            stats.items_evicted,
+            stats.items_expired
+        );
+    }
+
+    // a miss ratio curve needs more than one heap size
+    if heap_sizes.len() < 2 {
+        return;
+    }
+
+    println!();
+    println!("miss ratio curves:");
+    print!("{:<12}", "POLICY");
+    for heap_size in heap_sizes {
+        print!(" {:>10}", format_size(*heap_size));
+    }
+    println!();
+    for policy in policies {
+        print!("{:<12}", policy);
+        for (_, _, stats) in results.iter().filter(|(p, _, _)| p == policy) {
+            print!(" {:>10.4}", stats.miss_ratio());
+        }
+        println!();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn klog() {
+        assert_eq!(
+            parse_klog("2022-03-01T12:00:00.123+00:00 \"set coffee 0 3600 6\" 5 8"),
+            Some(Record {
+                time: 1646136000,
+                op: Op::Set,
+                key: b"coffee".to_vec().into_boxed_slice(),
+                value_size: 6,
+                ttl: 3600,
+            })
+        );
+        assert_eq!(
+            parse_klog("2022-03-01T13:00:01.000+01:00 \"get coffee\" 4 6"),
+            Some(Record {
+                time: 1646136001,
+                op: Op::Get,
+                key: b"coffee".to_vec().into_boxed_slice(),
+                value_size: 6,
+                ttl: 0,
+            })
+        );
+        assert_eq!(
+            parse_klog("2022-03-01T12:00:01Z \"mg coffee\" 4 6").map(|record| record.value_size),
+            Some(6)
+        );
+        assert_eq!(
+            parse_klog("2022-03-01T12:00:02Z \"delete coffee\" 7 9").map(|record| record.op),
+            Some(Op::Delete)
+        );
+
+        // commands which are not replayed and malformed lines are skipped
+        assert_eq!(
+            parse_klog("2022-03-01T12:00:00Z \"incr count 1\" 8 11"),
+            None
+        );
+        assert_eq!(parse_klog("\"get coffee\" 4 6"), None);
+        assert_eq!(parse_klog("2022-03-01T12:00:00Z get coffee"), None);
+    }
+
+    #[test]
+    fn binary() {
+        let mut buf = [0; RECORD_SIZE];
+        buf[0..4].copy_from_slice(&60_u32.to_le_bytes());
+        buf[4..12].copy_from_slice(&42_u64.to_le_bytes());
+        buf[12..16].copy_from_slice(&((10 << 22) | 100_u32).to_le_bytes());
+        buf[16..20].copy_from_slice(&((3 << 24) | 300_u32).to_le_bytes());
+
+        let mut key = 42_u64.to_le_bytes().to_vec();
+        key.resize(10, 0);
+        assert_eq!(
+            decode_binary(&buf),
+            Some(Record {
+                time: 60,
+                op: Op::Set,
+                key: key.into_boxed_slice(),
+                value_size: 100,
+                ttl: 300,
+            })
+        );
+
+        // incr is not replayed
+        buf[16..20].copy_from_slice(&(10_u32 << 24).to_le_bytes());
+        assert_eq!(decode_binary(&buf), None);
+    }
+
+    #[test]
+    fn replay() {
+        let trace = [
+            "2022-03-01T12:00:00Z \"set coffee 0 0 6\" 5 8",
+            "2022-03-01T12:00:00Z \"get coffee\" 4 6",
+            "2022-03-01T12:00:00Z \"get tea\" 0 0",
+            "2022-03-01T12:00:00Z \"set tea 0 60 3\" 5 8",
+            "2022-03-01T12:00:30Z \"mg tea\" 4 3",
+            // the item has expired
+            "2022-03-01T12:02:00Z \"get tea\" 0 0",
+            "2022-03-01T12:02:00Z \"delete coffee\" 7 9",
+            "2022-03-01T12:02:00Z \"get coffee\" 0 0",
+            "2022-03-01T12:02:00Z \"add coffee 0 0 6\" 5 8",
+            // the add is not stored as the key exists
+            "2022-03-01T12:02:00Z \"add coffee 0 0 5\" 9 12",
+            "2022-03-01T12:02:01Z \"get coffee\" 4 6",
+            "2022-03-01T12:02:01Z \"incr count 1\" 8 11",
+            "malformed",
+        ];
+        let path = std::env::temp_dir().join(format!("seg-sim-{}", std::process::id()));
+        std::fs::write(&path, trace.join("\n")).expect("failed to write trace");
+
+        let options = Options {
+            format: Format::Klog,
+            segment_size: 64 * KB as i32,
+            hash_power: 16,
+            max_hash_power: 16,
+            merge: Policy::Merge {
+                max: 8,
+                merge: 4,
+                compact: 2,
+            },
+            fill: None,
+        };
+
+        seg::freeze_clock();
+        let stats = simulate(path.to_str().unwrap(), &options, "fifo", MB).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(stats.requests, 11);
+        assert_eq!(stats.skipped, 2);
+        // three hits and three misses
+        assert_eq!(stats.gets, 6);
+        assert_eq!(stats.hits, 3);
+        assert_eq!(stats.miss_ratio(), 0.5);
+        assert_eq!(stats.store_failures, 0);
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("4096"), Some(4096));
+        assert_eq!(parse_size("64M"), Some(64 * MB));
+        assert_eq!(parse_size("2g"), Some(2 * GB));
+        assert_eq!(parse_size("M"), None);
+        assert_eq!(format_size(64 * MB), "64M");
+        assert_eq!(format_size(1000), "1000");
+    }
+}
@@ -0,0 +1,108 @@
+// Copyright 2022 Twitter, Inc.
+// Licensed under the Apache License, Version 2.0
+// http://www.apache.org/licenses/LICENSE-2.0
+
+//! Tags allow every item related to some entity to be invalidated at once,
+//! without tracking their keys. Each tag maps to a slot holding a generation
+//! counter. A tagged item stores the slot and the generation of its tag when
+//! it was written, and invalidating a tag increments the generation for its
+//! slot. The items written before the invalidation are removed lazily, the
+//! next time they are read.
+//!
+//! The number of slots is fixed, so tags which map to the same slot are
+//! invalidated together. This may remove more items than intended, but never
+//! returns an item which was invalidated.
+
+use ahash::RandomState;
+use core::hash::{BuildHasher, Hasher};
+
+// number of generation counters, tags share a slot once there are more tags
+const TAG_SLOTS: usize = 64 * 1024;
+
+/// The tag of an item as it is stored with the item
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Tag {
+    slot: u32,
+    generation: u32,
+}
+
+impl Tag {
+    /// The size of the tag as it is stored with an item
+    pub const SIZE: usize = std::mem::size_of::<u64>();
+
+    pub fn from_bits(bits: u64) -> Self {
+        Self {
+            slot: (bits >> 32) as u32,
+            generation: bits as u32,
+        }
+    }
+
+    pub fn to_bits(self) -> u64 {
+        ((self.slot as u64) << 32) | self.generation as u64
+    }
+}
+
+/// The current generation for each slot
+pub(crate) struct Tags {
+    hash_builder: RandomState,
+    generations: Box<[u32]>,
+}
+
+impl Tags {
+    pub fn new() -> Self {
+        Self {
+            hash_builder: RandomState::with_seeds(
+                0x6a09e667f3bcc908,
+                0xbb67ae8584caa73b,
+                0x3c6ef372fe94f82b,
+                0xa54ff53a5f1d36f1,
+            ),
+            generations: vec![0; TAG_SLOTS].into_boxed_slice(),
+        }
+    }
+
+    /// Returns the tag to store with an item written now
+    pub fn tag(&self, tag: &[u8]) -> Tag {
+        let slot = self.slot(tag);
+        Tag {
+            slot: slot as u32,
+            generation: self.generations[slot],
+        }
+    }
+
+    /// Invalidates the items which were written with the tag
+    pub fn invalidate(&mut self, tag: &[u8]) {
+        let slot = self.slot(tag);
+        self.generations[slot] = self.generations[slot].wrapping_add(1);
+    }
+
+    /// Returns true if the tag has not been invalidated since the item was
+    /// written
+    pub fn is_current(&self, tag: Tag) -> bool {
+        self.generations
+            .get(tag.slot as usize)
+            .map(|generation| *generation == tag.generation)
+            .unwrap_or(false)
+    }
+
+    /// Returns the current generation for each slot
+    pub fn generations(&self) -> &[u32] {
+        &self.generations
+    }
+
+    /// Replaces the generation for each slot, which is used when restoring
+    /// from a checkpoint. Returns false if the number of slots differs.
+    pub fn restore(&mut self, generations: &[u32]) -> bool {
+        if generations.len() != self.generations.len() {
+            return false;
+        }
+        self.generations.copy_from_slice(generations);
+        true
+    }
+
+    fn slot(&self, tag: &[u8]) -> usize {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write(tag);
+        hasher.finish() as usize % TAG_SLOTS
+    }
+}
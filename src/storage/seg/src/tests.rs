@@ -17,14 +17,14 @@ fn sizes() {
     #[cfg(not(feature = "magic"))]
     assert_eq!(ITEM_HDR_SIZE, 5);
 
-    assert_eq!(std::mem::size_of::<Segments>(), 64);
+    assert_eq!(std::mem::size_of::<Segments>(), 136);
     assert_eq!(std::mem::size_of::<SegmentHeader>(), 64);
 
     assert_eq!(std::mem::size_of::<HashBucket>(), 64);
     assert_eq!(std::mem::size_of::<HashTable>(), 64);
 
     assert_eq!(std::mem::size_of::<crate::ttl_buckets::TtlBucket>(), 64);
-    assert_eq!(std::mem::size_of::<TtlBuckets>(), 24);
+    assert_eq!(std::mem::size_of::<TtlBuckets>(), 40);
 }
 
 #[test]
@@ -107,6 +107,166 @@ fn cas() {
     assert_eq!(cache.cas(b"coffee", b"iced", None, ttl, item.cas()), Ok(()));
 }
 
+#[test]
+fn tags() {
+    let ttl = Duration::ZERO;
+    let segment_size = 4096;
+    let segments = 64;
+    let heap_size = segments * segment_size as usize;
+
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(heap_size)
+        .item_cas(true)
+        .build()
+        .expect("failed to create cache");
+
+    // invalidating a tag before any items are tagged has no effect
+    cache.invalidate_tag(b"user:1");
+    assert!(cache.insert(b"plain", b"value", None, ttl).is_ok());
+
+    assert!(cache
+        .insert_tagged(
+            b"user:1:name",
+            b"alice",
+            Some(&[1; MAX_OLEN]),
+            ttl,
+            b"user:1"
+        )
+        .is_ok());
+    assert!(cache
+        .insert_tagged(b"user:1:visits", 1_u64, None, ttl, b"user:1")
+        .is_ok());
+    assert!(cache
+        .insert_tagged(b"user:2:name", b"bob", None, ttl, b"user:2")
+        .is_ok());
+
+    // the tag does not change what is read back
+    let item = cache.get(b"user:1:name").unwrap();
+    assert_eq!(item.value(), b"alice");
+    assert_eq!(item.optional(), Some(&[1; MAX_OLEN][..]));
+
+    // the tag is kept when the value is changed
+    assert!(cache.append(b"user:1:name", b" smith").is_ok());
+    assert_eq!(cache.get(b"user:1:name").unwrap().value(), b"alice smith");
+    assert!(cache.wrapping_add(b"user:1:visits", 1).is_ok());
+
+    // only the items with the tag are invalidated
+    cache.invalidate_tag(b"user:1");
+    assert!(cache.get(b"user:1:name").is_none());
+    assert!(cache.get_no_freq_incr(b"user:1:visits").is_none());
+    assert!(cache.get(b"user:2:name").is_some());
+    assert!(cache.get(b"plain").is_some());
+    assert_eq!(cache.items(), 2);
+
+    // invalidated items behave as if they were deleted
+    assert!(cache
+        .insert_tagged(b"user:2:visits", 1_u64, None, ttl, b"user:2")
+        .is_ok());
+    assert!(cache
+        .insert_tagged(b"user:2:email", b"bob@", None, ttl, b"user:2")
+        .is_ok());
+    let cas = cache.get(b"user:2:email").unwrap().cas();
+    cache.invalidate_tag(b"user:2");
+    assert_eq!(
+        cache.wrapping_add(b"user:2:visits", 1).err(),
+        Some(SegError::NotFound)
+    );
+    assert_eq!(
+        cache.cas(b"user:2:email", b"bob", None, ttl, cas),
+        Err(SegError::NotFound)
+    );
+    assert_eq!(cache.scan(Cursor::default()).count(), 1);
+
+    // items written after the invalidation are not affected
+    assert!(cache
+        .insert_tagged(b"user:1:name", b"carol", None, ttl, b"user:1")
+        .is_ok());
+    assert_eq!(cache.get(b"user:1:name").unwrap().value(), b"carol");
+}
+
+#[test]
+fn item_cas() {
+    let ttl = Duration::ZERO;
+    let segment_size = 4096;
+    let segments = 64;
+    let heap_size = segments * segment_size as usize;
+
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(heap_size)
+        .item_cas(true)
+        .build()
+        .expect("failed to create cache");
+
+    // each write is given a unique CAS value
+    assert!(cache.insert(b"coffee", b"hot", None, ttl).is_ok());
+    assert!(cache.insert(b"tea", b"green", None, ttl).is_ok());
+    let coffee = cache.get(b"coffee").unwrap().cas();
+    let tea = cache.get(b"tea").unwrap().cas();
+    assert_ne!(coffee, tea);
+    assert_eq!(cache.get(b"coffee").unwrap().cas(), coffee);
+
+    // writes to other keys do not change the CAS value
+    assert!(cache.insert(b"tea", b"black", None, ttl).is_ok());
+    assert!(cache.insert(b"juice", b"orange", None, ttl).is_ok());
+    assert_eq!(
+        cache.cas(b"coffee", b"iced", None, ttl, coffee + 1),
+        Err(SegError::Exists)
+    );
+    assert_eq!(cache.cas(b"coffee", b"iced", None, ttl, coffee), Ok(()));
+
+    // a successful CAS is a write, so the old CAS value is now stale
+    let item = cache.get(b"coffee").unwrap();
+    assert_eq!(item.value(), b"iced");
+    assert_ne!(item.cas(), coffee);
+    assert_eq!(
+        cache.cas(b"coffee", b"hot", None, ttl, coffee),
+        Err(SegError::Exists)
+    );
+
+    // as are increments and decrements
+    assert!(cache.insert(b"count", 1_u64, None, ttl).is_ok());
+    let before = cache.get(b"count").unwrap().cas();
+    let after = cache.wrapping_add(b"count", 1).unwrap().cas();
+    assert_ne!(before, after);
+    assert_eq!(cache.get(b"count").unwrap().cas(), after);
+    assert_eq!(
+        cache.cas(b"count", 5_u64, None, ttl, before),
+        Err(SegError::Exists)
+    );
+    assert_eq!(cache.cas(b"count", 5_u64, None, ttl, after), Ok(()));
+
+    // the optional data is still stored with the item
+    assert!(cache
+        .insert(b"flags", b"coffee", Some(&[1; MAX_OLEN]), ttl)
+        .is_ok());
+    let item = cache.get(b"flags").unwrap();
+    assert_eq!(item.optional(), Some(&[1; MAX_OLEN][..]));
+    assert_eq!(item.value(), b"coffee");
+
+    // each item is 8 bytes larger than with the CAS value of the hash bucket
+    let mut shared = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(heap_size)
+        .build()
+        .expect("failed to create cache");
+    assert!(shared
+        .insert(b"flags", b"coffee", Some(&[1; MAX_OLEN]), ttl)
+        .is_ok());
+    let size = |cache: &mut Seg| {
+        let item_info = cache
+            .hashtable
+            .get_item_info(b"flags", &mut cache.ttl_buckets, &mut cache.segments)
+            .unwrap();
+        cache.segments.get_item(item_info).unwrap().size()
+    };
+    // the CAS value follows the extended flags, and the size of each item is
+    // rounded up to a multiple of 8 bytes
+    let extra = size(&mut cache) - size(&mut shared);
+    assert!(extra == CAS_SIZE || extra == 2 * CAS_SIZE);
+}
+
 #[test]
 fn overwrite() {
     let ttl = Duration::ZERO;
@@ -380,6 +540,232 @@ fn expiration() {
     assert_eq!(cache.segments.free(), segments);
 }
 
+#[test]
+fn leases() {
+    let segments = 64;
+    let segment_size = 2 * 1024;
+    let heap_size = segments * segment_size as usize;
+    let ttl = Duration::ZERO;
+    let timeout = Duration::from_secs(10);
+
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(heap_size)
+        .stale_grace(Duration::from_secs(10))
+        .build()
+        .expect("failed to create cache");
+
+    // a current item is returned without a lease
+    assert!(cache
+        .insert(b"latte", b"old", None, Duration::from_secs(2))
+        .is_ok());
+    assert!(matches!(
+        cache.get_lease(b"latte", timeout),
+        Ok(LeaseResult::Hit(_))
+    ));
+
+    // only the first miss is granted a lease
+    let token = match cache.get_lease(b"mocha", timeout) {
+        Ok(LeaseResult::Win { token, stale }) => {
+            assert!(stale.is_none());
+            token
+        }
+        _ => panic!("expected a lease"),
+    };
+    assert!(matches!(
+        cache.get_lease(b"mocha", timeout),
+        Ok(LeaseResult::Wait)
+    ));
+
+    // the lease is only released with its token
+    assert!(!cache.release_lease(b"mocha", token + 1));
+    assert!(cache.release_lease(b"mocha", token));
+    assert!(!cache.release_lease(b"mocha", token));
+
+    // a write or a delete ends the lease
+    let token = match cache.get_lease(b"cortado", timeout) {
+        Ok(LeaseResult::Win { token, .. }) => token,
+        _ => panic!("expected a lease"),
+    };
+    assert!(cache.insert(b"cortado", b"value", None, ttl).is_ok());
+    assert!(!cache.release_lease(b"cortado", token));
+    assert!(cache.delete(b"cortado"));
+    let token = match cache.get_lease(b"cortado", timeout) {
+        Ok(LeaseResult::Win { token, .. }) => token,
+        _ => panic!("expected a lease"),
+    };
+    assert!(!cache.delete(b"cortado"));
+    assert!(!cache.release_lease(b"cortado", token));
+
+    let timed_out = match cache.get_lease(b"espresso", Duration::from_secs(1)) {
+        Ok(LeaseResult::Win { token, .. }) => token,
+        _ => panic!("expected a lease"),
+    };
+
+    // a lease which replaced one that ended early is kept when the timeout of
+    // the earlier lease passes
+    let token = match cache.get_lease(b"macchiato", Duration::from_secs(1)) {
+        Ok(LeaseResult::Win { token, .. }) => token,
+        _ => panic!("expected a lease"),
+    };
+    assert!(cache.release_lease(b"macchiato", token));
+    let token = match cache.get_lease(b"macchiato", timeout) {
+        Ok(LeaseResult::Win { token, .. }) => token,
+        _ => panic!("expected a lease"),
+    };
+
+    // wait for the item to expire, it is retained for the grace period
+    std::thread::sleep(std::time::Duration::from_secs(3));
+    cache.expire();
+    assert!(cache.release_lease(b"macchiato", token));
+    assert_eq!(cache.items(), 1);
+
+    // the expired item is only returned with a lease result
+    assert!(cache.get(b"latte").is_none());
+    assert!(cache.get_no_freq_incr(b"latte").is_none());
+    assert_eq!(cache.append(b"latte", b"!"), Err(SegError::NotFound));
+    let token = match cache.get_lease(b"latte", timeout) {
+        Ok(LeaseResult::Win {
+            token,
+            stale: Some(item),
+        }) => {
+            assert_eq!(item.value(), b"old");
+            token
+        }
+        _ => panic!("expected a lease with the stale item"),
+    };
+    match cache.get_lease(b"latte", timeout) {
+        Ok(LeaseResult::Stale(item)) => assert_eq!(item.value(), b"old"),
+        _ => panic!("expected the stale item"),
+    }
+    assert!(cache.release_lease(b"latte", token));
+    assert!(cache.insert(b"latte", b"new", None, ttl).is_ok());
+    assert_eq!(cache.get(b"latte").unwrap().value(), b"new");
+
+    // a lease which timed out is granted to the next caller
+    assert!(!cache.release_lease(b"espresso", timed_out));
+    assert!(matches!(
+        cache.get_lease(b"espresso", timeout),
+        Ok(LeaseResult::Win { .. })
+    ));
+
+    // a timeout which is out of range is rejected
+    assert_eq!(
+        cache
+            .get_lease(b"ristretto", MAX_LEASE_TIMEOUT + Duration::from_secs(1))
+            .err(),
+        Some(SegError::LeaseTimeout)
+    );
+    assert!(matches!(
+        cache.get_lease(b"ristretto", MAX_LEASE_TIMEOUT),
+        Ok(LeaseResult::Win { .. })
+    ));
+}
+
+#[test]
+fn expire_budget() {
+    let segments = 64;
+    let segment_size = 1024;
+    let heap_size = segments * segment_size as usize;
+
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(heap_size)
+        .expire_max_segments(Some(3))
+        .build()
+        .expect("failed to create cache");
+
+    // each item fills most of a segment
+    let value = [0; 768];
+    for i in 0..10 {
+        let key = format!("{}", i);
+        assert!(cache
+            .insert(key.as_bytes(), &value[..], None, Duration::from_secs(2))
+            .is_ok());
+    }
+    assert_eq!(cache.segments.free(), segments - 10);
+
+    std::thread::sleep(std::time::Duration::from_secs(3));
+
+    // the expired segments are removed a few at a time
+    assert_eq!(cache.expire(), 3);
+    assert!(cache.expire_pending());
+    assert_eq!(cache.segments.free(), segments - 7);
+    assert_eq!(cache.expire(), 3);
+    assert_eq!(cache.expire(), 3);
+    assert!(cache.expire_pending());
+    assert_eq!(cache.expire(), 1);
+    assert!(!cache.expire_pending());
+    assert_eq!(cache.segments.free(), segments);
+    assert_eq!(cache.items(), 0);
+
+    // a time budget always allows some progress
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(heap_size)
+        .expire_max_time(Some(Duration::ZERO))
+        .build()
+        .expect("failed to create cache");
+
+    for i in 0..10 {
+        let key = format!("{}", i);
+        assert!(cache
+            .insert(key.as_bytes(), &value[..], None, Duration::from_secs(2))
+            .is_ok());
+    }
+
+    std::thread::sleep(std::time::Duration::from_secs(3));
+
+    let mut expired = cache.expire();
+    assert!(cache.expire_pending());
+    while cache.expire_pending() {
+        expired += cache.expire();
+    }
+    assert_eq!(expired, 10);
+    assert_eq!(cache.segments.free(), segments);
+}
+
+#[test]
+fn item_metadata() {
+    let segments = 64;
+    let segment_size = 2 * 1024;
+    let heap_size = segments * segment_size as usize;
+
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(heap_size)
+        .build()
+        .expect("failed to create cache");
+
+    assert!(cache
+        .insert(b"latte", b"", None, Duration::from_secs(100))
+        .is_ok());
+
+    // the item expires with its segment, which may be slightly sooner than
+    // the requested ttl
+    let item = cache.get(b"latte").unwrap();
+    let ttl = item.ttl().expect("item should expire");
+    assert!(ttl <= std::time::Duration::from_secs(100));
+    assert!(ttl > std::time::Duration::from_secs(80));
+    assert!(item.expire_at().unwrap() > std::time::SystemTime::now());
+    assert!(item.age() < std::time::Duration::from_secs(2));
+    assert!(item.create_at() <= std::time::SystemTime::now());
+    assert_eq!(item.freq(), 1);
+
+    // items stored without a ttl do not expire
+    assert!(cache.insert(b"mocha", b"", None, Duration::ZERO).is_ok());
+    let item = cache.get(b"mocha").unwrap();
+    assert!(item.ttl().is_none());
+    assert!(item.expire_at().is_none());
+
+    // the lookup without a frequency increment sees the same count
+    let item = cache
+        .hashtable
+        .get_no_freq_incr(b"latte", &mut cache.ttl_buckets, &mut cache.segments)
+        .unwrap();
+    assert_eq!(item.freq(), 1);
+}
+
 #[test]
 fn clear() {
     let ttl = Duration::ZERO;
@@ -483,6 +869,1248 @@ fn saturating_sub() {
     assert_eq!(item.value(), 0, "item is: {:?}", item);
 }
 
+#[test]
+fn append_prepend() {
+    let ttl = Duration::from_secs(60);
+    let segment_size = 4096;
+    let segments = 64;
+    let heap_size = segments * segment_size as usize;
+
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(heap_size)
+        .build()
+        .expect("failed to create cache");
+    assert_eq!(cache.append(b"coffee", b"!"), Err(SegError::NotFound));
+    assert_eq!(cache.prepend(b"coffee", b"!"), Err(SegError::NotFound));
+
+    assert!(cache
+        .insert(b"coffee", b"strong", Some(&[0, 0, 0, 42]), ttl)
+        .is_ok());
+    let expected_ttl = segment_ttl(&mut cache, b"coffee");
+    assert!(cache.append(b"coffee", b" and hot").is_ok());
+    assert!(cache.prepend(b"coffee", b"very ").is_ok());
+    assert_eq!(cache.items(), 1);
+
+    let item = cache.get(b"coffee").unwrap();
+    assert_eq!(item.value(), b"very strong and hot", "item is: {:?}", item);
+    assert_eq!(item.optional(), Some(&[0, 0, 0, 42][..]));
+
+    // the item remains in the same ttl bucket
+    assert_eq!(segment_ttl(&mut cache, b"coffee"), expected_ttl);
+
+    // numeric values are concatenated as their decimal representation
+    assert!(cache.insert(b"tea", 10, None, ttl).is_ok());
+    assert!(cache.append(b"tea", b"5").is_ok());
+    assert_eq!(cache.get(b"tea").unwrap().value(), 105);
+    assert!(cache.prepend(b"tea", b"2").is_ok());
+    assert_eq!(cache.get(b"tea").unwrap().value(), 2105);
+    assert!(cache.append(b"tea", b" cups").is_ok());
+    assert_eq!(cache.get(b"tea").unwrap().value(), b"2105 cups");
+    assert_eq!(
+        cache.wrapping_add(b"tea", 1).err(),
+        Some(SegError::NotNumeric)
+    );
+}
+
+#[test]
+fn append_near_expiry() {
+    let mut cache = Seg::builder().build().expect("failed to create cache");
+
+    assert!(cache
+        .insert(b"coffee", b"strong", None, Duration::from_secs(20))
+        .is_ok());
+    assert!(cache
+        .insert(b"latte", b"warm", None, Duration::from_secs(5))
+        .is_ok());
+    let ttl = segment_ttl(&mut cache, b"coffee");
+
+    std::thread::sleep(std::time::Duration::from_secs(3));
+
+    // the item keeps its remaining ttl rather than the ttl of its bucket
+    assert!(cache.append(b"coffee", b" and hot").is_ok());
+    assert_eq!(cache.get(b"coffee").unwrap().value(), b"strong and hot");
+    assert!(segment_ttl(&mut cache, b"coffee") < ttl);
+
+    // an item which has expired is not extended
+    assert_eq!(cache.append(b"latte", b"!"), Err(SegError::NotFound));
+    assert_eq!(cache.prepend(b"latte", b"!"), Err(SegError::NotFound));
+}
+
+fn segment_ttl(cache: &mut Seg, key: &[u8]) -> crate::Duration {
+    let item_info = cache
+        .hashtable
+        .get_item_info(key, &mut cache.ttl_buckets, &mut cache.segments)
+        .expect("not found");
+    cache
+        .segments
+        .get_mut(get_seg_id(item_info).unwrap())
+        .unwrap()
+        .ttl()
+}
+
+#[test]
+fn scan() {
+    let segment_size = 4096;
+    let segments = 64;
+    let heap_size = segments * segment_size as usize;
+
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(heap_size)
+        .hash_power(16)
+        .build()
+        .expect("failed to create cache");
+
+    // an empty cache finishes the scan immediately
+    let mut scan = cache.scan(Cursor::default());
+    assert!(scan.next().is_none());
+    assert!(scan.cursor().is_done());
+
+    for i in 0..200 {
+        let key = format!("key{}", i);
+        let ttl = if i % 2 == 0 {
+            Duration::from_secs(60)
+        } else {
+            Duration::from_secs(3600)
+        };
+        assert!(cache
+            .insert(key.as_bytes(), key.as_bytes(), None, ttl)
+            .is_ok());
+    }
+
+    // overwritten and deleted items are not returned
+    assert!(cache
+        .insert(b"key0", b"updated", None, Duration::from_secs(60))
+        .is_ok());
+    assert!(cache.delete(b"key1"));
+
+    // take a few items at a time, modifying the cache between calls
+    let mut found = std::collections::HashSet::new();
+    let mut cursor = Cursor::default();
+    while !cursor.is_done() {
+        let mut scan = cache.scan(cursor);
+        for (item, ttl) in scan.by_ref().take(7) {
+            assert!(ttl.unwrap() <= Duration::from_secs(3600));
+            if item.key() == b"key0" {
+                assert_eq!(item.value(), b"updated");
+            } else {
+                assert!(item.value() == Value::Bytes(item.key()));
+            }
+            found.insert(item.key().to_vec());
+        }
+        cursor = scan.cursor();
+        let _ = cache.get(b"key2");
+    }
+
+    assert_eq!(found.len(), 199);
+    assert!(!found.contains(&b"key1"[..]));
+}
+
+#[test]
+fn dump_load() {
+    let mut cache = Seg::builder()
+        .segment_size(4096)
+        .heap_size(64 * 4096)
+        .hash_power(16)
+        .build()
+        .expect("failed to create cache");
+
+    for i in 0..100 {
+        let key = format!("key{}", i);
+        assert!(cache
+            .insert(
+                key.as_bytes(),
+                key.as_bytes(),
+                Some(&[i as u8, 0, 0, 42]),
+                Duration::from_secs(3600)
+            )
+            .is_ok());
+    }
+    assert!(cache
+        .insert(b"number", 7_u64, None, Duration::from_secs(60))
+        .is_ok());
+
+    let mut dump = Vec::new();
+    assert_eq!(cache.dump(&mut dump).unwrap(), 101);
+
+    // load into a cache with a different size
+    let mut other = Seg::builder()
+        .segment_size(8192)
+        .heap_size(64 * 8192)
+        .hash_power(12)
+        .build()
+        .expect("failed to create cache");
+    assert_eq!(other.load(std::io::Cursor::new(&dump)).unwrap(), 101);
+
+    for i in 0..100 {
+        let key = format!("key{}", i);
+        let item = other.get(key.as_bytes()).expect("didn't get item back");
+        assert!(item.value() == Value::Bytes(key.as_bytes()));
+        assert_eq!(item.optional(), Some(&[i as u8, 0, 0, 42][..]));
+    }
+    assert!(other.wrapping_add(b"number", 1).is_ok());
+    assert_eq!(other.get(b"number").unwrap().value(), Value::U64(8));
+
+    // the expiry is preserved rather than reset to the bucket maximum
+    let ttl = segment_ttl(&mut other, b"number").as_secs();
+    assert!(ttl <= 60, "ttl: {}", ttl);
+
+    // a corrupted dump is rejected before any item is loaded
+    let last = dump.len() - 1;
+    dump[last] ^= 0xff;
+    let mut empty = Seg::builder().build().expect("failed to create cache");
+    assert!(empty.load(std::io::Cursor::new(&dump)).is_err());
+    assert_eq!(empty.items(), 0);
+    assert!(other.load(std::io::Cursor::new(&dump)).is_err());
+    assert!(other.load(std::io::Cursor::new(b"not a dump")).is_err());
+}
+
+#[test]
+fn dump_load_incremental() {
+    let mut cache = Seg::builder().build().expect("failed to create cache");
+    for i in 0..100 {
+        let key = format!("key{}", i);
+        assert!(cache
+            .insert(
+                key.as_bytes(),
+                key.as_bytes(),
+                None,
+                Duration::from_secs(3600)
+            )
+            .is_ok());
+    }
+
+    // a dump may be interleaved with writes to the cache
+    let mut buffer = Vec::new();
+    let mut dump = DumpTask::new(&mut buffer).expect("failed to start dump");
+    let mut steps = 0;
+    while !dump.step(&mut cache, 10).expect("failed to dump") {
+        steps += 1;
+        assert!(cache
+            .insert(b"coffee", b"hot", None, Duration::from_secs(60))
+            .is_ok());
+    }
+    assert!(steps >= 10);
+    let count = dump.finish().expect("failed to finish dump");
+    assert!((100..=101).contains(&count), "count: {}", count);
+
+    // nothing is inserted until the dump has been read in full
+    let mut other = Seg::builder().build().expect("failed to create cache");
+    let mut load = LoadTask::new(std::io::Cursor::new(&buffer)).expect("failed to start load");
+    for _ in 0..5 {
+        assert_eq!(load.step(&mut other, 10).expect("failed to load"), None);
+        assert_eq!(other.items(), 0);
+    }
+    let mut loaded = None;
+    while loaded.is_none() {
+        loaded = load.step(&mut other, 10).expect("failed to load");
+    }
+    assert_eq!(loaded, Some(count));
+    assert!(other.get(b"key99").is_some());
+
+    // a truncated dump is rejected without loading anything
+    let mut empty = Seg::builder().build().expect("failed to create cache");
+    let mut load = LoadTask::new(std::io::Cursor::new(&buffer[..buffer.len() / 2]))
+        .expect("failed to start load");
+    let result = loop {
+        match load.step(&mut empty, 10) {
+            Ok(None) => assert_eq!(empty.items(), 0),
+            result => break result,
+        }
+    };
+    assert!(result.is_err());
+    assert_eq!(empty.items(), 0);
+}
+
+#[test]
+fn large_item() {
+    let segment_size = 4096;
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(64 * segment_size as usize)
+        .hash_power(16)
+        .build()
+        .expect("failed to create cache");
+
+    // a value spanning several segments is stored in chunks
+    let value: Vec<u8> = (0..(5 * segment_size as usize)).map(|i| i as u8).collect();
+    assert!(cache
+        .insert(b"large", value.as_slice(), Some(b"flag"), Duration::ZERO)
+        .is_ok());
+    let item = cache.get(b"large").expect("didn't get item back");
+    assert!(item.value() == Value::Bytes(&value));
+    assert_eq!(item.optional(), Some(&b"flag"[..]));
+
+    // the chunks are hidden from a scan
+    assert_eq!(cache.scan(Cursor::default()).count(), 1);
+
+    // appending keeps the value intact
+    assert!(cache.append(b"large", b"tail").is_ok());
+    let item = cache.get(b"large").expect("didn't get item back");
+    match item.value() {
+        Value::Bytes(v) => {
+            assert_eq!(v.len(), value.len() + 4);
+            assert!(v.ends_with(b"tail"));
+        }
+        Value::U64(_) => panic!("value should not be numeric"),
+    }
+
+    // replacing and deleting the item removes the chunks
+    assert!(cache
+        .insert(b"large", b"small", None, Duration::ZERO)
+        .is_ok());
+    assert_eq!(cache.get(b"large").unwrap().value(), b"small");
+    assert_eq!(cache.items(), 1);
+    assert!(cache
+        .insert(b"large", value.as_slice(), None, Duration::ZERO)
+        .is_ok());
+    // the chunks are not counted as items
+    assert_eq!(cache.items(), 1);
+
+    // clients cannot use the keys which are reserved for chunks
+    let manifest = cache.manifest(b"large").expect("not a large item");
+    let chunk_key = manifest.chunk_keys().next().unwrap();
+    assert!(cache.get(&chunk_key).is_none());
+    assert!(cache.get_no_freq_incr(&chunk_key).is_none());
+    assert_eq!(
+        cache.insert(&chunk_key, b"evil", None, Duration::ZERO),
+        Err(SegError::ReservedKey)
+    );
+    assert_eq!(cache.append(&chunk_key, b"evil"), Err(SegError::NotFound));
+    assert_eq!(
+        cache.cas(&chunk_key, b"evil", None, Duration::ZERO, 0),
+        Err(SegError::NotFound)
+    );
+    assert!(!cache.delete(&chunk_key));
+    assert_eq!(cache.get(b"large").unwrap().value().len(), value.len());
+    assert!(cache.delete(b"large"));
+    assert!(cache.get(b"large").is_none());
+    assert_eq!(cache.items(), 0);
+
+    // a value which would need most of the heap is rejected
+    let value = vec![0; 40 * segment_size as usize];
+    assert!(matches!(
+        cache.insert(b"huge", value.as_slice(), None, Duration::ZERO),
+        Err(SegError::ItemOversized { .. })
+    ));
+
+    // a large item with a missing chunk is treated as a miss
+    let value = vec![1; 2 * segment_size as usize];
+    assert!(cache
+        .insert(b"large", value.as_slice(), None, Duration::ZERO)
+        .is_ok());
+    let manifest = cache.manifest(b"large").expect("not a large item");
+    let chunk_key = manifest.chunk_keys().next().unwrap();
+    assert!(cache
+        .hashtable
+        .delete(&chunk_key, &mut cache.ttl_buckets, &mut cache.segments));
+    assert!(cache.get(b"large").is_none());
+    assert_eq!(cache.items(), 0);
+}
+
+#[test]
+fn compression() {
+    let segment_size = 4096;
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(64 * segment_size as usize)
+        .hash_power(16)
+        .compression(Some(64))
+        .build()
+        .expect("failed to create cache");
+
+    // a value which compresses well is stored compressed
+    let value = b"{\"drink\": \"coffee\"}".repeat(100);
+    assert!(cache
+        .insert(b"json", value.as_slice(), Some(b"flag"), Duration::ZERO)
+        .is_ok());
+    let item = cache.get(b"json").expect("didn't get item back");
+    assert!(item.is_compressed());
+    assert!(item.raw_value() != Value::Bytes(&value));
+    assert!(item.value() == Value::Bytes(&value));
+    assert_eq!(item.optional(), Some(&b"flag"[..]));
+
+    // values below the threshold or which do not shrink are left as-is
+    assert!(cache
+        .insert(b"small", b"coffee", None, Duration::ZERO)
+        .is_ok());
+    assert!(!cache.get(b"small").unwrap().is_compressed());
+    let random: Vec<u8> = (0..256_u64)
+        .map(|i| (i.wrapping_mul(0x9E3779B97F4A7C15) >> 56) as u8)
+        .collect();
+    assert!(cache
+        .insert(b"random", random.as_slice(), None, Duration::ZERO)
+        .is_ok());
+    let item = cache.get(b"random").unwrap();
+    assert!(!item.is_compressed());
+    assert!(item.value() == Value::Bytes(&random));
+
+    // a compressed value which is larger than a segment is also chunked
+    let mut value = Vec::new();
+    let mut state = 0x2545F4914F6CDD1D_u64;
+    while value.len() < 5 * segment_size as usize {
+        // random runs which are each repeated once compress by about half
+        let run: Vec<u8> = (0..64)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        value.extend_from_slice(&run);
+        value.extend_from_slice(&run);
+    }
+    assert!(cache
+        .insert(b"json", value.as_slice(), None, Duration::ZERO)
+        .is_ok());
+    let item = cache.get(b"json").unwrap();
+    assert!(item.is_large() && item.is_compressed());
+    assert!(item.value() == Value::Bytes(&value));
+    assert!(cache.append(b"json", b"!").is_ok());
+    match cache.get(b"json").unwrap().value() {
+        Value::Bytes(v) => assert_eq!(v.len(), value.len() + 1),
+        Value::U64(_) => panic!("value should not be numeric"),
+    }
+
+    // optional data is limited by the item header
+    assert!(matches!(
+        cache.insert(
+            b"flags",
+            b"coffee",
+            Some(&[0; MAX_OLEN + 1]),
+            Duration::ZERO
+        ),
+        Err(SegError::ItemOversized { .. })
+    ));
+}
+
+#[test]
+fn optional_max_len() {
+    let segment_size = 4096;
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(64 * segment_size as usize)
+        .hash_power(16)
+        .compression(Some(64))
+        .item_cas(true)
+        .build()
+        .expect("failed to create cache");
+    assert_eq!(MAX_OLEN, 63);
+
+    // the largest optional data fits alongside every per-item flag
+    let optional = [7; MAX_OLEN];
+    let value = b"{\"drink\": \"coffee\"}".repeat(100);
+    assert!(cache
+        .insert_tagged(
+            b"json",
+            value.as_slice(),
+            Some(&optional),
+            Duration::ZERO,
+            b"drinks"
+        )
+        .is_ok());
+    let item = cache.get(b"json").expect("didn't get item back");
+    assert!(item.is_compressed());
+    assert!(item.value() == Value::Bytes(&value));
+    assert_eq!(item.optional(), Some(&optional[..]));
+
+    assert!(cache
+        .insert(b"flags", b"coffee", Some(&optional), Duration::ZERO)
+        .is_ok());
+    let item = cache.get(b"flags").expect("didn't get item back");
+    assert_eq!(item.value(), b"coffee");
+    assert_eq!(item.optional(), Some(&optional[..]));
+
+    // the tag is still honored
+    cache.invalidate_tag(b"drinks");
+    assert!(cache.get(b"json").is_none());
+    assert!(cache.get(b"flags").is_some());
+}
+
+#[test]
+fn admission() {
+    let mut cache = Seg::builder()
+        .segment_size(4096)
+        .heap_size(64 * 4096)
+        .hash_power(16)
+        .admission(Admission::Bloom {
+            items: 1024,
+            window: Duration::from_secs(60),
+        })
+        .build()
+        .expect("failed to create cache");
+
+    // a new key is admitted on its second write
+    assert!(cache
+        .insert(b"coffee", b"strong", None, Duration::ZERO)
+        .is_ok());
+    assert!(cache.get_no_freq_incr(b"coffee").is_none());
+    assert!(cache
+        .insert(b"coffee", b"strong", None, Duration::ZERO)
+        .is_ok());
+    assert!(cache.get(b"coffee").is_some());
+
+    // or on a write which follows a miss
+    assert!(cache.get(b"tea").is_none());
+    assert!(cache.insert(b"tea", b"green", None, Duration::ZERO).is_ok());
+    assert!(cache.get(b"tea").is_some());
+
+    // writes to keys in the cache are always admitted
+    assert!(cache
+        .insert(b"coffee", b"black", None, Duration::ZERO)
+        .is_ok());
+    assert_eq!(cache.get(b"coffee").unwrap().value(), b"black");
+
+    // loading a dump bypasses admission
+    let mut dump = Vec::new();
+    assert_eq!(cache.dump(&mut dump).unwrap(), 2);
+    let mut other = Seg::builder()
+        .admission(Admission::Bloom {
+            items: 1024,
+            window: Duration::from_secs(60),
+        })
+        .build()
+        .expect("failed to create cache");
+    assert_eq!(other.load(std::io::Cursor::new(&dump)).unwrap(), 2);
+    assert!(other.get(b"tea").is_some());
+}
+
+#[test]
+fn eviction_policy() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // evicts the segment with the fewest live items, counting each selection
+    struct FewestItems(Arc<AtomicUsize>);
+
+    impl EvictionPolicy for FewestItems {
+        fn select(&mut self, segments: &[SegmentStats]) -> Option<NonZeroU32> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            segments
+                .iter()
+                .filter(|s| s.can_evict())
+                .min_by_key(|s| s.live_items())
+                .map(|s| s.id())
+        }
+    }
+
+    let selected = Arc::new(AtomicUsize::new(0));
+    let mut cache = Seg::builder()
+        .segment_size(4096)
+        .heap_size(16 * 4096)
+        .hash_power(16)
+        .eviction(FewestItems(selected.clone()))
+        .build()
+        .expect("failed to create cache");
+
+    let value = [0; 512];
+    for i in 0..256 {
+        let key = format!("{}", i);
+        assert!(cache
+            .insert(key.as_bytes(), &value, None, Duration::ZERO)
+            .is_ok());
+    }
+    assert!(selected.load(Ordering::Relaxed) > 0);
+    assert!(cache.get(b"255").is_some());
+    assert!(cache.get(b"0").is_none());
+
+    // a policy which never selects a segment behaves like `Policy::None`
+    struct Never;
+
+    impl EvictionPolicy for Never {
+        fn select(&mut self, _segments: &[SegmentStats]) -> Option<NonZeroU32> {
+            None
+        }
+    }
+
+    let mut cache = Seg::builder()
+        .segment_size(4096)
+        .heap_size(16 * 4096)
+        .hash_power(16)
+        .eviction(Box::new(Never) as Box<dyn EvictionPolicy>)
+        .build()
+        .expect("failed to create cache");
+
+    let mut full = false;
+    for i in 0..256 {
+        let key = format!("{}", i);
+        if cache
+            .insert(key.as_bytes(), &value, None, Duration::ZERO)
+            .is_err()
+        {
+            full = true;
+            break;
+        }
+    }
+    assert!(full);
+}
+
+#[test]
+fn tier2() {
+    let path = std::env::temp_dir().join(format!("seg-tier2-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let mut cache = Seg::builder()
+        .segment_size(4096)
+        .heap_size(4 * 4096)
+        .hash_power(16)
+        .eviction(Policy::Fifo)
+        .tier2_path(Some(&path))
+        .tier2_size(16 * 4096)
+        .build()
+        .expect("failed to create cache");
+
+    // write twice as much as fits in memory
+    let value = [1; 512];
+    for i in 0..56 {
+        let key = format!("{}", i);
+        assert!(cache
+            .insert(key.as_bytes(), &value, None, Duration::ZERO)
+            .is_ok());
+    }
+
+    // items from evicted segments were demoted rather than evicted
+    let cap = cache.segments.cap() as u32;
+    assert_eq!(cache.items(), 56);
+    let key = (0..56)
+        .map(|i| format!("{}", i))
+        .find(|key| {
+            let info = cache.hashtable.get_item_info(
+                key.as_bytes(),
+                &mut cache.ttl_buckets,
+                &mut cache.segments,
+            );
+            get_seg_id(info.unwrap()).unwrap().get() > cap
+        })
+        .expect("no items were demoted");
+
+    // a read promotes the item back into memory
+    assert_eq!(cache.get(key.as_bytes()).unwrap().value(), value[..]);
+    let info =
+        cache
+            .hashtable
+            .get_item_info(key.as_bytes(), &mut cache.ttl_buckets, &mut cache.segments);
+    assert!(get_seg_id(info.unwrap()).unwrap().get() <= cap);
+    assert_eq!(cache.items(), 56);
+
+    // deletes and flushes apply to both tiers
+    assert!(cache.delete(b"1"));
+    assert!(cache.get(b"1").is_none());
+    cache.clear();
+    assert_eq!(cache.items(), 0);
+    assert!(cache.get(b"2").is_none());
+
+    drop(cache);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn namespaces() {
+    let mut cache = Seg::builder()
+        .segment_size(4096)
+        .heap_size(8 * 4096)
+        .hash_power(16)
+        .namespaces(vec![Namespace::new("a", 2), Namespace::new("b", 8)])
+        .namespace_delimiter(Some(b':'))
+        .build()
+        .expect("failed to create cache");
+
+    assert_eq!(cache.segments.namespace(b"a:0"), 1);
+    assert_eq!(cache.segments.namespace(b"b:0"), 2);
+    assert_eq!(cache.segments.namespace(b"ab:0"), 0);
+    assert_eq!(cache.segments.namespace(b"a"), 0);
+
+    // fill the cache with items from the first namespace
+    let value = [1; 512];
+    for i in 0..56 {
+        let key = format!("a:{}", i);
+        assert!(cache
+            .insert(key.as_bytes(), &value, None, Duration::ZERO)
+            .is_ok());
+    }
+
+    // the second namespace is within its quota, so its items are kept while
+    // segments are evicted from the first namespace
+    for i in 0..21 {
+        let key = format!("b:{}", i);
+        assert!(cache
+            .insert(key.as_bytes(), &value, None, Duration::ZERO)
+            .is_ok());
+    }
+    for i in 0..21 {
+        let key = format!("b:{}", i);
+        assert!(cache.get(key.as_bytes()).is_some());
+    }
+    assert!(cache.items() < 56 + 21);
+
+    // each segment holds items from a single namespace
+    let segments = cache.segments.cap();
+    for id in 1..=segments {
+        let segment = cache
+            .segments
+            .get_mut(NonZeroU32::new(id as u32).unwrap())
+            .unwrap();
+        if segment.accessible() && segment.live_items() > 0 {
+            assert!(segment.namespace() == 1 || segment.namespace() == 2);
+        }
+    }
+
+    // the segments held by each namespace are tracked as they are allocated
+    // and freed
+    for namespace in 0..3 {
+        let held = (1..=segments)
+            .filter(|id| {
+                let segment = cache
+                    .segments
+                    .get_mut(NonZeroU32::new(*id as u32).unwrap())
+                    .unwrap();
+                segment.accessible() && segment.namespace() == namespace
+            })
+            .count();
+        assert_eq!(cache.segments.namespace_segments(namespace), held);
+    }
+}
+
+#[test]
+fn hashtable_growth() {
+    let mut cache = Seg::builder()
+        .hash_power(4)
+        .max_hash_power(Some(12))
+        .overflow_factor(0.5)
+        .build()
+        .expect("failed to create cache");
+
+    // the initial hashtable only has room for ~21 items
+    for i in 0..2000 {
+        let key = format!("{}", i);
+        assert!(cache
+            .insert(key.as_bytes(), key.as_bytes(), None, Duration::ZERO)
+            .is_ok());
+    }
+    assert!(cache.hashtable.power() > 4);
+    assert_eq!(cache.items(), 2000);
+
+    // items can be read and updated while they are being moved
+    for i in 0..2000 {
+        let key = format!("{}", i);
+        let item = cache.get(key.as_bytes()).expect("item missing");
+        assert_eq!(item.value(), *key.as_bytes());
+    }
+    let item = cache.get(b"0").unwrap();
+    assert!(cache
+        .cas(b"0", b"updated", None, Duration::ZERO, item.cas())
+        .is_ok());
+    assert!(cache.delete(b"1"));
+
+    // expiration moves the remaining items into the larger hashtable
+    while cache.hashtable.is_growing() {
+        cache.expire();
+    }
+    assert_eq!(cache.get(b"0").unwrap().value(), b"updated");
+    assert!(cache.get(b"1").is_none());
+    for i in 2..2000 {
+        let key = format!("{}", i);
+        assert!(cache.get(key.as_bytes()).is_some());
+    }
+    assert_eq!(cache.items(), 1999);
+}
+
+#[test]
+fn hashtable_growth_drop() {
+    let mut cache = Seg::builder()
+        .hash_power(3)
+        .max_hash_power(Some(4))
+        .overflow_factor(1.0)
+        .build()
+        .expect("failed to create cache");
+
+    // the hashtable has a single primary bucket, and these keys all belong
+    // to the same primary bucket once the hashtable has grown
+    let keys: Vec<String> = (0..)
+        .map(|i| format!("{}", i))
+        .filter(|key| cache.hashtable.hash(key.as_bytes()) & 1 == 0)
+        .take(8)
+        .collect();
+    for key in &keys {
+        assert!(cache
+            .insert(key.as_bytes(), key.as_bytes(), None, Duration::ZERO)
+            .is_ok());
+    }
+    assert!(!cache.hashtable.is_growing());
+    assert_eq!(cache.items(), 8);
+
+    // there is only room for seven of the items in the larger hashtable, so
+    // one is removed while the items are moved
+    assert!(cache.hashtable.grow_without_overflow());
+    while cache.hashtable.is_growing() {
+        cache.expire();
+    }
+    let found: Vec<&String> = keys
+        .iter()
+        .filter(|key| cache.get(key.as_bytes()).is_some())
+        .collect();
+    assert_eq!(found.len(), 7);
+    assert_eq!(cache.items(), 7);
+
+    // the segment accounting matches the items which remain
+    for key in found {
+        assert!(cache.delete(key.as_bytes()));
+    }
+    assert_eq!(cache.items(), 0);
+    assert_eq!(cache.segments.free(), cache.segments.cap() - 1);
+}
+
+#[test]
+fn checkpoint() {
+    let path = std::env::temp_dir().join(format!("seg-checkpoint-{}", std::process::id()));
+    let meta = checkpoint::path(&path);
+    let _ = std::fs::remove_file(&path);
+    let heap_size = 64 * 4096;
+
+    let builder = || {
+        Seg::builder()
+            .segment_size(4096)
+            .heap_size(heap_size)
+            .datapool_path(Some(&path))
+            .namespaces(vec![Namespace::new("user", 8)])
+    };
+
+    let mut cache = builder().build().expect("failed to create cache");
+    for i in 0..100 {
+        let key = format!("user:{}", i);
+        assert!(cache
+            .insert(key.as_bytes(), i as u64, None, Duration::from_secs(3600))
+            .is_ok());
+    }
+    assert!(cache
+        .insert_tagged(b"coffee", b"hot", None, Duration::ZERO, b"drinks")
+        .is_ok());
+    assert!(cache
+        .insert_tagged(b"tea", b"green", None, Duration::ZERO, b"drinks")
+        .is_ok());
+    assert!(cache.delete(b"user:0"));
+    cache.invalidate_tag(b"drinks");
+    assert!(cache
+        .insert_tagged(b"tea", b"black", None, Duration::ZERO, b"drinks")
+        .is_ok());
+    let segments = cache.segments.namespace_segments(1);
+    let default_segments = cache.segments.namespace_segments(0);
+    assert!(segments > 0);
+    assert!(cache.close().is_ok());
+    drop(cache);
+
+    // the checkpoint can be read without opening the datapool
+    let checkpoint = Checkpoint::read(&path).expect("failed to read checkpoint");
+    assert_eq!(checkpoint.items(), 101);
+    assert_eq!(checkpoint.namespaces(), &[b"user".to_vec()]);
+    let live: usize = checkpoint.segments().map(|s| s.live_items()).sum();
+    assert_eq!(live, 101);
+    assert_eq!(
+        checkpoint
+            .segments()
+            .filter(|s| s.in_use() && s.namespace() == 1)
+            .count(),
+        segments
+    );
+    let file = std::fs::read(&path).unwrap();
+    let mut keys = Vec::new();
+    assert!(checkpoint
+        .for_each_key(&file[datapool::HEADER_SIZE..], |_, key| keys
+            .push(key.to_vec()))
+        .is_ok());
+    assert_eq!(keys.len(), 101);
+    assert!(keys.contains(&b"user:99".to_vec()));
+
+    // the items, tags, and namespace segments are restored
+    let mut cache = builder().build().expect("failed to create cache");
+    assert!(cache.get(b"user:0").is_none());
+    for i in 1..100 {
+        let key = format!("user:{}", i);
+        let item = cache.get(key.as_bytes()).expect("item not restored");
+        assert_eq!(item.value(), i as u64);
+        assert!(item.ttl().unwrap() <= Duration::from_secs(3600));
+    }
+    assert!(cache.get(b"coffee").is_none());
+    assert_eq!(cache.get(b"tea").unwrap().value(), b"black");
+    assert_eq!(cache.items(), 100);
+    assert_eq!(cache.segments.namespace_segments(1), segments);
+    assert_eq!(cache.segments.namespace_segments(0), default_segments);
+
+    // writes after the last checkpoint change the file, so it is discarded
+    assert!(cache
+        .insert(b"coffee", b"iced", None, Duration::ZERO)
+        .is_ok());
+    assert!(cache.checkpoint().is_ok());
+    assert!(cache.delete(b"coffee"));
+    assert!(cache
+        .insert(b"coffee", b"decaf", None, Duration::ZERO)
+        .is_ok());
+    drop(cache);
+    let mut cache = builder().build().expect("failed to create cache");
+    assert_eq!(cache.items(), 0);
+    assert!(cache.get(b"tea").is_none());
+
+    // the items are not restored once the heap has been resized
+    assert!(cache
+        .insert(b"coffee", b"hot", None, Duration::ZERO)
+        .is_ok());
+    assert!(cache.checkpoint().is_ok());
+    assert!(meta.exists());
+    assert!(cache.resize(heap_size / 2).is_ok());
+    assert!(cache.checkpoint().is_ok());
+    assert!(!meta.exists());
+    drop(cache);
+    let mut cache = builder().build().expect("failed to create cache");
+    assert!(cache.get(b"coffee").is_none());
+    drop(cache);
+
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(&meta);
+}
+
+#[test]
+fn huge_pages() {
+    // falls back to the default page size if huge pages are unavailable
+    for huge_pages in [HugePages::None, HugePages::Transparent, HugePages::Explicit] {
+        let mut cache = Seg::builder()
+            .segment_size(4096)
+            .heap_size(64 * 4096)
+            .huge_pages(huge_pages)
+            .build()
+            .expect("failed to create cache");
+        assert!(cache
+            .insert(b"coffee", b"hot", None, Duration::ZERO)
+            .is_ok());
+        assert_eq!(cache.get(b"coffee").unwrap().value(), b"hot");
+    }
+}
+
+#[test]
+fn resize_heap() {
+    let segment_size = 4096;
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(16 * segment_size as usize)
+        .max_heap_size(Some(64 * segment_size as usize))
+        .hash_power(16)
+        .build()
+        .expect("failed to create cache");
+    assert_eq!(cache.segments.cap(), 16);
+    assert_eq!(cache.segments.max(), 64);
+
+    // the heap may not grow beyond the max heap size or shrink to nothing
+    assert!(cache.resize(128 * segment_size as usize).is_err());
+    assert!(cache.resize(0).is_err());
+
+    // new segments are available immediately
+    cache
+        .resize(64 * segment_size as usize)
+        .expect("failed to grow");
+    assert_eq!(cache.segments.cap(), 64);
+    assert_eq!(cache.segments.free(), 64);
+
+    let value = vec![0; 1024];
+    for i in 0..150 {
+        let key = format!("{}", i);
+        assert!(cache
+            .insert(key.as_bytes(), value.as_slice(), None, Duration::ZERO)
+            .is_ok());
+    }
+    assert_eq!(cache.items(), 150);
+    assert!(cache.segments.free() < 16);
+
+    // free segments are removed immediately and segments which hold items
+    // are removed by expiration
+    cache
+        .resize(8 * segment_size as usize)
+        .expect("failed to shrink");
+    assert_eq!(cache.segments.target(), 8);
+    assert!(cache.expire_pending());
+    while cache.expire_pending() {
+        cache.expire();
+    }
+    assert_eq!(cache.segments.cap(), 8);
+    assert_eq!(cache.heap_size(), 8 * segment_size as usize);
+    assert!(cache.items() < 150);
+
+    // the items which remain can be read and new items can be written
+    for i in 0..150 {
+        let key = format!("{}", i);
+        if let Some(item) = cache.get(key.as_bytes()) {
+            assert_eq!(item.value(), value[..]);
+        }
+    }
+    for i in 200..400 {
+        let key = format!("{}", i);
+        assert!(cache
+            .insert(key.as_bytes(), value.as_slice(), None, Duration::ZERO)
+            .is_ok());
+    }
+    assert!(cache.get(b"399").is_some());
+
+    // the heap can grow again
+    cache
+        .resize(32 * segment_size as usize)
+        .expect("failed to grow");
+    assert_eq!(cache.segments.cap(), 32);
+    for i in 400..460 {
+        let key = format!("{}", i);
+        assert!(cache
+            .insert(key.as_bytes(), value.as_slice(), None, Duration::ZERO)
+            .is_ok());
+    }
+    assert!(cache.get(b"399").is_some());
+    assert!(cache.get(b"459").is_some());
+}
+
+#[test]
+fn shared_readers() {
+    let segment_size = 4096;
+    let cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(64 * segment_size as usize)
+        .hash_power(12)
+        .compression(Some(256))
+        .build()
+        .expect("failed to create cache");
+    let (mut writer, reader) = cache.into_shared();
+
+    // small, compressible, and chunked values are all readable
+    let json = b"{\"drink\": \"coffee\"}".repeat(100);
+    let large: Vec<u8> = (0..(3 * segment_size as usize)).map(|i| i as u8).collect();
+    {
+        let mut cache = writer.write();
+        assert!(cache
+            .insert(b"small", b"value", None, Duration::ZERO)
+            .is_ok());
+        assert!(cache
+            .insert(b"counter", 1_u64, None, Duration::ZERO)
+            .is_ok());
+        assert!(cache
+            .insert(b"json", json.as_slice(), None, Duration::ZERO)
+            .is_ok());
+        assert!(cache
+            .insert(b"large", large.as_slice(), None, Duration::ZERO)
+            .is_ok());
+    }
+    let item = reader.get(b"small").expect("busy").expect("missing");
+    assert_eq!(item.key(), b"small");
+    assert_eq!(item.value(), b"value");
+    assert_eq!(reader.get(b"counter").unwrap().unwrap().value(), 1_u64);
+    assert_eq!(reader.get(b"large").unwrap().unwrap().value(), large[..]);
+    assert_eq!(reader.get(b"json").unwrap().unwrap().value(), json[..]);
+    assert!(reader.get(b"missing").unwrap().is_none());
+
+    // readers never see a partially written value while the writer runs
+    let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let reader = reader.clone();
+            let done = done.clone();
+            std::thread::spawn(move || {
+                let mut hits = 0;
+                while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                    for i in 0..64 {
+                        let key = format!("{}", i);
+                        if let Ok(Some(item)) = reader.get(key.as_bytes()) {
+                            let value = match item.value() {
+                                Value::Bytes(v) => v.to_vec(),
+                                Value::U64(_) => panic!("unexpected value type"),
+                            };
+                            assert!(value.iter().all(|b| *b == value[0]));
+                            assert_eq!(value.len(), 16 + value[0] as usize);
+                            hits += 1;
+                        }
+                    }
+                }
+                hits
+            })
+        })
+        .collect();
+
+    for round in 0..200_usize {
+        for i in 0..64 {
+            let key = format!("{}", i);
+            let fill = ((round + i) % 64) as u8;
+            let value = vec![fill; 16 + fill as usize];
+            let mut cache = writer.write();
+            if (round + i) % 7 == 0 {
+                cache.delete(key.as_bytes());
+            } else {
+                assert!(cache
+                    .insert(key.as_bytes(), value.as_slice(), None, Duration::ZERO)
+                    .is_ok());
+            }
+        }
+    }
+    done.store(true, std::sync::atomic::Ordering::Relaxed);
+
+    let hits: usize = threads.into_iter().map(|t| t.join().unwrap()).sum();
+    assert!(hits > 0);
+
+    // items written before the readers started may have been evicted, but the
+    // reader and writer agree on which remain
+    for key in [&b"small"[..], b"counter", b"json", b"large"] {
+        let read = reader.get(key).expect("busy").is_some();
+        assert_eq!(read, writer.write().get(key).is_some());
+    }
+}
+
+#[test]
+fn shared_stress() {
+    // a small heap so that segments are evicted and reused while readers copy
+    // items out of them
+    let segment_size = 1024;
+    let cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(8 * segment_size as usize)
+        .hash_power(10)
+        .eviction(Policy::Fifo)
+        .build()
+        .expect("failed to create cache");
+    let (mut writer, reader) = cache.into_shared();
+
+    // each value repeats its key and a fill byte which changes on every write,
+    // so a copy mixing old and new bytes is detected
+    fn expected(key: &[u8], value: &[u8]) -> bool {
+        let fill = value[0];
+        value.len() == 8 + fill as usize
+            && value[1..].starts_with(key)
+            && value[1 + key.len()..].iter().all(|b| *b == fill)
+    }
+
+    let done = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let reader = reader.clone();
+            let done = done.clone();
+            std::thread::spawn(move || {
+                let mut hits = 0;
+                while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                    for i in 0..128 {
+                        let key = format!("{:03}", i);
+                        // misses and conflicts with the writer are expected
+                        if let Ok(Some(item)) = reader.get(key.as_bytes()) {
+                            assert_eq!(item.key(), key.as_bytes());
+                            match item.value() {
+                                Value::Bytes(v) => assert!(expected(key.as_bytes(), v)),
+                                Value::U64(_) => panic!("unexpected value type"),
+                            }
+                            hits += 1;
+                        }
+                    }
+                }
+                hits
+            })
+        })
+        .collect();
+
+    for round in 0..2000_usize {
+        for i in 0..128 {
+            let key = format!("{:03}", i);
+            let fill = (8 + (round * 7 + i) % 200) as u8;
+            let mut value = vec![fill; 8 + fill as usize];
+            value[1..4].copy_from_slice(key.as_bytes());
+            let mut cache = writer.write();
+            if (round + i) % 11 == 0 {
+                cache.delete(key.as_bytes());
+            } else {
+                assert!(cache
+                    .insert(key.as_bytes(), value.as_slice(), None, Duration::ZERO)
+                    .is_ok());
+            }
+        }
+    }
+    done.store(true, std::sync::atomic::Ordering::Relaxed);
+
+    let hits: usize = threads.into_iter().map(|t| t.join().unwrap()).sum();
+    assert!(hits > 0);
+}
+
+#[test]
+#[should_panic]
+fn shared_requires_fixed_hashtable() {
+    let cache = Seg::builder()
+        .hash_power(4)
+        .max_hash_power(Some(8))
+        .build()
+        .expect("failed to create cache");
+    let _ = cache.into_shared();
+}
+
+#[test]
+fn shared_expiry() {
+    let cache = Seg::builder()
+        .hash_power(12)
+        .build()
+        .expect("failed to create cache");
+    let (mut writer, reader) = cache.into_shared();
+
+    {
+        let mut cache = writer.write();
+        assert!(cache
+            .insert(b"latte", b"", None, Duration::from_secs(5))
+            .is_ok());
+        assert!(cache.insert(b"espresso", b"", None, Duration::ZERO).is_ok());
+    }
+    assert!(reader.get(b"latte").unwrap().is_some());
+    assert!(reader.get(b"espresso").unwrap().is_some());
+
+    // the expired item is not returned before the writer expires it
+    std::thread::sleep(std::time::Duration::from_secs(3));
+    assert!(reader.get(b"latte").unwrap().is_none());
+    assert!(reader.get(b"espresso").unwrap().is_some());
+
+    writer.write().expire();
+    assert!(reader.get(b"latte").unwrap().is_none());
+    assert!(reader.get(b"espresso").unwrap().is_some());
+}
+
+#[test]
+fn shared_resize() {
+    let segment_size = 4096;
+    let cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(16 * segment_size as usize)
+        .hash_power(12)
+        .build()
+        .expect("failed to create cache");
+    let (mut writer, _reader) = cache.into_shared();
+
+    // readers may be using the segments, so the heap cannot shrink
+    let mut cache = writer.write();
+    assert!(cache.resize(8 * segment_size as usize).is_err());
+    assert_eq!(cache.heap_size(), 16 * segment_size as usize);
+}
+
+#[test]
+#[should_panic]
+fn shared_requires_settled_heap() {
+    let segment_size = 4096;
+    let mut cache = Seg::builder()
+        .segment_size(segment_size)
+        .heap_size(16 * segment_size as usize)
+        .hash_power(12)
+        .build()
+        .expect("failed to create cache");
+
+    // fill every segment, so that shrinking the heap must evict segments
+    let value = vec![0; 1024];
+    for i in 0..100 {
+        let key = format!("{}", i);
+        assert!(cache
+            .insert(key.as_bytes(), value.as_slice(), None, Duration::ZERO)
+            .is_ok());
+    }
+    cache
+        .resize(8 * segment_size as usize)
+        .expect("failed to shrink");
+    assert!(cache.expire_pending());
+    let _ = cache.into_shared();
+}
+
 #[test]
 // This test caught a case where we interpreted old data as part of an item
 // header. Specifically, the first insert sets bytes that will be in-range for
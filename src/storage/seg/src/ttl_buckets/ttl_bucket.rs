@@ -11,11 +11,11 @@
 //! │   HEAD SEG   │   TAIL SEG   │     TTL     │     NSEG     │
 //! │              │              │             │              │
 //! │    32 bit    │    32 bit    │    32 bit   │    32 bit    │
-//! ├──────────────┼──────────────┴─────────────┴──────────────┤
-//! │  NEXT MERGE  │                  PADDING                  │
-//! │              │                                           │
-//! │    32 bit    │                  96 bit                   │
-//! ├──────────────┴───────────────────────────────────────────┤
+//! ├──────────────┼───────┬──────┴─────────────┴──────────────┤
+//! │  NEXT MERGE  │  NS   │              PADDING              │
+//! │              │       │                                   │
+//! │    32 bit    │16 bit │              80 bit               │
+//! ├──────────────┴───────┴───────────────────────────────────┤
 //! │                         PADDING                          │
 //! │                                                          │
 //! │                         128 bit                          │
@@ -39,19 +39,22 @@ pub struct TtlBucket {
     ttl: i32,
     nseg: i32,
     next_to_merge: Option<NonZeroU32>,
-    _pad: [u8; 44],
+    namespace: u16,
+    _pad: [u8; 42],
 }
 
 impl TtlBucket {
-    /// Create a new `TtlBucket` which will hold items with the provided TTL.
-    pub(super) fn new(ttl: i32) -> Self {
+    /// Create a new `TtlBucket` which will hold items with the provided TTL
+    /// for a namespace.
+    pub(super) fn new(ttl: i32, namespace: u16) -> Self {
         Self {
             head: None,
             tail: None,
             ttl,
             nseg: 0,
             next_to_merge: None,
-            _pad: [0; 44],
+            namespace,
+            _pad: [0; 42],
         }
     }
 
@@ -65,6 +68,16 @@ impl TtlBucket {
         self.head = id;
     }
 
+    /// Returns the segment ID of the tail of the `TtlBucket`.
+    pub fn tail(&self) -> Option<NonZeroU32> {
+        self.tail
+    }
+
+    /// Set the segment ID of the tail of the `TtlBucket`.
+    pub fn set_tail(&mut self, id: Option<NonZeroU32>) {
+        self.tail = id;
+    }
+
     /// Returns the segment ID of the next segment to merge within the
     /// `TtlBucket`.
     pub fn next_to_merge(&self) -> Option<NonZeroU32> {
@@ -76,22 +89,36 @@ impl TtlBucket {
         self.next_to_merge = next;
     }
 
-    /// Expire segments from this TtlBucket, returns the number of segments
-    /// expired.
-    pub(super) fn expire(&mut self, hashtable: &mut HashTable, segments: &mut Segments) -> usize {
+    /// Expire up to `limit` segments from this TtlBucket, returns the number of
+    /// segments expired. Segments are kept for `grace` past their TTL.
+    pub(super) fn expire(
+        &mut self,
+        hashtable: &mut HashTable,
+        segments: &mut Segments,
+        limit: usize,
+        grace: Duration,
+    ) -> usize {
         if self.head.is_none() {
             return 0;
         }
 
         let mut expired = 0;
-        let ts = Instant::recent();
+        let ts = clock::recent();
 
         loop {
+            if expired >= limit {
+                return expired;
+            }
+
             let seg_id = self.head;
             if let Some(seg_id) = seg_id {
                 let flush_at = segments.flush_at();
                 let mut segment = segments.get_mut(seg_id).unwrap();
-                if segment.create_at() + segment.ttl() <= ts || segment.create_at() < flush_at {
+                let due = segment
+                    .expire_at()
+                    .map(|expire_at| expire_at + grace <= ts)
+                    .unwrap_or(false);
+                if due || segment.create_at() < flush_at {
                     if let Some(next) = segment.next_seg() {
                         self.head = Some(next);
                     } else {
@@ -111,6 +138,29 @@ impl TtlBucket {
         }
     }
 
+    /// Returns the number of segments in this TtlBucket which are ready to be
+    /// expired, without expiring them.
+    pub(super) fn expired(&self, segments: &mut Segments, grace: Duration) -> usize {
+        let ts = clock::recent();
+        let flush_at = segments.flush_at();
+        let mut expired = 0;
+        let mut next = self.head;
+        while let Some(seg_id) = next {
+            let segment = segments.get_mut(seg_id).unwrap();
+            let due = segment
+                .expire_at()
+                .map(|expire_at| expire_at + grace <= ts)
+                .unwrap_or(false);
+            if due || segment.create_at() < flush_at {
+                expired += 1;
+                next = segment.next_seg();
+            } else {
+                break;
+            }
+        }
+        expired
+    }
+
     /// Clear segments from this TtlBucket, returns the number of segments
     /// expired.
     pub(super) fn clear(&mut self, hashtable: &mut HashTable, segments: &mut Segments) -> usize {
@@ -145,6 +195,7 @@ impl TtlBucket {
     /// return and error. It is up to the caller to handle the error and retry.
     fn try_expand(&mut self, segments: &mut Segments) -> Result<(), TtlBucketsError> {
         if let Some(id) = segments.pop_free() {
+            segments.set_namespace(id, self.namespace);
             {
                 if let Some(tail_id) = self.tail {
                     let mut tail = segments.get_mut(tail_id).unwrap();
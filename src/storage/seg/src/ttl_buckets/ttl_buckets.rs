@@ -14,7 +14,10 @@
 //! * 524_288-8_388_608s (~6 days - ~97 days) are stored in buckets which are
 //!   32_768s (~9 hours) wide.
 //! * TTLs beyond 8_388_608s (~97 days) and TTLs of 0 are all treated as the max
-//!   TTL.
+//!   TTL. Items with the max TTL do not expire.
+//!
+//! When namespaces are used, each namespace has its own set of 1024 buckets so
+//! that every segment holds items from a single namespace.
 //!
 //! See the
 //! [Segcache paper](https://www.usenix.org/system/files/nsdi21-yang.pdf) for
@@ -42,15 +45,41 @@ const TTL_BOUNDARY_3: i32 = 1 << (TTL_BUCKET_INTERVAL_N_BIT_3 + N_BUCKET_PER_STE
 const MAX_N_TTL_BUCKET: usize = N_BUCKET_PER_STEP * 4;
 const MAX_TTL_BUCKET_IDX: usize = MAX_N_TTL_BUCKET - 1;
 
+/// The TTL in seconds of the last bucket, which holds the items that do not
+/// expire
+pub(crate) const MAX_TTL: u32 = (TTL_BUCKET_INTERVAL_4 * (N_BUCKET_PER_STEP - 1) + 1) as u32;
+
+/// Limits the work done by each call to expire, so that a large number of
+/// segments expiring at once does not stall request processing. Expired
+/// segments which are not removed within the budget are removed by the
+/// following calls.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct ExpireBudget {
+    /// The maximum number of segments to expire
+    pub segments: Option<usize>,
+    /// The maximum time to spend expiring segments
+    pub time: Option<std::time::Duration>,
+}
+
 pub struct TtlBuckets {
     pub(crate) buckets: Box<[TtlBucket]>,
     pub(crate) last_expired: Instant,
+    next_to_expire: Option<u32>,
+    // expired segments left for the unfinished pass, counted once when the
+    // pass first runs out of budget and then reduced as they are expired
+    backlog: Option<u32>,
 }
 
 impl TtlBuckets {
     /// Create a new set of `TtlBuckets` which cover the full range of TTLs. See
     /// the module-level documentation for how the range of TTLs are stored.
     pub fn new() -> Self {
+        Self::with_namespaces(1)
+    }
+
+    /// Create a new set of `TtlBuckets` which cover the full range of TTLs for
+    /// each of the namespaces.
+    pub(crate) fn with_namespaces(namespaces: u16) -> Self {
         let intervals = [
             TTL_BUCKET_INTERVAL_1,
             TTL_BUCKET_INTERVAL_2,
@@ -59,22 +88,26 @@ impl TtlBuckets {
         ];
 
         let mut buckets = Vec::with_capacity(0);
-        buckets.reserve_exact(intervals.len() * N_BUCKET_PER_STEP as usize);
-
-        for interval in &intervals {
-            for j in 0..N_BUCKET_PER_STEP {
-                let ttl = interval * j + 1;
-                let bucket = TtlBucket::new(ttl as i32);
-                buckets.push(bucket);
+        buckets.reserve_exact(namespaces as usize * MAX_N_TTL_BUCKET);
+
+        for namespace in 0..namespaces {
+            for interval in &intervals {
+                for j in 0..N_BUCKET_PER_STEP {
+                    let ttl = interval * j + 1;
+                    let bucket = TtlBucket::new(ttl as i32, namespace);
+                    buckets.push(bucket);
+                }
             }
         }
 
         let buckets = buckets.into_boxed_slice();
-        let last_expired = Instant::now();
+        let last_expired = clock::now();
 
         Self {
             buckets,
             last_expired,
+            next_to_expire: None,
+            backlog: None,
         }
     }
 
@@ -82,7 +115,7 @@ impl TtlBuckets {
     pub(crate) fn get_bucket_index(&self, ttl: Duration) -> usize {
         let ttl = ttl.as_secs() as i32;
         if ttl <= 0 {
-            self.buckets.len() - 1
+            MAX_TTL_BUCKET_IDX
         } else if ttl & !(TTL_BOUNDARY_1 - 1) == 0 {
             (ttl >> TTL_BUCKET_INTERVAL_N_BIT_1) as usize
         } else if ttl & !(TTL_BOUNDARY_2 - 1) == 0 {
@@ -99,43 +132,110 @@ impl TtlBuckets {
         }
     }
 
+    /// Get the index of the `TtlBucket` for the given TTL within the buckets
+    /// for the namespace.
+    pub(crate) fn get_namespace_bucket_index(&self, namespace: u16, ttl: Duration) -> usize {
+        namespace as usize * MAX_N_TTL_BUCKET + self.get_bucket_index(ttl)
+    }
+
     // TODO(bmartin): confirm handling for negative TTLs here...
-    /// Get a mutable reference to the `TtlBucket` for the given TTL.
-    pub(crate) fn get_mut_bucket(&mut self, ttl: Duration) -> &mut TtlBucket {
-        let index = self.get_bucket_index(ttl);
+    /// Get a mutable reference to the `TtlBucket` for the given namespace and
+    /// TTL.
+    pub(crate) fn get_mut_bucket(&mut self, namespace: u16, ttl: Duration) -> &mut TtlBucket {
+        let index = self.get_namespace_bucket_index(namespace, ttl);
 
         // NOTE: since get_bucket_index() must return an index within the slice,
         // we do not need to worry about UB here.
         unsafe { self.buckets.get_unchecked_mut(index) }
     }
 
-    pub(crate) fn expire(&mut self, hashtable: &mut HashTable, segments: &mut Segments) -> usize {
-        let now = Instant::now();
+    /// Expire segments within the budget, returns the number of segments
+    /// expired. A new pass over the buckets is started at most once per
+    /// second, while an unfinished pass is continued on each call. Segments
+    /// are only expired once they are `grace` past their TTL.
+    pub(crate) fn expire(
+        &mut self,
+        hashtable: &mut HashTable,
+        segments: &mut Segments,
+        budget: ExpireBudget,
+        grace: Duration,
+    ) -> usize {
+        let mut idx = match self.next_to_expire {
+            Some(idx) => idx as usize,
+            None => {
+                let now = clock::now();
+                if now == self.last_expired {
+                    return 0;
+                }
+                self.last_expired = now;
+                0
+            }
+        };
 
-        if now == self.last_expired {
-            return 0;
-        } else {
-            self.last_expired = now;
+        let start = std::time::Instant::now();
+        let mut expired = 0;
+        while idx < self.buckets.len() {
+            let mut limit = budget
+                .segments
+                .map(|max| max.saturating_sub(expired))
+                .unwrap_or(usize::MAX);
+            if let Some(time) = budget.time {
+                // always expire at least one segment so progress is made
+                if expired > 0 && start.elapsed() >= time {
+                    break;
+                }
+                // expire one segment at a time so the budget is checked often
+                limit = limit.min(1);
+            }
+            if limit == 0 {
+                break;
+            }
+            let count = self.buckets[idx].expire(hashtable, segments, limit, grace);
+            expired += count;
+            if count < limit {
+                idx += 1;
+            }
         }
 
-        let start = Instant::now();
-        let mut expired = 0;
-        for bucket in self.buckets.iter_mut() {
-            expired += bucket.expire(hashtable, segments);
+        if idx < self.buckets.len() {
+            self.next_to_expire = Some(idx as u32);
+            let backlog = match self.backlog {
+                Some(backlog) => backlog.saturating_sub(expired as u32),
+                None => self.buckets[idx..]
+                    .iter()
+                    .map(|bucket| bucket.expired(segments, grace) as u32)
+                    .sum(),
+            };
+            self.backlog = Some(backlog);
+            EXPIRE_BACKLOG.set(backlog as _);
+        } else {
+            self.next_to_expire = None;
+            self.backlog = None;
+            EXPIRE_BACKLOG.set(0);
         }
+
         let duration = start.elapsed();
         debug!("expired: {} segments in {:?}", expired, duration);
         EXPIRE_TIME.add(duration.as_nanos() as _);
         expired
     }
 
+    /// Returns true if the last call to expire ran out of budget before all
+    /// the expired segments were removed.
+    pub(crate) fn expire_pending(&self) -> bool {
+        self.next_to_expire.is_some()
+    }
+
     pub(crate) fn clear(&mut self, hashtable: &mut HashTable, segments: &mut Segments) -> usize {
         let start = Instant::now();
         let mut cleared = 0;
         for bucket in self.buckets.iter_mut() {
             cleared += bucket.clear(hashtable, segments);
         }
-        segments.set_flush_at(Instant::now());
+        segments.set_flush_at(clock::now());
+        self.next_to_expire = None;
+        self.backlog = None;
+        EXPIRE_BACKLOG.set(0);
         let duration = start.elapsed();
         debug!("expired: {} segments in {:?}", cleared, duration);
         CLEAR_TIME.add(duration.as_nanos() as _);